@@ -4,3 +4,11 @@ hyperfine --warmup 3 \
   'seq 1 1000 | rust-parallel echo' \
   'seq 1 1000 | xargs -P8 -L1 echo' \
   'seq 1 1000 | parallel echo'
+
+hyperfine --warmup 3 \
+  "seq 1 100 | rust-parallel -s 'seq 1 50000 #' | cat > /dev/null" \
+  "seq 1 100 | rust-parallel --output-buffer-size 0 -s 'seq 1 50000 #' | cat > /dev/null"
+
+hyperfine --warmup 3 \
+  "seq 1 100 | rust-parallel -s 'seq 1 50000 #' > /dev/null" \
+  "seq 1 100 | rust-parallel --output-buffer-size 1M -s 'seq 1 50000 #' > /dev/null"
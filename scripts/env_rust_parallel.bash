@@ -0,0 +1,45 @@
+# Source this file in bash to define env_rust_parallel, which runs rust-parallel with
+# the functions, aliases, and variables of the calling shell available in -s commands.
+#
+# Once, in a clean shell, record the names defined there so they are not exported:
+#   env_rust_parallel --record-env
+#
+# Then run commands that use definitions from the current shell:
+#   myfunc() { echo "hello $1"; }
+#   env_rust_parallel -s myfunc ::: a b
+
+_env_rust_parallel_names() {
+    local _env_rust_parallel_kind
+    for _env_rust_parallel_kind in function alias variable; do
+        compgen -A "$_env_rust_parallel_kind" |
+            grep -v '^_env_rust_parallel' |
+            sed "s/^/$_env_rust_parallel_kind /"
+    done
+}
+
+env_rust_parallel() {
+    if [[ "$1" == "--record-env" ]]; then
+        _env_rust_parallel_names | cut -d' ' -f2 | rust-parallel --record-env
+        return
+    fi
+
+    local _env_rust_parallel_file _env_rust_parallel_kind _env_rust_parallel_name
+    local _env_rust_parallel_status
+    _env_rust_parallel_file=$(mktemp) || return
+
+    while read -r _env_rust_parallel_kind _env_rust_parallel_name; do
+        printf '%s\0%s\0' "$_env_rust_parallel_kind" "$_env_rust_parallel_name"
+        case "$_env_rust_parallel_kind" in
+            function) declare -f "$_env_rust_parallel_name" ;;
+            alias) alias "$_env_rust_parallel_name" ;;
+            variable) declare -p "$_env_rust_parallel_name" ;;
+        esac
+        printf '\0'
+    done < <(_env_rust_parallel_names) > "$_env_rust_parallel_file"
+
+    rust-parallel --use-recorded-env "$_env_rust_parallel_file" "$@"
+    _env_rust_parallel_status=$?
+
+    rm -f "$_env_rust_parallel_file"
+    return $_env_rust_parallel_status
+}
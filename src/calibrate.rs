@@ -0,0 +1,202 @@
+use anyhow::Context;
+
+use tokio::{io::AsyncWriteExt, process::Command, sync::mpsc};
+
+use std::{
+    process::Stdio,
+    time::{Duration, Instant},
+};
+
+use crate::command_line_args::CalibrateArgs;
+
+/// Chunk size and total bytes sent through the output path measurement.
+const OUTPUT_CHUNK_SIZE: usize = 64 << 10;
+const OUTPUT_TOTAL_BYTES: usize = 64 << 20;
+
+/// A job count is recommended if its throughput is within this fraction of the best.
+const THROUGHPUT_TOLERANCE: f64 = 0.95;
+
+fn trivial_command() -> Command {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "exit", "0"]);
+        command
+    } else {
+        Command::new("true")
+    };
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    command
+}
+
+async fn run_trivial_command() -> anyhow::Result<()> {
+    let output = trivial_command()
+        .output()
+        .await
+        .context("error running trivial command")?;
+
+    if !output.status.success() {
+        anyhow::bail!("trivial command failed: {}", output.status);
+    }
+
+    Ok(())
+}
+
+/// Average time to spawn and wait for a trivial command.
+async fn spawn_overhead(samples: usize) -> anyhow::Result<Duration> {
+    let start = Instant::now();
+
+    for _ in 0..samples {
+        run_trivial_command().await?;
+    }
+
+    Ok(start.elapsed() / samples as u32)
+}
+
+/// Average time from spawning a task to the task running.
+async fn scheduler_latency(samples: usize) -> anyhow::Result<Duration> {
+    let mut total = Duration::ZERO;
+
+    for _ in 0..samples {
+        let spawned = Instant::now();
+        total += tokio::spawn(async move { spawned.elapsed() }).await?;
+    }
+
+    Ok(total / samples as u32)
+}
+
+/// Bytes per second through a channel to a writer task, like command output.
+async fn output_throughput(channel_capacity: usize) -> anyhow::Result<f64> {
+    let (sender, mut receiver) = mpsc::channel::<Vec<u8>>(channel_capacity);
+
+    let start = Instant::now();
+
+    let writer = tokio::spawn(async move {
+        let mut sink = tokio::io::sink();
+        while let Some(chunk) = receiver.recv().await {
+            sink.write_all(&chunk).await?;
+        }
+        sink.flush().await
+    });
+
+    for _ in 0..(OUTPUT_TOTAL_BYTES / OUTPUT_CHUNK_SIZE) {
+        sender.send(vec![b'x'; OUTPUT_CHUNK_SIZE]).await?;
+    }
+    drop(sender);
+
+    writer.await??;
+
+    Ok(OUTPUT_TOTAL_BYTES as f64 / start.elapsed().as_secs_f64())
+}
+
+/// Trivial commands completed per second with `jobs` running in parallel.
+async fn commands_per_second(jobs: usize, commands: usize) -> anyhow::Result<f64> {
+    let semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(jobs));
+    let mut tasks = tokio::task::JoinSet::new();
+
+    let start = Instant::now();
+
+    for _ in 0..commands {
+        let permit = semaphore.clone().acquire_owned().await?;
+        tasks.spawn(async move {
+            let result = run_trivial_command().await;
+            drop(permit);
+            result
+        });
+    }
+
+    while let Some(result) = tasks.join_next().await {
+        result??;
+    }
+
+    Ok(commands as f64 / start.elapsed().as_secs_f64())
+}
+
+fn candidate_jobs(num_cpus: usize) -> Vec<usize> {
+    let mut jobs = vec![
+        1,
+        (num_cpus / 2).max(1),
+        num_cpus,
+        num_cpus * 2,
+        num_cpus * 4,
+    ];
+    jobs.dedup();
+    jobs
+}
+
+/// Smallest job count with throughput close to the best.
+fn recommended_jobs(results: &[(usize, f64)]) -> usize {
+    let best = results
+        .iter()
+        .map(|(_, throughput)| *throughput)
+        .fold(0.0, f64::max);
+
+    results
+        .iter()
+        .find(|(_, throughput)| *throughput >= best * THROUGHPUT_TOLERANCE)
+        .map(|(jobs, _)| *jobs)
+        .unwrap_or(1)
+}
+
+/// Measure overheads on this machine and print recommended -j and --channel-capacity.
+pub async fn run(calibrate_args: &CalibrateArgs) -> anyhow::Result<()> {
+    let samples = calibrate_args.samples;
+    let num_cpus = num_cpus::get();
+
+    let spawn_overhead = spawn_overhead(samples).await?;
+    println!("spawn_overhead={:?}", spawn_overhead);
+
+    let scheduler_latency = scheduler_latency(samples * 10).await?;
+    println!("scheduler_latency={:?}", scheduler_latency);
+
+    let output_throughput = output_throughput(num_cpus * 2).await?;
+    println!(
+        "output_throughput={:.1}MiB/s",
+        output_throughput / (1 << 20) as f64
+    );
+
+    let mut results = vec![];
+    for jobs in candidate_jobs(num_cpus) {
+        let commands_per_second = commands_per_second(jobs, samples.max(jobs * 4)).await?;
+        println!(
+            "jobs={} commands_per_second={:.1}",
+            jobs, commands_per_second
+        );
+        results.push((jobs, commands_per_second));
+    }
+
+    let jobs = recommended_jobs(&results);
+
+    // Enough buffered inputs to refill every slot twice while outputs are written.
+    let channel_capacity = jobs * 2;
+
+    println!(
+        "recommended: -j {} --channel-capacity {}",
+        jobs, channel_capacity
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_candidate_jobs() {
+        assert_eq!(candidate_jobs(1), vec![1, 2, 4]);
+        assert_eq!(candidate_jobs(8), vec![1, 4, 8, 16, 32]);
+    }
+
+    #[test]
+    fn test_recommended_jobs() {
+        assert_eq!(
+            recommended_jobs(&[(1, 100.0), (4, 390.0), (8, 400.0), (16, 380.0)]),
+            4
+        );
+        assert_eq!(recommended_jobs(&[(1, 100.0), (2, 50.0)]), 1);
+        assert_eq!(recommended_jobs(&[]), 1);
+    }
+}
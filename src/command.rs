@@ -1,29 +1,141 @@
-mod metrics;
+mod budget;
+mod collect;
+mod compare;
+mod confirm;
+mod control_socket;
+mod exit_status;
+mod expect_file;
+mod halt;
+mod http;
+mod interrupt;
+mod job_mutex;
+mod joblog;
+mod jobs_limit;
+mod load;
+mod local_notify;
+mod mail;
+mod memfree;
+pub mod metrics;
+mod output_match;
 mod path_cache;
+mod psi;
+mod rate_limit;
+mod repeat_stats;
+mod report;
+mod retry;
+mod run_control;
+mod slot_pool;
+mod start_delay;
+mod stop_file;
+mod then_stage;
+mod wave;
+mod webhook;
+mod window;
 
 use anyhow::Context;
 
-use tokio::sync::Semaphore;
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
 
-use tracing::{debug, error, info, instrument, span_enabled, trace, Level, Span};
+use tracing::{debug, error, info, instrument, span_enabled, trace, warn, Level, Span};
 
-use std::sync::Arc;
+use std::{
+    future::Future,
+    path::PathBuf,
+    process::Output,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::Poll,
+    time::{Duration, Instant, SystemTime},
+};
 
 use crate::{
     command_line_args::CommandLineArgs,
-    common::OwnedCommandAndArgs,
+    common::{JobCompletionNotifier, JobOptions, OwnedCommandAndArgs},
+    history::HistoryRecorder,
     input::{InputLineNumber, InputMessage, InputProducer},
     output::{OutputSender, OutputWriter},
-    process::ChildProcessFactory,
+    process::{ChildProcessExecutionError, ChildProcessFactory, SpawnOptions},
     progress::Progress,
+    run_dir::RunDir,
+};
+
+use self::{
+    budget::Budget,
+    collect::ArtifactCollector,
+    compare::OutputComparer,
+    confirm::Confirm,
+    control_socket::ControlSocket,
+    halt::Halt,
+    http::HttpExecutor,
+    interrupt::Interrupt,
+    job_mutex::JobMutexes,
+    joblog::JobLog,
+    jobs_limit::JobsLimit,
+    load::LoadThrottle,
+    local_notify::LocalNotifier,
+    mail::MailReporter,
+    memfree::MemoryThrottle,
+    metrics::CommandMetrics,
+    output_match::{OutputMatch, OutputMatcher},
+    path_cache::CommandPathCache,
+    psi::PsiThrottle,
+    rate_limit::RateLimiter,
+    repeat_stats::RepeatStats,
+    report::TestReport,
+    retry::RetryPolicy,
+    run_control::RunControl,
+    slot_pool::{SlotKeys, SlotPool},
+    start_delay::StartDelay,
+    stop_file::StopFile,
+    then_stage::ThenStage,
+    wave::Waves,
+    webhook::WebhookNotifier,
+    window::ExecutionWindow,
 };
 
-use self::{metrics::CommandMetrics, path_cache::CommandPathCache};
+pub use self::{interrupt::InterruptedError, joblog::stdout_sha256};
 
 #[derive(Debug)]
 struct Command {
     command_and_args: OwnedCommandAndArgs,
     input_line_number: InputLineNumber,
+    input_value: String,
+    job_number: usize,
+    job_options: JobOptions,
+    completion_notifier: Option<JobCompletionNotifier>,
+    is_then_stage: bool,
+}
+
+/// Outcome of a single attempt to run a command.
+enum RunAttemptResult {
+    SpawnError(anyhow::Error),
+    ExecutionError(ChildProcessExecutionError),
+    Completed {
+        /// Output with the exit status of the command.
+        output: Output,
+        /// Whether the command counts as succeeded, from its exit status unless
+        /// --expect-exit, --success-regex, --failure-regex, or --expect-file decided it.
+        succeeded: bool,
+    },
+}
+
+impl RunAttemptResult {
+    fn completed(output: Output) -> Self {
+        let succeeded = output.status.success();
+        Self::Completed { output, succeeded }
+    }
+
+    fn succeeded(&self) -> bool {
+        matches!(
+            self,
+            Self::Completed {
+                succeeded: true,
+                ..
+            }
+        )
+    }
 }
 
 impl Command {
@@ -37,24 +149,347 @@ impl Command {
             child_pid,
         ),
         level = "debug")]
-    async fn run(self, context: &CommandRunContext, output_sender: OutputSender) {
+    async fn run(self, context: &Arc<CommandRunContext>, output_sender: OutputSender, slot: usize) {
         debug!("begin run");
 
-        let command_metrics = &context.command_metrics;
+        let warmup = context.is_warmup_job(self.job_number);
 
-        let OwnedCommandAndArgs { command_path, args } = &self.command_and_args;
+        let command_metrics = if warmup {
+            &context.warmup_metrics
+        } else {
+            &context.command_metrics
+        };
 
         command_metrics.increment_commands_run();
 
+        let start_time = Instant::now();
+        let start_system_time = SystemTime::now();
+
+        let retries = context.retry_policy.retries(self.job_options.retries);
+        let mut attempt = 0;
+
+        let (result, output_failed) = loop {
+            let result = self.run_attempt(context, slot).await;
+            let result = self.check_expected_exit(result);
+            let (result, output_failed) = self.check_output(context, result).await;
+
+            if let (
+                RunAttemptResult::ExecutionError(ChildProcessExecutionError::Requeued),
+                Some(memory_throttle),
+            ) = (&result, &context.memory_throttle)
+            {
+                warn!("requeueing command: {} killed for --memfree", self);
+                memory_throttle.wait_until_available().await;
+                continue;
+            }
+
+            if result.succeeded()
+                || attempt >= retries
+                || !context.retry_policy.is_retryable(&result)
+                || context.halt.as_ref().is_some_and(Halt::halted)
+            {
+                break (result, output_failed);
+            }
+
+            attempt += 1;
+            let delay = context.retry_policy.delay(attempt);
+            warn!(
+                "retrying command: {} attempt {}/{} after {:?}",
+                self, attempt, retries, delay
+            );
+            tokio::time::sleep(delay).await;
+        };
+
+        let succeeded = result.succeeded();
+
+        if let (false, Some(halt)) = (warmup, &context.halt) {
+            if halt.job_finished(succeeded) {
+                context.child_process_factory.kill_all();
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+
+        if let Some(test_report) = &context.test_report {
+            test_report.add_test_case(
+                self.job_number,
+                &self.command_and_args,
+                elapsed,
+                &result,
+                warmup,
+            );
+        }
+
+        if let (false, Some(joblog)) = (self.is_then_stage, &context.joblog) {
+            joblog.add_job(
+                self.job_number,
+                &self.command_and_args,
+                start_system_time,
+                elapsed,
+                &result,
+                warmup,
+            );
+        }
+
+        if let (false, Some(output_comparer)) = (self.is_then_stage, &context.output_comparer) {
+            output_comparer
+                .compare(self.job_number, &self.command_and_args, &result)
+                .await;
+        }
+
+        if let (false, false, Some(repeat_stats)) =
+            (self.is_then_stage, warmup, &context.repeat_stats)
+        {
+            repeat_stats.add(
+                &self.input_line_number,
+                &self.input_value,
+                self.job_number,
+                elapsed,
+                succeeded,
+            );
+        }
+
+        match result {
+            RunAttemptResult::SpawnError(e) => {
+                error!("spawn error command: {}: {:#}", self, e);
+                command_metrics.increment_spawn_errors();
+                self.report_failure(context, format_args!("spawn error: {:#}", e));
+                output_sender
+                    .send_not_completed(
+                        self.command_and_args,
+                        self.input_line_number,
+                        self.input_value,
+                        self.job_number,
+                    )
+                    .await;
+            }
+            RunAttemptResult::ExecutionError(e) => {
+                error!("child process error command: {} error: {}", self, e);
+                self.report_failure(context, format_args!("{}", e));
+                command_metrics.handle_child_process_execution_error(e);
+                output_sender
+                    .send_not_completed(
+                        self.command_and_args,
+                        self.input_line_number,
+                        self.input_value,
+                        self.job_number,
+                    )
+                    .await;
+            }
+            RunAttemptResult::Completed {
+                mut output,
+                succeeded,
+            } => {
+                debug!("command exit status = {}", output.status);
+                if !succeeded {
+                    if output_failed {
+                        command_metrics.increment_output_failures();
+                    } else {
+                        command_metrics.increment_exit_status_errors();
+                    }
+                    self.report_failure(context, format_args!("exit_status={}", output.status));
+                } else if let (false, Some(then_stage)) = (self.is_then_stage, &context.then_stage)
+                {
+                    self.spawn_then_stage(context, then_stage, &output.stdout, &output_sender);
+                    output.stdout.clear();
+                }
+
+                output_sender
+                    .send(
+                        output,
+                        succeeded,
+                        self.command_and_args,
+                        self.input_line_number,
+                        self.input_value,
+                        self.job_number,
+                        self.job_options.output_tag.as_deref(),
+                    )
+                    .await;
+            }
+        };
+
+        if let Some(completion_notifier) = self.completion_notifier {
+            completion_notifier.complete(succeeded);
+        }
+
+        debug!("end run");
+    }
+
+    /// Count a command as succeeded if it exited with its --expect-exit code, and as failed
+    /// otherwise.
+    fn check_expected_exit(&self, result: RunAttemptResult) -> RunAttemptResult {
+        match (&self.job_options.expected_exit_code, result) {
+            (Some(expected), RunAttemptResult::Completed { output, .. }) => {
+                let expected_code = expected.trim().parse::<i32>().ok();
+
+                let succeeded = expected_code.is_some() && output.status.code() == expected_code;
+                if !succeeded {
+                    warn!(
+                        "command: {} exit_status={} but expected exit code '{}'",
+                        self, output.status, expected
+                    );
+                }
+                RunAttemptResult::Completed { output, succeeded }
+            }
+            (_, result) => result,
+        }
+    }
+
+    /// Determine success of a completed command from its output with --success-regex,
+    /// --failure-regex, and --expect-file.  Also returns true if its output failed it.
+    async fn check_output(
+        &self,
+        context: &CommandRunContext,
+        result: RunAttemptResult,
+    ) -> (RunAttemptResult, bool) {
+        let RunAttemptResult::Completed {
+            mut output,
+            mut succeeded,
+        } = result
+        else {
+            return (result, false);
+        };
+
+        let mut output_failed = false;
+
+        if let Some(output_matcher) = &context.output_matcher {
+            match output_matcher.check(&output) {
+                OutputMatch::Succeeded => succeeded = true,
+                OutputMatch::Failed(reason) => {
+                    warn!("command: {} output {}", self, reason);
+                    output_failed = true;
+                    succeeded = false;
+                }
+                OutputMatch::Undetermined => {}
+            }
+        }
+
+        if let (Some(expect_file), true) = (&self.job_options.expect_file, succeeded) {
+            if let Some(diff) = expect_file::unexpected_output(expect_file, &output.stdout).await {
+                warn!(
+                    "command: {} stdout differs from expected file '{}'",
+                    self,
+                    expect_file.display()
+                );
+                output_failed = true;
+                succeeded = false;
+                output.stderr.extend_from_slice(diff.as_bytes());
+            }
+        }
+
+        (
+            RunAttemptResult::Completed { output, succeeded },
+            output_failed,
+        )
+    }
+
+    /// Report a failed command to notifiers that track failures.
+    fn report_failure(&self, context: &CommandRunContext, failure: std::fmt::Arguments<'_>) {
+        if context.is_warmup_job(self.job_number) {
+            return;
+        }
+
+        if let Some(webhook_notifier) = &context.webhook_notifier {
+            webhook_notifier.command_failed(&context.command_metrics);
+        }
+
+        if let Some(mail_reporter) = &context.mail_reporter {
+            mail_reporter.command_failed(format!("{} {}", self, failure));
+        }
+    }
+
+    fn spawn_then_stage(
+        &self,
+        context: &Arc<CommandRunContext>,
+        then_stage: &ThenStage,
+        stdout: &[u8],
+        output_sender: &OutputSender,
+    ) {
+        for line in String::from_utf8_lossy(stdout).lines() {
+            let Some(command_and_args) = then_stage.parser.parse_line(line) else {
+                continue;
+            };
+
+            let command = Command {
+                command_and_args,
+                input_line_number: self.input_line_number.clone(),
+                input_value: line.to_owned(),
+                job_number: self.job_number,
+                job_options: JobOptions {
+                    output_tag: self.job_options.output_tag.clone(),
+                    ..Default::default()
+                },
+                completion_notifier: None,
+                is_then_stage: true,
+            };
+
+            debug!("spawning then stage command: {}", command);
+
+            context.progress.increment_total_commands(1);
+
+            let context = Arc::clone(context);
+            let output_sender = output_sender.clone();
+            let semaphore = Arc::clone(&then_stage.semaphore);
+            let slot_pool = Arc::clone(&then_stage.slot_pool);
+
+            tokio::spawn(async move {
+                let Ok(permit) = semaphore.acquire_owned().await else {
+                    warn!("then stage semaphore closed");
+                    return;
+                };
+
+                let slot_guard = slot_pool.acquire();
+
+                command
+                    .run(&context, output_sender, slot_guard.slot())
+                    .await;
+
+                drop(slot_guard);
+                drop(permit);
+
+                context.progress.command_finished();
+            });
+        }
+    }
+
+    async fn run_attempt(&self, context: &CommandRunContext, slot: usize) -> RunAttemptResult {
+        if let Some(http_executor) = &context.http_executor {
+            return http_executor
+                .run_attempt(&self.command_and_args, self.job_options.timeout)
+                .await;
+        }
+
+        let OwnedCommandAndArgs { command_path, args } = &self.command_and_args;
+
+        let job_dir = match &context.artifact_collector {
+            None => None,
+            Some(artifact_collector) => {
+                match artifact_collector.create_job_dir(self.job_number).await {
+                    Err(e) => return RunAttemptResult::SpawnError(e),
+                    Ok(job_dir) => Some(job_dir),
+                }
+            }
+        };
+
+        let spawn_options = SpawnOptions {
+            job_number: self.job_number,
+            slot,
+            current_dir: job_dir
+                .as_deref()
+                .or(self.job_options.current_dir.as_deref()),
+            env: &self.job_options.env,
+            clear_env: self.job_options.clear_env,
+            timeout: self.job_options.timeout,
+        };
+
         let child_process = match context
             .child_process_factory
-            .spawn(command_path, args)
+            .spawn(command_path, args, spawn_options)
             .await
         {
             Err(e) => {
-                error!("spawn error command: {}: {}", self, e);
-                command_metrics.increment_spawn_errors();
-                return;
+                self.finish_job_dir(context, job_dir, false).await;
+                return RunAttemptResult::SpawnError(e);
             }
             Ok(child_process) => child_process,
         };
@@ -66,24 +501,52 @@ impl Command {
             debug!("spawned child process, awaiting completion");
         }
 
-        match child_process.await_completion().await {
-            Err(e) => {
-                error!("child process error command: {} error: {}", self, e);
-                command_metrics.handle_child_process_execution_error(e);
-            }
-            Ok(output) => {
-                debug!("command exit status = {}", output.status);
-                if !output.status.success() {
-                    command_metrics.increment_exit_status_errors();
-                }
+        let mut result = match child_process.await_completion().await {
+            Err(e) => RunAttemptResult::ExecutionError(e),
+            Ok(output) => RunAttemptResult::completed(output),
+        };
 
-                output_sender
-                    .send(output, self.command_and_args, self.input_line_number)
-                    .await;
-            }
+        let collected = self
+            .finish_job_dir(context, job_dir, result.succeeded())
+            .await;
+
+        // a command whose artifacts could not be collected fails
+        if let (false, RunAttemptResult::Completed { succeeded, .. }) = (collected, &mut result) {
+            *succeeded = false;
+        }
+
+        result
+    }
+
+    /// Collect the artifacts of a successful command and remove its job directory, false if
+    /// collecting failed.
+    async fn finish_job_dir(
+        &self,
+        context: &CommandRunContext,
+        job_dir: Option<PathBuf>,
+        success: bool,
+    ) -> bool {
+        let (Some(artifact_collector), Some(job_dir)) = (&context.artifact_collector, job_dir)
+        else {
+            return true;
         };
 
-        debug!("end run");
+        if !success {
+            artifact_collector.remove_job_dir(&job_dir).await;
+            return true;
+        }
+
+        match artifact_collector.collect(&job_dir, self.job_number).await {
+            Err(e) => {
+                error!("collect error command: {}: {:#}", self, e);
+                artifact_collector.remove_job_dir(&job_dir).await;
+                false
+            }
+            Ok(destination) => {
+                debug!("collected artifacts to {:?}", destination);
+                true
+            }
+        }
     }
 }
 
@@ -93,7 +556,11 @@ impl std::fmt::Display for Command {
             f,
             "cmd={:?},args={:?},line={}",
             self.command_and_args.command_path, self.command_and_args.args, self.input_line_number,
-        )
+        )?;
+        if let Some(tag) = &self.job_options.tag {
+            write!(f, ",tag={}", tag)?;
+        }
+        Ok(())
     }
 }
 
@@ -101,46 +568,171 @@ pub struct CommandService {
     command_line_args: &'static CommandLineArgs,
     command_path_cache: CommandPathCache,
     command_semaphore: Arc<Semaphore>,
+    slot_pool: Arc<SlotPool>,
+    slot_keys: Option<SlotKeys>,
+    job_mutexes: Option<Arc<JobMutexes>>,
+    start_delay: Option<StartDelay>,
+    rate_limiter: Option<RateLimiter>,
+    execution_window: Option<ExecutionWindow>,
+    load_throttle: Option<LoadThrottle>,
+    _psi_throttle: Option<PsiThrottle>,
+    _jobs_limit: Arc<JobsLimit>,
+    run_control: Arc<RunControl>,
+    _control_socket: Option<ControlSocket>,
+    _stop_file: Option<StopFile>,
+    interrupt: Interrupt,
+    waves: Option<Waves>,
+    confirm: Option<Confirm>,
+    budget: Option<Budget>,
+    local_notifier: Option<LocalNotifier>,
+    history_recorder: Option<HistoryRecorder>,
+    run_dir: Option<RunDir>,
+    /// Jobs not run with --dry-run or --count.
+    counted_jobs: AtomicUsize,
+    /// Set until the first job is dispatched with --canary.
+    canary_pending: AtomicBool,
     context: Arc<CommandRunContext>,
     output_writer: OutputWriter,
 }
 
 impl CommandService {
-    pub fn new(command_line_args: &'static CommandLineArgs, progress: Arc<Progress>) -> Self {
+    pub fn new(
+        command_line_args: &'static CommandLineArgs,
+        progress: Arc<Progress>,
+    ) -> anyhow::Result<Self> {
+        let child_process_factory = ChildProcessFactory::new(command_line_args)?;
+        let memory_throttle =
+            MemoryThrottle::new(command_line_args, child_process_factory.running_jobs())?;
+
+        let command_semaphore = Arc::new(Semaphore::new(command_line_args.jobs));
+        let psi_throttle = PsiThrottle::new(command_line_args, &command_semaphore)?;
+        let slot_pool = SlotPool::new(command_line_args.jobs);
+        let jobs_limit = JobsLimit::new(command_line_args, &command_semaphore, &slot_pool)?;
+        let run_control = Arc::new(RunControl::new(child_process_factory.running_jobs()));
+
         let context = Arc::new(CommandRunContext {
-            child_process_factory: ChildProcessFactory::new(command_line_args),
+            artifact_collector: ArtifactCollector::new(command_line_args)?,
+            child_process_factory,
             command_metrics: CommandMetrics::default(),
+            warmup_jobs: command_line_args.warmup.unwrap_or(0),
+            warmup_metrics: CommandMetrics::default(),
+            http_executor: HttpExecutor::new(command_line_args)?,
             progress,
+            then_stage: ThenStage::new(command_line_args)?,
+            webhook_notifier: WebhookNotifier::new(command_line_args)?,
+            mail_reporter: MailReporter::new(command_line_args)?,
+            test_report: TestReport::new(command_line_args),
+            joblog: JobLog::new(command_line_args)?,
+            output_comparer: OutputComparer::new(command_line_args)?,
+            output_matcher: OutputMatcher::new(command_line_args)?,
+            repeat_stats: RepeatStats::new(command_line_args),
+            retry_policy: RetryPolicy::new(command_line_args),
+            halt: Halt::new(command_line_args),
+            memory_throttle,
         });
-        Self {
+        Ok(Self {
             command_line_args,
             command_path_cache: CommandPathCache::new(command_line_args),
-            command_semaphore: Arc::new(Semaphore::new(command_line_args.jobs)),
+            command_semaphore,
+            slot_pool,
+            slot_keys: command_line_args
+                .slot_key
+                .as_ref()
+                .map(|_| SlotKeys::new(command_line_args.jobs)),
+            job_mutexes: command_line_args.mutex.as_ref().map(|_| JobMutexes::new()),
+            start_delay: StartDelay::new(command_line_args),
+            rate_limiter: RateLimiter::new(command_line_args),
+            execution_window: ExecutionWindow::new(command_line_args)?,
+            load_throttle: LoadThrottle::new(command_line_args)?,
+            _psi_throttle: psi_throttle,
+            _control_socket: ControlSocket::new(
+                command_line_args,
+                &jobs_limit,
+                &run_control,
+                &context,
+            )?,
+            _stop_file: StopFile::new(command_line_args, &run_control),
+            _jobs_limit: jobs_limit,
+            interrupt: Interrupt::new(command_line_args, &run_control, &context)?,
+            run_control,
+            waves: Waves::new(command_line_args),
+            confirm: Confirm::new(command_line_args),
+            budget: Budget::new(command_line_args),
+            local_notifier: LocalNotifier::new(command_line_args),
+            history_recorder: HistoryRecorder::new(command_line_args),
+            run_dir: RunDir::new(command_line_args),
+            counted_jobs: AtomicUsize::new(0),
+            canary_pending: AtomicBool::new(command_line_args.canary),
             context,
-            output_writer: OutputWriter::new(command_line_args),
-        }
+            output_writer: OutputWriter::new(command_line_args)?,
+        })
     }
 
     async fn spawn_command(
         &self,
         command_and_args: OwnedCommandAndArgs,
         input_line_number: InputLineNumber,
+        input_value: String,
+        job_number: usize,
+        job_options: JobOptions,
+        completion_notifier: Option<JobCompletionNotifier>,
     ) -> anyhow::Result<()> {
         let command = Command {
             command_and_args,
             input_line_number,
+            input_value,
+            job_number,
+            job_options,
+            completion_notifier,
+            is_then_stage: false,
         };
 
-        if self.command_line_args.dry_run {
-            info!("{}", command);
+        if self.command_line_args.dry_run || self.command_line_args.count {
+            if self.command_line_args.dry_run {
+                info!("{}", command);
+            }
+            self.counted_jobs.fetch_add(1, Ordering::Relaxed);
+            // dropping the completion notifier reports the job as not run
+            return Ok(());
+        }
+
+        if self.context.halt.as_ref().is_some_and(Halt::halted) {
+            trace!("return from spawn_command due to halt");
             return Ok(());
         }
 
-        if self.command_line_args.exit_on_error && self.context.command_metrics.error_occurred() {
-            trace!("return from spawn_command due to exit_on_error");
+        if self.run_control.drained() {
+            trace!("return from spawn_command due to drain");
             return Ok(());
         }
 
+        if let Some(budget) = &self.budget {
+            if !budget.try_consume(command.job_options.cost) {
+                trace!("return from spawn_command due to budget");
+                return Ok(());
+            }
+        }
+
+        if let Some(execution_window) = &self.execution_window {
+            execution_window.wait_until_open().await;
+        }
+
+        if let Some(load_throttle) = &self.load_throttle {
+            load_throttle.wait_until_below().await;
+        }
+
+        if let Some(memory_throttle) = &self.context.memory_throttle {
+            memory_throttle.wait_until_available().await;
+        }
+
+        self.run_control.wait_until_resumed().await;
+
+        if let Some(waves) = &self.waves {
+            waves
+                .before_dispatch(&self.command_semaphore, self.command_line_args.jobs)
+                .await?;
+        }
+
         let context_clone = Arc::clone(&self.context);
 
         let output_sender = self.output_writer.sender();
@@ -150,14 +742,132 @@ impl CommandService {
             .await
             .context("command_semaphore.acquire_owned error")?;
 
+        // a job that finished while this one waited for a permit may have halted the run
+        if self.context.halt.as_ref().is_some_and(Halt::halted) {
+            trace!("return from spawn_command due to halt");
+            return Ok(());
+        }
+
+        if self.run_control.drained() {
+            trace!("return from spawn_command due to drain");
+            return Ok(());
+        }
+
+        if let Some(start_delay) = &self.start_delay {
+            start_delay.wait().await;
+        }
+
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.wait().await;
+        }
+
+        if self.command_semaphore.available_permits() == 0 {
+            self.context.child_process_factory.slots_saturated();
+        }
+
+        let slot_key_guard = match (&self.slot_keys, &command.job_options.slot_key) {
+            (Some(slot_keys), Some(slot_key)) => Some(slot_keys.acquire(slot_key)),
+            _ => None,
+        };
+
+        let job_mutex_guard = match (&self.job_mutexes, &command.job_options.mutex_key) {
+            (Some(job_mutexes), Some(mutex_key)) => Some(job_mutexes.lock(mutex_key)),
+            _ => None,
+        };
+
+        let command_semaphore = Arc::clone(&self.command_semaphore);
+        let slot_pool = Arc::clone(&self.slot_pool);
+
+        let (canary_sender, canary_receiver) = if self.canary_pending.swap(false, Ordering::SeqCst)
+        {
+            let (canary_sender, canary_receiver) = oneshot::channel();
+            (Some(canary_sender), Some(canary_receiver))
+        } else {
+            (None, None)
+        };
+
         tokio::spawn(async move {
-            command.run(&context_clone, output_sender).await;
+            let locks = async {
+                let job_mutex_guard = match job_mutex_guard {
+                    Some(job_mutex_guard) => Some(job_mutex_guard.await),
+                    None => None,
+                };
+                let slot_key_guard = match slot_key_guard {
+                    Some(slot_key_guard) => Some(slot_key_guard.await),
+                    None => None,
+                };
+                (job_mutex_guard, slot_key_guard)
+            };
+
+            let Some(((job_mutex_guard, slot_key_guard), permit)) =
+                Self::lock_then_permit(locks, permit, &command_semaphore).await
+            else {
+                return;
+            };
+
+            // a free slot is taken only with a permit
+            let slot_guard = slot_key_guard.unwrap_or_else(|| slot_pool.acquire());
+
+            command
+                .run(&context_clone, output_sender, slot_guard.slot())
+                .await;
 
+            drop(slot_guard);
+            drop(job_mutex_guard);
             drop(permit);
 
             context_clone.progress.command_finished();
+
+            if let Some(canary_sender) = canary_sender {
+                let _ = canary_sender.send(());
+            }
         });
 
+        if let Some(canary_receiver) = canary_receiver {
+            self.await_canary(canary_receiver).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Wait for the locks of a job, releasing its permit while they are busy so jobs waiting
+    /// for the same --mutex or --slot-key do not keep other jobs from running.
+    async fn lock_then_permit<T>(
+        lock: impl Future<Output = T>,
+        permit: OwnedSemaphorePermit,
+        command_semaphore: &Arc<Semaphore>,
+    ) -> Option<(T, OwnedSemaphorePermit)> {
+        let mut lock = std::pin::pin!(lock);
+
+        if let Poll::Ready(guard) = futures::poll!(lock.as_mut()) {
+            return Some((guard, permit));
+        }
+
+        drop(permit);
+
+        let guard = lock.await;
+
+        let permit = Arc::clone(command_semaphore).acquire_owned().await.ok()?;
+
+        Some((guard, permit))
+    }
+
+    /// Wait for the first job to finish alone with --canary, and stop if it failed.
+    ///
+    /// Other jobs are not dispatched until the canary job task signals it finished.
+    async fn await_canary(&self, canary_receiver: oneshot::Receiver<()>) -> anyhow::Result<()> {
+        if canary_receiver.await.is_err() {
+            anyhow::bail!("canary job did not run, not running remaining jobs");
+        }
+
+        if self.context.command_metrics.error_occurred()
+            || self.context.warmup_metrics.error_occurred()
+        {
+            anyhow::bail!("canary job failed, not running remaining jobs");
+        }
+
+        info!("canary job succeeded");
+
         Ok(())
     }
 
@@ -165,6 +875,10 @@ impl CommandService {
         let InputMessage {
             command_and_args,
             input_line_number,
+            input_value,
+            job_number,
+            job_options,
+            completion_notifier,
         } = input_message;
 
         let Some(command_and_args) = self
@@ -175,21 +889,82 @@ impl CommandService {
             return Ok(());
         };
 
-        self.spawn_command(command_and_args, input_line_number)
-            .await?;
+        self.spawn_command(
+            command_and_args,
+            input_line_number,
+            input_value,
+            job_number,
+            job_options,
+            completion_notifier,
+        )
+        .await?;
 
         Ok(())
     }
 
     async fn process_inputs(&self) -> anyhow::Result<()> {
-        let mut input_producer =
-            InputProducer::new(self.command_line_args, &self.context.progress)?;
+        let mut input_producer = InputProducer::new(
+            self.command_line_args,
+            &self.context.progress,
+            self.output_writer.flusher(),
+        )?;
+
+        if let Some(confirm) = &self.confirm {
+            let mut input_messages = vec![];
+            while let Some(input_message) = input_producer.receiver().recv().await {
+                input_messages.push(input_message);
+            }
+
+            if !confirm.confirm(&input_messages).await? {
+                anyhow::bail!("commands not confirmed, no commands run");
+            }
+
+            for input_message in input_messages {
+                self.process_input_message(input_message).await?;
+            }
+        }
 
-        while let Some(input_message) = input_producer.receiver().recv().await {
-            self.process_input_message(input_message).await?;
+        let input_idle_timeout = self
+            .command_line_args
+            .input_idle_timeout
+            .map(Duration::from_secs_f64);
+
+        loop {
+            let input_idle = async {
+                match input_idle_timeout {
+                    None => std::future::pending().await,
+                    Some(input_idle_timeout) => tokio::time::sleep(input_idle_timeout).await,
+                }
+            };
+
+            // once interrupted stop reading input and stop waiting to dispatch a job
+            let input_message = tokio::select! {
+                input_message = input_producer.receiver().recv() => input_message,
+                _ = self.interrupt.wait() => return Ok(()),
+                _ = input_idle => {
+                    info!(
+                        "no input for --input-idle-timeout {:?}, finishing run",
+                        input_idle_timeout.unwrap_or_default()
+                    );
+                    return Ok(());
+                }
+            };
+
+            let Some(input_message) = input_message else {
+                break;
+            };
+
+            tokio::select! {
+                result = self.process_input_message(input_message) => result?,
+                _ = self.interrupt.wait() => return Ok(()),
+            }
         }
 
-        input_producer.wait_for_completion().await?;
+        let input_summary = input_producer.wait_for_completion().await?;
+
+        self.context
+            .command_metrics
+            .add_dependency_failures(input_summary.dependency_failures);
 
         Ok(())
     }
@@ -206,10 +981,90 @@ impl CommandService {
 
         self.context.progress.finish();
 
+        if self.command_line_args.count {
+            println!("{}", self.counted_jobs.load(Ordering::Relaxed));
+            return Ok(());
+        }
+
+        if let Some(budget) = &self.budget {
+            budget.log_summary();
+        }
+
+        if self.context.warmup_jobs > 0 {
+            info!(
+                "warmup excluded from stats: {}",
+                self.context.warmup_metrics
+            );
+        }
+
+        if let Some(repeat_stats) = &self.context.repeat_stats {
+            repeat_stats.log_summary();
+        }
+
+        if let Some(joblog) = &self.context.joblog {
+            joblog.finish()?;
+        }
+
+        if let Some(test_report) = &self.context.test_report {
+            test_report.write().await?;
+        }
+
+        if let Some(webhook_notifier) = &self.context.webhook_notifier {
+            webhook_notifier
+                .run_finished(&self.context.command_metrics)
+                .await;
+        }
+
+        if let Some(mail_reporter) = &self.context.mail_reporter {
+            mail_reporter
+                .run_finished(&self.context.command_metrics)
+                .await;
+        }
+
+        if let Some(local_notifier) = &self.local_notifier {
+            local_notifier
+                .run_finished(&self.context.command_metrics.summary())
+                .await;
+        }
+
+        let exit_status = self
+            .context
+            .command_metrics
+            .exit_status(self.command_line_args.binary_exit_status);
+
+        if let Some(history_recorder) = &self.history_recorder {
+            let summary = self.context.command_metrics.summary();
+            history_recorder.run_finished(
+                summary.commands_run,
+                summary.total_failures,
+                exit_status,
+            );
+        }
+
+        if let Some(run_dir) = &self.run_dir {
+            run_dir.run_finished(&self.context.command_metrics, exit_status);
+        }
+
+        let comparison = self
+            .context
+            .output_comparer
+            .as_ref()
+            .map_or(Ok(()), OutputComparer::finish);
+
+        if let Some(interrupted_error) = self.interrupt.interrupted_error(&self.context) {
+            return Err(interrupted_error.into());
+        }
+
         if self.context.command_metrics.error_occurred() {
-            anyhow::bail!("command failures: {}", self.context.command_metrics);
+            return Err(self
+                .context
+                .command_metrics
+                .failures_error(self.command_line_args.binary_exit_status)
+                .into());
         }
 
+        comparison?;
+
         debug!(
             "end run_commands command_metrics = {}",
             self.context.command_metrics
@@ -220,7 +1075,30 @@ impl CommandService {
 }
 
 struct CommandRunContext {
+    artifact_collector: Option<ArtifactCollector>,
     child_process_factory: ChildProcessFactory,
     command_metrics: CommandMetrics,
+    /// Jobs numbered up to this are warmup jobs with --warmup.
+    warmup_jobs: usize,
+    /// Metrics of warmup jobs, kept out of command_metrics.
+    warmup_metrics: CommandMetrics,
+    http_executor: Option<HttpExecutor>,
     progress: Arc<Progress>,
+    then_stage: Option<ThenStage>,
+    webhook_notifier: Option<WebhookNotifier>,
+    mail_reporter: Option<MailReporter>,
+    test_report: Option<TestReport>,
+    joblog: Option<JobLog>,
+    output_comparer: Option<OutputComparer>,
+    output_matcher: Option<OutputMatcher>,
+    repeat_stats: Option<RepeatStats>,
+    retry_policy: RetryPolicy,
+    halt: Option<Halt>,
+    memory_throttle: Option<MemoryThrottle>,
+}
+
+impl CommandRunContext {
+    fn is_warmup_job(&self, job_number: usize) -> bool {
+        job_number <= self.warmup_jobs
+    }
 }
@@ -0,0 +1,97 @@
+use tracing::{info, warn};
+
+use std::sync::Mutex;
+
+use crate::command_line_args::CommandLineArgs;
+
+#[derive(Debug, Default)]
+struct BudgetState {
+    used: f64,
+    exhausted: bool,
+    skipped_commands: u64,
+}
+
+/// Limits total cost of started commands to --budget.
+#[derive(Debug)]
+pub struct Budget {
+    limit: f64,
+    default_cost: f64,
+    state: Mutex<BudgetState>,
+}
+
+impl Budget {
+    pub fn new(command_line_args: &CommandLineArgs) -> Option<Self> {
+        let limit = command_line_args.budget?;
+
+        let default_cost = command_line_args
+            .cost
+            .as_deref()
+            .and_then(|cost| cost.parse().ok())
+            .unwrap_or(1f64);
+
+        Some(Self {
+            limit,
+            default_cost,
+            state: Mutex::new(BudgetState::default()),
+        })
+    }
+
+    /// Returns true if the command may start, adding its cost to the used budget.
+    ///
+    /// Once a command would exceed the budget no further commands are started.
+    pub fn try_consume(&self, cost: Option<f64>) -> bool {
+        let cost = cost.unwrap_or(self.default_cost);
+
+        let mut state = self.state.lock().unwrap();
+
+        if !state.exhausted && state.used + cost > self.limit {
+            warn!(
+                "budget exhausted used={} limit={}, not starting more commands",
+                state.used, self.limit
+            );
+            state.exhausted = true;
+        }
+
+        if state.exhausted {
+            state.skipped_commands += 1;
+            return false;
+        }
+
+        state.used += cost;
+        true
+    }
+
+    pub fn log_summary(&self) {
+        let state = self.state.lock().unwrap();
+
+        info!(
+            "budget used={} limit={} skipped_commands={}",
+            state.used, self.limit, state.skipped_commands
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_try_consume() {
+        let command_line_args = CommandLineArgs {
+            budget: Some(5.0),
+            cost: Some("2".to_owned()),
+            ..Default::default()
+        };
+
+        let budget = Budget::new(&command_line_args).unwrap();
+
+        assert!(budget.try_consume(None));
+        assert!(budget.try_consume(Some(3.0)));
+        assert!(!budget.try_consume(Some(0.5)));
+        assert!(!budget.try_consume(Some(0.0)));
+
+        let state = budget.state.lock().unwrap();
+        assert_eq!(state.used, 5.0);
+        assert_eq!(state.skipped_commands, 2);
+    }
+}
@@ -0,0 +1,123 @@
+use anyhow::Context;
+
+use tempfile::TempDir;
+
+use tracing::warn;
+
+use std::path::{Path, PathBuf};
+
+use crate::command_line_args::CommandLineArgs;
+
+const JOB_NUMBER_PLACEHOLDER: &str = "{#}";
+
+/// Runs each command in a fresh temporary directory and moves the files
+/// created there to a destination directory when the command succeeds.
+///
+/// The temporary directory is removed when the collector is dropped.
+#[derive(Debug)]
+pub struct ArtifactCollector {
+    destination_template: String,
+    temp_root: TempDir,
+}
+
+impl ArtifactCollector {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        let Some(destination_template) = command_line_args.collect.clone() else {
+            return Ok(None);
+        };
+
+        // a fresh directory with an unpredictable name only accessible by this user
+        let temp_root = tempfile::Builder::new()
+            .prefix("rust-parallel.")
+            .tempdir()
+            .context("error creating collect temp directory")?;
+
+        Ok(Some(Self {
+            destination_template,
+            temp_root,
+        }))
+    }
+
+    pub async fn create_job_dir(&self, job_number: usize) -> anyhow::Result<PathBuf> {
+        let job_dir = self.temp_root.path().join(format!("job-{}", job_number));
+
+        tokio::fs::create_dir_all(&job_dir)
+            .await
+            .with_context(|| format!("error creating job directory {:?}", job_dir))?;
+
+        Ok(job_dir)
+    }
+
+    fn destination(&self, job_number: usize) -> PathBuf {
+        PathBuf::from(
+            self.destination_template
+                .replace(JOB_NUMBER_PLACEHOLDER, &job_number.to_string()),
+        )
+    }
+
+    /// Move everything in `job_dir` to the destination for `job_number`, then remove `job_dir`.
+    pub async fn collect(&self, job_dir: &Path, job_number: usize) -> anyhow::Result<PathBuf> {
+        let destination = self.destination(job_number);
+
+        tokio::fs::create_dir_all(&destination)
+            .await
+            .with_context(|| format!("error creating collect directory {:?}", destination))?;
+
+        let mut read_dir = tokio::fs::read_dir(job_dir)
+            .await
+            .with_context(|| format!("error reading job directory {:?}", job_dir))?;
+
+        while let Some(entry) = read_dir.next_entry().await? {
+            let target = destination.join(entry.file_name());
+
+            move_path(&entry.path(), &target)
+                .await
+                .with_context(|| format!("error moving {:?} to {:?}", entry.path(), target))?;
+        }
+
+        self.remove_job_dir(job_dir).await;
+
+        Ok(destination)
+    }
+
+    pub async fn remove_job_dir(&self, job_dir: &Path) {
+        if let Err(e) = tokio::fs::remove_dir_all(job_dir).await {
+            warn!("error removing job directory {:?}: {}", job_dir, e);
+        }
+    }
+}
+
+/// Rename `from` to `to`, falling back to copy and remove when they are on different filesystems.
+async fn move_path(from: &Path, to: &Path) -> anyhow::Result<()> {
+    if tokio::fs::rename(from, to).await.is_ok() {
+        return Ok(());
+    }
+
+    let from = from.to_owned();
+    let to = to.to_owned();
+
+    tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        copy_recursively(&from, &to)?;
+        if from.is_dir() {
+            std::fs::remove_dir_all(&from)?;
+        } else {
+            std::fs::remove_file(&from)?;
+        }
+        Ok(())
+    })
+    .await
+    .context("spawn_blocking error")?
+}
+
+fn copy_recursively(from: &Path, to: &Path) -> std::io::Result<()> {
+    if from.is_dir() {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursively(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        std::fs::copy(from, to)?;
+    }
+    Ok(())
+}
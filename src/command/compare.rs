@@ -0,0 +1,237 @@
+use anyhow::Context;
+
+use tracing::{info, warn};
+
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    command_line_args::CommandLineArgs, common::OwnedCommandAndArgs, run_dir::RESULTS_DIR,
+};
+
+use super::{joblog::stdout_sha256, RunAttemptResult};
+
+/// Outcome of comparing one job's stdout with the previous run.
+#[derive(Debug, PartialEq)]
+enum Comparison {
+    Same,
+    /// Per job diff summary.
+    Differs(String),
+    Missing,
+}
+
+/// Compares the stdout of each job with the stored results of a previous run for
+/// --compare-with, and fails the run if any job's output differs.
+///
+/// Jobs are matched by job number.  The previous stdout file is compared if it was kept,
+/// otherwise its --hash-output stdout.sha256.
+#[derive(Debug)]
+pub struct OutputComparer {
+    results_dir: PathBuf,
+    compared: AtomicUsize,
+    differed: AtomicUsize,
+    missing: AtomicUsize,
+}
+
+impl OutputComparer {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        let Some(dir) = &command_line_args.compare_with else {
+            return Ok(None);
+        };
+
+        let dir = PathBuf::from(dir);
+
+        // a --run-dir run directory, or a --results directory
+        let results_dir = if dir.join(RESULTS_DIR).is_dir() {
+            dir.join(RESULTS_DIR)
+        } else if dir.is_dir() {
+            dir
+        } else {
+            anyhow::bail!(
+                "--compare-with '{}' is not a run directory or results directory",
+                dir.display()
+            );
+        };
+
+        Ok(Some(Self {
+            results_dir,
+            compared: AtomicUsize::new(0),
+            differed: AtomicUsize::new(0),
+            missing: AtomicUsize::new(0),
+        }))
+    }
+
+    pub async fn compare(
+        &self,
+        job_number: usize,
+        command_and_args: &OwnedCommandAndArgs,
+        result: &RunAttemptResult,
+    ) {
+        let stdout: &[u8] = match result {
+            RunAttemptResult::Completed { output, .. } => &output.stdout,
+            RunAttemptResult::SpawnError(_) | RunAttemptResult::ExecutionError(_) => &[],
+        };
+
+        let job_dir = self.results_dir.join(job_number.to_string());
+
+        self.compared.fetch_add(1, Ordering::Relaxed);
+
+        match compare_job(&job_dir, stdout).await {
+            Ok(Comparison::Same) => {}
+            Ok(Comparison::Differs(summary)) => {
+                self.differed.fetch_add(1, Ordering::Relaxed);
+
+                let previous_command = tokio::fs::read_to_string(job_dir.join("cmd"))
+                    .await
+                    .unwrap_or_default();
+                let command = command_and_args.command_line_lossy();
+
+                if previous_command.trim_end_matches('\n') == command {
+                    warn!("job {} output differs: {}", job_number, summary);
+                } else {
+                    warn!(
+                        "job {} output differs: {}, previous command was: {}",
+                        job_number,
+                        summary,
+                        previous_command.trim_end_matches('\n')
+                    );
+                }
+            }
+            Ok(Comparison::Missing) => {
+                self.missing.fetch_add(1, Ordering::Relaxed);
+                warn!("job {} has no output in previous run", job_number);
+            }
+            Err(e) => {
+                self.missing.fetch_add(1, Ordering::Relaxed);
+                warn!("job {} compare error: {:#}", job_number, e);
+            }
+        }
+    }
+
+    /// Log how many jobs differ, and fail the run if any did.
+    pub fn finish(&self) -> anyhow::Result<()> {
+        let compared = self.compared.load(Ordering::Relaxed);
+        let differed = self.differed.load(Ordering::Relaxed);
+        let missing = self.missing.load(Ordering::Relaxed);
+
+        info!(
+            "compared {} jobs with '{}': {} differ, {} missing",
+            compared,
+            self.results_dir.display(),
+            differed,
+            missing
+        );
+
+        if differed > 0 {
+            anyhow::bail!(
+                "{} of {} jobs output differs from --compare-with",
+                differed,
+                compared
+            );
+        }
+
+        Ok(())
+    }
+}
+
+async fn compare_job(job_dir: &Path, stdout: &[u8]) -> anyhow::Result<Comparison> {
+    let stdout_path = job_dir.join("stdout");
+    if stdout_path.is_file() {
+        let previous = tokio::fs::read(&stdout_path)
+            .await
+            .with_context(|| format!("error reading '{}'", stdout_path.display()))?;
+
+        return Ok(match diff_summary(&previous, stdout) {
+            Some(summary) => Comparison::Differs(summary),
+            None => Comparison::Same,
+        });
+    }
+
+    let hash_path = job_dir.join("stdout.sha256");
+    if hash_path.is_file() {
+        let previous = tokio::fs::read_to_string(&hash_path)
+            .await
+            .with_context(|| format!("error reading '{}'", hash_path.display()))?;
+
+        return Ok(if previous.trim() == stdout_sha256(stdout) {
+            Comparison::Same
+        } else {
+            Comparison::Differs("stdout sha256 differs".to_owned())
+        });
+    }
+
+    Ok(Comparison::Missing)
+}
+
+/// Summary of the difference between previous and current output, or None if equal.
+///
+/// Reports the line counts and the first line that differs.
+fn diff_summary(previous: &[u8], current: &[u8]) -> Option<String> {
+    if previous == current {
+        return None;
+    }
+
+    let previous = String::from_utf8_lossy(previous);
+    let current = String::from_utf8_lossy(current);
+
+    let previous_lines: Vec<&str> = previous.lines().collect();
+    let current_lines: Vec<&str> = current.lines().collect();
+
+    let first_difference = previous_lines
+        .iter()
+        .zip(&current_lines)
+        .position(|(previous_line, current_line)| previous_line != current_line);
+
+    let first_difference = match first_difference {
+        Some(first_difference) => first_difference,
+        None if previous_lines.len() == current_lines.len() => {
+            return Some(format!(
+                "{} lines, differing only in line endings",
+                current_lines.len()
+            ));
+        }
+        None => previous_lines.len().min(current_lines.len()),
+    };
+
+    let line = |lines: &[&str]| {
+        lines
+            .get(first_difference)
+            .map_or_else(|| "<none>".to_owned(), |line| format!("{:?}", line))
+    };
+
+    Some(format!(
+        "{} lines, previously {}, first difference at line {}: {} was {}",
+        current_lines.len(),
+        previous_lines.len(),
+        first_difference + 1,
+        line(&current_lines),
+        line(&previous_lines),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_diff_summary() {
+        assert_eq!(diff_summary(b"a\nb\n", b"a\nb\n"), None);
+
+        assert_eq!(
+            diff_summary(b"a\nb\n", b"a\nc\nd\n"),
+            Some("3 lines, previously 2, first difference at line 2: \"c\" was \"b\"".to_owned())
+        );
+
+        assert_eq!(
+            diff_summary(b"a\nb\n", b"a\n"),
+            Some("1 lines, previously 2, first difference at line 2: <none> was \"b\"".to_owned())
+        );
+
+        assert_eq!(
+            diff_summary(b"a\nb", b"a\nb\n"),
+            Some("2 lines, differing only in line endings".to_owned())
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::Context;
+
+use std::io::{BufRead, Write};
+
+use crate::{
+    command_line_args::CommandLineArgs,
+    input::{self, InputMessage},
+};
+
+#[cfg(unix)]
+const TERMINAL: &str = "/dev/tty";
+
+#[cfg(windows)]
+const TERMINAL: &str = "CONIN$";
+
+/// Shows the first expanded commands and the total count with --confirm, and asks
+/// once whether to run them.
+#[derive(Debug)]
+pub struct Confirm {
+    show_commands: usize,
+    reads_stdin: bool,
+}
+
+impl Confirm {
+    pub fn new(command_line_args: &'static CommandLineArgs) -> Option<Self> {
+        command_line_args.confirm.map(|show_commands| Self {
+            show_commands,
+            reads_stdin: input::reads_stdin(command_line_args),
+        })
+    }
+
+    pub async fn confirm(&self, input_messages: &[InputMessage]) -> anyhow::Result<bool> {
+        let mut stdout = std::io::stdout().lock();
+
+        for input_message in input_messages.iter().take(self.show_commands) {
+            writeln!(
+                stdout,
+                "{}",
+                input_message.command_and_args.command_line_lossy()
+            )?;
+        }
+
+        if input_messages.len() > self.show_commands {
+            writeln!(
+                stdout,
+                "... and {} more",
+                input_messages.len() - self.show_commands
+            )?;
+        }
+
+        stdout.flush()?;
+        drop(stdout);
+
+        eprint!("Run {} commands? [y/N] ", input_messages.len());
+
+        let reads_stdin = self.reads_stdin;
+
+        let answer = tokio::task::spawn_blocking(move || read_answer(reads_stdin))
+            .await
+            .context("spawn_blocking error")??;
+
+        Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes" | "YES"))
+    }
+}
+
+/// Read the answer from stdin, or from the terminal if stdin is the input.
+fn read_answer(reads_stdin: bool) -> anyhow::Result<String> {
+    let mut answer = String::new();
+
+    if reads_stdin {
+        let tty = std::fs::File::open(TERMINAL)
+            .with_context(|| format!("error opening {}, stdin is the input", TERMINAL))?;
+        std::io::BufReader::new(tty)
+            .read_line(&mut answer)
+            .with_context(|| format!("error reading answer from {}", TERMINAL))?;
+        return Ok(answer);
+    }
+
+    std::io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .context("error reading answer from stdin")?;
+
+    Ok(answer)
+}
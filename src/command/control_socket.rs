@@ -0,0 +1,235 @@
+use serde::Serialize;
+
+use tokio::task::JoinHandle;
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, Weak},
+};
+
+use crate::command_line_args::CommandLineArgs;
+
+use super::{
+    jobs_limit::JobsLimit, metrics::CommandMetricsSummary, run_control::RunControl,
+    CommandRunContext,
+};
+
+/// Request line on the --control-socket.
+#[derive(Debug, PartialEq)]
+enum ControlRequest {
+    Status,
+    SetJobs(usize),
+    Pause,
+    Resume,
+    Drain,
+}
+
+impl ControlRequest {
+    fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+
+        let request = match (words.next(), words.next()) {
+            (Some("status"), None) => Self::Status,
+            (Some("set-jobs"), Some(jobs)) => match jobs.parse() {
+                Ok(jobs) if jobs > 0 => Self::SetJobs(jobs),
+                _ => {
+                    return Err(format!(
+                        "invalid jobs '{}', expected a positive number",
+                        jobs
+                    ))
+                }
+            },
+            (Some("pause"), None) => Self::Pause,
+            (Some("resume"), None) => Self::Resume,
+            (Some("drain"), None) => Self::Drain,
+            _ => {
+                return Err(format!(
+                    "unknown request '{}', expected status, set-jobs N, pause, resume, or drain",
+                    line
+                ))
+            }
+        };
+
+        match words.next() {
+            None => Ok(request),
+            Some(_) => Err(format!("too many arguments in '{}'", line)),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Status {
+    jobs: usize,
+    running: usize,
+    paused: bool,
+    drained: bool,
+    metrics: CommandMetricsSummary,
+}
+
+/// State the --control-socket reports and changes.
+struct ControlState {
+    jobs_limit: Arc<JobsLimit>,
+    run_control: Arc<RunControl>,
+    /// Weak so connections do not keep the context alive after the run finishes.
+    context: Weak<CommandRunContext>,
+}
+
+impl ControlState {
+    /// One line response: JSON for status, otherwise ok or error: MESSAGE.
+    fn respond(&self, line: &str) -> String {
+        let request = match ControlRequest::parse(line.trim()) {
+            Ok(request) => request,
+            Err(e) => return format!("error: {}", e),
+        };
+
+        tracing::info!("control socket request: {:?}", request);
+
+        match request {
+            ControlRequest::Status => {
+                let Some(context) = self.context.upgrade() else {
+                    return "error: run finished".to_owned();
+                };
+                let status = Status {
+                    jobs: self.jobs_limit.jobs(),
+                    running: context.child_process_factory.running_jobs().len(),
+                    paused: self.run_control.paused(),
+                    drained: self.run_control.drained(),
+                    metrics: context.command_metrics.summary(),
+                };
+                serde_json::to_string(&status).unwrap_or_else(|e| format!("error: {}", e))
+            }
+            ControlRequest::SetJobs(jobs) => format!("ok jobs={}", self.jobs_limit.set(jobs)),
+            ControlRequest::Pause => ok_or_error(self.run_control.pause(), "already paused"),
+            ControlRequest::Resume => ok_or_error(self.run_control.resume(), "not paused"),
+            ControlRequest::Drain => {
+                self.run_control.drain();
+                "ok".to_owned()
+            }
+        }
+    }
+}
+
+fn ok_or_error(ok: bool, error: &str) -> String {
+    if ok {
+        "ok".to_owned()
+    } else {
+        format!("error: {}", error)
+    }
+}
+
+/// Unix domain socket at --control-socket accepting one request per line: status, set-jobs N,
+/// pause, resume, and drain.
+///
+/// The socket file is removed when the run finishes.
+pub struct ControlSocket {
+    path: PathBuf,
+    listener_task: JoinHandle<()>,
+}
+
+impl ControlSocket {
+    #[cfg(unix)]
+    pub fn new(
+        command_line_args: &CommandLineArgs,
+        jobs_limit: &Arc<JobsLimit>,
+        run_control: &Arc<RunControl>,
+        context: &Arc<CommandRunContext>,
+    ) -> anyhow::Result<Option<Self>> {
+        use anyhow::Context;
+
+        use tokio::{
+            io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+            net::UnixListener,
+        };
+
+        use tracing::warn;
+
+        let Some(path) = &command_line_args.control_socket else {
+            return Ok(None);
+        };
+
+        let path = PathBuf::from(path);
+
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("error binding --control-socket '{}'", path.display()))?;
+
+        let state = Arc::new(ControlState {
+            jobs_limit: Arc::clone(jobs_limit),
+            run_control: Arc::clone(run_control),
+            context: Arc::downgrade(context),
+        });
+
+        let listener_task = tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!("control socket accept error: {}", e);
+                        continue;
+                    }
+                };
+
+                let state = Arc::clone(&state);
+
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let response = state.respond(&line) + "\n";
+                        if let Err(e) = writer.write_all(response.as_bytes()).await {
+                            warn!("control socket write error: {}", e);
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+
+        Ok(Some(Self {
+            path,
+            listener_task,
+        }))
+    }
+
+    #[cfg(not(unix))]
+    pub fn new(
+        command_line_args: &CommandLineArgs,
+        _jobs_limit: &Arc<JobsLimit>,
+        _run_control: &Arc<RunControl>,
+        _context: &Arc<CommandRunContext>,
+    ) -> anyhow::Result<Option<Self>> {
+        if command_line_args.control_socket.is_some() {
+            anyhow::bail!("--control-socket is only supported on unix");
+        }
+        Ok(None)
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        self.listener_task.abort();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_control_request() {
+        assert_eq!(ControlRequest::parse("status"), Ok(ControlRequest::Status));
+        assert_eq!(
+            ControlRequest::parse("set-jobs 4"),
+            Ok(ControlRequest::SetJobs(4))
+        );
+        assert_eq!(ControlRequest::parse("pause"), Ok(ControlRequest::Pause));
+        assert_eq!(ControlRequest::parse("resume"), Ok(ControlRequest::Resume));
+        assert_eq!(ControlRequest::parse("drain"), Ok(ControlRequest::Drain));
+
+        assert!(ControlRequest::parse("set-jobs 0").is_err());
+        assert!(ControlRequest::parse("set-jobs").is_err());
+        assert!(ControlRequest::parse("status now").is_err());
+        assert!(ControlRequest::parse("stop").is_err());
+    }
+}
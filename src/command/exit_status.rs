@@ -0,0 +1,3 @@
+/// Exit value logged for commands that exited successfully but are counted as failed, e.g.
+/// by their output.
+pub const FAILURE_CODE: i32 = 1;
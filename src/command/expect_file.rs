@@ -0,0 +1,212 @@
+use std::{fmt::Write, path::Path};
+
+/// Lines of context around changes in unified diffs.
+const CONTEXT_LINES: usize = 3;
+
+/// Largest number of line pairs compared to find the shortest diff, larger outputs are
+/// diffed as one replaced block after their common prefix and suffix.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Unified diff of the expected file and the job's stdout, or None if they match.
+pub async fn unexpected_output(expect_file: &Path, stdout: &[u8]) -> Option<String> {
+    let expected = match tokio::fs::read(expect_file).await {
+        Ok(expected) => expected,
+        Err(e) => {
+            return Some(format!(
+                "error reading --expect-file '{}': {}\n",
+                expect_file.display(),
+                e
+            ))
+        }
+    };
+
+    if expected == stdout {
+        return None;
+    }
+
+    Some(unified_diff(
+        &expect_file.to_string_lossy(),
+        "stdout",
+        &String::from_utf8_lossy(&expected),
+        &String::from_utf8_lossy(stdout),
+    ))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Line edits turning `old` into `new`, from their longest common subsequence.
+fn line_edits<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(Edit, &'a str)> {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut edits: Vec<(Edit, &str)> = old[..prefix]
+        .iter()
+        .map(|line| (Edit::Equal, *line))
+        .collect();
+
+    let (n, m) = (old_middle.len(), new_middle.len());
+
+    if n * m > MAX_DIFF_CELLS {
+        edits.extend(old_middle.iter().map(|line| (Edit::Delete, *line)));
+        edits.extend(new_middle.iter().map(|line| (Edit::Insert, *line)));
+    } else {
+        // lcs[i * (m + 1) + j] is the length of the common subsequence of old_middle[i..]
+        // and new_middle[j..]
+        let mut lcs = vec![0usize; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i * (m + 1) + j] = if old_middle[i] == new_middle[j] {
+                    lcs[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * (m + 1) + j].max(lcs[i * (m + 1) + j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_middle[i] == new_middle[j] {
+                edits.push((Edit::Equal, old_middle[i]));
+                i += 1;
+                j += 1;
+            } else if j == m || (i < n && lcs[(i + 1) * (m + 1) + j] >= lcs[i * (m + 1) + j + 1]) {
+                edits.push((Edit::Delete, old_middle[i]));
+                i += 1;
+            } else {
+                edits.push((Edit::Insert, new_middle[j]));
+                j += 1;
+            }
+        }
+    }
+
+    edits.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|line| (Edit::Equal, *line)),
+    );
+
+    edits
+}
+
+/// Start and length of a hunk range, where an empty range starts at the line before it.
+fn hunk_range(lines_before: usize, len: usize) -> String {
+    let start = if len == 0 {
+        lines_before
+    } else {
+        lines_before + 1
+    };
+    format!("{},{}", start, len)
+}
+
+fn unified_diff(old_name: &str, new_name: &str, old: &str, new: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+
+    let mut diff = format!("--- {}\n+++ {}\n", old_name, new_name);
+
+    let edits = line_edits(&old_lines, &new_lines);
+
+    let changes: Vec<usize> = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, (edit, _))| *edit != Edit::Equal)
+        .map(|(index, _)| index)
+        .collect();
+
+    if changes.is_empty() {
+        diff.push_str("line endings differ\n");
+        return diff;
+    }
+
+    // group changes separated by at most twice the context into hunks
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for &change in &changes {
+        match hunks.last_mut() {
+            Some((_, end)) if change <= *end + 2 * CONTEXT_LINES => *end = change,
+            _ => hunks.push((change, change)),
+        }
+    }
+
+    for (first_change, last_change) in hunks {
+        let start = first_change.saturating_sub(CONTEXT_LINES);
+        let end = (last_change + 1 + CONTEXT_LINES).min(edits.len());
+
+        let count = |edits: &[(Edit, &str)], side: Edit| {
+            edits
+                .iter()
+                .filter(|(edit, _)| *edit == Edit::Equal || *edit == side)
+                .count()
+        };
+
+        let _ = writeln!(
+            diff,
+            "@@ -{} +{} @@",
+            hunk_range(
+                count(&edits[..start], Edit::Delete),
+                count(&edits[start..end], Edit::Delete)
+            ),
+            hunk_range(
+                count(&edits[..start], Edit::Insert),
+                count(&edits[start..end], Edit::Insert)
+            ),
+        );
+
+        for (edit, line) in &edits[start..end] {
+            let prefix = match edit {
+                Edit::Equal => ' ',
+                Edit::Delete => '-',
+                Edit::Insert => '+',
+            };
+            let _ = writeln!(diff, "{}{}", prefix, line);
+        }
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        assert_eq!(
+            unified_diff("expected", "stdout", "a\nb\nc\n", "a\nx\nc\nd\n"),
+            "--- expected\n+++ stdout\n@@ -1,3 +1,4 @@\n a\n-b\n+x\n c\n+d\n"
+        );
+
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let new = "1\n2\nthree\n4\n5\n6\n7\n8\n9\nten\n";
+        assert_eq!(
+            unified_diff("expected", "stdout", old, new),
+            "--- expected\n+++ stdout\n@@ -1,6 +1,6 @@\n 1\n 2\n-3\n+three\n 4\n 5\n 6\n@@ -7,4 +7,4 @@\n 7\n 8\n 9\n-10\n+ten\n"
+        );
+
+        assert_eq!(
+            unified_diff("expected", "stdout", "a\n", ""),
+            "--- expected\n+++ stdout\n@@ -1,1 +0,0 @@\n-a\n"
+        );
+
+        assert_eq!(
+            unified_diff("expected", "stdout", "a", "a\n"),
+            "--- expected\n+++ stdout\nline endings differ\n"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use tracing::warn;
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::command_line_args::{
+    CommandLineArgs, HaltCondition, HaltPolicy, HaltThreshold, HaltWhen,
+};
+
+/// Percentage thresholds are checked once this many jobs finished.
+const MIN_JOBS_FOR_PERCENT: usize = 3;
+
+/// Stops running jobs according to --halt, or --exit-on-error.
+#[derive(Debug)]
+pub struct Halt {
+    policy: HaltPolicy,
+    succeeded: AtomicUsize,
+    failed: AtomicUsize,
+    halted: AtomicBool,
+}
+
+impl Halt {
+    pub fn new(command_line_args: &CommandLineArgs) -> Option<Self> {
+        let policy = if command_line_args.exit_on_error {
+            HaltPolicy {
+                when: HaltWhen::Soon,
+                condition: HaltCondition::Fail,
+                threshold: HaltThreshold::Count(1),
+            }
+        } else {
+            command_line_args.halt?
+        };
+
+        if policy.when == HaltWhen::Never {
+            return None;
+        }
+
+        Some(Self {
+            policy,
+            succeeded: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+            halted: AtomicBool::new(false),
+        })
+    }
+
+    /// True once the policy's condition was met, no new jobs are started.
+    pub fn halted(&self) -> bool {
+        self.halted.load(Ordering::SeqCst)
+    }
+
+    /// Count a finished job, returns true if running jobs should be killed now.
+    pub fn job_finished(&self, succeeded: bool) -> bool {
+        let (counter, other) = if succeeded {
+            (&self.succeeded, &self.failed)
+        } else {
+            (&self.failed, &self.succeeded)
+        };
+
+        let count = counter.fetch_add(1, Ordering::SeqCst) + 1;
+        let other_count = other.load(Ordering::SeqCst);
+
+        let (matching, finished) = match self.policy.condition {
+            HaltCondition::Fail if succeeded => return false,
+            HaltCondition::Success if !succeeded => return false,
+            HaltCondition::Fail | HaltCondition::Success => (count, count + other_count),
+            HaltCondition::Done => (count + other_count, count + other_count),
+        };
+
+        let condition_met = match self.policy.threshold {
+            HaltThreshold::Count(threshold) => matching >= threshold,
+            HaltThreshold::Percent(percent) => {
+                finished >= MIN_JOBS_FOR_PERCENT
+                    && matching as f64 * 100.0 >= percent * finished as f64
+            }
+        };
+
+        if !condition_met || self.halted.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+
+        warn!(
+            "halting {} after {} succeeded and {} failed jobs",
+            match self.policy.when {
+                HaltWhen::Now => "now, killing running jobs",
+                _ => "soon, waiting for running jobs",
+            },
+            self.succeeded.load(Ordering::SeqCst),
+            self.failed.load(Ordering::SeqCst),
+        );
+
+        self.policy.when == HaltWhen::Now
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn halt(policy: HaltPolicy) -> Halt {
+        Halt {
+            policy,
+            succeeded: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+            halted: AtomicBool::new(false),
+        }
+    }
+
+    #[test]
+    fn test_fail_count() {
+        let halt = halt(HaltPolicy {
+            when: HaltWhen::Now,
+            condition: HaltCondition::Fail,
+            threshold: HaltThreshold::Count(2),
+        });
+
+        assert!(!halt.job_finished(false));
+        assert!(!halt.job_finished(true));
+        assert!(!halt.halted());
+        assert!(halt.job_finished(false));
+        assert!(halt.halted());
+        assert!(!halt.job_finished(false));
+    }
+
+    #[test]
+    fn test_success_percent() {
+        let halt = halt(HaltPolicy {
+            when: HaltWhen::Soon,
+            condition: HaltCondition::Success,
+            threshold: HaltThreshold::Percent(50.0),
+        });
+
+        assert!(!halt.job_finished(true));
+        assert!(!halt.job_finished(false));
+        assert!(!halt.halted());
+        assert!(!halt.job_finished(false));
+        assert!(!halt.halted());
+        assert!(!halt.job_finished(true));
+        assert!(halt.halted());
+    }
+}
@@ -0,0 +1,253 @@
+use reqwest::Method;
+
+use std::{
+    process::{ExitStatus, Output},
+    time::Duration,
+};
+
+use crate::{
+    command_line_args::{CommandLineArgs, DiscardOutput},
+    common::OwnedCommandAndArgs,
+    process::ChildProcessExecutionError,
+};
+
+use super::RunAttemptResult;
+
+/// A request described by a command line like `URL -X POST -H 'Name: value' -d BODY`.
+#[derive(Debug, PartialEq)]
+struct HttpRequestSpec {
+    method: Method,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Option<String>,
+}
+
+impl HttpRequestSpec {
+    /// Parse a subset of curl arguments, a body without -X defaults to POST like curl.
+    fn parse(command_and_args: &OwnedCommandAndArgs) -> anyhow::Result<Self> {
+        let mut words =
+            std::iter::once(command_and_args.command_path.to_string_lossy().into_owned()).chain(
+                command_and_args
+                    .args
+                    .iter()
+                    .map(|arg| arg.to_string_lossy().into_owned()),
+            );
+
+        let mut method = None;
+        let mut url = None;
+        let mut headers = vec![];
+        let mut body = None;
+
+        while let Some(word) = words.next() {
+            let mut value = |option: &str| {
+                words
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("{} requires a value", option))
+            };
+
+            match word.as_str() {
+                "-X" | "--request" => {
+                    let value = value(&word)?;
+                    method = Some(
+                        Method::from_bytes(value.as_bytes())
+                            .map_err(|_| anyhow::anyhow!("invalid method '{}'", value))?,
+                    );
+                }
+                "-H" | "--header" => {
+                    let value = value(&word)?;
+                    let Some((name, header_value)) = value.split_once(':') else {
+                        anyhow::bail!("header '{}' is not 'Name: value'", value);
+                    };
+                    headers.push((name.trim().to_owned(), header_value.trim().to_owned()));
+                }
+                "-d" | "--data" => body = Some(value(&word)?),
+                _ if url.is_none() => url = Some(word),
+                _ => anyhow::bail!("unexpected argument '{}'", word),
+            }
+        }
+
+        let Some(url) = url else {
+            anyhow::bail!("no url");
+        };
+
+        let method = method.unwrap_or(if body.is_some() {
+            Method::POST
+        } else {
+            Method::GET
+        });
+
+        Ok(Self {
+            method,
+            url,
+            headers,
+            body,
+        })
+    }
+}
+
+/// Performs requests directly for --http instead of spawning processes.
+#[derive(Debug)]
+pub struct HttpExecutor {
+    client: reqwest::Client,
+    discard_stdout: bool,
+    timeout: Option<Duration>,
+}
+
+impl HttpExecutor {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        if !command_line_args.http {
+            return Ok(None);
+        }
+
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("rust-parallel/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+
+        Ok(Some(Self {
+            client,
+            discard_stdout: matches!(
+                command_line_args.discard_output,
+                Some(DiscardOutput::All) | Some(DiscardOutput::Stdout)
+            ),
+            timeout: command_line_args
+                .timeout_seconds
+                .map(Duration::from_secs_f64),
+        }))
+    }
+
+    /// The response body is stdout, non 2xx responses fail with exit status 1.
+    pub async fn run_attempt(
+        &self,
+        command_and_args: &OwnedCommandAndArgs,
+        timeout: Option<Duration>,
+    ) -> RunAttemptResult {
+        let spec = match HttpRequestSpec::parse(command_and_args) {
+            Err(e) => return RunAttemptResult::SpawnError(e.context("invalid http request")),
+            Ok(spec) => spec,
+        };
+
+        let response_future = self.send(spec);
+
+        let result = match timeout.or(self.timeout) {
+            None => response_future.await,
+            Some(timeout) => match tokio::time::timeout(timeout, response_future).await {
+                Err(e) => return RunAttemptResult::ExecutionError(e.into()),
+                Ok(result) => result,
+            },
+        };
+
+        match result {
+            Err(e) => RunAttemptResult::ExecutionError(ChildProcessExecutionError::IOError(
+                std::io::Error::other(e),
+            )),
+            Ok(output) => RunAttemptResult::completed(output),
+        }
+    }
+
+    async fn send(&self, spec: HttpRequestSpec) -> reqwest::Result<Output> {
+        let mut request = self.client.request(spec.method, spec.url);
+
+        for (name, value) in spec.headers {
+            request = request.header(name, value);
+        }
+
+        if let Some(body) = spec.body {
+            request = request.body(body);
+        }
+
+        let response = request.send().await?;
+
+        let status = response.status();
+
+        let body = response.bytes().await?;
+
+        Ok(Output {
+            status: exit_status(if status.is_success() { 0 } else { 1 }),
+            stdout: if self.discard_stdout {
+                vec![]
+            } else {
+                body.into()
+            },
+            stderr: if status.is_success() {
+                vec![]
+            } else {
+                format!("HTTP {}\n", status).into_bytes()
+            },
+        })
+    }
+}
+
+#[cfg(unix)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+
+    ExitStatus::from_raw(code << 8)
+}
+
+#[cfg(windows)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+
+    ExitStatus::from_raw(code as u32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use itertools::Itertools;
+
+    use std::path::PathBuf;
+
+    fn command_and_args(words: &[&str]) -> OwnedCommandAndArgs {
+        OwnedCommandAndArgs {
+            command_path: PathBuf::from(words[0]),
+            args: words[1..].iter().map_into().collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            HttpRequestSpec::parse(&command_and_args(&["https://example.com/1"])).unwrap(),
+            HttpRequestSpec {
+                method: Method::GET,
+                url: "https://example.com/1".to_owned(),
+                headers: vec![],
+                body: None,
+            }
+        );
+
+        assert_eq!(
+            HttpRequestSpec::parse(&command_and_args(&[
+                "https://example.com/items",
+                "-H",
+                "Content-Type: application/json",
+                "-d",
+                "{\"id\":1}",
+            ]))
+            .unwrap(),
+            HttpRequestSpec {
+                method: Method::POST,
+                url: "https://example.com/items".to_owned(),
+                headers: vec![("Content-Type".to_owned(), "application/json".to_owned())],
+                body: Some("{\"id\":1}".to_owned()),
+            }
+        );
+
+        assert_eq!(
+            HttpRequestSpec::parse(&command_and_args(&[
+                "-X",
+                "DELETE",
+                "https://example.com/1"
+            ]))
+            .unwrap()
+            .method,
+            Method::DELETE
+        );
+
+        assert!(HttpRequestSpec::parse(&command_and_args(&["-X", "GET"])).is_err());
+        assert!(HttpRequestSpec::parse(&command_and_args(&["https://a", "https://b"])).is_err());
+        assert!(HttpRequestSpec::parse(&command_and_args(&["https://a", "-H", "bad"])).is_err());
+    }
+}
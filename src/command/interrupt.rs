@@ -0,0 +1,234 @@
+use tokio::{sync::watch, task::JoinHandle, time::Duration};
+
+use tracing::warn;
+
+use std::sync::{Arc, Weak};
+
+use crate::command_line_args::CommandLineArgs;
+
+use super::{run_control::RunControl, CommandRunContext};
+
+/// Time running commands have to exit after SIGTERM before they are killed, without
+/// --term-timeout.
+const TERMINATE_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// Exit status after SIGINT, like a shell.
+const INTERRUPTED_EXIT_STATUS: i32 = 130;
+
+/// Exit status after --max-runtime, like timeout(1).
+const MAX_RUNTIME_EXIT_STATUS: i32 = 124;
+
+/// Exit status after a second SIGINT, like a process killed by SIGKILL.
+const FORCED_EXIT_STATUS: i32 = 137;
+
+/// Why the run was interrupted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Interruption {
+    Sigint,
+    MaxRuntime(Duration),
+}
+
+impl Interruption {
+    fn exit_status(self) -> i32 {
+        match self {
+            Self::Sigint => INTERRUPTED_EXIT_STATUS,
+            Self::MaxRuntime(_) => MAX_RUNTIME_EXIT_STATUS,
+        }
+    }
+}
+
+impl std::fmt::Display for Interruption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sigint => write!(f, "received SIGINT"),
+            Self::MaxRuntime(max_runtime) => write!(f, "--max-runtime {:?} exceeded", max_runtime),
+        }
+    }
+}
+
+/// Error returned when the run was interrupted, the process exits with its exit status.
+#[derive(thiserror::Error, Debug)]
+#[error("interrupted, {interruption}: {command_metrics}")]
+pub struct InterruptedError {
+    pub exit_status: i32,
+    interruption: Interruption,
+    command_metrics: String,
+}
+
+/// Graceful shutdown on SIGINT or once the run exceeds --max-runtime.
+///
+/// Input stops being read, queued jobs are skipped, and running commands are sent SIGTERM and
+/// killed if still running after a grace period.  The run then finishes as usual, reporting
+/// its summary.
+///
+/// A second SIGINT kills running commands with SIGKILL and exits right away.
+pub struct Interrupt {
+    interrupted: watch::Sender<Option<Interruption>>,
+    signal_handler: JoinHandle<()>,
+}
+
+impl Interrupt {
+    pub fn new(
+        command_line_args: &CommandLineArgs,
+        run_control: &Arc<RunControl>,
+        context: &Arc<CommandRunContext>,
+    ) -> anyhow::Result<Self> {
+        let mut sigint = sigint::listen()?;
+
+        let max_runtime = command_line_args.max_runtime.map(Duration::from_secs_f64);
+        let term_timeout = command_line_args.term_timeout.map(Duration::from_secs_f64);
+
+        let interrupted = watch::Sender::new(None);
+
+        let signal_handler = tokio::spawn({
+            let interrupted = interrupted.clone();
+            let run_control = Arc::clone(run_control);
+            // weak so the context, e.g. the --collect temp directory, is dropped with the
+            // command service rather than kept alive by this task
+            let context = Arc::downgrade(context);
+
+            async move {
+                let max_runtime_exceeded = async {
+                    match max_runtime {
+                        None => std::future::pending().await,
+                        Some(max_runtime) => {
+                            tokio::time::sleep(max_runtime).await;
+                            max_runtime
+                        }
+                    }
+                };
+
+                let interruption = tokio::select! {
+                    received = sigint.recv() => match received {
+                        Some(()) => Interruption::Sigint,
+                        None => return,
+                    },
+                    max_runtime = max_runtime_exceeded => Interruption::MaxRuntime(max_runtime),
+                };
+
+                interrupted.send_replace(Some(interruption));
+                run_control.drain();
+
+                let Some(context) = Weak::upgrade(&context) else {
+                    return;
+                };
+
+                tokio::select! {
+                    _ = terminate(interruption, &context, term_timeout) => {}
+                    Some(()) = sigint.recv() => kill_and_exit(&context),
+                }
+
+                if sigint.recv().await.is_some() {
+                    kill_and_exit(&context);
+                }
+            }
+        });
+
+        Ok(Self {
+            interrupted,
+            signal_handler,
+        })
+    }
+
+    pub async fn wait(&self) {
+        let mut interrupted = self.interrupted.subscribe();
+
+        // the sender is held by self, so waiting never fails
+        let _ = interrupted
+            .wait_for(|interrupted| interrupted.is_some())
+            .await;
+    }
+
+    /// Error if the run was interrupted.
+    pub fn interrupted_error(&self, context: &CommandRunContext) -> Option<InterruptedError> {
+        let interruption = (*self.interrupted.borrow())?;
+
+        Some(InterruptedError {
+            exit_status: interruption.exit_status(),
+            interruption,
+            command_metrics: context.command_metrics.to_string(),
+        })
+    }
+}
+
+impl Drop for Interrupt {
+    fn drop(&mut self) {
+        self.signal_handler.abort();
+    }
+}
+
+/// Terminate running commands with SIGTERM, and kill those still running after the grace
+/// period.
+async fn terminate(
+    interruption: Interruption,
+    context: &CommandRunContext,
+    term_timeout: Option<Duration>,
+) {
+    let running_jobs = context.child_process_factory.running_jobs();
+
+    // killed commands are sent SIGTERM and given --term-timeout to exit on their own
+    if term_timeout.is_some() {
+        warn!(
+            "{}, skipping remaining jobs and terminating {} running commands",
+            interruption,
+            running_jobs.len()
+        );
+        context.child_process_factory.kill_all();
+        return;
+    }
+
+    let terminated = running_jobs.terminate_all();
+    warn!(
+        "{}, skipping remaining jobs and terminating {} running commands",
+        interruption, terminated
+    );
+
+    tokio::time::sleep(TERMINATE_GRACE_PERIOD).await;
+
+    let running = running_jobs.len();
+    if running > 0 {
+        warn!(
+            "killing {} commands still running {:?} after SIGTERM",
+            running, TERMINATE_GRACE_PERIOD
+        );
+        context.child_process_factory.kill_all();
+    }
+}
+
+/// Kill running commands with SIGKILL and exit without waiting for them or reporting.
+fn kill_and_exit(context: &CommandRunContext) -> ! {
+    let killed = context
+        .child_process_factory
+        .running_jobs()
+        .force_kill_all();
+    warn!(
+        "received second SIGINT, killed {} running commands, exiting",
+        killed
+    );
+
+    crate::detach::run_finished(FORCED_EXIT_STATUS);
+    std::process::exit(FORCED_EXIT_STATUS);
+}
+
+/// SIGINT is handled from before any command runs.
+#[cfg(unix)]
+mod sigint {
+    use anyhow::Context;
+
+    use tokio::signal::unix::{signal, Signal, SignalKind};
+
+    pub fn listen() -> anyhow::Result<Signal> {
+        signal(SignalKind::interrupt()).context("error handling SIGINT")
+    }
+}
+
+#[cfg(windows)]
+mod sigint {
+    use anyhow::Context;
+
+    use tokio::signal::windows::{ctrl_c, CtrlC};
+
+    pub fn listen() -> anyhow::Result<CtrlC> {
+        ctrl_c().context("error handling Ctrl-C")
+    }
+}
@@ -0,0 +1,98 @@
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex as SyncMutex},
+};
+
+/// Locks per --mutex key, jobs with the same key never run at the same time.
+///
+/// Locks are created on first use and removed when no job holds or waits for them.
+#[derive(Debug, Default)]
+pub struct JobMutexes {
+    locks: SyncMutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl JobMutexes {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Wait until no other job holds the key.
+    pub fn lock(
+        self: &Arc<Self>,
+        key: &str,
+    ) -> impl Future<Output = JobMutexGuard> + Send + 'static {
+        let lock = Arc::clone(
+            self.locks
+                .lock()
+                .unwrap()
+                .entry(key.to_owned())
+                .or_default(),
+        );
+        let job_mutexes = Arc::clone(self);
+        let key = key.to_owned();
+
+        async move {
+            JobMutexGuard {
+                guard: Some(lock.lock_owned().await),
+                key,
+                job_mutexes,
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.locks.lock().unwrap().len()
+    }
+}
+
+#[derive(Debug)]
+pub struct JobMutexGuard {
+    guard: Option<OwnedMutexGuard<()>>,
+    key: String,
+    job_mutexes: Arc<JobMutexes>,
+}
+
+impl Drop for JobMutexGuard {
+    fn drop(&mut self) {
+        drop(self.guard.take());
+
+        let mut locks = self.job_mutexes.locks.lock().unwrap();
+
+        if locks
+            .get(&self.key)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            locks.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::FutureExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_job_mutexes() {
+        let job_mutexes = JobMutexes::new();
+
+        let a1 = job_mutexes.lock("a").await;
+        let b1 = job_mutexes.lock("b").await;
+
+        let mut a2 = Box::pin(job_mutexes.lock("a"));
+        assert!(a2.as_mut().now_or_never().is_none());
+
+        drop(a1);
+        let a2 = a2.await;
+        assert_eq!(job_mutexes.len(), 2);
+
+        drop(a2);
+        drop(b1);
+        assert_eq!(job_mutexes.len(), 0);
+    }
+}
@@ -0,0 +1,321 @@
+use anyhow::Context;
+
+use sha2::{Digest, Sha256};
+
+use tracing::warn;
+
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::Write,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    command_line_args::CommandLineArgs, common::OwnedCommandAndArgs, output::SyncSchedule,
+};
+
+use super::{exit_status, RunAttemptResult};
+
+const HEADER: &str = "Seq\tHost\tStarttime\tJobRuntime\tSend\tReceive\tExitval\tSignal\tCommand";
+
+/// Column added after Command with --hash-output.
+const HASH_OUTPUT_HEADER: &str = "\tStdout_sha256";
+
+/// Column added after Command and Stdout_sha256 with --warmup, 1 for warmup jobs and 0 for
+/// other jobs.
+const WARMUP_HEADER: &str = "\tWarmup";
+
+/// Stdout_sha256 of jobs without output because they did not complete.
+const NO_OUTPUT_HASH: &str = "-";
+
+/// Host column of jobs run on this machine.
+const LOCAL_HOST: &str = ":";
+
+#[derive(Debug)]
+struct JobLogState {
+    file: File,
+    sync_schedule: SyncSchedule,
+}
+
+/// Tab separated record of every job in the GNU parallel --joblog format.
+#[derive(Debug)]
+pub struct JobLog {
+    path: &'static str,
+    state: Mutex<JobLogState>,
+    retry_failed: bool,
+    hash_output: bool,
+    warmup: bool,
+}
+
+impl JobLog {
+    pub fn new(command_line_args: &'static CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        let Some(path) = &command_line_args.joblog else {
+            return Ok(None);
+        };
+
+        // --resume and --retry-failed append to the joblog of the previous run
+        let append = command_line_args.resume || command_line_args.retry_failed;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)
+            .with_context(|| format!("error opening joblog '{}'", path))?;
+
+        let empty = file
+            .metadata()
+            .with_context(|| format!("error reading joblog '{}'", path))?
+            .len()
+            == 0;
+
+        if empty {
+            let mut header = HEADER.to_owned();
+            if command_line_args.hash_output {
+                header.push_str(HASH_OUTPUT_HEADER);
+            }
+            if command_line_args.warmup.is_some() {
+                header.push_str(WARMUP_HEADER);
+            }
+            header.push('\n');
+
+            file.write_all(header.as_bytes())
+                .with_context(|| format!("error writing joblog '{}'", path))?;
+        }
+
+        Ok(Some(Self {
+            path,
+            state: Mutex::new(JobLogState {
+                file,
+                sync_schedule: SyncSchedule::new(command_line_args.fsync),
+            }),
+            retry_failed: command_line_args.retry_failed,
+            hash_output: command_line_args.hash_output,
+            warmup: command_line_args.warmup.is_some(),
+        }))
+    }
+
+    /// With --retry-failed replace the records of retried jobs with their new records,
+    /// which were appended during the run.
+    pub fn finish(&self) -> anyhow::Result<()> {
+        if !self.retry_failed {
+            return Ok(());
+        }
+
+        let state = self.state.lock().unwrap();
+
+        let contents = std::fs::read_to_string(self.path)
+            .with_context(|| format!("error reading joblog '{}'", self.path))?;
+
+        let temp_path = format!("{}.tmp", self.path);
+
+        let mut temp_file = File::create(&temp_path)
+            .with_context(|| format!("error creating joblog '{}'", temp_path))?;
+
+        temp_file
+            .write_all(replace_retried_records(&contents).as_bytes())
+            .with_context(|| format!("error writing joblog '{}'", temp_path))?;
+
+        if state.sync_schedule.enabled() {
+            temp_file
+                .sync_data()
+                .with_context(|| format!("error syncing joblog '{}'", temp_path))?;
+        }
+
+        std::fs::rename(&temp_path, self.path)
+            .with_context(|| format!("error replacing joblog '{}'", self.path))
+    }
+
+    pub fn add_job(
+        &self,
+        job_number: usize,
+        command_and_args: &OwnedCommandAndArgs,
+        start_time: SystemTime,
+        runtime: Duration,
+        result: &RunAttemptResult,
+        warmup: bool,
+    ) {
+        let (exit_value, signal) = match result {
+            RunAttemptResult::Completed { output, succeeded } => {
+                match (exit_value_and_signal(output.status), succeeded) {
+                    // --resume and --retry-failed count exit value 0 without a signal as
+                    // succeeded, so log the outcome of commands whose exit status did not
+                    // decide it, e.g. with --expect-exit or --failure-regex
+                    ((0, 0), false) => (exit_status::FAILURE_CODE, 0),
+                    (_, true) => (0, 0),
+                    (exit_value_and_signal, false) => exit_value_and_signal,
+                }
+            }
+            RunAttemptResult::SpawnError(_) | RunAttemptResult::ExecutionError(_) => (-1, 0),
+        };
+
+        let mut extra_columns = vec![];
+
+        if self.hash_output {
+            extra_columns.push(match result {
+                RunAttemptResult::Completed { output, .. } => stdout_sha256(&output.stdout),
+                RunAttemptResult::SpawnError(_) | RunAttemptResult::ExecutionError(_) => {
+                    NO_OUTPUT_HASH.to_owned()
+                }
+            });
+        }
+
+        if self.warmup {
+            extra_columns.push(if warmup { "1" } else { "0" }.to_owned());
+        }
+
+        let record = format_record(
+            job_number,
+            start_time
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64(),
+            runtime.as_secs_f64(),
+            exit_value,
+            signal,
+            &command_and_args.command_line_lossy(),
+            &extra_columns,
+        );
+
+        let mut state = self.state.lock().unwrap();
+
+        // one write per record so concurrent jobs never interleave within a line
+        if let Err(e) = state.file.write_all(record.as_bytes()) {
+            warn!("error writing joblog '{}': {}", self.path, e);
+        }
+
+        if state.sync_schedule.sync_due() {
+            if let Err(e) = state.file.sync_data() {
+                warn!("error syncing joblog '{}': {}", self.path, e);
+            }
+        }
+    }
+}
+
+impl Drop for JobLog {
+    fn drop(&mut self) {
+        if let Ok(state) = self.state.lock() {
+            if state.sync_schedule.enabled() {
+                let _ = state.file.sync_data();
+            }
+        }
+    }
+}
+
+/// Keep only the last record of each Seq, at the position of its first record.
+fn replace_retried_records(contents: &str) -> String {
+    let mut lines = contents.lines();
+
+    let header = lines.next().unwrap_or_default();
+
+    let mut seqs: Vec<&str> = vec![];
+    let mut records: HashMap<&str, &str> = HashMap::new();
+
+    for line in lines {
+        let seq = line.split('\t').next().unwrap_or_default();
+        if records.insert(seq, line).is_none() {
+            seqs.push(seq);
+        }
+    }
+
+    std::iter::once(header)
+        .chain(seqs.into_iter().map(|seq| records[seq]))
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+fn format_record(
+    job_number: usize,
+    start_time: f64,
+    runtime: f64,
+    exit_value: i32,
+    signal: i32,
+    command: &str,
+    extra_columns: &[String],
+) -> String {
+    let mut record = format!(
+        "{}\t{}\t{:10.3}\t{:8.3}\t0\t0\t{}\t{}\t{}",
+        job_number,
+        LOCAL_HOST,
+        start_time,
+        runtime,
+        exit_value,
+        signal,
+        command.replace(['\t', '\n'], " "),
+    );
+
+    for column in extra_columns {
+        record.push('\t');
+        record.push_str(column);
+    }
+
+    record.push('\n');
+    record
+}
+
+/// Hex sha256 of the stdout of a job with --hash-output.
+pub fn stdout_sha256(stdout: &[u8]) -> String {
+    hex::encode(Sha256::digest(stdout))
+}
+
+#[cfg(unix)]
+fn exit_value_and_signal(exit_status: std::process::ExitStatus) -> (i32, i32) {
+    use std::os::unix::process::ExitStatusExt;
+
+    match (exit_status.code(), exit_status.signal()) {
+        (Some(code), _) => (code, 0),
+        (None, Some(signal)) => (-1, signal),
+        (None, None) => (-1, 0),
+    }
+}
+
+#[cfg(not(unix))]
+fn exit_value_and_signal(exit_status: std::process::ExitStatus) -> (i32, i32) {
+    (exit_status.code().unwrap_or(-1), 0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_record() {
+        assert_eq!(
+            format_record(3, 1700000000.25, 1.5, 2, 0, "echo a\tb", &[]),
+            "3\t:\t1700000000.250\t   1.500\t0\t0\t2\t0\techo a b\n"
+        );
+        assert_eq!(
+            format_record(
+                3,
+                1700000000.25,
+                1.5,
+                2,
+                0,
+                "echo a",
+                &["-".to_owned(), "1".to_owned()]
+            ),
+            "3\t:\t1700000000.250\t   1.500\t0\t0\t2\t0\techo a\t-\t1\n"
+        );
+    }
+
+    #[test]
+    fn test_stdout_sha256() {
+        assert_eq!(
+            stdout_sha256(b"A\n"),
+            "06f961b802bc46ee168555f066d28f4f0e9afdf3f88174c1ee6f9de004fc30a0"
+        );
+    }
+
+    #[test]
+    fn test_replace_retried_records() {
+        let contents = "Seq\tCommand\n1\tok\n2\tfailed\n3\tok\n2\tretried\n";
+
+        assert_eq!(
+            replace_retried_records(contents),
+            "Seq\tCommand\n1\tok\n2\tretried\n3\tok\n"
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use tokio::{
+    sync::{watch, OwnedSemaphorePermit, Semaphore},
+    task::JoinHandle,
+};
+
+use tracing::info;
+
+use std::sync::Arc;
+
+use crate::command_line_args::CommandLineArgs;
+
+use super::slot_pool::SlotPool;
+
+/// Number of jobs changed while commands are running, by SIGUSR1 and SIGUSR2 with
+/// --jobs-signals or the --control-socket.
+///
+/// Lowering holds back a job slot once a running command finishes, raising releases a held
+/// back job slot or adds a new one.
+#[derive(Debug)]
+pub struct JobsLimit {
+    jobs: watch::Sender<usize>,
+    monitor: JoinHandle<()>,
+    signal_handler: Option<JoinHandle<()>>,
+}
+
+impl JobsLimit {
+    pub fn new(
+        command_line_args: &CommandLineArgs,
+        command_semaphore: &Arc<Semaphore>,
+        slot_pool: &Arc<SlotPool>,
+    ) -> anyhow::Result<Arc<Self>> {
+        let (jobs, jobs_receiver) = watch::channel(command_line_args.jobs);
+
+        let monitor = tokio::spawn(monitor(
+            jobs_receiver,
+            Arc::clone(command_semaphore),
+            Arc::clone(slot_pool),
+            command_line_args.jobs,
+        ));
+
+        let signal_handler = if command_line_args.jobs_signals {
+            handle_signals(jobs.clone())?
+        } else {
+            None
+        };
+
+        Ok(Arc::new(Self {
+            jobs,
+            monitor,
+            signal_handler,
+        }))
+    }
+
+    pub fn jobs(&self) -> usize {
+        *self.jobs.borrow()
+    }
+
+    /// Set the number of jobs, at least 1.
+    pub fn set(&self, jobs: usize) -> usize {
+        let jobs = jobs.max(1);
+        self.jobs.send_replace(jobs);
+        jobs
+    }
+}
+
+impl Drop for JobsLimit {
+    fn drop(&mut self) {
+        self.monitor.abort();
+
+        if let Some(signal_handler) = &self.signal_handler {
+            signal_handler.abort();
+        }
+    }
+}
+
+/// Raise the number of jobs by 1 on SIGUSR1 and lower it by 1 on SIGUSR2.
+///
+/// Handlers are installed before any command runs and could signal.
+#[cfg(unix)]
+fn handle_signals(jobs: watch::Sender<usize>) -> anyhow::Result<Option<JoinHandle<()>>> {
+    use anyhow::Context;
+
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigusr1 = signal(SignalKind::user_defined1()).context("error handling SIGUSR1")?;
+    let mut sigusr2 = signal(SignalKind::user_defined2()).context("error handling SIGUSR2")?;
+
+    Ok(Some(tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                Some(()) = sigusr1.recv() => {
+                    jobs.send_modify(|jobs| *jobs += 1);
+                    info!("received SIGUSR1, raising jobs to {}", *jobs.borrow());
+                }
+                Some(()) = sigusr2.recv() => {
+                    if *jobs.borrow() > 1 {
+                        jobs.send_modify(|jobs| *jobs -= 1);
+                        info!("received SIGUSR2, lowering jobs to {}", *jobs.borrow());
+                    } else {
+                        info!("received SIGUSR2, jobs already 1");
+                    }
+                }
+            }
+        }
+    })))
+}
+
+#[cfg(not(unix))]
+fn handle_signals(_jobs: watch::Sender<usize>) -> anyhow::Result<Option<JoinHandle<()>>> {
+    Ok(None)
+}
+
+async fn monitor(
+    mut jobs_receiver: watch::Receiver<usize>,
+    command_semaphore: Arc<Semaphore>,
+    slot_pool: Arc<SlotPool>,
+    mut slots: usize,
+) {
+    // job slots ever created are each free, running a command, or held back
+    let mut held_permits: Vec<OwnedSemaphorePermit> = vec![];
+
+    loop {
+        let jobs = *jobs_receiver.borrow_and_update();
+
+        if jobs > slots {
+            held_permits.clear();
+            for slot in slots + 1..=jobs {
+                slot_pool.add_slot(slot);
+            }
+            command_semaphore.add_permits(jobs - slots);
+            slots = jobs;
+        } else {
+            held_permits.truncate(slots - jobs);
+        }
+
+        tokio::select! {
+            result = jobs_receiver.changed() => {
+                if result.is_err() {
+                    return;
+                }
+            }
+            Ok(permit) = Arc::clone(&command_semaphore).acquire_owned(),
+                if held_permits.len() < slots - jobs =>
+            {
+                held_permits.push(permit);
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use anyhow::Context;
+
+use tokio::{sync::watch, time::Duration};
+
+use tracing::{info, warn};
+
+use crate::command_line_args::CommandLineArgs;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Pauses starting commands while the 1 minute load average is above --load.
+///
+/// A background task samples the load average and publishes whether it is above the limit.
+#[derive(Debug)]
+pub struct LoadThrottle {
+    above_limit: watch::Receiver<bool>,
+}
+
+impl LoadThrottle {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        let Some(max_load) = command_line_args.load else {
+            return Ok(None);
+        };
+
+        let initial_load = load_average()?;
+
+        if initial_load > max_load {
+            log_change(initial_load, max_load, true);
+        }
+
+        let (sender, above_limit) = watch::channel(initial_load > max_load);
+
+        tokio::spawn(monitor(sender, max_load, initial_load));
+
+        Ok(Some(Self { above_limit }))
+    }
+
+    /// Wait until the load average is at most --load.
+    pub async fn wait_until_below(&self) {
+        let mut above_limit = self.above_limit.clone();
+
+        // the sender is only dropped if sampling failed, then do not wait
+        let _ = above_limit.wait_for(|above_limit| !above_limit).await;
+    }
+}
+
+async fn monitor(sender: watch::Sender<bool>, max_load: f64, initial_load: f64) {
+    let mut load = initial_load;
+
+    loop {
+        let above_limit = load > max_load;
+
+        if sender.send_replace(above_limit) != above_limit {
+            log_change(load, max_load, above_limit);
+        }
+
+        tokio::time::sleep(SAMPLE_INTERVAL).await;
+
+        load = match load_average() {
+            Ok(load) => load,
+            Err(e) => {
+                warn!("--load error, no longer throttling: {:#}", e);
+                sender.send_replace(false);
+                return;
+            }
+        };
+    }
+}
+
+fn log_change(load: f64, max_load: f64, above_limit: bool) {
+    if above_limit {
+        info!(
+            "load average {:.2} above --load {:.2}, pausing",
+            load, max_load
+        );
+    } else {
+        info!(
+            "load average {:.2} at most --load {:.2}, resuming",
+            load, max_load
+        );
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn load_average() -> anyhow::Result<f64> {
+    let loadavg =
+        std::fs::read_to_string("/proc/loadavg").context("error reading /proc/loadavg")?;
+
+    parse_loadavg(&loadavg).with_context(|| format!("invalid /proc/loadavg '{}'", loadavg.trim()))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn load_average() -> anyhow::Result<f64> {
+    anyhow::bail!("--load is only supported on linux")
+}
+
+/// 1 minute load average, the first field of /proc/loadavg.
+fn parse_loadavg(loadavg: &str) -> Option<f64> {
+    loadavg.split_whitespace().next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_loadavg() {
+        assert_eq!(parse_loadavg("0.52 0.58 0.59 2/1234 56789\n"), Some(0.52));
+        assert_eq!(parse_loadavg(""), None);
+        assert_eq!(parse_loadavg("x 0.58"), None);
+    }
+}
@@ -0,0 +1,124 @@
+use tracing::warn;
+
+use std::io::{IsTerminal, Write};
+
+use crate::command_line_args::{CommandLineArgs, NotifyMethod};
+
+use super::metrics::CommandMetricsSummary;
+
+const TITLE: &str = "rust-parallel";
+
+/// Notifies the local user when the run finishes with --notify.
+#[derive(Debug)]
+pub struct LocalNotifier {
+    methods: &'static [NotifyMethod],
+}
+
+impl LocalNotifier {
+    pub fn new(command_line_args: &'static CommandLineArgs) -> Option<Self> {
+        if command_line_args.notify.is_empty() {
+            None
+        } else {
+            Some(Self {
+                methods: &command_line_args.notify,
+            })
+        }
+    }
+
+    pub async fn run_finished(&self, summary: &CommandMetricsSummary) {
+        let success = summary.total_failures == 0;
+        let message = message(summary);
+
+        for method in self.methods {
+            let result = match method {
+                NotifyMethod::Bell => write_to_terminal("\x07"),
+                NotifyMethod::Title => {
+                    write_to_terminal(&format!("\x1b]0;{}: {}\x07", TITLE, message))
+                }
+                NotifyMethod::Desktop => desktop_notification(&message, success).await,
+            };
+
+            if let Err(e) = result {
+                warn!("{:?} notification error: {:#}", method, e);
+            }
+        }
+    }
+}
+
+fn message(summary: &CommandMetricsSummary) -> String {
+    if summary.total_failures == 0 {
+        format!("finished, {} commands succeeded", summary.commands_run)
+    } else {
+        format!(
+            "failed, {} of {} commands failed",
+            summary.total_failures, summary.commands_run
+        )
+    }
+}
+
+/// Escape sequences go to stderr so redirected command output is not affected.
+fn write_to_terminal(s: &str) -> anyhow::Result<()> {
+    let mut stderr = std::io::stderr();
+
+    if stderr.is_terminal() {
+        stderr.write_all(s.as_bytes())?;
+        stderr.flush()?;
+    }
+
+    Ok(())
+}
+
+async fn desktop_notification(message: &str, success: bool) -> anyhow::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = tokio::process::Command::new("osascript");
+        command.arg("-e").arg(format!(
+            "display notification {:?} with title {:?}",
+            message, TITLE
+        ));
+        command
+    } else if cfg!(unix) {
+        let mut command = tokio::process::Command::new("notify-send");
+        command
+            .arg("--urgency")
+            .arg(if success { "normal" } else { "critical" })
+            .arg(TITLE)
+            .arg(message);
+        command
+    } else {
+        anyhow::bail!("desktop notifications are not supported on this platform");
+    };
+
+    let status = command.status().await?;
+    if !status.success() {
+        anyhow::bail!("notification command failed: {}", status);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_message() {
+        let mut summary = CommandMetricsSummary {
+            commands_run: 10,
+            total_failures: 0,
+            spawn_errors: 0,
+            timeouts: 0,
+            io_errors: 0,
+            exit_status_errors: 0,
+            output_failures: 0,
+            dependency_failures: 0,
+            killed: 0,
+        };
+
+        assert_eq!(message(&summary), "finished, 10 commands succeeded");
+
+        summary.total_failures = 2;
+        summary.exit_status_errors = 2;
+
+        assert_eq!(message(&summary), "failed, 2 of 10 commands failed");
+    }
+}
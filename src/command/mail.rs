@@ -0,0 +1,179 @@
+use anyhow::Context;
+
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    AsyncSendmailTransport, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+use tracing::{debug, warn};
+
+use std::{fmt::Write, sync::Mutex, time::Instant};
+
+use crate::command_line_args::{CommandLineArgs, NotifyOn};
+
+use super::metrics::CommandMetrics;
+
+/// Maximum number of failed commands listed in the report.
+const MAX_LISTED_FAILURES: usize = 1000;
+
+#[derive(Debug, Default)]
+struct FailureList {
+    failures: Vec<String>,
+    unlisted_failures: usize,
+}
+
+/// Emails a summary and the list of failed commands with --mail-to when the run ends.
+#[derive(Debug)]
+pub struct MailReporter {
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    smtp_url: Option<&'static str>,
+    mail_on: NotifyOn,
+    failure_list: Mutex<FailureList>,
+    start_time: Instant,
+}
+
+impl MailReporter {
+    pub fn new(command_line_args: &'static CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        if command_line_args.mail_to.is_empty() {
+            return Ok(None);
+        }
+
+        let to = command_line_args
+            .mail_to
+            .iter()
+            .map(|address| {
+                address
+                    .parse()
+                    .with_context(|| format!("invalid mail address '{}'", address))
+            })
+            .collect::<anyhow::Result<Vec<Mailbox>>>()?;
+
+        let from = command_line_args
+            .mail_from
+            .parse()
+            .with_context(|| format!("invalid mail address '{}'", command_line_args.mail_from))?;
+
+        Ok(Some(Self {
+            from,
+            to,
+            smtp_url: command_line_args.smtp_url.as_deref(),
+            mail_on: command_line_args.mail_on,
+            failure_list: Mutex::new(FailureList::default()),
+            start_time: Instant::now(),
+        }))
+    }
+
+    pub fn command_failed(&self, description: String) {
+        let mut failure_list = self.failure_list.lock().unwrap();
+
+        if failure_list.failures.len() < MAX_LISTED_FAILURES {
+            failure_list.failures.push(description);
+        } else {
+            failure_list.unlisted_failures += 1;
+        }
+    }
+
+    pub async fn run_finished(&self, command_metrics: &CommandMetrics) {
+        if self.mail_on == NotifyOn::Failure && !command_metrics.error_occurred() {
+            return;
+        }
+
+        if let Err(e) = self.send(command_metrics).await {
+            warn!("mail report error: {:#}", e);
+        }
+    }
+
+    fn body(&self, command_metrics: &CommandMetrics) -> String {
+        let mut body = format!(
+            "{}\nelapsed={:?}\n",
+            command_metrics,
+            self.start_time.elapsed()
+        );
+
+        let failure_list = self.failure_list.lock().unwrap();
+
+        if !failure_list.failures.is_empty() {
+            body.push_str("\nfailed commands:\n");
+            for failure in &failure_list.failures {
+                let _ = writeln!(body, "{}", failure);
+            }
+        }
+
+        if failure_list.unlisted_failures > 0 {
+            let _ = writeln!(body, "... and {} more", failure_list.unlisted_failures);
+        }
+
+        body
+    }
+
+    async fn send(&self, command_metrics: &CommandMetrics) -> anyhow::Result<()> {
+        let summary = command_metrics.summary();
+
+        let subject = if summary.total_failures == 0 {
+            format!("rust-parallel: {} commands succeeded", summary.commands_run)
+        } else {
+            format!(
+                "rust-parallel: {} of {} commands failed",
+                summary.total_failures, summary.commands_run
+            )
+        };
+
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+
+        let message = builder
+            .body(self.body(command_metrics))
+            .context("error building mail message")?;
+
+        match self.smtp_url {
+            Some(smtp_url) => {
+                debug!("sending mail report with smtp");
+                AsyncSmtpTransport::<Tokio1Executor>::from_url(smtp_url)
+                    .context("invalid smtp url")?
+                    .build()
+                    .send(message)
+                    .await
+                    .context("error sending mail with smtp")?;
+            }
+            None => {
+                debug!("sending mail report with sendmail");
+                AsyncSendmailTransport::<Tokio1Executor>::new()
+                    .send(message)
+                    .await
+                    .context("error sending mail with sendmail")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_body() {
+        let command_line_args = Box::leak(Box::new(CommandLineArgs {
+            mail_to: vec!["ops@example.com".to_owned()],
+            mail_from: "rust-parallel@localhost".to_owned(),
+            ..Default::default()
+        }));
+
+        let mail_reporter = MailReporter::new(command_line_args).unwrap().unwrap();
+
+        mail_reporter.command_failed("cmd=\"false\",args=[],line=stdin:1 exit_status=1".to_owned());
+
+        let body = mail_reporter.body(&CommandMetrics::default());
+
+        assert!(body.starts_with("commands_run=0 total_failures=0 "));
+        assert!(body
+            .ends_with("\nfailed commands:\ncmd=\"false\",args=[],line=stdin:1 exit_status=1\n"));
+    }
+}
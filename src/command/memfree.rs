@@ -0,0 +1,146 @@
+use anyhow::Context;
+
+use tokio::{sync::watch, time::Duration};
+
+use tracing::{info, warn};
+
+use std::sync::Arc;
+
+use crate::{command_line_args::CommandLineArgs, process::RunningJobs};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Pauses starting commands while available memory is below --memfree, and kills and
+/// requeues the youngest running command while it is below half of --memfree.
+///
+/// A background task samples available memory and publishes whether it is below --memfree.
+#[derive(Debug)]
+pub struct MemoryThrottle {
+    below_limit: watch::Receiver<bool>,
+}
+
+impl MemoryThrottle {
+    pub fn new(
+        command_line_args: &CommandLineArgs,
+        running_jobs: &Arc<RunningJobs>,
+    ) -> anyhow::Result<Option<Self>> {
+        let Some(memfree) = command_line_args.memfree else {
+            return Ok(None);
+        };
+
+        let initial_available = available_memory()?;
+
+        if initial_available < memfree {
+            log_change(initial_available, memfree, true);
+        }
+
+        let (sender, below_limit) = watch::channel(initial_available < memfree);
+
+        tokio::spawn(monitor(
+            sender,
+            memfree,
+            initial_available,
+            Arc::clone(running_jobs),
+        ));
+
+        Ok(Some(Self { below_limit }))
+    }
+
+    /// Wait until available memory is at least --memfree.
+    pub async fn wait_until_available(&self) {
+        let mut below_limit = self.below_limit.clone();
+
+        // the sender is only dropped if sampling failed, then do not wait
+        let _ = below_limit.wait_for(|below_limit| !below_limit).await;
+    }
+}
+
+async fn monitor(
+    sender: watch::Sender<bool>,
+    memfree: u64,
+    initial_available: u64,
+    running_jobs: Arc<RunningJobs>,
+) {
+    let mut available = initial_available;
+
+    loop {
+        let below_limit = available < memfree;
+
+        if sender.send_replace(below_limit) != below_limit {
+            log_change(available, memfree, below_limit);
+        }
+
+        // keep the last running command so the run can always make progress
+        if available < memfree / 2 && running_jobs.len() > 1 && running_jobs.requeue_youngest() {
+            warn!(
+                "available memory {} below half of --memfree {}, killing youngest command to requeue it",
+                available, memfree
+            );
+        }
+
+        tokio::time::sleep(SAMPLE_INTERVAL).await;
+
+        available = match available_memory() {
+            Ok(available) => available,
+            Err(e) => {
+                warn!("--memfree error, no longer throttling: {:#}", e);
+                sender.send_replace(false);
+                return;
+            }
+        };
+    }
+}
+
+fn log_change(available: u64, memfree: u64, below_limit: bool) {
+    if below_limit {
+        info!(
+            "available memory {} below --memfree {}, pausing",
+            available, memfree
+        );
+    } else {
+        info!(
+            "available memory {} at least --memfree {}, resuming",
+            available, memfree
+        );
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn available_memory() -> anyhow::Result<u64> {
+    let meminfo =
+        std::fs::read_to_string("/proc/meminfo").context("error reading /proc/meminfo")?;
+
+    parse_mem_available(&meminfo).context("MemAvailable not found in /proc/meminfo")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn available_memory() -> anyhow::Result<u64> {
+    anyhow::bail!("--memfree is only supported on linux")
+}
+
+/// MemAvailable of /proc/meminfo in bytes.
+fn parse_mem_available(meminfo: &str) -> Option<u64> {
+    let kilobytes: u64 = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse()
+        .ok()?;
+
+    Some(kilobytes * 1024)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_mem_available() {
+        let meminfo = "MemTotal:       16303428 kB\nMemFree:         1234567 kB\nMemAvailable:    8151714 kB\n";
+        assert_eq!(parse_mem_available(meminfo), Some(8151714 * 1024));
+
+        assert_eq!(parse_mem_available("MemTotal: 16303428 kB\n"), None);
+    }
+}
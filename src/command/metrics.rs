@@ -1,9 +1,36 @@
+use serde::Serialize;
+
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
 use crate::process::ChildProcessExecutionError;
 
 const ORDERING: Ordering = Ordering::SeqCst;
 
+/// Exit status for more failed jobs than this, like GNU parallel.
+const MAX_FAILURES_EXIT_STATUS: u64 = 101;
+
+/// Error returned when jobs failed, the process exits with its exit status.
+#[derive(thiserror::Error, Debug)]
+#[error("command failures: {command_metrics}")]
+pub struct CommandFailuresError {
+    pub exit_status: i32,
+    command_metrics: String,
+}
+
+/// Snapshot of command metrics, e.g. for notifications.
+#[derive(Debug, Serialize)]
+pub struct CommandMetricsSummary {
+    pub commands_run: u64,
+    pub total_failures: u64,
+    pub spawn_errors: u64,
+    pub timeouts: u64,
+    pub io_errors: u64,
+    pub exit_status_errors: u64,
+    pub output_failures: u64,
+    pub dependency_failures: u64,
+    pub killed: u64,
+}
+
 #[derive(Debug, Default)]
 pub struct CommandMetrics {
     commands_run: AtomicU64,
@@ -12,6 +39,11 @@ pub struct CommandMetrics {
     timeouts: AtomicU64,
     io_errors: AtomicU64,
     exit_status_errors: AtomicU64,
+    /// Jobs failed by --success-regex, --failure-regex, or --expect-file.
+    output_failures: AtomicU64,
+    dependency_failures: AtomicU64,
+    /// Jobs killed by --halt now, these are not failures.
+    killed: AtomicU64,
 }
 
 impl CommandMetrics {
@@ -31,8 +63,45 @@ impl CommandMetrics {
         self.error_occurred.store(true, ORDERING);
     }
 
-    fn total_failures(&self) -> u64 {
-        self.spawn_errors() + self.timeouts() + self.io_errors() + self.exit_status_errors()
+    /// Number of failed jobs capped at 101, or 1 if any job failed with --binary-exit-status.
+    pub fn exit_status(&self, binary_exit_status: bool) -> i32 {
+        let total_failures = self.total_failures();
+
+        if binary_exit_status {
+            total_failures.min(1) as i32
+        } else {
+            total_failures.min(MAX_FAILURES_EXIT_STATUS) as i32
+        }
+    }
+
+    pub fn failures_error(&self, binary_exit_status: bool) -> CommandFailuresError {
+        CommandFailuresError {
+            exit_status: self.exit_status(binary_exit_status),
+            command_metrics: self.to_string(),
+        }
+    }
+
+    pub fn summary(&self) -> CommandMetricsSummary {
+        CommandMetricsSummary {
+            commands_run: self.commands_run(),
+            total_failures: self.total_failures(),
+            spawn_errors: self.spawn_errors(),
+            timeouts: self.timeouts(),
+            io_errors: self.io_errors(),
+            exit_status_errors: self.exit_status_errors(),
+            output_failures: self.output_failures(),
+            dependency_failures: self.dependency_failures(),
+            killed: self.killed(),
+        }
+    }
+
+    pub fn total_failures(&self) -> u64 {
+        self.spawn_errors()
+            + self.timeouts()
+            + self.io_errors()
+            + self.exit_status_errors()
+            + self.output_failures()
+            + self.dependency_failures()
     }
 
     pub fn increment_spawn_errors(&self) {
@@ -47,10 +116,19 @@ impl CommandMetrics {
     pub fn handle_child_process_execution_error(&self, error: ChildProcessExecutionError) {
         match error {
             ChildProcessExecutionError::IOError(_) => self.increment_io_errors(),
-            ChildProcessExecutionError::Timeout(_) => self.increment_timeouts(),
+            ChildProcessExecutionError::Timeout(_) | ChildProcessExecutionError::CpuTimeout(_) => {
+                self.increment_timeouts()
+            }
+            ChildProcessExecutionError::Killed | ChildProcessExecutionError::Requeued => {
+                self.killed.fetch_add(1, ORDERING);
+            }
         }
     }
 
+    fn killed(&self) -> u64 {
+        self.killed.load(ORDERING)
+    }
+
     fn increment_timeouts(&self) {
         self.set_error_occurred();
         self.timeouts.fetch_add(1, ORDERING);
@@ -60,7 +138,7 @@ impl CommandMetrics {
         self.timeouts.load(ORDERING)
     }
 
-    fn increment_io_errors(&self) {
+    pub fn increment_io_errors(&self) {
         self.set_error_occurred();
         self.io_errors.fetch_add(1, ORDERING);
     }
@@ -77,19 +155,42 @@ impl CommandMetrics {
     fn exit_status_errors(&self) -> u64 {
         self.exit_status_errors.load(ORDERING)
     }
+
+    pub fn increment_output_failures(&self) {
+        self.set_error_occurred();
+        self.output_failures.fetch_add(1, ORDERING);
+    }
+
+    fn output_failures(&self) -> u64 {
+        self.output_failures.load(ORDERING)
+    }
+
+    pub fn add_dependency_failures(&self, count: u64) {
+        if count > 0 {
+            self.set_error_occurred();
+            self.dependency_failures.fetch_add(count, ORDERING);
+        }
+    }
+
+    fn dependency_failures(&self) -> u64 {
+        self.dependency_failures.load(ORDERING)
+    }
 }
 
 impl std::fmt::Display for CommandMetrics {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "commands_run={} total_failures={} spawn_errors={} timeouts={} io_errors={} exit_status_errors={}",
+            "commands_run={} total_failures={} spawn_errors={} timeouts={} io_errors={} exit_status_errors={} output_failures={} dependency_failures={} killed={}",
             self.commands_run(),
             self.total_failures(),
             self.spawn_errors(),
             self.timeouts(),
             self.io_errors(),
             self.exit_status_errors(),
+            self.output_failures(),
+            self.dependency_failures(),
+            self.killed(),
         )
     }
 }
@@ -0,0 +1,114 @@
+use anyhow::Context;
+
+use regex::bytes::Regex;
+
+use std::process::Output;
+
+use crate::command_line_args::CommandLineArgs;
+
+/// Success of a command determined from its output.
+#[derive(Debug, PartialEq)]
+pub enum OutputMatch {
+    Succeeded,
+    /// Failed with the reason.
+    Failed(&'static str),
+    /// The exit status determines success.
+    Undetermined,
+}
+
+/// Matches --success-regex and --failure-regex against the stdout and stderr of commands,
+/// overriding their exit status.
+///
+/// A failure regex match fails a command, otherwise a command succeeds if its output
+/// matches the success regex and fails if not.
+#[derive(Debug)]
+pub struct OutputMatcher {
+    success_regex: Option<Regex>,
+    failure_regex: Option<Regex>,
+}
+
+impl OutputMatcher {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        let regex = |pattern: &Option<String>, name: &str| {
+            pattern
+                .as_deref()
+                .map(|pattern| {
+                    Regex::new(pattern).with_context(|| format!("invalid --{} '{}'", name, pattern))
+                })
+                .transpose()
+        };
+
+        let success_regex = regex(&command_line_args.success_regex, "success-regex")?;
+        let failure_regex = regex(&command_line_args.failure_regex, "failure-regex")?;
+
+        if success_regex.is_none() && failure_regex.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            success_regex,
+            failure_regex,
+        }))
+    }
+
+    pub fn check(&self, output: &Output) -> OutputMatch {
+        let is_match =
+            |regex: &Regex| regex.is_match(&output.stdout) || regex.is_match(&output.stderr);
+
+        if self.failure_regex.as_ref().is_some_and(is_match) {
+            return OutputMatch::Failed("matched --failure-regex");
+        }
+
+        match &self.success_regex {
+            Some(success_regex) if is_match(success_regex) => OutputMatch::Succeeded,
+            Some(_) => OutputMatch::Failed("did not match --success-regex"),
+            None => OutputMatch::Undetermined,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::process::ExitStatus;
+
+    fn output(stdout: &str, stderr: &str) -> Output {
+        Output {
+            status: ExitStatus::default(),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: stderr.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let output_matcher = OutputMatcher {
+            success_regex: Some(Regex::new("^PASS").unwrap()),
+            failure_regex: Some(Regex::new("ERROR").unwrap()),
+        };
+
+        assert_eq!(
+            output_matcher.check(&output("PASS\n", "")),
+            OutputMatch::Succeeded
+        );
+        assert_eq!(
+            output_matcher.check(&output("PASS\n", "ERROR: disk\n")),
+            OutputMatch::Failed("matched --failure-regex")
+        );
+        assert_eq!(
+            output_matcher.check(&output("FAIL\n", "")),
+            OutputMatch::Failed("did not match --success-regex")
+        );
+
+        let output_matcher = OutputMatcher {
+            success_regex: None,
+            failure_regex: Some(Regex::new("ERROR").unwrap()),
+        };
+
+        assert_eq!(
+            output_matcher.check(&output("ok\n", "")),
+            OutputMatch::Undetermined
+        );
+    }
+}
@@ -22,7 +22,7 @@ pub struct CommandPathCache {
 impl CommandPathCache {
     pub fn new(command_line_args: &CommandLineArgs) -> Self {
         Self {
-            enabled: !command_line_args.disable_path_cache,
+            enabled: !command_line_args.disable_path_cache && !command_line_args.http,
             cache: Mutex::new(HashMap::new()),
         }
     }
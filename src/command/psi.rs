@@ -0,0 +1,173 @@
+use anyhow::Context;
+
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    task::JoinHandle,
+    time::Duration,
+};
+
+use tracing::{info, warn};
+
+use std::sync::Arc;
+
+use crate::command_line_args::CommandLineArgs;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Pressure stall information resource with a --psi-cpu or --psi-io threshold.
+#[derive(Clone, Copy, Debug)]
+struct PsiLimit {
+    resource: &'static str,
+    max_avg10: f64,
+}
+
+impl PsiLimit {
+    fn pressure(&self) -> anyhow::Result<f64> {
+        let path = format!("/proc/pressure/{}", self.resource);
+        let contents =
+            std::fs::read_to_string(&path).with_context(|| format!("error reading {}", path))?;
+
+        parse_some_avg10(&contents).with_context(|| format!("invalid pressure in {}", path))
+    }
+}
+
+/// Reduces the number of running commands while linux pressure stall information is above
+/// --psi-cpu or --psi-io, and raises it back to --jobs when pressure subsides.
+///
+/// Every sample one job slot is held back while under pressure, down to 1 running command,
+/// and one held back job slot is released otherwise.
+#[derive(Debug)]
+pub struct PsiThrottle {
+    monitor: JoinHandle<()>,
+}
+
+impl PsiThrottle {
+    pub fn new(
+        command_line_args: &CommandLineArgs,
+        command_semaphore: &Arc<Semaphore>,
+    ) -> anyhow::Result<Option<Self>> {
+        let limits: Vec<PsiLimit> = [
+            ("cpu", command_line_args.psi_cpu),
+            ("io", command_line_args.psi_io),
+        ]
+        .into_iter()
+        .filter_map(|(resource, max_avg10)| {
+            max_avg10.map(|max_avg10| PsiLimit {
+                resource,
+                max_avg10,
+            })
+        })
+        .collect();
+
+        if limits.is_empty() {
+            return Ok(None);
+        }
+
+        if !cfg!(target_os = "linux") {
+            anyhow::bail!("--psi-cpu and --psi-io are only supported on linux");
+        }
+
+        for limit in &limits {
+            limit.pressure()?;
+        }
+
+        let monitor = tokio::spawn(monitor(
+            limits,
+            Arc::clone(command_semaphore),
+            command_line_args.jobs,
+        ));
+
+        Ok(Some(Self { monitor }))
+    }
+}
+
+impl Drop for PsiThrottle {
+    fn drop(&mut self) {
+        self.monitor.abort();
+    }
+}
+
+/// First limit exceeded and its pressure.
+fn exceeded_limit(limits: &[PsiLimit]) -> anyhow::Result<Option<(PsiLimit, f64)>> {
+    for &limit in limits {
+        let pressure = limit.pressure()?;
+        if pressure > limit.max_avg10 {
+            return Ok(Some((limit, pressure)));
+        }
+    }
+    Ok(None)
+}
+
+async fn monitor(limits: Vec<PsiLimit>, command_semaphore: Arc<Semaphore>, jobs: usize) {
+    let mut held_permits: Vec<OwnedSemaphorePermit> = vec![];
+
+    loop {
+        tokio::time::sleep(SAMPLE_INTERVAL).await;
+
+        let exceeded = match exceeded_limit(&limits) {
+            Ok(exceeded) => exceeded,
+            Err(e) => {
+                warn!("pressure error, no longer throttling: {:#}", e);
+                return;
+            }
+        };
+
+        match exceeded {
+            Some((limit, pressure)) if held_permits.len() + 1 < jobs => {
+                // wait at most one sample for a running command to finish
+                let permit = tokio::time::timeout(
+                    SAMPLE_INTERVAL,
+                    Arc::clone(&command_semaphore).acquire_owned(),
+                )
+                .await;
+
+                if let Ok(Ok(permit)) = permit {
+                    held_permits.push(permit);
+                    info!(
+                        "{} pressure avg10={:.2} above --psi-{} {:.2}, reducing jobs to {}",
+                        limit.resource,
+                        pressure,
+                        limit.resource,
+                        limit.max_avg10,
+                        jobs - held_permits.len()
+                    );
+                }
+            }
+            None if !held_permits.is_empty() => {
+                held_permits.pop();
+                info!(
+                    "pressure subsided, raising jobs to {}",
+                    jobs - held_permits.len()
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+/// avg10 percentage of the "some" line of a /proc/pressure file.
+fn parse_some_avg10(contents: &str) -> Option<f64> {
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix("some "))?
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("avg10="))?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_some_avg10() {
+        let contents = "some avg10=8.53 avg60=10.34 avg300=8.30 total=977071064\nfull avg10=0.00 avg60=0.00 avg300=0.00 total=0\n";
+        assert_eq!(parse_some_avg10(contents), Some(8.53));
+
+        assert_eq!(
+            parse_some_avg10("full avg10=1.00 avg60=0.00 avg300=0.00 total=0\n"),
+            None
+        );
+    }
+}
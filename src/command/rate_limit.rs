@@ -0,0 +1,112 @@
+use tokio::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tracing::trace;
+
+use crate::command_line_args::CommandLineArgs;
+
+/// Token bucket holding at most 1 token, refilled at the --max-rate.
+///
+/// Tokens may go negative to reserve future starts for waiting callers.
+#[derive(Debug)]
+struct TokenBucket {
+    rate_per_second: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    const CAPACITY: f64 = 1.0;
+
+    fn new(rate_per_second: f64, now: Instant) -> Self {
+        Self {
+            rate_per_second,
+            tokens: Self::CAPACITY,
+            last_refill: now,
+        }
+    }
+
+    /// Take a token, returning how long to wait before it is available.
+    fn take(&mut self, now: Instant) -> Duration {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate_per_second).min(Self::CAPACITY);
+        self.last_refill = now;
+
+        self.tokens -= 1.0;
+
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate_per_second)
+        }
+    }
+}
+
+/// Limits the rate of starting commands with --max-rate, and delays each start by a random
+/// --jitter.
+#[derive(Debug)]
+pub struct RateLimiter {
+    token_bucket: Option<Mutex<TokenBucket>>,
+    jitter: Option<Duration>,
+}
+
+impl RateLimiter {
+    pub fn new(command_line_args: &CommandLineArgs) -> Option<Self> {
+        let jitter = command_line_args
+            .jitter
+            .filter(|jitter| *jitter > 0f64)
+            .map(Duration::from_secs_f64);
+
+        if command_line_args.max_rate.is_none() && jitter.is_none() {
+            return None;
+        }
+
+        Some(Self {
+            token_bucket: command_line_args
+                .max_rate
+                .map(|max_rate| Mutex::new(TokenBucket::new(max_rate, Instant::now()))),
+            jitter,
+        })
+    }
+
+    /// Wait until the next command may start.
+    pub async fn wait(&self) {
+        let mut wait = Duration::ZERO;
+
+        if let Some(token_bucket) = &self.token_bucket {
+            wait += token_bucket.lock().await.take(Instant::now());
+        }
+
+        if let Some(jitter) = self.jitter {
+            wait += jitter.mul_f64(fastrand::f64());
+        }
+
+        trace!("rate limiter wait = {:?}", wait);
+
+        tokio::time::sleep(wait).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut token_bucket = TokenBucket::new(4.0, start);
+
+        assert_eq!(token_bucket.take(start), Duration::ZERO);
+        assert_eq!(token_bucket.take(start), Duration::from_millis(250));
+        assert_eq!(token_bucket.take(start), Duration::from_millis(500));
+
+        // tokens do not accumulate past the capacity while idle
+        let later = start + Duration::from_secs(10);
+        assert_eq!(token_bucket.take(later), Duration::ZERO);
+        assert_eq!(token_bucket.take(later), Duration::from_millis(250));
+    }
+}
@@ -0,0 +1,81 @@
+use tracing::info;
+
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use crate::{command_line_args::CommandLineArgs, input::InputLineNumber};
+
+#[derive(Debug)]
+struct CommandStats {
+    first_job_number: usize,
+    input_value: String,
+    runs: usize,
+    failures: usize,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+/// Aggregates the repetitions of each distinct command for --repeat.
+#[derive(Debug, Default)]
+pub struct RepeatStats {
+    /// Repetitions share the input line they were built from.
+    commands: Mutex<HashMap<String, CommandStats>>,
+}
+
+impl RepeatStats {
+    pub fn new(command_line_args: &CommandLineArgs) -> Option<Self> {
+        command_line_args.repeat.map(|_| Self::default())
+    }
+
+    pub fn add(
+        &self,
+        input_line_number: &InputLineNumber,
+        input_value: &str,
+        job_number: usize,
+        elapsed: Duration,
+        succeeded: bool,
+    ) {
+        let mut commands = self.commands.lock().unwrap();
+
+        let stats = commands
+            .entry(input_line_number.to_string())
+            .or_insert_with(|| CommandStats {
+                first_job_number: job_number,
+                input_value: input_value.to_owned(),
+                runs: 0,
+                failures: 0,
+                total: Duration::ZERO,
+                min: Duration::MAX,
+                max: Duration::ZERO,
+            });
+
+        stats.first_job_number = stats.first_job_number.min(job_number);
+        stats.runs += 1;
+        if !succeeded {
+            stats.failures += 1;
+        }
+        stats.total += elapsed;
+        stats.min = stats.min.min(elapsed);
+        stats.max = stats.max.max(elapsed);
+    }
+
+    pub fn log_summary(&self) {
+        let commands = self.commands.lock().unwrap();
+
+        let mut commands: Vec<_> = commands.iter().collect();
+        commands.sort_by_key(|(_, stats)| stats.first_job_number);
+
+        for (input_line_number, stats) in commands {
+            info!(
+                "repeat line={} input={:?} runs={} failures={} min={:?} mean={:?} max={:?}",
+                input_line_number,
+                stats.input_value,
+                stats.runs,
+                stats.failures,
+                stats.min,
+                stats.total / stats.runs as u32,
+                stats.max,
+            );
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use anyhow::Context;
+
+use std::{fmt::Write, sync::Mutex, time::Duration};
+
+use crate::{
+    command_line_args::{CommandLineArgs, ReportFormat},
+    common::OwnedCommandAndArgs,
+};
+
+use super::RunAttemptResult;
+
+#[derive(Debug, PartialEq)]
+enum TestOutcome {
+    Passed,
+    /// Command ran and exited unsuccessfully.
+    Failed(String),
+    /// Command could not be spawned, timed out, or had an i/o error.
+    Error(String),
+    /// Warmup job excluded from results with --warmup.
+    Skipped(String),
+}
+
+#[derive(Debug)]
+struct TestCase {
+    job_number: usize,
+    name: String,
+    duration: Duration,
+    outcome: TestOutcome,
+    stdout: String,
+    stderr: String,
+}
+
+/// Collects a test case per command and writes them as TAP or JUnit XML with --report.
+#[derive(Debug)]
+pub struct TestReport {
+    format: ReportFormat,
+    path: &'static str,
+    test_cases: Mutex<Vec<TestCase>>,
+}
+
+impl TestReport {
+    pub fn new(command_line_args: &'static CommandLineArgs) -> Option<Self> {
+        command_line_args.report.as_ref().map(|spec| Self {
+            format: spec.format,
+            path: &spec.path,
+            test_cases: Mutex::new(vec![]),
+        })
+    }
+
+    pub fn add_test_case(
+        &self,
+        job_number: usize,
+        command_and_args: &OwnedCommandAndArgs,
+        duration: Duration,
+        result: &RunAttemptResult,
+        warmup: bool,
+    ) {
+        let (mut outcome, stdout, stderr) = match result {
+            RunAttemptResult::SpawnError(e) => (
+                TestOutcome::Error(format!("spawn error: {:#}", e)),
+                String::new(),
+                String::new(),
+            ),
+            RunAttemptResult::ExecutionError(e) => (
+                TestOutcome::Error(e.to_string()),
+                String::new(),
+                String::new(),
+            ),
+            RunAttemptResult::Completed { output, succeeded } => (
+                if *succeeded {
+                    TestOutcome::Passed
+                } else {
+                    TestOutcome::Failed(format!("exit_status={}", output.status))
+                },
+                String::from_utf8_lossy(&output.stdout).into_owned(),
+                String::from_utf8_lossy(&output.stderr).into_owned(),
+            ),
+        };
+
+        if warmup {
+            outcome = TestOutcome::Skipped("warmup".to_owned());
+        }
+
+        let name = std::iter::once(command_and_args.command_path.to_string_lossy().into_owned())
+            .chain(
+                command_and_args
+                    .args
+                    .iter()
+                    .map(|arg| arg.to_string_lossy().into_owned()),
+            )
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        self.test_cases.lock().unwrap().push(TestCase {
+            job_number,
+            name,
+            duration,
+            outcome,
+            stdout,
+            stderr,
+        });
+    }
+
+    pub async fn write(&self) -> anyhow::Result<()> {
+        let contents = {
+            let mut test_cases = self.test_cases.lock().unwrap();
+            test_cases.sort_by_key(|test_case| test_case.job_number);
+
+            match self.format {
+                ReportFormat::Tap => tap(&test_cases),
+                ReportFormat::Junit => junit(&test_cases),
+            }
+        };
+
+        tokio::fs::write(self.path, contents)
+            .await
+            .with_context(|| format!("error writing report '{}'", self.path))
+    }
+}
+
+fn tap(test_cases: &[TestCase]) -> String {
+    let mut tap = format!("TAP version 13\n1..{}\n", test_cases.len());
+
+    for (i, test_case) in test_cases.iter().enumerate() {
+        let (status, message) = match &test_case.outcome {
+            TestOutcome::Passed | TestOutcome::Skipped(_) => ("ok", None),
+            TestOutcome::Failed(message) | TestOutcome::Error(message) => ("not ok", Some(message)),
+        };
+
+        let _ = write!(
+            tap,
+            "{} {} - {}",
+            status,
+            i + 1,
+            test_case.name.replace('#', "\\#")
+        );
+        if let TestOutcome::Skipped(reason) = &test_case.outcome {
+            let _ = write!(tap, " # SKIP {}", reason);
+        }
+        tap.push('\n');
+        tap.push_str("  ---\n");
+        let _ = writeln!(
+            tap,
+            "  duration_ms: {:.3}",
+            test_case.duration.as_secs_f64() * 1000.0
+        );
+        if let Some(message) = message {
+            let _ = writeln!(tap, "  message: {:?}", message);
+        }
+        for (key, output) in [("stdout", &test_case.stdout), ("stderr", &test_case.stderr)] {
+            if !output.is_empty() {
+                let _ = writeln!(tap, "  {}: |", key);
+                for line in output.lines() {
+                    let _ = writeln!(tap, "    {}", line);
+                }
+            }
+        }
+        tap.push_str("  ...\n");
+    }
+
+    tap
+}
+
+fn junit(test_cases: &[TestCase]) -> String {
+    let count = |f: fn(&TestOutcome) -> bool| {
+        test_cases
+            .iter()
+            .filter(|test_case| f(&test_case.outcome))
+            .count()
+    };
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+
+    let _ = writeln!(
+        xml,
+        "  <testsuite name=\"rust-parallel\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+        test_cases.len(),
+        count(|outcome| matches!(outcome, TestOutcome::Failed(_))),
+        count(|outcome| matches!(outcome, TestOutcome::Error(_))),
+        count(|outcome| matches!(outcome, TestOutcome::Skipped(_))),
+        test_cases
+            .iter()
+            .map(|test_case| test_case.duration.as_secs_f64())
+            .sum::<f64>()
+    );
+
+    for test_case in test_cases {
+        let _ = writeln!(
+            xml,
+            "    <testcase name=\"{}\" classname=\"rust-parallel\" time=\"{:.3}\">",
+            xml_escape(&test_case.name),
+            test_case.duration.as_secs_f64()
+        );
+        match &test_case.outcome {
+            TestOutcome::Passed => {}
+            TestOutcome::Failed(message) => {
+                let _ = writeln!(xml, "      <failure message=\"{}\"/>", xml_escape(message));
+            }
+            TestOutcome::Error(message) => {
+                let _ = writeln!(xml, "      <error message=\"{}\"/>", xml_escape(message));
+            }
+            TestOutcome::Skipped(message) => {
+                let _ = writeln!(xml, "      <skipped message=\"{}\"/>", xml_escape(message));
+            }
+        }
+        for (element, output) in [
+            ("system-out", &test_case.stdout),
+            ("system-err", &test_case.stderr),
+        ] {
+            if !output.is_empty() {
+                let _ = writeln!(
+                    xml,
+                    "      <{}>{}</{}>",
+                    element,
+                    xml_escape(output),
+                    element
+                );
+            }
+        }
+        xml.push_str("    </testcase>\n");
+    }
+
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+
+    xml
+}
+
+/// Escape text and attribute values, dropping control characters XML 1.0 does not allow.
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_cases() -> Vec<TestCase> {
+        vec![
+            TestCase {
+                job_number: 1,
+                name: "./test.sh a".to_owned(),
+                duration: Duration::from_millis(5),
+                outcome: TestOutcome::Passed,
+                stdout: "pass\n".to_owned(),
+                stderr: String::new(),
+            },
+            TestCase {
+                job_number: 2,
+                name: "./test.sh <b>".to_owned(),
+                duration: Duration::from_millis(10),
+                outcome: TestOutcome::Failed("exit_status=exit status: 1".to_owned()),
+                stdout: String::new(),
+                stderr: "bad & worse\n".to_owned(),
+            },
+            TestCase {
+                job_number: 3,
+                name: "./test.sh c".to_owned(),
+                duration: Duration::from_millis(1),
+                outcome: TestOutcome::Skipped("warmup".to_owned()),
+                stdout: String::new(),
+                stderr: String::new(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_tap() {
+        assert_eq!(
+            tap(&test_cases()),
+            "TAP version 13\n\
+             1..3\n\
+             ok 1 - ./test.sh a\n  ---\n  duration_ms: 5.000\n  stdout: |\n    pass\n  ...\n\
+             not ok 2 - ./test.sh <b>\n  ---\n  duration_ms: 10.000\n  message: \"exit_status=exit status: 1\"\n  stderr: |\n    bad & worse\n  ...\n\
+             ok 3 - ./test.sh c # SKIP warmup\n  ---\n  duration_ms: 1.000\n  ...\n"
+        );
+    }
+
+    #[test]
+    fn test_junit() {
+        let xml = junit(&test_cases());
+
+        assert!(xml.contains(
+            "<testsuite name=\"rust-parallel\" tests=\"3\" failures=\"1\" errors=\"0\" skipped=\"1\" time=\"0.016\">"
+        ));
+        assert!(xml.contains(
+            "<testcase name=\"./test.sh &lt;b&gt;\" classname=\"rust-parallel\" time=\"0.010\">\n      <failure message=\"exit_status=exit status: 1\"/>\n      <system-err>bad &amp; worse\n</system-err>"
+        ));
+        assert!(xml.contains(
+            "<testcase name=\"./test.sh c\" classname=\"rust-parallel\" time=\"0.001\">\n      <skipped message=\"warmup\"/>"
+        ));
+    }
+}
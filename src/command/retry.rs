@@ -0,0 +1,125 @@
+use std::{process::ExitStatus, time::Duration};
+
+use crate::command_line_args::{CommandLineArgs, RetryExitStatus};
+
+use super::RunAttemptResult;
+
+/// Retries of failed commands from --retries, --retry-delay, and --retry-backoff.
+#[derive(Debug)]
+pub struct RetryPolicy {
+    retries: usize,
+    delay_seconds: f64,
+    backoff: f64,
+    /// Retry only these exit statuses if not empty.
+    exit_statuses: &'static [RetryExitStatus],
+}
+
+impl RetryPolicy {
+    pub fn new(command_line_args: &'static CommandLineArgs) -> Self {
+        Self {
+            retries: command_line_args.retries,
+            delay_seconds: command_line_args.retry_delay,
+            backoff: command_line_args.retry_backoff,
+            exit_statuses: &command_line_args.retry_on_exit_codes,
+        }
+    }
+
+    /// With --retry-on-exit-codes only commands that exited with a listed status are retried.
+    pub fn is_retryable(&self, result: &RunAttemptResult) -> bool {
+        if self.exit_statuses.is_empty() {
+            return true;
+        }
+
+        match result {
+            RunAttemptResult::Completed { output, .. } => self
+                .exit_statuses
+                .contains(&retry_exit_status(output.status)),
+            RunAttemptResult::SpawnError(_) | RunAttemptResult::ExecutionError(_) => false,
+        }
+    }
+
+    /// Retries of a job, a retries= annotation overrides --retries.
+    pub fn retries(&self, job_retries: Option<usize>) -> usize {
+        job_retries.unwrap_or(self.retries)
+    }
+
+    /// Delay before retry attempt 1, 2, ..., multiplied by the backoff after each retry.
+    pub fn delay(&self, attempt: usize) -> Duration {
+        let exponent = i32::try_from(attempt.saturating_sub(1)).unwrap_or(i32::MAX);
+
+        Duration::try_from_secs_f64(self.delay_seconds * self.backoff.powi(exponent))
+            .unwrap_or(Duration::MAX)
+    }
+}
+
+#[cfg(unix)]
+fn retry_exit_status(exit_status: ExitStatus) -> RetryExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+
+    match exit_status.signal() {
+        Some(signal) => RetryExitStatus::Signal(signal),
+        None => RetryExitStatus::Code(exit_status.code().unwrap_or(-1)),
+    }
+}
+
+#[cfg(not(unix))]
+fn retry_exit_status(exit_status: ExitStatus) -> RetryExitStatus {
+    RetryExitStatus::Code(exit_status.code().unwrap_or(-1))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let retry_policy = RetryPolicy {
+            retries: 3,
+            delay_seconds: 0.5,
+            backoff: 2.0,
+            exit_statuses: &[],
+        };
+
+        assert_eq!(retry_policy.delay(1), Duration::from_millis(500));
+        assert_eq!(retry_policy.delay(2), Duration::from_secs(1));
+        assert_eq!(retry_policy.delay(3), Duration::from_secs(2));
+
+        assert_eq!(retry_policy.retries(None), 3);
+        assert_eq!(retry_policy.retries(Some(1)), 1);
+
+        let retry_policy = RetryPolicy {
+            retries: 0,
+            delay_seconds: 0.0,
+            backoff: 2.0,
+            exit_statuses: &[],
+        };
+
+        assert_eq!(retry_policy.delay(5), Duration::ZERO);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_retryable() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let retry_policy = RetryPolicy {
+            retries: 1,
+            delay_seconds: 0.0,
+            backoff: 2.0,
+            exit_statuses: &[RetryExitStatus::Code(75), RetryExitStatus::Signal(9)],
+        };
+
+        let completed = |status| {
+            RunAttemptResult::completed(std::process::Output {
+                status: ExitStatus::from_raw(status),
+                stdout: vec![],
+                stderr: vec![],
+            })
+        };
+
+        assert!(retry_policy.is_retryable(&completed(75 << 8)));
+        assert!(retry_policy.is_retryable(&completed(9)));
+        assert!(!retry_policy.is_retryable(&completed(1 << 8)));
+        assert!(!retry_policy.is_retryable(&completed(15)));
+    }
+}
@@ -0,0 +1,77 @@
+use tokio::sync::watch;
+
+use tracing::info;
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::process::RunningJobs;
+
+/// Pausing and draining of the run from the --control-socket.
+///
+/// While paused no new commands are started and running commands are stopped with SIGSTOP
+/// until resumed with SIGCONT.  Once drained the remaining jobs are skipped while running
+/// commands finish.
+#[derive(Debug)]
+pub struct RunControl {
+    paused: watch::Sender<bool>,
+    drained: AtomicBool,
+    running_jobs: Arc<RunningJobs>,
+}
+
+impl RunControl {
+    pub fn new(running_jobs: &Arc<RunningJobs>) -> Self {
+        Self {
+            paused: watch::Sender::new(false),
+            drained: AtomicBool::new(false),
+            running_jobs: Arc::clone(running_jobs),
+        }
+    }
+
+    pub fn paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Returns false if already paused.
+    pub fn pause(&self) -> bool {
+        if self.paused.send_replace(true) {
+            return false;
+        }
+
+        let stopped = self.running_jobs.stop_all();
+        info!("paused, stopped {} running commands", stopped);
+
+        true
+    }
+
+    /// Returns false if not paused.
+    pub fn resume(&self) -> bool {
+        if !self.paused.send_replace(false) {
+            return false;
+        }
+
+        let continued = self.running_jobs.continue_all();
+        info!("resumed, continued {} running commands", continued);
+
+        true
+    }
+
+    pub fn drained(&self) -> bool {
+        self.drained.load(Ordering::SeqCst)
+    }
+
+    /// Skip remaining jobs, also resumes a paused run so they are skipped.
+    pub fn drain(&self) {
+        self.drained.store(true, Ordering::SeqCst);
+        self.resume();
+    }
+
+    pub async fn wait_until_resumed(&self) {
+        let mut paused = self.paused.subscribe();
+
+        // the sender is held by self, so waiting never fails
+        let _ = paused.wait_for(|paused| !paused).await;
+    }
+}
@@ -0,0 +1,167 @@
+use tokio::sync::OwnedMutexGuard;
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+/// Pool of job slot numbers `1..=jobs`.
+///
+/// A slot is held for as long as a command is running so that commands
+/// can be placed consistently per slot.  The lowest free slot is always
+/// handed out first.
+#[derive(Debug)]
+pub struct SlotPool {
+    free_slots: Mutex<BinaryHeap<Reverse<usize>>>,
+}
+
+impl SlotPool {
+    pub fn new(jobs: usize) -> Arc<Self> {
+        Arc::new(Self {
+            free_slots: Mutex::new((1..=jobs).map(Reverse).collect()),
+        })
+    }
+
+    /// Acquire a free slot.  The caller must hold a command semaphore permit
+    /// so a free slot is always available.
+    pub fn acquire(self: &Arc<Self>) -> SlotGuard {
+        let slot = self
+            .free_slots
+            .lock()
+            .unwrap()
+            .pop()
+            .map(|Reverse(slot)| slot)
+            .expect("SlotPool::acquire: no free slots");
+
+        SlotGuard {
+            slot,
+            pool: Some(Arc::clone(self)),
+            _key_lock: None,
+        }
+    }
+
+    /// Add a slot when the number of jobs is raised while running.
+    pub fn add_slot(&self, slot: usize) {
+        self.free_slots.lock().unwrap().push(Reverse(slot));
+    }
+}
+
+/// Fixed slot per --slot-key, so jobs of a key run one at a time on the same slot while
+/// jobs of other keys run in parallel.
+///
+/// Keys are assigned to slots `1..=jobs` round robin in the order they are first seen.
+#[derive(Debug)]
+pub struct SlotKeys {
+    slot_locks: Vec<Arc<tokio::sync::Mutex<()>>>,
+    key_slots: Mutex<HashMap<String, usize>>,
+}
+
+impl SlotKeys {
+    pub fn new(jobs: usize) -> Self {
+        Self {
+            slot_locks: (0..jobs)
+                .map(|_| Arc::new(tokio::sync::Mutex::new(())))
+                .collect(),
+            key_slots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn slot(&self, key: &str) -> usize {
+        let mut key_slots = self.key_slots.lock().unwrap();
+
+        let next_slot = key_slots.len() % self.slot_locks.len() + 1;
+
+        *key_slots.entry(key.to_owned()).or_insert(next_slot)
+    }
+
+    /// Wait until the slot of the key is free.
+    pub fn acquire(&self, key: &str) -> impl Future<Output = SlotGuard> + Send + 'static {
+        let slot = self.slot(key);
+        let slot_lock = Arc::clone(&self.slot_locks[slot - 1]);
+
+        async move {
+            SlotGuard {
+                slot,
+                pool: None,
+                _key_lock: Some(slot_lock.lock_owned().await),
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SlotGuard {
+    slot: usize,
+    /// Pool the slot is returned to, not set for --slot-key slots.
+    pool: Option<Arc<SlotPool>>,
+    _key_lock: Option<OwnedMutexGuard<()>>,
+}
+
+impl SlotGuard {
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+}
+
+impl Drop for SlotGuard {
+    fn drop(&mut self) {
+        if let Some(pool) = &self.pool {
+            pool.free_slots.lock().unwrap().push(Reverse(self.slot));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::FutureExt;
+
+    use super::*;
+
+    #[test]
+    fn test_slot_pool() {
+        let slot_pool = SlotPool::new(3);
+
+        let slot1 = slot_pool.acquire();
+        let slot2 = slot_pool.acquire();
+        assert_eq!(slot1.slot(), 1);
+        assert_eq!(slot2.slot(), 2);
+
+        drop(slot1);
+
+        let slot1 = slot_pool.acquire();
+        let slot3 = slot_pool.acquire();
+        assert_eq!(slot1.slot(), 1);
+        assert_eq!(slot3.slot(), 3);
+
+        slot_pool.add_slot(4);
+        assert_eq!(slot_pool.acquire().slot(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_slot_keys() {
+        let slot_keys = SlotKeys::new(2);
+
+        let a1 = slot_keys.acquire("a").await;
+        let b1 = slot_keys.acquire("b").await;
+        assert_eq!(a1.slot(), 1);
+        assert_eq!(b1.slot(), 2);
+
+        assert_eq!(
+            slot_keys.acquire("b").now_or_never().map(|g| g.slot()),
+            None
+        );
+
+        drop(a1);
+        let c1 = slot_keys.acquire("c").await;
+        assert_eq!(c1.slot(), 1);
+
+        let mut a2 = Box::pin(slot_keys.acquire("a"));
+        assert!(futures::poll!(a2.as_mut()).is_pending());
+
+        drop(c1);
+        assert_eq!(a2.await.slot(), 1);
+        drop(b1);
+    }
+}
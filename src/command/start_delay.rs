@@ -0,0 +1,36 @@
+use tokio::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::command_line_args::CommandLineArgs;
+
+/// Paces job starts with --delay, independent of the number of job slots.
+#[derive(Debug)]
+pub struct StartDelay {
+    delay: Duration,
+    last_start: Mutex<Option<Instant>>,
+}
+
+impl StartDelay {
+    pub fn new(command_line_args: &CommandLineArgs) -> Option<Self> {
+        command_line_args
+            .delay
+            .filter(|delay| *delay > 0f64)
+            .map(|delay| Self {
+                delay: Duration::from_secs_f64(delay),
+                last_start: Mutex::new(None),
+            })
+    }
+
+    /// Wait until --delay has passed since the previous job started.
+    pub async fn wait(&self) {
+        let mut last_start = self.last_start.lock().await;
+
+        if let Some(last_start) = *last_start {
+            tokio::time::sleep_until(last_start + self.delay).await;
+        }
+
+        *last_start = Some(Instant::now());
+    }
+}
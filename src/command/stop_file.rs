@@ -0,0 +1,51 @@
+use tokio::{task::JoinHandle, time::Duration};
+
+use tracing::warn;
+
+use std::{path::PathBuf, sync::Arc};
+
+use crate::command_line_args::CommandLineArgs;
+
+use super::run_control::RunControl;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Drains the run once the --stop-file exists: remaining jobs are skipped while running
+/// commands finish.
+#[derive(Debug)]
+pub struct StopFile {
+    monitor: JoinHandle<()>,
+}
+
+impl StopFile {
+    pub fn new(command_line_args: &CommandLineArgs, run_control: &Arc<RunControl>) -> Option<Self> {
+        let path = PathBuf::from(command_line_args.stop_file.as_ref()?);
+
+        let monitor = tokio::spawn(monitor(path, Arc::clone(run_control)));
+
+        Some(Self { monitor })
+    }
+}
+
+impl Drop for StopFile {
+    fn drop(&mut self) {
+        self.monitor.abort();
+    }
+}
+
+async fn monitor(path: PathBuf, run_control: Arc<RunControl>) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if tokio::fs::try_exists(&path).await.unwrap_or(false) {
+            warn!(
+                "--stop-file '{}' exists, skipping remaining jobs",
+                path.display()
+            );
+            run_control.drain();
+            return;
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use tokio::sync::Semaphore;
+
+use std::sync::Arc;
+
+use crate::{command_line_args::CommandLineArgs, parser::buffered::BufferedInputLineParser};
+
+use super::slot_pool::SlotPool;
+
+/// Second pipeline stage configured with --then.
+///
+/// Has its own semaphore and slots so first and second stage commands
+/// do not compete for the same permits.
+pub struct ThenStage {
+    pub parser: BufferedInputLineParser,
+    pub semaphore: Arc<Semaphore>,
+    pub slot_pool: Arc<SlotPool>,
+}
+
+impl ThenStage {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        let Some(then_command) = &command_line_args.then else {
+            return Ok(None);
+        };
+
+        let jobs = command_line_args
+            .then_jobs
+            .unwrap_or(command_line_args.jobs);
+
+        Ok(Some(Self {
+            parser: BufferedInputLineParser::new_then_stage(command_line_args, then_command)?,
+            semaphore: Arc::new(Semaphore::new(jobs)),
+            slot_pool: SlotPool::new(jobs),
+        }))
+    }
+}
@@ -0,0 +1,95 @@
+use anyhow::Context;
+
+use tokio::sync::Semaphore;
+
+use tracing::info;
+
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use crate::command_line_args::CommandLineArgs;
+
+/// Dispatches jobs in waves of --wave-size, every job of a wave finishes before the
+/// next wave starts, after an optional --wave-delay and --wave-hook.
+#[derive(Debug)]
+pub struct Waves {
+    wave_size: usize,
+    delay: Option<Duration>,
+    hook: Option<&'static str>,
+    shell_path: &'static str,
+    shell_argument: &'static str,
+    dispatched_jobs: AtomicUsize,
+}
+
+impl Waves {
+    pub fn new(command_line_args: &'static CommandLineArgs) -> Option<Self> {
+        command_line_args.wave_size.map(|wave_size| Self {
+            wave_size,
+            delay: command_line_args.wave_delay.map(Duration::from_secs_f64),
+            hook: command_line_args.wave_hook.as_deref(),
+            shell_path: &command_line_args.shell_path,
+            shell_argument: &command_line_args.shell_argument,
+            dispatched_jobs: AtomicUsize::new(0),
+        })
+    }
+
+    /// Called before dispatching each job, waits for the running wave when a new one starts.
+    ///
+    /// All permits of command_semaphore are free once every running job has finished.
+    pub async fn before_dispatch(
+        &self,
+        command_semaphore: &Semaphore,
+        jobs: usize,
+    ) -> anyhow::Result<()> {
+        let dispatched_jobs = self.dispatched_jobs.fetch_add(1, Ordering::SeqCst);
+
+        if dispatched_jobs == 0 || !dispatched_jobs.is_multiple_of(self.wave_size) {
+            return Ok(());
+        }
+
+        let wave = dispatched_jobs / self.wave_size;
+
+        let permits = command_semaphore
+            .acquire_many(u32::try_from(jobs).unwrap_or(u32::MAX))
+            .await
+            .context("command_semaphore.acquire_many error")?;
+
+        info!("wave {} finished", wave);
+
+        if let Some(delay) = self.delay {
+            tokio::time::sleep(delay).await;
+        }
+
+        if let Some(hook) = self.hook {
+            self.run_hook(hook, wave).await?;
+        }
+
+        drop(permits);
+
+        Ok(())
+    }
+
+    /// Run the hook with the shell, the finished wave number is in $RUST_PARALLEL_WAVE.
+    async fn run_hook(&self, hook: &str, wave: usize) -> anyhow::Result<()> {
+        let status = tokio::process::Command::new(self.shell_path)
+            .args(self.shell_argument.split_whitespace())
+            .arg(hook)
+            .env("RUST_PARALLEL_WAVE", wave.to_string())
+            .status()
+            .await
+            .with_context(|| format!("error running wave hook '{}'", hook))?;
+
+        if !status.success() {
+            anyhow::bail!(
+                "wave hook '{}' failed after wave {}: {}, not starting further waves",
+                hook,
+                wave,
+                status
+            );
+        }
+
+        Ok(())
+    }
+}
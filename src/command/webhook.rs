@@ -0,0 +1,131 @@
+use anyhow::Context;
+
+use serde::Serialize;
+
+use tokio::task::JoinHandle;
+
+use tracing::{debug, warn};
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use crate::command_line_args::{CommandLineArgs, NotifyOn};
+
+use super::metrics::{CommandMetrics, CommandMetricsSummary};
+
+#[derive(Debug, Serialize)]
+struct WebhookPayload {
+    /// "failure_threshold" during the run or "finished" at the end.
+    event: &'static str,
+    success: bool,
+    elapsed_seconds: f64,
+    #[serde(flatten)]
+    metrics: CommandMetricsSummary,
+}
+
+/// POSTs a JSON run summary to --notify-url.
+#[derive(Debug)]
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: &'static str,
+    notify_on: NotifyOn,
+    failure_threshold: Option<u64>,
+    threshold_notified: AtomicBool,
+    threshold_task: Mutex<Option<JoinHandle<()>>>,
+    start_time: Instant,
+}
+
+impl WebhookNotifier {
+    pub fn new(command_line_args: &'static CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        let Some(url) = &command_line_args.notify_url else {
+            return Ok(None);
+        };
+
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("rust-parallel/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+
+        Ok(Some(Self {
+            client,
+            url,
+            notify_on: command_line_args.notify_on,
+            failure_threshold: command_line_args.notify_failure_threshold,
+            threshold_notified: AtomicBool::new(false),
+            threshold_task: Mutex::new(None),
+            start_time: Instant::now(),
+        }))
+    }
+
+    fn payload(&self, event: &'static str, command_metrics: &CommandMetrics) -> WebhookPayload {
+        WebhookPayload {
+            event,
+            success: !command_metrics.error_occurred(),
+            elapsed_seconds: self.start_time.elapsed().as_secs_f64(),
+            metrics: command_metrics.summary(),
+        }
+    }
+
+    /// Called after a command fails, notifies once when failures exceed --notify-failure-threshold.
+    pub fn command_failed(&self, command_metrics: &CommandMetrics) {
+        let Some(failure_threshold) = self.failure_threshold else {
+            return;
+        };
+
+        if command_metrics.total_failures() <= failure_threshold
+            || self.threshold_notified.swap(true, Ordering::SeqCst)
+        {
+            return;
+        }
+
+        let payload = self.payload("failure_threshold", command_metrics);
+        let client = self.client.clone();
+        let url = self.url;
+
+        let task = tokio::spawn(async move {
+            if let Err(e) = post(&client, url, &payload).await {
+                warn!("webhook notification error: {:#}", e);
+            }
+        });
+
+        *self.threshold_task.lock().unwrap() = Some(task);
+    }
+
+    pub async fn run_finished(&self, command_metrics: &CommandMetrics) {
+        let threshold_task = self.threshold_task.lock().unwrap().take();
+        if let Some(threshold_task) = threshold_task {
+            let _ = threshold_task.await;
+        }
+
+        if self.notify_on == NotifyOn::Failure && !command_metrics.error_occurred() {
+            return;
+        }
+
+        let payload = self.payload("finished", command_metrics);
+
+        if let Err(e) = post(&self.client, self.url, &payload).await {
+            warn!("webhook notification error: {:#}", e);
+        }
+    }
+}
+
+async fn post(client: &reqwest::Client, url: &str, payload: &WebhookPayload) -> anyhow::Result<()> {
+    let body = serde_json::to_string(payload)?;
+
+    debug!("posting webhook notification to {}: {}", url, body);
+
+    client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .with_context(|| format!("error posting to '{}'", url))?;
+
+    Ok(())
+}
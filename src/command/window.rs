@@ -0,0 +1,127 @@
+use anyhow::Context;
+
+use chrono::{Local, NaiveTime, Timelike};
+
+use tracing::info;
+
+use std::time::Duration;
+
+use crate::command_line_args::CommandLineArgs;
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// Time of day window configured with --window, e.g. 22:00-06:00.
+///
+/// Commands are only started inside the window, a window ending before it starts
+/// spans midnight.
+#[derive(Debug, Eq, PartialEq)]
+pub struct ExecutionWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl ExecutionWindow {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        command_line_args
+            .window
+            .as_deref()
+            .map(|window| {
+                Self::parse(window).with_context(|| format!("invalid window '{}'", window))
+            })
+            .transpose()
+    }
+
+    fn parse(window: &str) -> anyhow::Result<Self> {
+        let Some((start, end)) = window.split_once('-') else {
+            anyhow::bail!("expected HH:MM-HH:MM");
+        };
+
+        let start = NaiveTime::parse_from_str(start.trim(), "%H:%M")?;
+        let end = NaiveTime::parse_from_str(end.trim(), "%H:%M")?;
+
+        if start == end {
+            anyhow::bail!("window start and end are equal");
+        }
+
+        Ok(Self { start, end })
+    }
+
+    /// Time until the window opens, or None if time is inside the window.
+    fn duration_until_open(&self, time: NaiveTime) -> Option<Duration> {
+        let inside = if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        };
+
+        if inside {
+            return None;
+        }
+
+        let seconds = (self.start.num_seconds_from_midnight() + SECONDS_PER_DAY
+            - time.num_seconds_from_midnight())
+            % SECONDS_PER_DAY;
+
+        Some(Duration::from_secs(seconds.max(1).into()))
+    }
+
+    /// Sleep until local time is inside the window.
+    pub async fn wait_until_open(&self) {
+        while let Some(duration) = self.duration_until_open(Local::now().time()) {
+            info!(
+                "outside window {}-{}, pausing for {:?}",
+                self.start.format("%H:%M"),
+                self.end.format("%H:%M"),
+                duration
+            );
+            tokio::time::sleep(duration).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            ExecutionWindow::parse("22:00-06:00").unwrap(),
+            ExecutionWindow {
+                start: time(22, 0),
+                end: time(6, 0),
+            }
+        );
+        assert!(ExecutionWindow::parse("22:00").is_err());
+        assert!(ExecutionWindow::parse("25:00-06:00").is_err());
+        assert!(ExecutionWindow::parse("06:00-06:00").is_err());
+    }
+
+    #[test]
+    fn test_duration_until_open() {
+        let overnight = ExecutionWindow::parse("22:00-06:00").unwrap();
+
+        assert_eq!(overnight.duration_until_open(time(23, 0)), None);
+        assert_eq!(overnight.duration_until_open(time(5, 59)), None);
+        assert_eq!(
+            overnight.duration_until_open(time(6, 0)),
+            Some(Duration::from_secs(16 * 60 * 60))
+        );
+
+        let daytime = ExecutionWindow::parse("09:00-17:30").unwrap();
+
+        assert_eq!(daytime.duration_until_open(time(12, 0)), None);
+        assert_eq!(
+            daytime.duration_until_open(time(8, 30)),
+            Some(Duration::from_secs(30 * 60))
+        );
+        assert_eq!(
+            daytime.duration_until_open(time(17, 30)),
+            Some(Duration::from_secs(15 * 60 * 60 + 30 * 60))
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::Context;
+
+use std::path::PathBuf;
+
+use crate::common::UserDir;
+
+fn templates_dir() -> anyhow::Result<PathBuf> {
+    Ok(UserDir::Config.path()?.join("templates"))
+}
+
+fn template_path(name: &str) -> anyhow::Result<PathBuf> {
+    if name.is_empty() || name.starts_with('.') || name.contains(std::path::is_separator) {
+        anyhow::bail!("invalid template name '{}'", name);
+    }
+
+    Ok(templates_dir()?.join(name))
+}
+
+pub fn save(name: &str, template: &[String]) -> anyhow::Result<PathBuf> {
+    let path = template_path(name)?;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("error creating template directory '{}'", dir.display()))?;
+    }
+
+    std::fs::write(&path, format!("{}\n", template.join(" ")))
+        .with_context(|| format!("error writing template '{}'", path.display()))?;
+
+    Ok(path)
+}
+
+/// Command and initial arguments of a saved template, split on whitespace like --then.
+pub fn load(name: &str) -> anyhow::Result<Vec<String>> {
+    let path = template_path(name)?;
+
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("error reading template '{}'", path.display()))?;
+
+    let words: Vec<String> = contents.split_whitespace().map(str::to_owned).collect();
+
+    if words.is_empty() {
+        anyhow::bail!("template '{}' is empty", path.display());
+    }
+
+    Ok(words)
+}
+
+/// Saved templates as (name, template) sorted by name.
+pub fn list() -> anyhow::Result<Vec<(String, String)>> {
+    let dir = templates_dir()?;
+
+    let entries = match std::fs::read_dir(&dir) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        result => result
+            .with_context(|| format!("error reading template directory '{}'", dir.display()))?,
+    };
+
+    let mut templates = vec![];
+
+    for entry in entries {
+        let entry = entry?;
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+
+        if let Ok(words) = load(&name) {
+            templates.push((name, words.join(" ")));
+        }
+    }
+
+    templates.sort();
+
+    Ok(templates)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_template_path() {
+        assert!(template_path("thumbnail").is_ok());
+        assert!(template_path("").is_err());
+        assert!(template_path(".hidden").is_err());
+        assert!(template_path("../thumbnail").is_err());
+        assert!(template_path("a/b").is_err());
+    }
+}
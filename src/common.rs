@@ -1,9 +1,23 @@
-use std::{collections::VecDeque, path::PathBuf};
+use tokio::sync::mpsc::UnboundedSender;
+
+use std::{collections::VecDeque, ffi::OsString, path::PathBuf, time::Duration};
 
 #[derive(Debug, Eq, PartialEq)]
 pub struct OwnedCommandAndArgs {
     pub command_path: PathBuf,
-    pub args: Vec<String>,
+    /// Arguments may not be valid UTF-8, e.g. file names read from `find -print0`.
+    pub args: Vec<OsString>,
+}
+
+impl OwnedCommandAndArgs {
+    /// Command and arguments joined with spaces, e.g. for logs written by rust-parallel.
+    pub fn command_line_lossy(&self) -> String {
+        std::iter::once(self.command_path.as_os_str())
+            .chain(self.args.iter().map(|arg| arg.as_os_str()))
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
 }
 
 impl std::fmt::Display for OwnedCommandAndArgs {
@@ -18,10 +32,10 @@ pub enum OwnedCommandAndArgsConversionError {
     EmptyInput,
 }
 
-impl TryFrom<VecDeque<String>> for OwnedCommandAndArgs {
+impl TryFrom<VecDeque<OsString>> for OwnedCommandAndArgs {
     type Error = OwnedCommandAndArgsConversionError;
 
-    fn try_from(mut deque: VecDeque<String>) -> Result<Self, Self::Error> {
+    fn try_from(mut deque: VecDeque<OsString>) -> Result<Self, Self::Error> {
         let command = deque
             .pop_front()
             .ok_or(OwnedCommandAndArgsConversionError::EmptyInput)?;
@@ -33,10 +47,133 @@ impl TryFrom<VecDeque<String>> for OwnedCommandAndArgs {
     }
 }
 
+impl TryFrom<Vec<OsString>> for OwnedCommandAndArgs {
+    type Error = OwnedCommandAndArgsConversionError;
+
+    fn try_from(vec: Vec<OsString>) -> Result<Self, Self::Error> {
+        Self::try_from(VecDeque::from(vec))
+    }
+}
+
 impl TryFrom<Vec<String>> for OwnedCommandAndArgs {
     type Error = OwnedCommandAndArgsConversionError;
 
     fn try_from(vec: Vec<String>) -> Result<Self, Self::Error> {
-        Self::try_from(VecDeque::from(vec))
+        Self::try_from(vec.into_iter().map(OsString::from).collect::<VecDeque<_>>())
+    }
+}
+
+/// Per-job settings that override command line settings, e.g. from a manifest.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct JobOptions {
+    pub env: Vec<(String, String)>,
+    /// Run with only `env` instead of inheriting the environment of rust-parallel.
+    pub clear_env: bool,
+    pub current_dir: Option<PathBuf>,
+    pub timeout: Option<Duration>,
+    pub retries: Option<usize>,
+    /// Label shown in log messages for the job.
+    pub tag: Option<String>,
+    /// Cost counted against --budget.
+    pub cost: Option<f64>,
+    /// Prefix of output lines with --tag or --tagstring.
+    pub output_tag: Option<String>,
+    /// Jobs with the same --slot-key run one at a time on the same slot.
+    pub slot_key: Option<String>,
+    /// Jobs with the same --mutex key never run at the same time.
+    pub mutex_key: Option<String>,
+    /// File the job's stdout must match with --expect-file.
+    pub expect_file: Option<PathBuf>,
+    /// Exit code counted as success with --expect-exit, any other exit is a failure.
+    pub expected_exit_code: Option<String>,
+}
+
+/// How a job completed, reported to the input task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobOutcome {
+    Succeeded,
+    Failed,
+    /// Never run, e.g. with --dry-run or once the run halted.
+    NotRun,
+}
+
+/// Reports completion of a job to the input task, e.g. for dependency scheduling.
+///
+/// Dropping the notifier without calling `complete` reports that the job was not run.
+#[derive(Debug)]
+pub struct JobCompletionNotifier {
+    receivers: Vec<(usize, UnboundedSender<(usize, JobOutcome)>)>,
+}
+
+impl JobCompletionNotifier {
+    pub fn new(id: usize, sender: UnboundedSender<(usize, JobOutcome)>) -> Self {
+        Self {
+            receivers: vec![(id, sender)],
+        }
+    }
+
+    /// Also report completion to another receiver, e.g. --loop cycle tracking.
+    pub fn with_receiver(
+        mut self,
+        id: usize,
+        sender: UnboundedSender<(usize, JobOutcome)>,
+    ) -> Self {
+        self.receivers.push((id, sender));
+        self
+    }
+
+    pub fn complete(mut self, success: bool) {
+        self.notify(if success {
+            JobOutcome::Succeeded
+        } else {
+            JobOutcome::Failed
+        });
+    }
+
+    fn notify(&mut self, outcome: JobOutcome) {
+        for (id, sender) in self.receivers.drain(..) {
+            let _ = sender.send((id, outcome));
+        }
+    }
+}
+
+impl Drop for JobCompletionNotifier {
+    fn drop(&mut self) {
+        self.notify(JobOutcome::NotRun);
+    }
+}
+
+/// Per-user directories for files rust-parallel keeps between runs.
+#[derive(Clone, Copy, Debug)]
+pub enum UserDir {
+    Config,
+    Data,
+}
+
+impl UserDir {
+    /// rust-parallel directory under $XDG_CONFIG_HOME or $XDG_DATA_HOME, defaulting to
+    /// ~/.config or ~/.local/share, or under %APPDATA% or %LOCALAPPDATA% on windows.
+    pub fn path(self) -> anyhow::Result<PathBuf> {
+        let (windows_var, xdg_var, home_default) = match self {
+            Self::Config => ("APPDATA", "XDG_CONFIG_HOME", ".config"),
+            Self::Data => ("LOCALAPPDATA", "XDG_DATA_HOME", ".local/share"),
+        };
+
+        let dir = if cfg!(windows) {
+            std::env::var_os(windows_var).map(PathBuf::from)
+        } else {
+            std::env::var_os(xdg_var)
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| {
+                    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(home_default))
+                })
+        };
+
+        let Some(dir) = dir else {
+            anyhow::bail!("unable to determine {:?} directory", self);
+        };
+
+        Ok(dir.join("rust-parallel"))
     }
 }
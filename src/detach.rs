@@ -0,0 +1,226 @@
+use anyhow::Context;
+
+use tracing::warn;
+
+use std::{
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{command_line_args::CommandLineArgs, common::UserDir, input};
+
+/// Set in the environment of the background process of a --detach run to its run directory.
+pub const DETACHED_RUN_DIR_VAR: &str = "RUST_PARALLEL_DETACHED_RUN_DIR";
+
+const PID_FILE: &str = "pid";
+const ARGS_FILE: &str = "args";
+const LOG_FILE: &str = "log";
+const EXIT_STATUS_FILE: &str = "exit_status";
+
+const ATTACH_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Directory of --detach runs, each in a subdirectory named by its run id.
+fn runs_dir() -> anyhow::Result<PathBuf> {
+    Ok(UserDir::Data.path()?.join("runs"))
+}
+
+/// Run directory of this process if it is the background process of a --detach run.
+fn detached_run_dir() -> Option<PathBuf> {
+    std::env::var_os(DETACHED_RUN_DIR_VAR).map(PathBuf::from)
+}
+
+/// True if this is the foreground process of a --detach run.
+pub fn is_foreground(command_line_args: &CommandLineArgs) -> bool {
+    command_line_args.detach && detached_run_dir().is_none()
+}
+
+/// For --detach, returns true if this is the foreground process, which starts the run in a
+/// background process and exits.
+///
+/// The background process runs in its own session so it survives the terminal closing.
+pub fn detach(command_line_args: &'static CommandLineArgs) -> anyhow::Result<bool> {
+    if !command_line_args.detach {
+        return Ok(false);
+    }
+
+    if detached_run_dir().is_some() {
+        start_session();
+        return Ok(false);
+    }
+
+    if input::reads_stdin(command_line_args) {
+        anyhow::bail!("--detach can not read stdin, use input files instead");
+    }
+
+    let run_id = format!(
+        "{}-{}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+        std::process::id()
+    );
+
+    let run_dir = runs_dir()?.join(&run_id);
+
+    std::fs::create_dir_all(&run_dir)
+        .with_context(|| format!("error creating run directory '{}'", run_dir.display()))?;
+
+    write_run_file(
+        &run_dir,
+        ARGS_FILE,
+        &serde_json::to_string(&command_line_args.invocation_args)?,
+    )?;
+
+    let log_path = run_dir.join(LOG_FILE);
+    let log = std::fs::File::create(&log_path)
+        .with_context(|| format!("error creating run log '{}'", log_path.display()))?;
+
+    let child = std::process::Command::new(
+        std::env::current_exe().context("error finding rust-parallel executable")?,
+    )
+    .args(std::env::args_os().skip(1))
+    .env(DETACHED_RUN_DIR_VAR, &run_dir)
+    .stdin(std::process::Stdio::null())
+    .stdout(log.try_clone()?)
+    .stderr(log)
+    .spawn()
+    .context("error starting detached run")?;
+
+    write_run_file(&run_dir, PID_FILE, &child.id().to_string())?;
+
+    println!(
+        "detached run {} pid {}, attach with: {} attach {}",
+        run_id,
+        child.id(),
+        env!("CARGO_PKG_NAME"),
+        run_id
+    );
+
+    Ok(true)
+}
+
+#[cfg(unix)]
+fn start_session() {
+    if let Err(e) = nix::unistd::setsid() {
+        warn!("detached run setsid error: {}", e);
+    }
+}
+
+#[cfg(not(unix))]
+fn start_session() {}
+
+/// Record the exit status of the background process of a --detach run for "attach".
+pub fn run_finished(exit_status: i32) {
+    let Some(run_dir) = detached_run_dir() else {
+        return;
+    };
+
+    if let Err(e) = write_run_file(&run_dir, EXIT_STATUS_FILE, &exit_status.to_string()) {
+        warn!("detached run error: {:#}", e);
+    }
+}
+
+fn write_run_file(run_dir: &Path, name: &str, contents: &str) -> anyhow::Result<()> {
+    let path = run_dir.join(name);
+    std::fs::write(&path, contents)
+        .with_context(|| format!("error writing run file '{}'", path.display()))
+}
+
+fn read_exit_status(run_dir: &Path) -> anyhow::Result<Option<i32>> {
+    let path = run_dir.join(EXIT_STATUS_FILE);
+
+    match std::fs::read_to_string(&path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        result => {
+            let contents = result.with_context(|| format!("error reading '{}'", path.display()))?;
+            let exit_status = contents
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid exit status in '{}'", path.display()))?;
+            Ok(Some(exit_status))
+        }
+    }
+}
+
+#[cfg(unix)]
+fn process_alive(run_dir: &Path) -> bool {
+    let Some(pid) = std::fs::read_to_string(run_dir.join(PID_FILE))
+        .ok()
+        .and_then(|pid| pid.trim().parse().ok())
+    else {
+        // the pid is written just after the process starts
+        return true;
+    };
+
+    nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid), None).is_ok()
+}
+
+#[cfg(not(unix))]
+fn process_alive(_run_dir: &Path) -> bool {
+    true
+}
+
+/// Latest run id, run ids start with the time the run was started.
+fn latest_run_id() -> anyhow::Result<String> {
+    let runs_dir = runs_dir()?;
+
+    let entries = match std::fs::read_dir(&runs_dir) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            anyhow::bail!("no detached runs found")
+        }
+        result => result
+            .with_context(|| format!("error reading runs directory '{}'", runs_dir.display()))?,
+    };
+
+    entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .max()
+        .ok_or_else(|| anyhow::anyhow!("no detached runs found"))
+}
+
+/// Print the log of a --detach run and follow it until the run finishes.
+pub async fn attach(run_id: Option<&str>) -> anyhow::Result<()> {
+    let run_id = match run_id {
+        Some(run_id) => run_id.to_owned(),
+        None => latest_run_id()?,
+    };
+
+    let run_dir = runs_dir()?.join(&run_id);
+
+    let log_path = run_dir.join(LOG_FILE);
+    let mut log = std::fs::File::open(&log_path).with_context(|| {
+        format!(
+            "run {} not found, error opening '{}'",
+            run_id,
+            log_path.display()
+        )
+    })?;
+
+    let mut position = 0;
+    let mut buffer = vec![];
+
+    loop {
+        // read the exit status first so no output written before it is missed
+        let exit_status = read_exit_status(&run_dir)?;
+        let alive = exit_status.is_some() || process_alive(&run_dir);
+
+        log.seek(SeekFrom::Start(position))?;
+        buffer.clear();
+        position += log.read_to_end(&mut buffer)? as u64;
+
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(&buffer)?;
+        stdout.flush()?;
+
+        match exit_status {
+            Some(0) => {
+                println!("run {} finished", run_id);
+                return Ok(());
+            }
+            Some(exit_status) => {
+                anyhow::bail!("run {} failed with exit status {}", run_id, exit_status)
+            }
+            None if !alive => anyhow::bail!("run {} ended without finishing", run_id),
+            None => tokio::time::sleep(ATTACH_POLL_INTERVAL).await,
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::Context;
+
+use serde::{Deserialize, Serialize};
+
+use tracing::warn;
+
+use std::{io::Write, path::PathBuf, time::Instant};
+
+use crate::{command_line_args::CommandLineArgs, common::UserDir};
+
+/// A completed run recorded in the history file.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct HistoryEntry {
+    pub timestamp: String,
+    pub cwd: PathBuf,
+    pub args: Vec<String>,
+    pub commands_run: u64,
+    pub total_failures: u64,
+    pub elapsed_seconds: f64,
+    pub exit_status: i32,
+}
+
+impl std::fmt::Display for HistoryEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} exit_status={} commands_run={} total_failures={} elapsed={:.3}s {}",
+            self.timestamp,
+            self.exit_status,
+            self.commands_run,
+            self.total_failures,
+            self.elapsed_seconds,
+            self.args.join(" ")
+        )
+    }
+}
+
+/// History file with one JSON entry per line, numbered from 1 in "history" output.
+fn history_file() -> anyhow::Result<PathBuf> {
+    Ok(UserDir::Data.path()?.join("history.jsonl"))
+}
+
+pub fn load() -> anyhow::Result<Vec<HistoryEntry>> {
+    let path = history_file()?;
+
+    let contents = match std::fs::read_to_string(&path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        result => result.with_context(|| format!("error reading history '{}'", path.display()))?,
+    };
+
+    parse(&contents).with_context(|| format!("error parsing history '{}'", path.display()))
+}
+
+fn parse(contents: &str) -> anyhow::Result<Vec<HistoryEntry>> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| {
+            serde_json::from_str(line).with_context(|| format!("invalid entry {}", i + 1))
+        })
+        .collect()
+}
+
+/// Entry number `number` counting from 1.
+pub fn entry(number: usize) -> anyhow::Result<HistoryEntry> {
+    let mut entries = load()?;
+
+    if number == 0 || number > entries.len() {
+        anyhow::bail!(
+            "history entry {} not found, there are {} entries",
+            number,
+            entries.len()
+        );
+    }
+
+    Ok(entries.swap_remove(number - 1))
+}
+
+fn append(entry: &HistoryEntry) -> anyhow::Result<()> {
+    let path = history_file()?;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("error creating history directory '{}'", dir.display()))?;
+    }
+
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .with_context(|| format!("error writing history '{}'", path.display()))?;
+
+    Ok(())
+}
+
+/// Records the run in the history file when it finishes, unless --no-history.
+#[derive(Debug)]
+pub struct HistoryRecorder {
+    args: &'static [String],
+    start_time: Instant,
+}
+
+impl HistoryRecorder {
+    pub fn new(command_line_args: &'static CommandLineArgs) -> Option<Self> {
+        if command_line_args.no_history {
+            None
+        } else {
+            Some(Self {
+                args: &command_line_args.invocation_args,
+                start_time: Instant::now(),
+            })
+        }
+    }
+
+    pub fn run_finished(&self, commands_run: u64, total_failures: u64, exit_status: i32) {
+        let entry = HistoryEntry {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            cwd: std::env::current_dir().unwrap_or_default(),
+            args: self.args.to_vec(),
+            commands_run,
+            total_failures,
+            elapsed_seconds: self.start_time.elapsed().as_secs_f64(),
+            exit_status,
+        };
+
+        if let Err(e) = append(&entry) {
+            warn!("history error: {:#}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let contents = concat!(
+            r#"{"timestamp":"2024-01-01T00:00:00+00:00","cwd":"/tmp","args":["-j1","echo",":::","A"],"commands_run":1,"total_failures":0,"elapsed_seconds":0.5,"exit_status":0}"#,
+            "\n\n",
+        );
+
+        let entries = parse(contents).unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0].to_string(),
+            "2024-01-01T00:00:00+00:00 exit_status=0 commands_run=1 total_failures=0 elapsed=0.500s -j1 echo ::: A"
+        );
+
+        assert!(parse("{}\n").is_err());
+    }
+}
@@ -1,4 +1,14 @@
+mod annotations;
 mod buffered_reader;
+mod checksum;
+mod dag;
+mod fair;
+pub mod manifest;
+mod object_list;
+mod replay;
+mod resume;
+mod shard;
+mod sql;
 mod task;
 
 use anyhow::Context;
@@ -12,9 +22,14 @@ use tracing::debug;
 
 use std::sync::Arc;
 
-use crate::{command_line_args::CommandLineArgs, common::OwnedCommandAndArgs, progress::Progress};
+use crate::{
+    command_line_args::{CommandLineArgs, CommandLineSubcommand},
+    common::{JobCompletionNotifier, JobOptions, OwnedCommandAndArgs},
+    output::OutputFlusher,
+    progress::Progress,
+};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum BufferedInput {
     Stdin,
 
@@ -35,6 +50,14 @@ pub enum Input {
     Buffered(BufferedInput),
 
     CommandLineArgs,
+
+    Manifest { file_name: &'static str },
+
+    Replay { file_name: &'static str },
+
+    Sql,
+
+    ObjectList { url: &'static str },
 }
 
 impl std::fmt::Display for Input {
@@ -42,11 +65,16 @@ impl std::fmt::Display for Input {
         match self {
             Self::Buffered(b) => write!(f, "{}", b),
             Self::CommandLineArgs => write!(f, "command_line_args"),
+            Self::Manifest { file_name } | Self::Replay { file_name } => {
+                write!(f, "{}", file_name)
+            }
+            Self::Sql => write!(f, "sql"),
+            Self::ObjectList { url } => write!(f, "{}", url),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct InputLineNumber {
     pub input: Input,
     pub line_number: usize,
@@ -59,18 +87,51 @@ impl std::fmt::Display for InputLineNumber {
 }
 
 enum InputList {
-    BufferedInputList(Vec<BufferedInput>),
+    Buffered(Vec<BufferedInput>),
 
     CommandLineArgs,
+
+    Manifest {
+        file_name: &'static str,
+    },
+
+    Replay {
+        file_name: &'static str,
+        failed_only: bool,
+    },
+
+    Sql {
+        url: &'static str,
+        query: &'static str,
+    },
+
+    ObjectList {
+        url: &'static str,
+    },
 }
 
 fn build_input_list(command_line_args: &'static CommandLineArgs) -> InputList {
-    if command_line_args.commands_from_args_mode() {
+    if let Some(CommandLineSubcommand::Replay(replay_args)) = &command_line_args.subcommand {
+        InputList::Replay {
+            file_name: &replay_args.audit_log_file,
+            failed_only: replay_args.failed,
+        }
+    } else if let (Some(query), Some(url)) = (&command_line_args.sql, &command_line_args.sql_url) {
+        InputList::Sql { url, query }
+    } else if let Some(url) = &command_line_args.s3_list {
+        InputList::ObjectList { url }
+    } else if let Some(manifest) = &command_line_args.manifest {
+        InputList::Manifest {
+            file_name: manifest,
+        }
+    } else if command_line_args.commands_from_args_mode()
+        || command_line_args.inline_commands_mode()
+    {
         InputList::CommandLineArgs
     } else if command_line_args.input_file.is_empty() {
-        InputList::BufferedInputList(vec![BufferedInput::Stdin])
+        InputList::Buffered(vec![BufferedInput::Stdin])
     } else {
-        InputList::BufferedInputList(
+        InputList::Buffered(
             command_line_args
                 .input_file
                 .iter()
@@ -88,14 +149,33 @@ fn build_input_list(command_line_args: &'static CommandLineArgs) -> InputList {
     }
 }
 
+/// True if commands are built from stdin, so it can not be read for anything else.
+pub fn reads_stdin(command_line_args: &'static CommandLineArgs) -> bool {
+    match build_input_list(command_line_args) {
+        InputList::Buffered(buffered_inputs) => buffered_inputs.contains(&BufferedInput::Stdin),
+        _ => false,
+    }
+}
+
 #[derive(Debug)]
 pub struct InputMessage {
     pub command_and_args: OwnedCommandAndArgs,
     pub input_line_number: InputLineNumber,
+    /// Input line or arguments the command was built from.
+    pub input_value: String,
+    pub job_number: usize,
+    pub job_options: JobOptions,
+    pub completion_notifier: Option<JobCompletionNotifier>,
+}
+
+#[derive(Debug, Default)]
+pub struct InputSummary {
+    /// Jobs not run because a job they depend on failed.
+    pub dependency_failures: u64,
 }
 
 pub struct InputProducer {
-    input_task_join_handle: JoinHandle<()>,
+    input_task_join_handle: JoinHandle<anyhow::Result<InputSummary>>,
     receiver: Receiver<InputMessage>,
 }
 
@@ -103,6 +183,7 @@ impl InputProducer {
     pub fn new(
         command_line_args: &'static CommandLineArgs,
         progress: &Arc<Progress>,
+        output_flusher: OutputFlusher,
     ) -> anyhow::Result<Self> {
         let (sender, receiver) = channel(command_line_args.channel_capacity);
         debug!(
@@ -110,7 +191,8 @@ impl InputProducer {
             command_line_args.channel_capacity
         );
 
-        let input_sender_task = task::InputTask::new(command_line_args, sender, progress)?;
+        let input_sender_task =
+            task::InputTask::new(command_line_args, sender, progress, output_flusher)?;
 
         let input_task_join_handle = tokio::spawn(input_sender_task.run());
 
@@ -124,11 +206,12 @@ impl InputProducer {
         &mut self.receiver
     }
 
-    pub async fn wait_for_completion(self) -> anyhow::Result<()> {
-        self.input_task_join_handle
+    pub async fn wait_for_completion(self) -> anyhow::Result<InputSummary> {
+        let input_summary = self
+            .input_task_join_handle
             .await
-            .context("InputProducer::wait_for_completion: input_task_join_handle.await error")?;
+            .context("InputProducer::wait_for_completion: input_task_join_handle.await error")??;
 
-        Ok(())
+        Ok(input_summary)
     }
 }
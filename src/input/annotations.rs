@@ -0,0 +1,115 @@
+use anyhow::Context;
+
+use std::time::Duration;
+
+use crate::common::JobOptions;
+
+use super::InputLineNumber;
+
+/// Marker starting job annotations in a buffered input line.
+const ANNOTATION_MARKER: &[u8] = b"#parallel:";
+
+/// Fails the run rather than only the input line with --annotations.
+#[derive(thiserror::Error, Debug)]
+#[error("invalid job annotations line {input_line_number}: {message}")]
+pub struct InvalidAnnotationsError {
+    pub input_line_number: InputLineNumber,
+    pub message: String,
+}
+
+/// Split job annotations from the end of an input line.
+///
+/// `cmd args #parallel: timeout=10 retries=2 tag=prod` runs `cmd args` with a
+/// 10 second timeout, up to 2 retries, and tag `prod`.  `cost=N` sets the
+/// cost counted against --budget.
+pub fn parse_annotations(mut segment: Vec<u8>) -> anyhow::Result<(Vec<u8>, JobOptions)> {
+    let Some(marker_position) = segment
+        .windows(ANNOTATION_MARKER.len())
+        .position(|window| window == ANNOTATION_MARKER)
+    else {
+        return Ok((segment, JobOptions::default()));
+    };
+
+    let annotations = segment.split_off(marker_position);
+    let annotations = std::str::from_utf8(&annotations[ANNOTATION_MARKER.len()..])
+        .context("annotations are not valid utf-8")?;
+
+    let mut job_options = JobOptions::default();
+
+    for annotation in annotations.split_whitespace() {
+        let Some((key, value)) = annotation.split_once('=') else {
+            anyhow::bail!("annotation '{}' is not key=value", annotation);
+        };
+
+        match key {
+            "timeout" => {
+                let timeout: f64 = value
+                    .parse()
+                    .with_context(|| format!("invalid timeout '{}'", value))?;
+                if timeout.is_nan() || timeout <= 0f64 {
+                    anyhow::bail!("timeout {} not greater than 0", value);
+                }
+                job_options.timeout = Some(Duration::from_secs_f64(timeout));
+            }
+            "retries" => {
+                job_options.retries = Some(
+                    value
+                        .parse()
+                        .with_context(|| format!("invalid retries '{}'", value))?,
+                );
+            }
+            "tag" => job_options.tag = Some(value.to_owned()),
+            "cost" => job_options.cost = Some(parse_cost(value)?),
+            _ => anyhow::bail!("unknown annotation '{}'", key),
+        }
+    }
+
+    Ok((segment, job_options))
+}
+
+/// Parse a --budget cost, which must be a non-negative number.
+pub fn parse_cost(value: &str) -> anyhow::Result<f64> {
+    match value.trim().parse::<f64>() {
+        Ok(cost) if cost.is_finite() && cost >= 0f64 => Ok(cost),
+        _ => anyhow::bail!("invalid cost '{}'", value),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_annotations() {
+        let (segment, job_options) = parse_annotations(
+            b"echo hi #parallel: timeout=10 retries=2 tag=prod cost=2.5".to_vec(),
+        )
+        .unwrap();
+
+        assert_eq!(segment, b"echo hi ".to_vec());
+        assert_eq!(
+            job_options,
+            JobOptions {
+                timeout: Some(Duration::from_secs(10)),
+                retries: Some(2),
+                tag: Some("prod".to_owned()),
+                cost: Some(2.5),
+                ..Default::default()
+            }
+        );
+
+        let (segment, job_options) = parse_annotations(b"echo #hi".to_vec()).unwrap();
+
+        assert_eq!(segment, b"echo #hi".to_vec());
+        assert_eq!(job_options, JobOptions::default());
+    }
+
+    #[test]
+    fn test_parse_annotations_errors() {
+        assert!(parse_annotations(b"echo #parallel: timeout=0".to_vec()).is_err());
+        assert!(parse_annotations(b"echo #parallel: retries=x".to_vec()).is_err());
+        assert!(parse_annotations(b"echo #parallel: color=red".to_vec()).is_err());
+        assert!(parse_annotations(b"echo #parallel: tag".to_vec()).is_err());
+        assert!(parse_annotations(b"echo #parallel: cost=-1".to_vec()).is_err());
+    }
+}
@@ -1,16 +1,35 @@
 use anyhow::Context;
 
-use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader, Split};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader};
 
-use crate::command_line_args::CommandLineArgs;
+use tracing::warn;
+
+use crate::command_line_args::{CommandLineArgs, InputEncoding, MaxLineLengthPolicy};
 
 use super::{BufferedInput, Input, InputLineNumber};
 
 type AsyncBufReadBox = Box<dyn AsyncBufRead + Unpin + Send>;
 
+/// U+FEFF encoded as UTF-8, also what a UTF-16 byte order mark decodes to.
+const UTF8_BYTE_ORDER_MARK: &[u8] = b"\xEF\xBB\xBF";
+
+/// Fails the run rather than only the input with --max-line-length-policy fail.
+#[derive(thiserror::Error, Debug)]
+#[error("input line {input_line_number} of {length} bytes is longer than --max-line-length {max_line_length}")]
+pub struct LineTooLongError {
+    input_line_number: InputLineNumber,
+    length: usize,
+    max_line_length: usize,
+}
+
 pub struct BufferedInputReader {
     buffered_input: BufferedInput,
-    split: Split<AsyncBufReadBox>,
+    buf_reader: AsyncBufReadBox,
+    line_separator: u8,
+    encoding: InputEncoding,
+    strip_cr: bool,
+    max_line_length: usize,
+    max_line_length_policy: MaxLineLengthPolicy,
     next_line_number: usize,
 }
 
@@ -27,11 +46,14 @@ impl BufferedInputReader {
             b'\n'
         };
 
-        let split = buf_reader.split(line_separator);
-
         Ok(Self {
             buffered_input,
-            split,
+            buf_reader,
+            line_separator,
+            encoding: command_line_args.input_encoding,
+            strip_cr: !command_line_args.null_separator && !command_line_args.keep_cr,
+            max_line_length: command_line_args.max_line_length.unwrap_or(usize::MAX),
+            max_line_length_policy: command_line_args.max_line_length_policy,
             next_line_number: 0,
         })
     }
@@ -54,21 +76,153 @@ impl BufferedInputReader {
         }
     }
 
+    /// Next segment up to --max-line-length bytes and the full length of the line.
+    async fn next_byte_segment(&mut self) -> std::io::Result<Option<(Vec<u8>, usize)>> {
+        let mut segment = vec![];
+        let mut length = 0;
+
+        loop {
+            let buf = self.buf_reader.fill_buf().await?;
+            if buf.is_empty() {
+                if length == 0 {
+                    return Ok(None);
+                }
+                break;
+            }
+
+            let (chunk, found_line_separator) =
+                match buf.iter().position(|b| *b == self.line_separator) {
+                    Some(i) => (&buf[..i], true),
+                    None => (buf, false),
+                };
+
+            let keep = chunk.len().min(self.max_line_length.saturating_sub(length));
+            segment.extend_from_slice(&chunk[..keep]);
+
+            let chunk_length = chunk.len();
+            length += chunk_length;
+            self.buf_reader
+                .consume(chunk_length + usize::from(found_line_separator));
+
+            if found_line_separator {
+                break;
+            }
+        }
+
+        Ok(Some((segment, length)))
+    }
+
+    async fn next_utf16le_segment(&mut self) -> std::io::Result<Option<(Vec<u8>, usize)>> {
+        let mut code_units = vec![];
+        let mut length = 0;
+        let mut found_line_separator = false;
+
+        loop {
+            let mut code_unit = [0u8; 2];
+            match self.buf_reader.read_exact(&mut code_unit).await {
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+                Ok(_) => {}
+            };
+
+            let code_unit = u16::from_le_bytes(code_unit);
+            if code_unit == u16::from(self.line_separator) {
+                found_line_separator = true;
+                break;
+            }
+
+            length += 2;
+            if length <= self.max_line_length {
+                code_units.push(code_unit);
+            }
+        }
+
+        if length == 0 && !found_line_separator {
+            return Ok(None);
+        }
+
+        Ok(Some((
+            String::from_utf16_lossy(&code_units).into_bytes(),
+            length,
+        )))
+    }
+
+    /// Next line transcoded to UTF-8, without a leading byte order mark.
+    ///
+    /// Lines longer than --max-line-length are handled by --max-line-length-policy.
     pub async fn next_segment(&mut self) -> anyhow::Result<Option<(InputLineNumber, Vec<u8>)>> {
-        let segment = self.split.next_segment().await?;
+        loop {
+            let first_line = self.next_line_number == 0;
+
+            let next_segment = match self.encoding {
+                InputEncoding::Utf8 | InputEncoding::Latin1 => self.next_byte_segment().await?,
+                InputEncoding::Utf16le => self.next_utf16le_segment().await?,
+            };
+
+            let Some((mut segment, length)) = next_segment else {
+                return Ok(None);
+            };
+
+            self.next_line_number += 1;
+
+            let input_line_number = InputLineNumber {
+                input: Input::Buffered(self.buffered_input),
+                line_number: self.next_line_number,
+            };
+
+            if length > self.max_line_length {
+                match self.max_line_length_policy {
+                    MaxLineLengthPolicy::Skip => {
+                        warn!(
+                            "skipping input line {} of {} bytes, longer than --max-line-length {}",
+                            input_line_number, length, self.max_line_length
+                        );
+                        continue;
+                    }
+                    MaxLineLengthPolicy::Fail => {
+                        return Err(LineTooLongError {
+                            input_line_number,
+                            length,
+                            max_line_length: self.max_line_length,
+                        }
+                        .into());
+                    }
+                    MaxLineLengthPolicy::Truncate => {
+                        warn!(
+                            "truncating input line {} of {} bytes to --max-line-length {}",
+                            input_line_number, length, self.max_line_length
+                        );
+
+                        // do not leave a partial character at the end
+                        if self.encoding == InputEncoding::Utf8 {
+                            if let Err(e) = std::str::from_utf8(&segment) {
+                                if e.error_len().is_none() {
+                                    segment.truncate(e.valid_up_to());
+                                }
+                            }
+                        }
+                    }
+                }
+            }
 
-        match segment {
-            None => Ok(None),
-            Some(segment) => {
-                self.next_line_number += 1;
+            if self.encoding == InputEncoding::Latin1 {
+                segment = segment
+                    .into_iter()
+                    .map(char::from)
+                    .collect::<String>()
+                    .into();
+            }
 
-                let input_line_number = InputLineNumber {
-                    input: Input::Buffered(self.buffered_input),
-                    line_number: self.next_line_number,
-                };
+            if first_line && segment.starts_with(UTF8_BYTE_ORDER_MARK) {
+                segment.drain(..UTF8_BYTE_ORDER_MARK.len());
+            }
 
-                Ok(Some((input_line_number, segment)))
+            // lines from Windows files end with \r\n
+            if self.strip_cr && segment.last() == Some(&b'\r') {
+                segment.pop();
             }
+
+            return Ok(Some((input_line_number, segment)));
         }
     }
 }
@@ -0,0 +1,136 @@
+use anyhow::Context;
+
+use sha2::{Digest, Sha256};
+
+use tracing::debug;
+
+use std::{fs::File, io::BufReader};
+
+/// An expected checksum line from a sha256sum style checksum file.
+#[derive(Debug, Eq, PartialEq)]
+struct ExpectedChecksum {
+    line_number: usize,
+    sha256: String,
+    file_name: String,
+}
+
+/// Verify files listed in a sha256sum style checksum file before any command runs.
+///
+/// Lines are `<sha256 hex>  <file name>`, as written by `sha256sum`.  Relative
+/// file names are relative to the current directory.
+pub async fn verify_input_checksums(checksum_file_name: &str) -> anyhow::Result<()> {
+    let contents = tokio::fs::read_to_string(checksum_file_name)
+        .await
+        .with_context(|| format!("error reading checksum file '{}'", checksum_file_name))?;
+
+    let expected_checksums = parse(&contents)
+        .with_context(|| format!("error parsing checksum file '{}'", checksum_file_name))?;
+
+    let verified = tokio::task::spawn_blocking(move || -> anyhow::Result<usize> {
+        for expected in &expected_checksums {
+            let actual = file_sha256(&expected.file_name)?;
+
+            if actual != expected.sha256 {
+                anyhow::bail!(
+                    "checksum mismatch for input '{}' line {}: expected {} actual {}",
+                    expected.file_name,
+                    expected.line_number,
+                    expected.sha256,
+                    actual
+                );
+            }
+        }
+        Ok(expected_checksums.len())
+    })
+    .await
+    .context("spawn_blocking error")??;
+
+    debug!(
+        "verified {} input checksums from '{}'",
+        verified, checksum_file_name
+    );
+
+    Ok(())
+}
+
+fn parse(contents: &str) -> anyhow::Result<Vec<ExpectedChecksum>> {
+    let mut expected_checksums = vec![];
+
+    for (i, line) in contents.lines().enumerate() {
+        let line_number = i + 1;
+
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let Some((sha256, file_name)) = line.split_once(' ') else {
+            anyhow::bail!("line {} is not '<sha256> <file>'", line_number);
+        };
+
+        if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+            anyhow::bail!("line {} has invalid sha256 '{}'", line_number, sha256);
+        }
+
+        // sha256sum separates with two spaces in text mode and " *" in binary mode.
+        let file_name = file_name
+            .strip_prefix(' ')
+            .or_else(|| file_name.strip_prefix('*'))
+            .unwrap_or(file_name);
+
+        if file_name.is_empty() {
+            anyhow::bail!("line {} has no file name", line_number);
+        }
+
+        expected_checksums.push(ExpectedChecksum {
+            line_number,
+            sha256: sha256.to_ascii_lowercase(),
+            file_name: file_name.to_owned(),
+        });
+    }
+
+    Ok(expected_checksums)
+}
+
+fn file_sha256(file_name: &str) -> anyhow::Result<String> {
+    let file = File::open(file_name)
+        .with_context(|| format!("error opening input '{}' for checksum", file_name))?;
+
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut BufReader::new(file), &mut hasher)
+        .with_context(|| format!("error reading input '{}' for checksum", file_name))?;
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let sha256 = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+        assert_eq!(
+            parse(&format!(
+                "{sha256}  data.txt\n# comment\n\n{sha256} *dir/data.bin\n"
+            ))
+            .unwrap(),
+            vec![
+                ExpectedChecksum {
+                    line_number: 1,
+                    sha256: sha256.to_owned(),
+                    file_name: "data.txt".to_owned(),
+                },
+                ExpectedChecksum {
+                    line_number: 4,
+                    sha256: sha256.to_owned(),
+                    file_name: "dir/data.bin".to_owned(),
+                },
+            ]
+        );
+
+        assert!(parse("abc  data.txt\n").is_err());
+        assert!(parse(sha256).is_err());
+    }
+}
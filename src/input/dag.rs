@@ -0,0 +1,245 @@
+use std::collections::{BTreeSet, HashMap};
+
+use super::manifest::Manifest;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum JobState {
+    Waiting,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// Schedules manifest jobs according to their `depends_on` lists.
+///
+/// Jobs become ready in manifest order once all of their dependencies
+/// succeed.  When a job fails all jobs depending on it are failed without
+/// running.
+#[derive(Debug)]
+pub struct DagScheduler {
+    dependents: Vec<Vec<usize>>,
+    remaining_dependencies: Vec<usize>,
+    states: Vec<JobState>,
+    ready: BTreeSet<usize>,
+}
+
+impl DagScheduler {
+    pub fn new(manifest: &Manifest) -> anyhow::Result<Self> {
+        let jobs = &manifest.jobs;
+
+        let mut name_to_index = HashMap::with_capacity(jobs.len());
+        for (i, job) in jobs.iter().enumerate() {
+            if let Some(name) = &job.name {
+                if name_to_index.insert(name.as_str(), i).is_some() {
+                    anyhow::bail!("duplicate manifest job name '{}'", name);
+                }
+            }
+        }
+
+        let mut dependents = vec![vec![]; jobs.len()];
+        let mut remaining_dependencies = vec![0; jobs.len()];
+
+        for (i, job) in jobs.iter().enumerate() {
+            for dependency in &job.depends_on {
+                let Some(&dependency_index) = name_to_index.get(dependency.as_str()) else {
+                    anyhow::bail!(
+                        "manifest job {} depends on unknown job '{}'",
+                        i + 1,
+                        dependency
+                    );
+                };
+                dependents[dependency_index].push(i);
+                remaining_dependencies[i] += 1;
+            }
+        }
+
+        let scheduler = Self {
+            ready: (0..jobs.len())
+                .filter(|&i| remaining_dependencies[i] == 0)
+                .collect(),
+            dependents,
+            remaining_dependencies,
+            states: vec![JobState::Waiting; jobs.len()],
+        };
+
+        scheduler.check_for_cycles(manifest)?;
+
+        Ok(scheduler)
+    }
+
+    fn check_for_cycles(&self, manifest: &Manifest) -> anyhow::Result<()> {
+        let mut remaining = self.remaining_dependencies.clone();
+        let mut queue: Vec<usize> = self.ready.iter().copied().collect();
+        let mut visited = 0;
+
+        while let Some(i) = queue.pop() {
+            visited += 1;
+            for &dependent in &self.dependents[i] {
+                remaining[dependent] -= 1;
+                if remaining[dependent] == 0 {
+                    queue.push(dependent);
+                }
+            }
+        }
+
+        if visited != remaining.len() {
+            let cycle_jobs: Vec<String> = remaining
+                .iter()
+                .enumerate()
+                .filter(|(_, &count)| count > 0)
+                .map(|(i, _)| manifest.jobs[i].display_name(i))
+                .collect();
+            anyhow::bail!("manifest dependency cycle between jobs {:?}", cycle_jobs);
+        }
+
+        Ok(())
+    }
+
+    /// Take jobs whose dependencies have all succeeded, in manifest order.
+    pub fn take_ready(&mut self) -> Vec<usize> {
+        let ready = std::mem::take(&mut self.ready);
+        for &i in &ready {
+            self.states[i] = JobState::Running;
+        }
+        ready.into_iter().collect()
+    }
+
+    /// Record completion of a running job.  Returns the jobs that were failed
+    /// because they depend on it.
+    pub fn complete(&mut self, index: usize, success: bool) -> Vec<usize> {
+        if self.states[index] != JobState::Running {
+            return vec![];
+        }
+
+        if success {
+            self.states[index] = JobState::Succeeded;
+            for &dependent in &self.dependents[index] {
+                self.remaining_dependencies[dependent] -= 1;
+                if self.remaining_dependencies[dependent] == 0
+                    && self.states[dependent] == JobState::Waiting
+                {
+                    self.ready.insert(dependent);
+                }
+            }
+            return vec![];
+        }
+
+        self.states[index] = JobState::Failed;
+
+        let mut skipped = vec![];
+        let mut stack = self.dependents[index].clone();
+        while let Some(dependent) = stack.pop() {
+            if self.states[dependent] == JobState::Waiting {
+                self.states[dependent] = JobState::Failed;
+                skipped.push(dependent);
+                stack.extend(self.dependents[dependent].iter().copied());
+            }
+        }
+        skipped.sort_unstable();
+        skipped
+    }
+
+    pub fn has_running_jobs(&self) -> bool {
+        self.states.contains(&JobState::Running)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn manifest(yaml: &str) -> Manifest {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_dag_order() {
+        let manifest = manifest(
+            r#"
+jobs:
+  - name: a
+    command: echo a
+  - name: b
+    command: echo b
+    depends_on: [a]
+  - command: echo c
+  - command: echo d
+    depends_on: [a, b]
+"#,
+        );
+
+        let mut scheduler = DagScheduler::new(&manifest).unwrap();
+
+        assert_eq!(scheduler.take_ready(), vec![0, 2]);
+        assert_eq!(scheduler.take_ready(), Vec::<usize>::new());
+        assert_eq!(scheduler.complete(2, true), Vec::<usize>::new());
+        assert_eq!(scheduler.complete(0, true), Vec::<usize>::new());
+        assert_eq!(scheduler.take_ready(), vec![1]);
+        assert_eq!(scheduler.complete(1, true), Vec::<usize>::new());
+        assert_eq!(scheduler.take_ready(), vec![3]);
+        assert!(scheduler.has_running_jobs());
+        scheduler.complete(3, true);
+        assert!(!scheduler.has_running_jobs());
+    }
+
+    #[test]
+    fn test_dag_failure_skips_dependents() {
+        let manifest = manifest(
+            r#"
+jobs:
+  - name: a
+    command: echo a
+  - name: b
+    command: echo b
+    depends_on: [a]
+  - command: echo c
+    depends_on: [b]
+  - command: echo d
+"#,
+        );
+
+        let mut scheduler = DagScheduler::new(&manifest).unwrap();
+
+        assert_eq!(scheduler.take_ready(), vec![0, 3]);
+        assert_eq!(scheduler.complete(0, false), vec![1, 2]);
+        assert_eq!(scheduler.take_ready(), Vec::<usize>::new());
+        scheduler.complete(3, true);
+        assert!(!scheduler.has_running_jobs());
+    }
+
+    #[test]
+    fn test_dag_errors() {
+        let cycle = manifest(
+            r#"
+jobs:
+  - name: a
+    command: echo a
+    depends_on: [b]
+  - name: b
+    command: echo b
+    depends_on: [a]
+"#,
+        );
+        assert!(DagScheduler::new(&cycle).is_err());
+
+        let unknown = manifest(
+            r#"
+jobs:
+  - command: echo a
+    depends_on: [missing]
+"#,
+        );
+        assert!(DagScheduler::new(&unknown).is_err());
+
+        let duplicate = manifest(
+            r#"
+jobs:
+  - name: a
+    command: echo a
+  - name: a
+    command: echo a
+"#,
+        );
+        assert!(DagScheduler::new(&duplicate).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use futures::{Stream, StreamExt};
+
+/// Weighted round robin over input streams with --fair-inputs, taking up to the weight of a
+/// stream from it before moving to the next one.
+pub struct FairMerge<S> {
+    /// Weight and stream of each input, None after the stream ended.
+    sources: Vec<Option<(usize, S)>>,
+    current: usize,
+    taken: usize,
+}
+
+impl<S> FairMerge<S>
+where
+    S: Stream + Unpin,
+{
+    pub fn new(sources: impl IntoIterator<Item = (usize, S)>) -> Self {
+        Self {
+            sources: sources.into_iter().map(Some).collect(),
+            current: 0,
+            taken: 0,
+        }
+    }
+
+    fn advance(&mut self) {
+        self.current = (self.current + 1) % self.sources.len();
+        self.taken = 0;
+    }
+
+    /// Next item and the index of its stream, None when all streams ended.
+    pub async fn next(&mut self) -> Option<(usize, S::Item)> {
+        while self.sources.iter().any(Option::is_some) {
+            let index = self.current;
+
+            if let Some((weight, stream)) = &mut self.sources[index] {
+                if self.taken < *weight {
+                    match stream.next().await {
+                        Some(item) => {
+                            self.taken += 1;
+                            return Some((index, item));
+                        }
+                        None => self.sources[index] = None,
+                    }
+                }
+            }
+
+            self.advance();
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::stream;
+
+    use super::*;
+
+    async fn collect<S: Stream + Unpin>(mut fair_merge: FairMerge<S>) -> Vec<S::Item> {
+        let mut items = vec![];
+        while let Some((_, item)) = fair_merge.next().await {
+            items.push(item);
+        }
+        items
+    }
+
+    #[tokio::test]
+    async fn test_fair_merge_weights() {
+        let fair_merge = FairMerge::new([
+            (2, stream::iter(vec!["a1", "a2", "a3", "a4", "a5", "a6"])),
+            (1, stream::iter(vec!["b1", "b2"])),
+        ]);
+
+        assert_eq!(
+            collect(fair_merge).await,
+            vec!["a1", "a2", "b1", "a3", "a4", "b2", "a5", "a6"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fair_merge_ended_streams() {
+        let fair_merge = FairMerge::new([
+            (1, stream::iter(vec!["a1"])),
+            (1, stream::iter(vec![])),
+            (1, stream::iter(vec!["c1", "c2", "c3"])),
+        ]);
+
+        assert_eq!(collect(fair_merge).await, vec!["a1", "c1", "c2", "c3"]);
+    }
+}
@@ -0,0 +1,194 @@
+use anyhow::Context;
+
+use serde::Deserialize;
+
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+use crate::common::JobOptions;
+
+/// A command given either as a single command line or as an argument list.
+#[derive(Debug, Deserialize, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum ManifestCommand {
+    Line(String),
+    Args(Vec<String>),
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestJob {
+    /// Name used to refer to this job in `depends_on`.
+    pub name: Option<String>,
+    pub command: ManifestCommand,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    pub workdir: Option<PathBuf>,
+    /// Timeout in seconds.
+    pub timeout: Option<f64>,
+    pub retries: Option<usize>,
+    /// Cost counted against --budget.
+    pub cost: Option<f64>,
+    /// Names of jobs that must succeed before this job runs.
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+}
+
+impl ManifestJob {
+    pub fn display_name(&self, index: usize) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("#{}", index + 1),
+        }
+    }
+
+    pub fn job_options(&self) -> anyhow::Result<JobOptions> {
+        let timeout = match self.timeout {
+            None => None,
+            Some(timeout) if timeout > 0f64 => Some(Duration::from_secs_f64(timeout)),
+            Some(timeout) => anyhow::bail!("timeout {} not greater than 0", timeout),
+        };
+
+        if let Some(cost) = self.cost {
+            if !cost.is_finite() || cost < 0f64 {
+                anyhow::bail!("cost {} is negative", cost);
+            }
+        }
+
+        Ok(JobOptions {
+            env: self
+                .env
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            clear_env: false,
+            current_dir: self.workdir.clone(),
+            timeout,
+            retries: self.retries,
+            tag: None,
+            cost: self.cost,
+            output_tag: None,
+            slot_key: None,
+            mutex_key: None,
+            expect_file: None,
+            expected_exit_code: None,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub jobs: Vec<ManifestJob>,
+}
+
+impl Manifest {
+    pub fn has_dependencies(&self) -> bool {
+        self.jobs.iter().any(|job| !job.depends_on.is_empty())
+    }
+
+    pub async fn load(file_name: &str) -> anyhow::Result<Self> {
+        let contents = tokio::fs::read_to_string(file_name)
+            .await
+            .with_context(|| format!("error reading manifest file_name = '{}'", file_name))?;
+
+        Self::parse(file_name, &contents)
+            .with_context(|| format!("error parsing manifest file_name = '{}'", file_name))
+    }
+
+    /// Files ending in .toml are parsed as TOML, anything else as YAML.
+    fn parse(file_name: &str, contents: &str) -> anyhow::Result<Self> {
+        if file_name.ends_with(".toml") {
+            Ok(toml::from_str(contents)?)
+        } else {
+            Ok(serde_yaml::from_str(contents)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_yaml() {
+        let manifest = Manifest::parse(
+            "jobs.yaml",
+            r#"
+jobs:
+  - command: echo hello
+  - command: [ls, -l]
+    env:
+      FOO: bar
+    workdir: /tmp
+    timeout: 1.5
+    retries: 2
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.jobs.len(), 2);
+        assert_eq!(
+            manifest.jobs[0].command,
+            ManifestCommand::Line("echo hello".to_owned())
+        );
+        assert_eq!(
+            manifest.jobs[0].job_options().unwrap(),
+            JobOptions::default()
+        );
+        assert_eq!(
+            manifest.jobs[1].command,
+            ManifestCommand::Args(vec!["ls".to_owned(), "-l".to_owned()])
+        );
+        assert_eq!(
+            manifest.jobs[1].job_options().unwrap(),
+            JobOptions {
+                env: vec![("FOO".to_owned(), "bar".to_owned())],
+                clear_env: false,
+                current_dir: Some(PathBuf::from("/tmp")),
+                timeout: Some(Duration::from_millis(1500)),
+                retries: Some(2),
+                tag: None,
+                cost: None,
+                output_tag: None,
+                slot_key: None,
+                mutex_key: None,
+                expect_file: None,
+                expected_exit_code: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_toml() {
+        let manifest = Manifest::parse(
+            "jobs.toml",
+            r#"
+[[jobs]]
+command = "echo hello"
+
+[[jobs]]
+command = ["echo", "world"]
+timeout = 10
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.jobs.len(), 2);
+        assert_eq!(
+            manifest.jobs[1].job_options().unwrap().timeout,
+            Some(Duration::from_secs(10))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Manifest::parse("jobs.yaml", "jobs:\n  - cmd: echo\n").is_err());
+        assert!(
+            Manifest::parse("jobs.yaml", "jobs:\n  - command: echo\n    timeout: 0\n")
+                .unwrap()
+                .jobs[0]
+                .job_options()
+                .is_err()
+        );
+    }
+}
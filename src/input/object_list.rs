@@ -0,0 +1,52 @@
+use anyhow::Context;
+
+use futures::stream::BoxStream;
+
+use object_store::{path::Path, ObjectMeta, ObjectStore};
+
+use url::Url;
+
+/// Lists objects under an s3://bucket/prefix or gs://bucket/prefix URL for --s3-list.
+///
+/// Credentials and region are taken from the usual environment variables such as
+/// AWS_ACCESS_KEY_ID, AWS_REGION, or GOOGLE_SERVICE_ACCOUNT.
+pub struct ObjectList {
+    store: Box<dyn ObjectStore>,
+    prefix: Path,
+}
+
+impl ObjectList {
+    pub fn new(url: &str) -> anyhow::Result<Self> {
+        let parsed_url = Url::parse(url).with_context(|| format!("invalid url '{}'", url))?;
+
+        let options = std::env::vars().map(|(k, v)| (k.to_ascii_lowercase(), v));
+
+        let (store, prefix) = object_store::parse_url_opts(&parsed_url, options)
+            .with_context(|| format!("unsupported object store url '{}'", url))?;
+
+        Ok(Self { store, prefix })
+    }
+
+    /// Objects under the prefix, fetching further pages as the stream is consumed.
+    pub fn list(&self) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+        let prefix = (!self.prefix.as_ref().is_empty()).then_some(&self.prefix);
+
+        self.store.list(prefix)
+    }
+}
+
+/// Placeholder values for an object, the key first so it is appended when
+/// the command has no placeholders.
+pub fn object_row(object_meta: ObjectMeta) -> Vec<(String, String)> {
+    vec![
+        ("key".to_owned(), object_meta.location.to_string()),
+        ("size".to_owned(), object_meta.size.to_string()),
+        (
+            "etag".to_owned(),
+            object_meta
+                .e_tag
+                .map(|e_tag| e_tag.trim_matches('"').to_owned())
+                .unwrap_or_default(),
+        ),
+    ]
+}
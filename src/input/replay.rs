@@ -0,0 +1,151 @@
+use anyhow::Context;
+
+use std::{collections::HashMap, ffi::OsString, path::PathBuf};
+
+use crate::{
+    common::{JobOptions, OwnedCommandAndArgs},
+    process::{AuditEvent, AuditRecord, REDACTED},
+};
+
+/// A process recorded in an --audit-log file.
+#[derive(Debug, PartialEq)]
+pub struct ReplayEntry {
+    pub line_number: usize,
+    pub command_and_args: OwnedCommandAndArgs,
+    pub job_options: JobOptions,
+    /// None if no exit was recorded, e.g. the run was interrupted.
+    pub success: Option<bool>,
+}
+
+pub async fn load(file_name: &str, failed_only: bool) -> anyhow::Result<Vec<ReplayEntry>> {
+    let contents = tokio::fs::read_to_string(file_name)
+        .await
+        .with_context(|| format!("error reading audit log file_name = '{}'", file_name))?;
+
+    let entries = parse(&contents)
+        .with_context(|| format!("error parsing audit log file_name = '{}'", file_name))?;
+
+    Ok(entries
+        .into_iter()
+        .filter(|entry| !failed_only || entry.success != Some(true))
+        .collect())
+}
+
+fn parse(contents: &str) -> anyhow::Result<Vec<ReplayEntry>> {
+    let mut entries = vec![];
+
+    // Latest spawned entry for each job and pid, so exits of appended runs match
+    // their own spawn.
+    let mut running = HashMap::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        let line_number = i + 1;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: AuditRecord =
+            serde_json::from_str(line).with_context(|| format!("invalid line {}", line_number))?;
+
+        match record.event {
+            AuditEvent::Spawn {
+                job,
+                pid,
+                program,
+                argv,
+                cwd,
+                env,
+            } => {
+                let env = env
+                    .into_iter()
+                    .filter_map(|(k, v)| {
+                        if v == REDACTED {
+                            std::env::var(&k).ok().map(|v| (k, v))
+                        } else {
+                            Some((k, v))
+                        }
+                    })
+                    .collect();
+
+                running.insert((job, pid), entries.len());
+
+                entries.push(ReplayEntry {
+                    line_number,
+                    command_and_args: OwnedCommandAndArgs {
+                        command_path: PathBuf::from(program),
+                        args: argv.into_iter().skip(1).map(OsString::from).collect(),
+                    },
+                    job_options: JobOptions {
+                        env,
+                        clear_env: true,
+                        current_dir: Some(PathBuf::from(cwd)),
+                        ..Default::default()
+                    },
+                    success: None,
+                });
+            }
+            AuditEvent::Exit {
+                job, pid, success, ..
+            } => {
+                if let Some(index) = running.remove(&(job, pid)) {
+                    entries[index].success = Some(success);
+                }
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let entries = parse(
+            r#"{"timestamp":"t","event":"spawn","job":1,"pid":10,"program":"/bin/echo","argv":["echo","A"],"cwd":"/tmp","env":{"FOO":"bar"},"prev_hash":"0"}
+{"timestamp":"t","event":"spawn","job":2,"pid":11,"program":"/bin/false","argv":["false"],"cwd":"/","env":{},"prev_hash":"1"}
+{"timestamp":"t","event":"exit","job":2,"pid":11,"success":false,"exit_code":1,"prev_hash":"2"}
+{"timestamp":"t","event":"exit","job":1,"pid":10,"success":true,"exit_code":0,"prev_hash":"3"}
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            entries,
+            vec![
+                ReplayEntry {
+                    line_number: 1,
+                    command_and_args: OwnedCommandAndArgs {
+                        command_path: PathBuf::from("/bin/echo"),
+                        args: vec!["A".into()],
+                    },
+                    job_options: JobOptions {
+                        env: vec![("FOO".to_owned(), "bar".to_owned())],
+                        clear_env: true,
+                        current_dir: Some(PathBuf::from("/tmp")),
+                        ..Default::default()
+                    },
+                    success: Some(true),
+                },
+                ReplayEntry {
+                    line_number: 2,
+                    command_and_args: OwnedCommandAndArgs {
+                        command_path: PathBuf::from("/bin/false"),
+                        args: vec![],
+                    },
+                    job_options: JobOptions {
+                        clear_env: true,
+                        current_dir: Some(PathBuf::from("/")),
+                        ..Default::default()
+                    },
+                    success: Some(false),
+                },
+            ]
+        );
+
+        assert!(parse("not json\n").is_err());
+    }
+}
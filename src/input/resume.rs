@@ -0,0 +1,156 @@
+use anyhow::Context;
+
+use tracing::warn;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::common::OwnedCommandAndArgs;
+
+/// Job numbers that succeeded according to the --joblog of a previous run, for --resume.
+///
+/// A missing joblog has no completed jobs.
+pub fn load_succeeded_jobs(joblog: &str) -> anyhow::Result<HashSet<usize>> {
+    let contents = match std::fs::read_to_string(joblog) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        result => result.with_context(|| format!("error reading joblog '{}'", joblog))?,
+    };
+
+    parse_succeeded_jobs(&contents).with_context(|| format!("invalid joblog '{}'", joblog))
+}
+
+/// Commands of the jobs whose last record in a previous run's --joblog failed, for --retry-failed.
+#[derive(Debug, Default)]
+pub struct FailedJobs(HashMap<usize, String>);
+
+impl FailedJobs {
+    /// A job is retried only if the command built from the input is the one that failed.
+    pub fn is_retried(&self, job_number: usize, command_and_args: &OwnedCommandAndArgs) -> bool {
+        let Some(failed_command) = self.0.get(&job_number) else {
+            return false;
+        };
+
+        // the same substitution as the joblog Command column
+        let command = command_and_args
+            .command_line_lossy()
+            .replace(['\t', '\n'], " ");
+
+        if command != *failed_command {
+            warn!(
+                "not retrying job {}, command '{}' does not match joblog command '{}'",
+                job_number, command, failed_command
+            );
+            return false;
+        }
+
+        true
+    }
+}
+
+pub fn load_failed_jobs(joblog: &str) -> anyhow::Result<FailedJobs> {
+    let contents = std::fs::read_to_string(joblog)
+        .with_context(|| format!("error reading joblog '{}'", joblog))?;
+
+    parse_failed_jobs(&contents).with_context(|| format!("invalid joblog '{}'", joblog))
+}
+
+struct JobLogRecord<'a> {
+    seq: usize,
+    succeeded: bool,
+    command: &'a str,
+}
+
+fn parse_records(contents: &str) -> anyhow::Result<Vec<JobLogRecord<'_>>> {
+    // the first line is the header
+    contents
+        .lines()
+        .enumerate()
+        .skip(1)
+        .map(|(i, line)| {
+            let columns: Vec<&str> = line.split('\t').collect();
+
+            let (Some(seq), Some(exit_value), Some(signal)) =
+                (columns.first(), columns.get(6), columns.get(7))
+            else {
+                anyhow::bail!("line {} has too few columns", i + 1);
+            };
+
+            let seq: usize = seq
+                .parse()
+                .with_context(|| format!("line {} has invalid Seq '{}'", i + 1, seq))?;
+
+            Ok(JobLogRecord {
+                seq,
+                succeeded: *exit_value == "0" && *signal == "0",
+                command: columns.get(8).copied().unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+fn parse_succeeded_jobs(contents: &str) -> anyhow::Result<HashSet<usize>> {
+    Ok(parse_records(contents)?
+        .into_iter()
+        .filter(|record| record.succeeded)
+        .map(|record| record.seq)
+        .collect())
+}
+
+fn parse_failed_jobs(contents: &str) -> anyhow::Result<FailedJobs> {
+    let mut failed_jobs = HashMap::new();
+
+    // a job retried earlier has several records, the last one counts
+    for record in parse_records(contents)? {
+        if record.succeeded {
+            failed_jobs.remove(&record.seq);
+        } else {
+            failed_jobs.insert(record.seq, record.command.to_owned());
+        }
+    }
+
+    Ok(FailedJobs(failed_jobs))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_succeeded_jobs() {
+        let contents =
+            "Seq\tHost\tStarttime\tJobRuntime\tSend\tReceive\tExitval\tSignal\tCommand\n\
+                        1\t:\t1700000000.000\t   0.010\t0\t0\t0\t0\techo A\n\
+                        2\t:\t1700000000.000\t   0.010\t0\t0\t1\t0\tfalse\n\
+                        3\t:\t1700000000.000\t   0.010\t0\t0\t0\t0\techo C\n";
+
+        assert_eq!(
+            parse_succeeded_jobs(contents).unwrap(),
+            HashSet::from([1, 3])
+        );
+
+        assert!(parse_succeeded_jobs("header\n1\t:\n").is_err());
+        assert!(parse_succeeded_jobs("header\nx\t:\t0\t0\t0\t0\t0\t0\techo\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_failed_jobs() {
+        let contents =
+            "Seq\tHost\tStarttime\tJobRuntime\tSend\tReceive\tExitval\tSignal\tCommand\n\
+                        1\t:\t1700000000.000\t   0.010\t0\t0\t0\t0\techo A\n\
+                        2\t:\t1700000000.000\t   0.010\t0\t0\t1\t0\tfalse\n\
+                        3\t:\t1700000000.000\t   0.010\t0\t0\t0\t9\tsleep 10\n\
+                        2\t:\t1700000001.000\t   0.010\t0\t0\t0\t0\tfalse\n";
+
+        let failed_jobs = parse_failed_jobs(contents).unwrap();
+
+        assert_eq!(failed_jobs.0, HashMap::from([(3, "sleep 10".to_owned())]));
+
+        let command_and_args = |command: Vec<&str>| {
+            OwnedCommandAndArgs::try_from(command.into_iter().map(String::from).collect::<Vec<_>>())
+                .unwrap()
+        };
+
+        assert!(failed_jobs.is_retried(3, &command_and_args(vec!["sleep", "10"])));
+        assert!(!failed_jobs.is_retried(3, &command_and_args(vec!["sleep", "5"])));
+        assert!(!failed_jobs.is_retried(1, &command_and_args(vec!["echo", "A"])));
+    }
+}
@@ -0,0 +1,104 @@
+use sha2::{Digest, Sha256};
+
+use crate::{
+    command_line_args::{CommandLineArgs, ShardBy},
+    common::OwnedCommandAndArgs,
+};
+
+/// Selects the jobs of this invocation with --shard-count and --shard-index.
+///
+/// Selection only depends on the job number or command, so invocations with the same inputs
+/// and different shard indexes run disjoint sets of jobs covering all of them.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Shard {
+    count: usize,
+    index: usize,
+    by: ShardBy,
+}
+
+impl Shard {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        let (Some(count), Some(index)) =
+            (command_line_args.shard_count, command_line_args.shard_index)
+        else {
+            return Ok(None);
+        };
+
+        if index >= count {
+            anyhow::bail!(
+                "--shard-index {} must be less than --shard-count {}",
+                index,
+                count
+            );
+        }
+
+        Ok(Some(Self {
+            count,
+            index,
+            by: command_line_args.shard_by,
+        }))
+    }
+
+    pub fn includes(&self, job_number: usize, command_and_args: &OwnedCommandAndArgs) -> bool {
+        let key = match self.by {
+            ShardBy::Sequence => job_number.saturating_sub(1) as u64,
+            ShardBy::Hash => command_hash(command_and_args),
+        };
+
+        key % self.count as u64 == self.index as u64
+    }
+}
+
+/// First 8 bytes of the sha256 of the command line, the same on every machine.
+fn command_hash(command_and_args: &OwnedCommandAndArgs) -> u64 {
+    let digest = Sha256::digest(command_and_args.command_line_lossy().as_bytes());
+
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn command(arg: &str) -> OwnedCommandAndArgs {
+        OwnedCommandAndArgs {
+            command_path: "echo".into(),
+            args: vec![arg.into()],
+        }
+    }
+
+    fn shard_jobs(count: usize, by: ShardBy) -> Vec<Vec<usize>> {
+        (0..count)
+            .map(|index| {
+                let shard = Shard { count, index, by };
+                (1..=20)
+                    .filter(|&job_number| {
+                        shard.includes(job_number, &command(&job_number.to_string()))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_shard_by_sequence() {
+        let shards = shard_jobs(3, ShardBy::Sequence);
+
+        assert_eq!(shards[0], vec![1, 4, 7, 10, 13, 16, 19]);
+        assert_eq!(shards[1], vec![2, 5, 8, 11, 14, 17, 20]);
+        assert_eq!(shards[2], vec![3, 6, 9, 12, 15, 18]);
+    }
+
+    #[test]
+    fn test_shard_by_hash() {
+        let shards = shard_jobs(3, ShardBy::Hash);
+
+        let mut all_jobs: Vec<usize> = shards.concat();
+        all_jobs.sort();
+        assert_eq!(all_jobs, (1..=20).collect::<Vec<_>>());
+
+        assert_eq!(shards, shard_jobs(3, ShardBy::Hash));
+    }
+}
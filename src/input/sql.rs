@@ -0,0 +1,219 @@
+use anyhow::Context;
+
+use sqlx::{any::AnyRow, AnyPool, Column, Row};
+
+/// Column names and values of one query result row.
+#[derive(Debug)]
+pub struct SqlRow {
+    /// Values as strings for commands, NULL becomes an empty string.
+    pub columns: Vec<(String, String)>,
+    /// Typed values bound as statement parameters.
+    values: Vec<SqlValue>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum SqlValue {
+    Null,
+    Text(String),
+    Integer(i64),
+    Real(f64),
+    Bool(bool),
+}
+
+/// Database configured with --sql-url.
+pub struct SqlDatabase {
+    pool: AnyPool,
+    /// Postgres numbers statement parameters $1, $2, ..., other databases use ?.
+    numbered_parameters: bool,
+}
+
+impl SqlDatabase {
+    pub async fn connect(url: &str) -> anyhow::Result<Self> {
+        sqlx::any::install_default_drivers();
+
+        let pool = AnyPool::connect(url)
+            .await
+            .context("error connecting to sql database")?;
+
+        Ok(Self {
+            pool,
+            numbered_parameters: url.starts_with("postgres"),
+        })
+    }
+
+    /// Run the --sql query and return all result rows.
+    pub async fn fetch_rows(&self, query: &str) -> anyhow::Result<Vec<SqlRow>> {
+        let rows = sqlx::query(query)
+            .fetch_all(&self.pool)
+            .await
+            .with_context(|| format!("error running sql query '{}'", query))?;
+
+        rows.iter().map(row_values).collect()
+    }
+
+    /// Execute a statement with {column} placeholders bound to a row's values in one
+    /// transaction.
+    pub async fn execute_for_row(&self, statement: &str, row: &SqlRow) -> anyhow::Result<()> {
+        let (prepared_statement, parameters) =
+            prepare_statement(statement, &row.columns, self.numbered_parameters);
+
+        let mut query = sqlx::query(&prepared_statement);
+        for &i in &parameters {
+            query = match &row.values[i] {
+                SqlValue::Null => query.bind(None::<String>),
+                SqlValue::Text(value) => query.bind(value.clone()),
+                SqlValue::Integer(value) => query.bind(*value),
+                SqlValue::Real(value) => query.bind(*value),
+                SqlValue::Bool(value) => query.bind(*value),
+            };
+        }
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .context("error starting sql transaction")?;
+
+        query
+            .execute(&mut *transaction)
+            .await
+            .with_context(|| format!("error executing sql statement '{}'", statement))?;
+
+        transaction
+            .commit()
+            .await
+            .context("error committing sql transaction")?;
+
+        Ok(())
+    }
+
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+}
+
+/// Replace {column} placeholders with statement parameters, returning the statement and the
+/// column index bound to each parameter in order.
+///
+/// A placeholder in single quotes such as '{name}' is replaced including the quotes.  Braces
+/// that do not name a column are kept.
+fn prepare_statement(
+    statement: &str,
+    columns: &[(String, String)],
+    numbered_parameters: bool,
+) -> (String, Vec<usize>) {
+    let mut prepared_statement = String::with_capacity(statement.len());
+    let mut parameters = vec![];
+
+    let mut rest = statement;
+
+    while let Some(start) = rest.find('{') {
+        let column = rest[start + 1..].find('}').and_then(|end| {
+            let name = &rest[start + 1..start + 1 + end];
+            columns
+                .iter()
+                .position(|(column, _)| column == name)
+                .map(|i| (i, start + 1 + end + 1))
+        });
+
+        let Some((i, end)) = column else {
+            prepared_statement.push_str(&rest[..start + 1]);
+            rest = &rest[start + 1..];
+            continue;
+        };
+
+        let quoted = rest[..start].ends_with('\'') && rest[end..].starts_with('\'');
+        let (before, after) = if quoted {
+            (&rest[..start - 1], &rest[end + 1..])
+        } else {
+            (&rest[..start], &rest[end..])
+        };
+
+        prepared_statement.push_str(before);
+        parameters.push(i);
+        if numbered_parameters {
+            prepared_statement.push_str(&format!("${}", parameters.len()));
+        } else {
+            prepared_statement.push('?');
+        }
+
+        rest = after;
+    }
+
+    prepared_statement.push_str(rest);
+
+    (prepared_statement, parameters)
+}
+
+fn row_values(row: &AnyRow) -> anyhow::Result<SqlRow> {
+    let mut columns = vec![];
+    let mut values = vec![];
+
+    for column in row.columns() {
+        let i = column.ordinal();
+
+        let value = if let Ok(value) = row.try_get::<Option<String>, _>(i) {
+            value.map(SqlValue::Text)
+        } else if let Ok(value) = row.try_get::<Option<i64>, _>(i) {
+            value.map(SqlValue::Integer)
+        } else if let Ok(value) = row.try_get::<Option<f64>, _>(i) {
+            value.map(SqlValue::Real)
+        } else if let Ok(value) = row.try_get::<Option<bool>, _>(i) {
+            value.map(SqlValue::Bool)
+        } else {
+            anyhow::bail!("unsupported type for sql column '{}'", column.name());
+        };
+
+        let value = value.unwrap_or(SqlValue::Null);
+
+        let string_value = match &value {
+            SqlValue::Null => String::new(),
+            SqlValue::Text(value) => value.clone(),
+            SqlValue::Integer(value) => value.to_string(),
+            SqlValue::Real(value) => value.to_string(),
+            SqlValue::Bool(value) => value.to_string(),
+        };
+
+        columns.push((column.name().to_owned(), string_value));
+        values.push(value);
+    }
+
+    Ok(SqlRow { columns, values })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_prepare_statement() {
+        let columns = vec![
+            ("id".to_owned(), "7".to_owned()),
+            ("name".to_owned(), "{id}".to_owned()),
+        ];
+
+        assert_eq!(
+            prepare_statement(
+                "update jobs set status='done', owner='{name}' where id={id}",
+                &columns,
+                false
+            ),
+            (
+                "update jobs set status='done', owner=? where id=?".to_owned(),
+                vec![1, 0]
+            )
+        );
+
+        assert_eq!(
+            prepare_statement(
+                "update jobs set owner={name}, note='{x}' where id={id} or id={id}",
+                &columns,
+                true
+            ),
+            (
+                "update jobs set owner=$1, note='{x}' where id=$2 or id=$3".to_owned(),
+                vec![1, 0, 0]
+            )
+        );
+    }
+}
@@ -1,27 +1,92 @@
 use anyhow::Context;
 
-use tokio::sync::mpsc::Sender;
+use futures::StreamExt;
 
-use tracing::{debug, instrument, warn};
+use itertools::Itertools;
 
-use std::sync::Arc;
+use tokio::sync::mpsc::{unbounded_channel, Sender, UnboundedReceiver, UnboundedSender};
+
+use tracing::{debug, error, info, instrument, warn};
+
+use std::{
+    collections::HashSet,
+    ffi::{OsStr, OsString},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
 
 use crate::{
-    command_line_args::CommandLineArgs,
+    command_line_args::{CommandLineArgs, LoopCount},
+    common::{JobCompletionNotifier, JobOptions, JobOutcome, OwnedCommandAndArgs},
+    output::OutputFlusher,
     parser::{buffered::BufferedInputLineParser, command_line::CommandLineArgsParser, Parsers},
     progress::Progress,
 };
 
 use super::{
-    buffered_reader::BufferedInputReader, BufferedInput, Input, InputLineNumber, InputList,
-    InputMessage,
+    annotations::{self, InvalidAnnotationsError},
+    buffered_reader::{BufferedInputReader, LineTooLongError},
+    checksum,
+    dag::DagScheduler,
+    fair::FairMerge,
+    manifest::Manifest,
+    object_list, replay,
+    resume::{self, FailedJobs},
+    shard::Shard,
+    sql, BufferedInput, Input, InputLineNumber, InputList, InputMessage, InputSummary,
 };
 
+const REPETITION_PLACEHOLDER: &str = "{rep}";
+
+/// Errors reading buffered input that fail the run, other errors only skip the input.
+fn fails_run(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<LineTooLongError>().is_some()
+        || e.downcast_ref::<InvalidAnnotationsError>().is_some()
+}
+
+fn expand_repetition(command_and_args: &OwnedCommandAndArgs, rep: usize) -> OwnedCommandAndArgs {
+    let rep = rep.to_string();
+
+    let expand = |s: &OsStr| -> OsString {
+        match s.to_str() {
+            Some(s) if s.contains(REPETITION_PLACEHOLDER) => {
+                s.replace(REPETITION_PLACEHOLDER, &rep).into()
+            }
+            _ => s.to_owned(),
+        }
+    };
+
+    OwnedCommandAndArgs {
+        command_path: expand(command_and_args.command_path.as_os_str()).into(),
+        args: command_and_args
+            .args
+            .iter()
+            .map(|arg| expand(arg))
+            .collect(),
+    }
+}
+
 pub struct InputTask {
     sender: Sender<InputMessage>,
     command_line_args: &'static CommandLineArgs,
     progress: Arc<Progress>,
     parsers: Parsers,
+    next_job_number: AtomicUsize,
+    cost_template: Option<&'static str>,
+    /// Writes output of a --loop cycle's commands before its summary.
+    output_flusher: OutputFlusher,
+    /// Receives completion of every command in the current --loop cycle.
+    cycle_completion_sender: Option<UnboundedSender<(usize, JobOutcome)>>,
+    /// Job numbers that succeeded in the --joblog of a previous run with --resume.
+    succeeded_jobs: HashSet<usize>,
+    /// Jobs that failed in the --joblog of a previous run with --retry-failed.
+    failed_jobs: Option<FailedJobs>,
+    /// Jobs of other invocations with --shard-count are skipped.
+    shard: Option<Shard>,
 }
 
 impl InputTask {
@@ -29,24 +94,185 @@ impl InputTask {
         command_line_args: &'static CommandLineArgs,
         sender: Sender<InputMessage>,
         progress: &Arc<Progress>,
+        output_flusher: OutputFlusher,
     ) -> anyhow::Result<Self> {
         let parsers = Parsers::new(command_line_args)?;
+
+        let cost_template = command_line_args
+            .cost
+            .as_deref()
+            .filter(|cost| annotations::parse_cost(cost).is_err());
+
+        if let Some(cost_template) = cost_template {
+            if !parsers.regex_mode() {
+                anyhow::bail!(
+                    "cost '{}' is not a number and no regex is set",
+                    cost_template
+                );
+            }
+        }
+
+        let succeeded_jobs = match (&command_line_args.joblog, command_line_args.resume) {
+            (Some(joblog), true) => resume::load_succeeded_jobs(joblog)?,
+            _ => HashSet::new(),
+        };
+
+        let failed_jobs = match (&command_line_args.joblog, command_line_args.retry_failed) {
+            (Some(joblog), true) => Some(resume::load_failed_jobs(joblog)?),
+            _ => None,
+        };
+
         Ok(Self {
             sender,
             command_line_args,
             progress: Arc::clone(progress),
             parsers,
+            next_job_number: AtomicUsize::new(1),
+            cost_template,
+            output_flusher,
+            cycle_completion_sender: None,
+            succeeded_jobs,
+            failed_jobs,
+            shard: Shard::new(command_line_args)?,
         })
     }
 
-    async fn send(&self, input_message: InputMessage) {
+    /// Send the command, or with --repeat each repetition with {rep} expanded.
+    ///
+    /// Only the last repetition completes the job for dependency scheduling.
+    async fn send(
+        &self,
+        command_and_args: OwnedCommandAndArgs,
+        input_line_number: InputLineNumber,
+        input_value: String,
+        job_options: JobOptions,
+        mut completion_notifier: Option<JobCompletionNotifier>,
+    ) {
+        let Some(repeat) = self.command_line_args.repeat else {
+            self.send_message(
+                command_and_args,
+                input_line_number,
+                input_value,
+                job_options,
+                completion_notifier,
+            )
+            .await;
+            return;
+        };
+
+        for rep in 1..=repeat {
+            self.send_message(
+                expand_repetition(&command_and_args, rep),
+                input_line_number.clone(),
+                input_value.clone(),
+                job_options.clone(),
+                if rep == repeat {
+                    completion_notifier.take()
+                } else {
+                    None
+                },
+            )
+            .await;
+        }
+    }
+
+    async fn send_message(
+        &self,
+        command_and_args: OwnedCommandAndArgs,
+        input_line_number: InputLineNumber,
+        input_value: String,
+        mut job_options: JobOptions,
+        completion_notifier: Option<JobCompletionNotifier>,
+    ) {
+        let job_number = self.next_job_number.fetch_add(1, Ordering::SeqCst);
+
+        let skip = self.succeeded_jobs.contains(&job_number)
+            || self
+                .failed_jobs
+                .as_ref()
+                .is_some_and(|failed_jobs| !failed_jobs.is_retried(job_number, &command_and_args));
+
+        if skip {
+            debug!("skipping job {} according to joblog", job_number);
+            if let Some(completion_notifier) = completion_notifier {
+                completion_notifier.complete(true);
+            }
+            return;
+        }
+
+        if self
+            .shard
+            .as_ref()
+            .is_some_and(|shard| !shard.includes(job_number, &command_and_args))
+        {
+            // dropping the completion notifier reports the job as not run, so the shard that
+            // runs it reports its outcome
+            debug!("skipping job {} of another shard", job_number);
+            return;
+        }
+
         self.progress.increment_total_commands(1);
 
+        job_options.output_tag = self.output_tag(&input_value, job_number);
+        job_options.slot_key = self
+            .command_line_args
+            .slot_key
+            .as_deref()
+            .map(|slot_key| self.expand_job_template(slot_key, &input_value, job_number));
+        job_options.mutex_key = self
+            .command_line_args
+            .mutex
+            .as_deref()
+            .map(|mutex| self.expand_job_template(mutex, &input_value, job_number));
+        job_options.expect_file =
+            self.command_line_args
+                .expect_file
+                .as_deref()
+                .map(|expect_file| {
+                    PathBuf::from(self.expand_job_template(expect_file, &input_value, job_number))
+                });
+
+        job_options.expected_exit_code = self
+            .command_line_args
+            .expect_exit
+            .as_deref()
+            .map(|expect_exit| self.expand_job_template(expect_exit, &input_value, job_number));
+
+        let completion_notifier = match (completion_notifier, &self.cycle_completion_sender) {
+            (Some(notifier), Some(sender)) => Some(notifier.with_receiver(0, sender.clone())),
+            (None, Some(sender)) => Some(JobCompletionNotifier::new(0, sender.clone())),
+            (notifier, None) => notifier,
+        };
+
+        let input_message = InputMessage {
+            command_and_args,
+            input_line_number,
+            input_value,
+            job_number,
+            job_options,
+            completion_notifier,
+        };
+
         if let Err(e) = self.sender.send(input_message).await {
             warn!("input sender send error: {}", e);
         }
     }
 
+    /// Output line prefix with --tag, or --tagstring with placeholders expanded.
+    fn output_tag(&self, input_value: &str, job_number: usize) -> Option<String> {
+        let Some(tagstring) = &self.command_line_args.tagstring else {
+            return self.command_line_args.tag.then(|| input_value.to_owned());
+        };
+
+        Some(self.expand_job_template(tagstring, input_value, job_number))
+    }
+
+    /// Expand placeholders such as {1}, {#}, and {} in a per job template.
+    fn expand_job_template(&self, template: &str, input_value: &str, job_number: usize) -> String {
+        self.parsers
+            .expand_job_template(template, input_value, job_number)
+    }
+
     #[instrument(
         skip_all,
         fields(
@@ -59,14 +285,72 @@ impl InputTask {
         parser: &BufferedInputLineParser,
         input_line_number: InputLineNumber,
         segment: Vec<u8>,
-    ) {
-        if let Some(command_and_args) = parser.parse_segment(segment) {
-            self.send(InputMessage {
+    ) -> anyhow::Result<()> {
+        let (segment, mut job_options) = if self.command_line_args.annotations {
+            annotations::parse_annotations(segment).map_err(|e| InvalidAnnotationsError {
+                input_line_number: input_line_number.clone(),
+                message: format!("{:#}", e),
+            })?
+        } else {
+            (segment, JobOptions::default())
+        };
+
+        if let (None, Some(cost_template)) = (job_options.cost, self.cost_template) {
+            match self.expand_cost(cost_template, &segment) {
+                Ok(cost) => job_options.cost = Some(cost),
+                Err(e) => {
+                    warn!("invalid cost line {}: {:#}", input_line_number, e);
+                    return Ok(());
+                }
+            }
+        }
+
+        let input_line = match String::from_utf8(segment) {
+            Ok(input_line) => input_line,
+            Err(e) => {
+                let segment = e.into_bytes();
+                match parser.parse_non_utf8_line(&segment) {
+                    Some(command_and_args) => {
+                        self.send(
+                            command_and_args,
+                            input_line_number,
+                            String::from_utf8_lossy(&segment).into_owned(),
+                            job_options,
+                            None,
+                        )
+                        .await
+                    }
+                    None => warn!(
+                        "skipping input line {} that is not valid utf-8",
+                        input_line_number
+                    ),
+                }
+                return Ok(());
+            }
+        };
+
+        if let Some(command_and_args) = parser.parse_line(&input_line) {
+            self.send(
                 command_and_args,
                 input_line_number,
-            })
+                input_line,
+                job_options,
+                None,
+            )
             .await
         }
+
+        Ok(())
+    }
+
+    fn expand_cost(&self, cost_template: &str, segment: &[u8]) -> anyhow::Result<f64> {
+        let input_line = std::str::from_utf8(segment).context("input line is not valid utf-8")?;
+
+        let Some(cost) = self.parsers.expand_template(cost_template, input_line) else {
+            anyhow::bail!("regex did not match input line");
+        };
+
+        annotations::parse_cost(&cost)
     }
 
     async fn process_buffered_input(&self, buffered_input: BufferedInput) -> anyhow::Result<()> {
@@ -88,7 +372,7 @@ impl InputTask {
             {
                 Some((input_line_number, segment)) => {
                     self.process_buffered_input_line(parser, input_line_number, segment)
-                        .await
+                        .await?
                 }
                 None => {
                     debug!("input_reader.next_segment EOF");
@@ -100,6 +384,84 @@ impl InputTask {
         Ok(())
     }
 
+    /// Interleave lines of the buffered inputs with --fair-inputs, in proportion to their
+    /// --input-weight.
+    async fn process_buffered_inputs_fair(
+        &self,
+        buffered_inputs: &[BufferedInput],
+    ) -> anyhow::Result<()> {
+        let weights = &self.command_line_args.input_weight;
+
+        if weights.len() > buffered_inputs.len() {
+            anyhow::bail!(
+                "--input-weight has {} weights for {} inputs",
+                weights.len(),
+                buffered_inputs.len()
+            );
+        }
+
+        let mut sources = vec![];
+
+        for (index, &buffered_input) in buffered_inputs.iter().enumerate() {
+            let input_reader =
+                match BufferedInputReader::new(buffered_input, self.command_line_args).await {
+                    Ok(input_reader) => input_reader,
+                    Err(e) => {
+                        warn!(
+                            "process_buffered_input error buffered_input = {}: {}",
+                            buffered_input, e
+                        );
+                        continue;
+                    }
+                };
+
+            // the stream ends after the first error
+            let segments = futures::stream::unfold(Some(input_reader), |input_reader| async {
+                let mut input_reader = input_reader?;
+                match input_reader.next_segment().await {
+                    Ok(Some(segment)) => Some((Ok(segment), Some(input_reader))),
+                    Ok(None) => None,
+                    Err(e) => Some((Err(e), None)),
+                }
+            });
+
+            let weight = weights.get(index).copied().unwrap_or(1);
+
+            sources.push((buffered_input, weight, Box::pin(segments)));
+        }
+
+        let parser = self.parsers.buffered_input_line_parser().await;
+
+        let buffered_inputs: Vec<_> = sources.iter().map(|(input, _, _)| *input).collect();
+
+        let mut fair_merge = FairMerge::new(
+            sources
+                .into_iter()
+                .map(|(_, weight, segments)| (weight, segments)),
+        );
+
+        while let Some((index, result)) = fair_merge.next().await {
+            match result {
+                Ok((input_line_number, segment)) => {
+                    self.process_buffered_input_line(parser, input_line_number, segment)
+                        .await?
+                }
+                Err(e) => {
+                    if fails_run(&e) {
+                        return Err(e);
+                    }
+                    warn!(
+                        "process_buffered_input error buffered_input = {}: {:#}",
+                        buffered_inputs[index],
+                        e.context("next_segment error")
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     #[instrument(
         skip_all,
         fields(
@@ -112,16 +474,19 @@ impl InputTask {
         parser: &mut CommandLineArgsParser,
         input_line_number: InputLineNumber,
     ) {
-        if let Some(command_and_args) = parser.parse_next_argument_group() {
-            self.send(InputMessage {
+        if let Some((command_and_args, input_value)) = parser.parse_next_argument_group() {
+            self.send(
                 command_and_args,
                 input_line_number,
-            })
+                input_value,
+                JobOptions::default(),
+                None,
+            )
             .await
         };
     }
 
-    async fn process_command_line_args_input(self) {
+    async fn process_command_line_args_input(&self) {
         debug!("begin process_command_line_args_input");
 
         let mut parser = self.parsers.command_line_args_parser();
@@ -141,14 +506,300 @@ impl InputTask {
         }
     }
 
-    #[instrument(skip_all, name = "InputTask::run", level = "debug")]
-    pub async fn run(self) {
-        debug!("begin run");
+    async fn process_manifest_input(&self, file_name: &'static str) -> anyhow::Result<u64> {
+        debug!("begin process_manifest_input file_name {}", file_name);
+
+        let manifest = Manifest::load(file_name).await?;
+
+        let parser = self.parsers.manifest_command_parser();
+
+        let mut jobs_options = manifest
+            .jobs
+            .iter()
+            .enumerate()
+            .map(|(i, job)| {
+                job.job_options()
+                    .with_context(|| format!("invalid manifest job {}:{}", file_name, i + 1))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let input_line_number = |i: usize| InputLineNumber {
+            input: Input::Manifest { file_name },
+            line_number: i + 1,
+        };
+
+        if !manifest.has_dependencies() {
+            for (i, job) in manifest.jobs.iter().enumerate() {
+                match parser.parse_command(&job.command) {
+                    None => warn!("empty command in manifest job {}", input_line_number(i)),
+                    Some(command_and_args) => {
+                        let job_options = std::mem::take(&mut jobs_options[i]);
+                        self.send(
+                            command_and_args,
+                            input_line_number(i),
+                            job.display_name(i),
+                            job_options,
+                            None,
+                        )
+                        .await
+                    }
+                }
+            }
+            return Ok(0);
+        }
+
+        let mut scheduler = DagScheduler::new(&manifest)
+            .with_context(|| format!("invalid manifest file_name = '{}'", file_name))?;
+
+        let (completion_sender, mut completion_receiver) = unbounded_channel();
+
+        let mut dependency_failures = 0;
+
+        loop {
+            for i in scheduler.take_ready() {
+                let skipped = match parser.parse_command(&manifest.jobs[i].command) {
+                    None => {
+                        warn!("empty command in manifest job {}", input_line_number(i));
+                        scheduler.complete(i, false)
+                    }
+                    Some(command_and_args) => {
+                        let job_options = std::mem::take(&mut jobs_options[i]);
+                        let notifier = JobCompletionNotifier::new(i, completion_sender.clone());
+                        self.send(
+                            command_and_args,
+                            input_line_number(i),
+                            manifest.jobs[i].display_name(i),
+                            job_options,
+                            Some(notifier),
+                        )
+                        .await;
+                        vec![]
+                    }
+                };
+                dependency_failures += self.report_skipped_jobs(&manifest, i, skipped);
+            }
+
+            if !scheduler.has_running_jobs() {
+                break;
+            }
+
+            let Some((i, outcome)) = completion_receiver.recv().await else {
+                break;
+            };
+
+            // jobs not run, e.g. with --dry-run, do not fail the jobs depending on them
+            let skipped = scheduler.complete(i, outcome != JobOutcome::Failed);
+            dependency_failures += self.report_skipped_jobs(&manifest, i, skipped);
+        }
 
-        match super::build_input_list(self.command_line_args) {
-            InputList::BufferedInputList(buffered_inputs) => {
-                for buffered_input in buffered_inputs {
+        Ok(dependency_failures)
+    }
+
+    async fn process_replay_input(
+        &self,
+        file_name: &'static str,
+        failed_only: bool,
+    ) -> anyhow::Result<()> {
+        debug!(
+            "begin process_replay_input file_name {} failed_only {}",
+            file_name, failed_only
+        );
+
+        for entry in replay::load(file_name, failed_only).await? {
+            let input_line_number = InputLineNumber {
+                input: Input::Replay { file_name },
+                line_number: entry.line_number,
+            };
+
+            let input_value = std::iter::once(
+                entry
+                    .command_and_args
+                    .command_path
+                    .to_string_lossy()
+                    .into_owned(),
+            )
+            .chain(
+                entry
+                    .command_and_args
+                    .args
+                    .iter()
+                    .map(|arg| arg.to_string_lossy().into_owned()),
+            )
+            .collect::<Vec<_>>()
+            .join(" ");
+
+            self.send(
+                entry.command_and_args,
+                input_line_number,
+                input_value,
+                entry.job_options,
+                None,
+            )
+            .await
+        }
+
+        Ok(())
+    }
+
+    async fn process_sql_input(&self, url: &str, query: &str) -> anyhow::Result<()> {
+        debug!("begin process_sql_input query {}", query);
+
+        let database = Arc::new(sql::SqlDatabase::connect(url).await?);
+
+        let rows = Arc::new(database.fetch_rows(query).await?);
+
+        let on_success = self.command_line_args.sql_on_success.as_deref();
+        let on_failure = self.command_line_args.sql_on_failure.as_deref();
+
+        let (completion_sender, completion_receiver) = unbounded_channel();
+
+        let completion_task = (on_success.is_some() || on_failure.is_some()).then(|| {
+            tokio::spawn(Self::run_sql_completion_statements(
+                Arc::clone(&database),
+                Arc::clone(&rows),
+                completion_receiver,
+                on_success,
+                on_failure,
+            ))
+        });
+
+        let parser = self.parsers.row_parser();
+
+        for (i, row) in rows.iter().enumerate() {
+            let input_line_number = InputLineNumber {
+                input: Input::Sql,
+                line_number: i + 1,
+            };
+
+            match parser.parse_row(&row.columns, row.columns.len()) {
+                None => warn!("empty command for sql row {}", input_line_number),
+                Some(command_and_args) => {
+                    let notifier = completion_task
+                        .is_some()
+                        .then(|| JobCompletionNotifier::new(i, completion_sender.clone()));
+                    let input_value = row
+                        .columns
+                        .iter()
+                        .map(|(_, value)| value.as_str())
+                        .join("\t");
+                    self.send(
+                        command_and_args,
+                        input_line_number,
+                        input_value,
+                        JobOptions::default(),
+                        notifier,
+                    )
+                    .await
+                }
+            }
+        }
+
+        drop(completion_sender);
+
+        if let Some(completion_task) = completion_task {
+            completion_task
+                .await
+                .context("sql completion task join error")?;
+        }
+
+        database.close().await;
+
+        Ok(())
+    }
+
+    async fn process_object_list_input(&self, url: &'static str) -> anyhow::Result<()> {
+        debug!("begin process_object_list_input url {}", url);
+
+        let object_list = object_list::ObjectList::new(url)?;
+
+        let parser = self.parsers.row_parser();
+
+        let mut objects = object_list.list();
+
+        let mut line_number = 0;
+
+        while let Some(object_meta) = objects
+            .next()
+            .await
+            .transpose()
+            .with_context(|| format!("error listing objects url = '{}'", url))?
+        {
+            line_number += 1;
+
+            let input_line_number = InputLineNumber {
+                input: Input::ObjectList { url },
+                line_number,
+            };
+
+            let row = object_list::object_row(object_meta);
+
+            match parser.parse_row(&row, 1) {
+                None => warn!("empty command for object {}", input_line_number),
+                Some(command_and_args) => {
+                    let key = row[0].1.clone();
+                    self.send(
+                        command_and_args,
+                        input_line_number,
+                        key,
+                        JobOptions::default(),
+                        None,
+                    )
+                    .await
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run --sql-on-success or --sql-on-failure for each row as its command completes.
+    async fn run_sql_completion_statements(
+        database: Arc<sql::SqlDatabase>,
+        rows: Arc<Vec<sql::SqlRow>>,
+        mut completion_receiver: UnboundedReceiver<(usize, JobOutcome)>,
+        on_success: Option<&'static str>,
+        on_failure: Option<&'static str>,
+    ) {
+        while let Some((i, outcome)) = completion_receiver.recv().await {
+            // rows of commands not run are left unchanged
+            let statement = match outcome {
+                JobOutcome::Succeeded => on_success,
+                JobOutcome::Failed => on_failure,
+                JobOutcome::NotRun => None,
+            };
+
+            if let Some(statement) = statement {
+                if let Err(e) = database.execute_for_row(statement, &rows[i]).await {
+                    error!("sql statement error for sql row {}: {:#}", i + 1, e);
+                }
+            }
+        }
+    }
+
+    fn report_skipped_jobs(&self, manifest: &Manifest, failed: usize, skipped: Vec<usize>) -> u64 {
+        for &i in &skipped {
+            error!(
+                "skipping manifest job {}: dependency {} failed",
+                manifest.jobs[i].display_name(i),
+                manifest.jobs[failed].display_name(failed),
+            );
+        }
+        skipped.len() as u64
+    }
+
+    async fn process_input_list(&self, input_list: &InputList) -> anyhow::Result<InputSummary> {
+        let mut input_summary = InputSummary::default();
+
+        match input_list {
+            InputList::Buffered(buffered_inputs) if self.command_line_args.fair_inputs() => {
+                self.process_buffered_inputs_fair(buffered_inputs).await?
+            }
+            InputList::Buffered(buffered_inputs) => {
+                for &buffered_input in buffered_inputs {
                     if let Err(e) = self.process_buffered_input(buffered_input).await {
+                        if fails_run(&e) {
+                            return Err(e);
+                        }
                         warn!(
                             "process_buffered_input error buffered_input = {}: {}",
                             buffered_input, e
@@ -157,8 +808,99 @@ impl InputTask {
                 }
             }
             InputList::CommandLineArgs => self.process_command_line_args_input().await,
+            &InputList::Manifest { file_name } => {
+                input_summary.dependency_failures = self.process_manifest_input(file_name).await?
+            }
+            &InputList::Replay {
+                file_name,
+                failed_only,
+            } => self.process_replay_input(file_name, failed_only).await?,
+            InputList::Sql { url, query } => self.process_sql_input(url, query).await?,
+            &InputList::ObjectList { url } => self.process_object_list_input(url).await?,
+        }
+
+        Ok(input_summary)
+    }
+
+    /// Process the inputs once per --loop cycle, waiting for each cycle's commands to finish.
+    async fn process_input_list_cycles(
+        &mut self,
+        input_list: &InputList,
+        loop_count: LoopCount,
+    ) -> anyhow::Result<InputSummary> {
+        if let InputList::Buffered(buffered_inputs) = input_list {
+            if buffered_inputs.contains(&BufferedInput::Stdin) {
+                anyhow::bail!("--loop can not re-read stdin, use input files instead");
+            }
+        }
+
+        let mut input_summary = InputSummary::default();
+
+        let mut cycle = 1;
+
+        while loop_count.includes_cycle(cycle) {
+            let cycle_start = Instant::now();
+
+            let (completion_sender, mut completion_receiver) = unbounded_channel();
+            self.cycle_completion_sender = Some(completion_sender);
+
+            let result = self.process_input_list(input_list).await;
+
+            self.cycle_completion_sender = None;
+
+            let (mut commands, mut failures) = (0, 0);
+            while let Some((_, outcome)) = completion_receiver.recv().await {
+                match outcome {
+                    JobOutcome::Succeeded => commands += 1,
+                    JobOutcome::Failed => {
+                        commands += 1;
+                        failures += 1;
+                    }
+                    JobOutcome::NotRun => {}
+                }
+            }
+
+            let cycle_summary = result?;
+            input_summary.dependency_failures += cycle_summary.dependency_failures;
+
+            // commands send their output before completing
+            self.output_flusher.flush().await;
+
+            info!(
+                "loop cycle={} commands={} failures={} dependency_failures={} elapsed={:?}",
+                cycle,
+                commands,
+                failures,
+                cycle_summary.dependency_failures,
+                cycle_start.elapsed(),
+            );
+
+            cycle += 1;
         }
 
+        Ok(input_summary)
+    }
+
+    #[instrument(skip_all, name = "InputTask::run", level = "debug")]
+    pub async fn run(mut self) -> anyhow::Result<InputSummary> {
+        debug!("begin run");
+
+        if let Some(checksum_file_name) = &self.command_line_args.verify_input {
+            checksum::verify_input_checksums(checksum_file_name).await?;
+        }
+
+        let input_list = super::build_input_list(self.command_line_args);
+
+        let input_summary = match self.command_line_args.loop_count {
+            None => self.process_input_list(&input_list).await?,
+            Some(loop_count) => {
+                self.process_input_list_cycles(&input_list, loop_count)
+                    .await?
+            }
+        };
+
         debug!("end run");
+
+        Ok(input_summary)
     }
 }
@@ -1,15 +1,22 @@
 use tracing::{debug, error, instrument};
 
+use tracing_subscriber::{filter::Targets, fmt, layer::SubscriberExt, util::SubscriberInitExt};
+
 use crate::command_line_args::CommandLineArgs;
 
+mod calibrate;
 mod command;
 mod command_line_args;
 mod common;
+mod detach;
+mod history;
 mod input;
 mod output;
 mod parser;
 mod process;
 mod progress;
+mod recorded_env;
+mod run_dir;
 
 #[instrument(skip_all, name = "try_main", level = "debug")]
 async fn try_main() -> anyhow::Result<()> {
@@ -17,9 +24,17 @@ async fn try_main() -> anyhow::Result<()> {
 
     let command_line_args = CommandLineArgs::instance().await;
 
+    if command_line_args.run_subcommand().await? {
+        return Ok(());
+    }
+
+    if detach::detach(command_line_args)? {
+        return Ok(());
+    }
+
     let progress = progress::Progress::new(command_line_args)?;
 
-    let command_service = command::CommandService::new(command_line_args, progress);
+    let command_service = command::CommandService::new(command_line_args, progress)?;
 
     command_service.run_commands().await?;
 
@@ -28,12 +43,50 @@ async fn try_main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Log to stdout as configured by RUST_LOG, and to the --run-dir log.
+fn init_tracing() {
+    let targets = std::env::var("RUST_LOG")
+        .ok()
+        .and_then(|rust_log| rust_log.parse::<Targets>().ok())
+        .unwrap_or_else(|| Targets::new().with_default(tracing::Level::INFO));
+
+    tracing_subscriber::registry()
+        .with(fmt::layer())
+        .with(
+            fmt::layer()
+                .with_ansi(false)
+                .with_writer(|| run_dir::LogWriter),
+        )
+        .with(targets)
+        .init();
+}
+
+/// Exit status for an error of try_main.
+fn exit_status(err: &anyhow::Error) -> i32 {
+    if let Some(e) = err.downcast_ref::<command::metrics::CommandFailuresError>() {
+        e.exit_status
+    } else if let Some(e) = err.downcast_ref::<command::InterruptedError>() {
+        e.exit_status
+    } else {
+        1
+    }
+}
+
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    init_tracing();
 
     if let Err(err) = try_main().await {
         error!("fatal error in main: {:#}", err);
-        std::process::exit(1);
+
+        let exit_status = exit_status(&err);
+
+        detach::run_finished(exit_status);
+        std::process::exit(exit_status);
     }
+
+    detach::run_finished(0);
+
+    // exit without waiting for a stdin reader left blocked, e.g. after --input-idle-timeout
+    std::process::exit(0);
 }
@@ -1,91 +1,314 @@
+mod filter;
+mod fsync;
+mod join;
+mod log_level;
+mod map;
+mod results;
+mod route;
+mod tag;
 mod task;
 
+pub use self::fsync::SyncSchedule;
+
 use anyhow::Context;
 
-use tokio::{
-    sync::mpsc::{channel, Sender},
-    task::JoinHandle,
+use tokio::sync::{
+    mpsc::{channel, Sender, WeakSender},
+    oneshot,
 };
 
 use tracing::{debug, warn};
 
-use std::process::{ExitStatus, Output};
+use std::{
+    process::{ExitStatus, Output},
+    sync::Arc,
+};
 
 use crate::{
-    command_line_args::CommandLineArgs, common::OwnedCommandAndArgs, input::InputLineNumber,
+    command_line_args::{CommandLineArgs, ShowOutput},
+    common::OwnedCommandAndArgs,
+    input::InputLineNumber,
 };
 
 #[derive(Debug)]
 struct OutputMessage {
-    exit_status: ExitStatus,
+    /// None if the command could not be spawned or did not exit, which is
+    /// already logged.
+    exit_status: Option<ExitStatus>,
+    /// Whether the command counts as succeeded, which its exit status may not decide.
+    succeeded: bool,
     stdout: Vec<u8>,
     stderr: Vec<u8>,
     command_and_args: OwnedCommandAndArgs,
     input_line_number: InputLineNumber,
+    input_value: String,
+    job_number: usize,
+    /// Output before --show-output and output filters apply, if written to --results.
+    results_output: Option<Box<results::ResultsOutput>>,
+}
+
+/// Message to the output thread.
+#[derive(Debug)]
+enum OutputTaskMessage {
+    Output(OutputMessage),
+    /// Reply once output of earlier messages is written.
+    Flush(oneshot::Sender<()>),
+}
+
+/// Applied to output in command tasks before it is sent to the output thread.
+struct OutputSenderSettings {
+    log_level_filter: Option<log_level::ChildLogLevelFilter>,
+    output_filters: Option<filter::OutputFilters>,
+    show_output: ShowOutput,
+    /// Send messages for every command with its unfiltered output for --results.
+    write_results: bool,
+    /// Send messages for successful commands without output, e.g. for --success-out.
+    send_all_successes: bool,
+    /// Send messages for commands that did not complete, e.g. for --failure-out.
+    send_not_completed: bool,
 }
 
+#[derive(Clone)]
 pub struct OutputSender {
-    sender: Sender<OutputMessage>,
+    sender: Sender<OutputTaskMessage>,
+    settings: Arc<OutputSenderSettings>,
 }
 
 impl OutputSender {
+    #[allow(clippy::too_many_arguments)]
     pub async fn send(
         self,
-        output: Output,
+        mut output: Output,
+        succeeded: bool,
         command_and_args: OwnedCommandAndArgs,
         input_line_number: InputLineNumber,
+        input_value: String,
+        job_number: usize,
+        output_tag: Option<&str>,
     ) {
-        if output.status.success() && output.stdout.is_empty() && output.stderr.is_empty() {
+        let settings = &self.settings;
+
+        let results_output = settings.write_results.then(|| {
+            Box::new(results::ResultsOutput {
+                stdout: output.stdout.clone(),
+                stderr: output.stderr.clone(),
+            })
+        });
+
+        let show = match settings.show_output {
+            ShowOutput::All => true,
+            ShowOutput::Failed => !succeeded,
+            ShowOutput::None => false,
+        };
+
+        if !show {
+            output.stdout.clear();
+            output.stderr.clear();
+        }
+
+        if let Some(log_level_filter) = &settings.log_level_filter {
+            output.stdout = log_level_filter.filter(output.stdout);
+            output.stderr = log_level_filter.filter(output.stderr);
+        }
+
+        if let Some(output_filters) = &settings.output_filters {
+            output.stdout = output_filters.filter(output.stdout);
+            output.stderr = output_filters.filter(output.stderr);
+        }
+
+        if let Some(output_tag) = output_tag {
+            output.stdout = tag::tag_lines(output.stdout, output_tag);
+            output.stderr = tag::tag_lines(output.stderr, output_tag);
+        }
+
+        if succeeded
+            && output.stdout.is_empty()
+            && output.stderr.is_empty()
+            && !settings.send_all_successes
+            && !settings.write_results
+        {
             return;
         }
 
         let output_message = OutputMessage {
-            exit_status: output.status,
+            exit_status: Some(output.status),
+            succeeded,
             stdout: output.stdout,
             stderr: output.stderr,
             command_and_args,
             input_line_number,
+            input_value,
+            job_number,
+            results_output,
+        };
+
+        if let Err(e) = self
+            .sender
+            .send(OutputTaskMessage::Output(output_message))
+            .await
+        {
+            warn!("sender.send error: {}", e);
+        }
+    }
+
+    /// Report a command that could not be spawned or did not exit.
+    pub async fn send_not_completed(
+        self,
+        command_and_args: OwnedCommandAndArgs,
+        input_line_number: InputLineNumber,
+        input_value: String,
+        job_number: usize,
+    ) {
+        let settings = &self.settings;
+
+        if !settings.send_not_completed && !settings.write_results {
+            return;
+        }
+
+        let output_message = OutputMessage {
+            exit_status: None,
+            succeeded: false,
+            stdout: vec![],
+            stderr: vec![],
+            command_and_args,
+            input_line_number,
+            input_value,
+            job_number,
+            results_output: settings.write_results.then(Box::default),
         };
 
-        if let Err(e) = self.sender.send(output_message).await {
+        if let Err(e) = self
+            .sender
+            .send(OutputTaskMessage::Output(output_message))
+            .await
+        {
             warn!("sender.send error: {}", e);
         }
     }
 }
 
+/// Waits until output sent so far is written, e.g. before logging a summary after it.
+///
+/// Does not keep the output thread running.
+#[derive(Clone)]
+pub struct OutputFlusher {
+    sender: WeakSender<OutputTaskMessage>,
+}
+
+impl OutputFlusher {
+    pub async fn flush(&self) {
+        let Some(sender) = self.sender.upgrade() else {
+            return;
+        };
+
+        let (flushed_sender, flushed_receiver) = oneshot::channel();
+        if sender
+            .send(OutputTaskMessage::Flush(flushed_sender))
+            .await
+            .is_err()
+        {
+            return;
+        }
+        drop(sender);
+
+        let _ = flushed_receiver.await;
+    }
+}
+
 pub struct OutputWriter {
-    sender: Sender<OutputMessage>,
-    output_task_join_handle: JoinHandle<()>,
+    sender: Sender<OutputTaskMessage>,
+    settings: Arc<OutputSenderSettings>,
+    output_thread_join_handle: std::thread::JoinHandle<()>,
 }
 
 impl OutputWriter {
-    pub fn new(command_line_args: &CommandLineArgs) -> Self {
+    /// Output is written by a dedicated thread with its own runtime, so slow
+    /// output sinks back-pressure only through the bounded output channel
+    /// and never stall tasks running commands.
+    pub fn new(command_line_args: &'static CommandLineArgs) -> anyhow::Result<Self> {
         let (sender, receiver) = channel(command_line_args.channel_capacity);
         debug!(
             "created output channel with capacity {}",
             command_line_args.channel_capacity,
         );
 
-        let output_task_join_handle = tokio::spawn(task::OutputTask::new(receiver).run());
+        let output_router = route::OutputRouter::new(command_line_args)?;
+
+        let map_output = map::MapOutput::new(command_line_args);
+
+        let join_output = join::JoinOutput::new(command_line_args);
+
+        let results_writer = results::ResultsWriter::new(command_line_args)?;
+
+        // --map and --join-output print a record for every command.
+        let record_every_command = map_output.is_some() || join_output.is_some();
+
+        let settings = Arc::new(OutputSenderSettings {
+            log_level_filter: log_level::ChildLogLevelFilter::new(command_line_args)?,
+            output_filters: filter::OutputFilters::new(command_line_args)?,
+            show_output: command_line_args.show_output,
+            write_results: results_writer.is_some(),
+            send_all_successes: record_every_command
+                || output_router
+                    .as_ref()
+                    .is_some_and(route::OutputRouter::routes_successes),
+            send_not_completed: record_every_command
+                || output_router
+                    .as_ref()
+                    .is_some_and(route::OutputRouter::routes_failures),
+        });
+
+        let output_task = task::OutputTask::new(
+            receiver,
+            command_line_args.output_buffer_size,
+            fsync::OutputSyncer::new(command_line_args.fsync),
+            output_router,
+            map_output,
+            join_output,
+            results_writer,
+        );
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .context("OutputWriter::new: error building output runtime")?;
 
-        Self {
+        let output_thread_join_handle = std::thread::Builder::new()
+            .name("output".to_owned())
+            .spawn(move || runtime.block_on(output_task.run()))
+            .context("OutputWriter::new: error spawning output thread")?;
+
+        Ok(Self {
             sender,
-            output_task_join_handle,
-        }
+            settings,
+            output_thread_join_handle,
+        })
     }
 
     pub fn sender(&self) -> OutputSender {
         OutputSender {
             sender: self.sender.clone(),
+            settings: Arc::clone(&self.settings),
+        }
+    }
+
+    pub fn flusher(&self) -> OutputFlusher {
+        OutputFlusher {
+            sender: self.sender.downgrade(),
         }
     }
 
     pub async fn wait_for_completion(self) -> anyhow::Result<()> {
         drop(self.sender);
 
-        self.output_task_join_handle
+        let output_thread_join_handle = self.output_thread_join_handle;
+
+        tokio::task::spawn_blocking(move || output_thread_join_handle.join())
             .await
-            .context("OutputWriter::wait_for_completion: output_task_join_handle.await error")?;
+            .context("OutputWriter::wait_for_completion: spawn_blocking error")?
+            .map_err(|_| {
+                anyhow::anyhow!("OutputWriter::wait_for_completion: output thread panicked")
+            })?;
 
         Ok(())
     }
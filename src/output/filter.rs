@@ -0,0 +1,172 @@
+use anyhow::Context;
+
+use regex::bytes::{Regex, RegexBuilder};
+
+use crate::command_line_args::CommandLineArgs;
+
+/// A sed like s/pattern/replacement/flags substitution from --output-filter.
+#[derive(Debug)]
+struct Substitution {
+    regex: Regex,
+    replacement: Vec<u8>,
+    global: bool,
+}
+
+impl Substitution {
+    /// Any character may follow s as the delimiter, e.g. s|/home/[^/]+|~|g.
+    /// Supported flags are g for every match and i for case insensitive.
+    fn parse(s: &str) -> anyhow::Result<Self> {
+        let mut chars = s.chars();
+
+        let delimiter = match (chars.next(), chars.next()) {
+            (Some('s'), Some(delimiter)) if !delimiter.is_alphanumeric() && delimiter != '\\' => {
+                delimiter
+            }
+            _ => anyhow::bail!("expected s/pattern/replacement/"),
+        };
+
+        let mut parts = vec![String::new()];
+        let mut escaped = false;
+
+        for c in chars {
+            if escaped {
+                if c != delimiter {
+                    parts.last_mut().unwrap().push('\\');
+                }
+                parts.last_mut().unwrap().push(c);
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == delimiter {
+                parts.push(String::new());
+            } else {
+                parts.last_mut().unwrap().push(c);
+            }
+        }
+
+        if escaped {
+            parts.last_mut().unwrap().push('\\');
+        }
+
+        let [pattern, replacement, flags] = <[String; 3]>::try_from(parts)
+            .map_err(|_| anyhow::anyhow!("expected s/pattern/replacement/"))?;
+
+        let mut global = false;
+        let mut case_insensitive = false;
+
+        for flag in flags.chars() {
+            match flag {
+                'g' => global = true,
+                'i' => case_insensitive = true,
+                _ => anyhow::bail!("unknown flag '{}'", flag),
+            }
+        }
+
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(case_insensitive)
+            .build()?;
+
+        Ok(Self {
+            regex,
+            replacement: replacement.into_bytes(),
+            global,
+        })
+    }
+
+    fn apply(&self, line: &[u8]) -> Vec<u8> {
+        let limit = if self.global { 0 } else { 1 };
+
+        self.regex
+            .replacen(line, limit, self.replacement.as_slice())
+            .into_owned()
+    }
+}
+
+/// Substitutions from --output-filter applied in order to each line of command output.
+#[derive(Debug)]
+pub struct OutputFilters {
+    substitutions: Vec<Substitution>,
+}
+
+impl OutputFilters {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        if command_line_args.output_filter.is_empty() {
+            return Ok(None);
+        }
+
+        let substitutions = command_line_args
+            .output_filter
+            .iter()
+            .map(|s| {
+                Substitution::parse(s).with_context(|| format!("invalid --output-filter '{}'", s))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Some(Self { substitutions }))
+    }
+
+    /// Patterns match within a line, the line terminator is not part of the match.
+    pub fn filter(&self, buffer: Vec<u8>) -> Vec<u8> {
+        if buffer.is_empty() {
+            return buffer;
+        }
+
+        let mut filtered = Vec::with_capacity(buffer.len());
+
+        for line in buffer.split_inclusive(|&b| b == b'\n') {
+            let (content, terminator) = match line.strip_suffix(b"\n") {
+                Some(content) => (content, &b"\n"[..]),
+                None => (line, &b""[..]),
+            };
+
+            let mut content = content.to_vec();
+            for substitution in &self.substitutions {
+                content = substitution.apply(&content);
+            }
+
+            filtered.extend_from_slice(&content);
+            filtered.extend_from_slice(terminator);
+        }
+
+        filtered
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn output_filters(filters: &[&str]) -> anyhow::Result<Option<OutputFilters>> {
+        OutputFilters::new(&CommandLineArgs {
+            output_filter: filters.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_filter() {
+        let filters = output_filters(&[r"s/token=\w+/token=REDACTED/g", r"s|/home/[^/]+|~|"])
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            filters.filter(b"token=abc token=def\n/home/alice/a /home/bob/b\nplain".to_vec()),
+            b"token=REDACTED token=REDACTED\n~/a /home/bob/b\nplain".to_vec()
+        );
+
+        let filters = output_filters(&[r"s/^(\w+) (\w+)$/$2 $1/", r"s/A\/B/x/i"])
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            filters.filter(b"hello world\na/b\n".to_vec()),
+            b"world hello\nx\n".to_vec()
+        );
+
+        assert!(output_filters(&[]).unwrap().is_none());
+        assert!(output_filters(&["s/a/b"]).is_err());
+        assert!(output_filters(&["s/a/b/x"]).is_err());
+        assert!(output_filters(&["y/a/b/"]).is_err());
+        assert!(output_filters(&["s/(/b/"]).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use tracing::{debug, trace, warn};
+
+use std::time::{Duration, Instant};
+
+use crate::command_line_args::FsyncPolicy;
+
+const PERIODIC_FSYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Decides when files written under the --fsync policy are synced.
+#[derive(Debug)]
+pub struct SyncSchedule {
+    policy: FsyncPolicy,
+    last_sync: Instant,
+}
+
+impl SyncSchedule {
+    pub fn new(policy: FsyncPolicy) -> Self {
+        Self {
+            policy,
+            last_sync: Instant::now(),
+        }
+    }
+
+    /// True if files should be synced after a job is written, which starts a new
+    /// period for --fsync periodic.
+    pub fn sync_due(&mut self) -> bool {
+        let sync_due = match self.policy {
+            FsyncPolicy::Never => false,
+            FsyncPolicy::PerJob => true,
+            FsyncPolicy::Periodic => self.last_sync.elapsed() >= PERIODIC_FSYNC_INTERVAL,
+        };
+        if sync_due {
+            self.last_sync = Instant::now();
+        }
+        sync_due
+    }
+
+    /// True if files are synced at all, and so when all commands complete.
+    pub fn enabled(&self) -> bool {
+        self.policy != FsyncPolicy::Never
+    }
+}
+
+/// Applies the --fsync policy to stdout.
+pub struct OutputSyncer {
+    schedule: SyncSchedule,
+}
+
+impl OutputSyncer {
+    pub fn new(policy: FsyncPolicy) -> Self {
+        Self {
+            schedule: SyncSchedule::new(policy),
+        }
+    }
+
+    /// True if output written so far should be synced after output of a command is written.
+    pub fn sync_due(&mut self) -> bool {
+        self.schedule.sync_due()
+    }
+
+    /// True if output should be synced when all commands complete.
+    pub fn enabled(&self) -> bool {
+        self.schedule.enabled()
+    }
+
+    pub async fn sync_stdout(&self) {
+        match tokio::task::spawn_blocking(sync_stdout).await {
+            Ok(Ok(())) => trace!("synced stdout"),
+            Ok(Err(e)) => warn!("fsync stdout error: {}", e),
+            Err(e) => debug!("spawn_blocking error: {}", e),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn sync_stdout() -> std::io::Result<()> {
+    use std::os::fd::AsFd;
+
+    let stdout = std::io::stdout();
+    match nix::unistd::fsync(stdout.as_fd()) {
+        // pipes and terminals do not support fsync
+        Ok(()) | Err(nix::errno::Errno::EINVAL) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_stdout() -> std::io::Result<()> {
+    Ok(())
+}
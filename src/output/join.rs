@@ -0,0 +1,110 @@
+use std::process::ExitStatus;
+
+use crate::command_line_args::CommandLineArgs;
+
+/// Formats input<TAB>exit_code<TAB>stdout records for --join-output.
+pub struct JoinOutput {
+    delimiter: String,
+}
+
+impl JoinOutput {
+    pub fn new(command_line_args: &CommandLineArgs) -> Option<Self> {
+        command_line_args.join_output.then(|| Self {
+            delimiter: command_line_args.join_delimiter.clone(),
+        })
+    }
+
+    /// Backslash escape so a record is one line and fields never contain the delimiter.
+    fn escape(&self, field: &str, record: &mut String) {
+        let mut rest = field;
+
+        while let Some(c) = rest.chars().next() {
+            let escaped = match c {
+                '\\' => "\\\\",
+                '\n' => "\\n",
+                '\r' => "\\r",
+                '\t' => "\\t",
+                _ => "",
+            };
+
+            if !escaped.is_empty() {
+                record.push_str(escaped);
+                rest = &rest[c.len_utf8()..];
+            } else if !self.delimiter.is_empty() && rest.starts_with(self.delimiter.as_str()) {
+                record.push('\\');
+                record.push_str(&self.delimiter);
+                rest = &rest[self.delimiter.len()..];
+            } else {
+                record.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    /// Exit code is empty if the command did not exit or was killed by a signal.
+    pub fn record(
+        &self,
+        input_value: &str,
+        exit_status: Option<ExitStatus>,
+        stdout: &[u8],
+    ) -> Vec<u8> {
+        let stdout = String::from_utf8_lossy(stdout);
+        let stdout = stdout.strip_suffix('\n').unwrap_or(&stdout);
+
+        let exit_code = exit_status
+            .and_then(|exit_status| exit_status.code())
+            .map(|code| code.to_string())
+            .unwrap_or_default();
+
+        let mut record = String::with_capacity(input_value.len() + stdout.len() + 8);
+        self.escape(input_value, &mut record);
+        record.push_str(&self.delimiter);
+        record.push_str(&exit_code);
+        record.push_str(&self.delimiter);
+        self.escape(stdout, &mut record);
+        record.push('\n');
+
+        record.into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(unix)]
+    fn exit_status(code: i32) -> ExitStatus {
+        use std::os::unix::process::ExitStatusExt;
+
+        ExitStatus::from_raw(code << 8)
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_record() {
+        let join_output = JoinOutput::new(&CommandLineArgs {
+            join_output: true,
+            join_delimiter: "\t".to_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            join_output.record("a.txt", Some(exit_status(0)), b"line 1\nline\t2\n"),
+            b"a.txt\t0\tline 1\\nline\\t2\n"
+        );
+        assert_eq!(join_output.record("c:\\b", None, b""), b"c:\\\\b\t\t\n");
+
+        let join_output = JoinOutput::new(&CommandLineArgs {
+            join_output: true,
+            join_delimiter: ",".to_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            join_output.record("a,b", Some(exit_status(2)), b"x\ty"),
+            b"a\\,b,2,x\\ty\n"
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use anyhow::Context;
+
+use regex::bytes::Regex;
+
+use crate::command_line_args::{ChildLogLevel, CommandLineArgs};
+
+/// Matches levels like "WARN", "[warning]", or "level=error", the first capture group is the level.
+const DEFAULT_LEVEL_REGEX: &str =
+    r"(?i)\b(trace|debug|info|warn|warning|error|err|fatal|critical|crit)\b";
+
+/// Drops command output lines with a log level below --child-log-level.
+#[derive(Debug)]
+pub struct ChildLogLevelFilter {
+    regex: Regex,
+    threshold: ChildLogLevel,
+}
+
+impl ChildLogLevelFilter {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        let Some(threshold) = command_line_args.child_log_level else {
+            return Ok(None);
+        };
+
+        let regex_string = command_line_args
+            .child_log_level_regex
+            .as_deref()
+            .unwrap_or(DEFAULT_LEVEL_REGEX);
+
+        let regex = Regex::new(regex_string)
+            .with_context(|| format!("invalid --child-log-level-regex '{}'", regex_string))?;
+
+        if regex.captures_len() < 2 {
+            anyhow::bail!(
+                "--child-log-level-regex '{}' has no capture group for the level",
+                regex_string
+            );
+        }
+
+        Ok(Some(Self { regex, threshold }))
+    }
+
+    /// Level of the first match in line, None if there is no match or the
+    /// captured text is not a known level.
+    fn line_level(&self, line: &[u8]) -> Option<ChildLogLevel> {
+        let level = self.regex.captures(line)?.get(1)?.as_bytes();
+
+        match level.to_ascii_lowercase().as_slice() {
+            b"trace" => Some(ChildLogLevel::Trace),
+            b"debug" => Some(ChildLogLevel::Debug),
+            b"info" => Some(ChildLogLevel::Info),
+            b"warn" | b"warning" => Some(ChildLogLevel::Warn),
+            b"error" | b"err" | b"fatal" | b"critical" | b"crit" => Some(ChildLogLevel::Error),
+            _ => None,
+        }
+    }
+
+    /// Remove lines below the threshold, lines without a recognized level are kept.
+    pub fn filter(&self, buffer: Vec<u8>) -> Vec<u8> {
+        if buffer.is_empty() {
+            return buffer;
+        }
+
+        let mut filtered = Vec::with_capacity(buffer.len());
+
+        for line in buffer.split_inclusive(|&b| b == b'\n') {
+            match self.line_level(line) {
+                Some(level) if level < self.threshold => {}
+                _ => filtered.extend_from_slice(line),
+            }
+        }
+
+        filtered
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        let command_line_args = CommandLineArgs {
+            child_log_level: Some(ChildLogLevel::Warn),
+            ..Default::default()
+        };
+
+        let filter = ChildLogLevelFilter::new(&command_line_args)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            filter.filter(
+                b"DEBUG connecting\n[info] connected\nWARNING: slow\nlevel=error msg=failed\nplain line\nINFO no newline"
+                    .to_vec()
+            ),
+            b"WARNING: slow\nlevel=error msg=failed\nplain line\n".to_vec()
+        );
+
+        let command_line_args = CommandLineArgs {
+            child_log_level: Some(ChildLogLevel::Info),
+            child_log_level_regex: Some(r"^\d+ ([A-Z])".to_owned()),
+            ..Default::default()
+        };
+
+        let filter = ChildLogLevelFilter::new(&command_line_args)
+            .unwrap()
+            .unwrap();
+
+        // Single letter levels are not known, so every line is kept.
+        assert_eq!(filter.filter(b"1 D x\n".to_vec()), b"1 D x\n".to_vec());
+
+        let command_line_args = CommandLineArgs {
+            child_log_level: Some(ChildLogLevel::Info),
+            child_log_level_regex: Some("debug".to_owned()),
+            ..Default::default()
+        };
+
+        assert!(ChildLogLevelFilter::new(&command_line_args).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+
+use crate::command_line_args::{CommandLineArgs, MapMode};
+
+/// Reorders one record per command into input order for --map.
+pub struct MapOutput {
+    mode: MapMode,
+    next_job_number: usize,
+    pending: BTreeMap<usize, Vec<u8>>,
+}
+
+impl MapOutput {
+    pub fn new(command_line_args: &CommandLineArgs) -> Option<Self> {
+        command_line_args.map.map(|mode| Self {
+            mode,
+            next_job_number: 1,
+            pending: BTreeMap::new(),
+        })
+    }
+
+    /// Record for a command's stdout without the line terminator, empty if the
+    /// command failed.
+    fn record(&self, success: bool, stdout: &[u8]) -> Vec<u8> {
+        if !success {
+            return vec![];
+        }
+
+        let stdout = stdout.strip_suffix(b"\n").unwrap_or(stdout);
+
+        let mut lines = stdout
+            .split(|&b| b == b'\n')
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line));
+
+        match self.mode {
+            MapMode::FirstLine => lines.next().unwrap_or_default().to_vec(),
+            MapMode::Joined => lines.collect::<Vec<_>>().join(&b' '),
+        }
+    }
+
+    /// Add the record of job_number, returns records now ready to print in input order.
+    pub fn add(&mut self, job_number: usize, success: bool, stdout: &[u8]) -> Vec<u8> {
+        let record = self.record(success, stdout);
+        self.pending.insert(job_number, record);
+
+        let mut ready = vec![];
+        while let Some(record) = self.pending.remove(&self.next_job_number) {
+            ready.extend_from_slice(&record);
+            ready.push(b'\n');
+            self.next_job_number += 1;
+        }
+        ready
+    }
+
+    /// Remaining records in input order, skipping jobs that never reported.
+    pub fn finish(&mut self) -> Vec<u8> {
+        let mut ready = vec![];
+        for (_, record) in std::mem::take(&mut self.pending) {
+            ready.extend_from_slice(&record);
+            ready.push(b'\n');
+        }
+        ready
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn map_output(mode: MapMode) -> MapOutput {
+        MapOutput::new(&CommandLineArgs {
+            map: Some(mode),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_first_line() {
+        let mut map_output = map_output(MapMode::FirstLine);
+
+        assert_eq!(map_output.add(2, true, b"b1\nb2\n"), b"");
+        assert_eq!(map_output.add(1, true, b"a1\r\n"), b"a1\nb1\n");
+        assert_eq!(map_output.add(3, false, b"c1\n"), b"\n");
+        assert_eq!(map_output.add(5, true, b""), b"");
+        assert_eq!(map_output.finish(), b"\n");
+    }
+
+    #[test]
+    fn test_joined() {
+        let mut map_output = map_output(MapMode::Joined);
+
+        assert_eq!(map_output.add(1, true, b"a1\na2\n"), b"a1 a2\n");
+        assert_eq!(map_output.add(2, true, b"b1"), b"b1\n");
+        assert_eq!(map_output.add(3, true, b""), b"\n");
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::Context;
+
+use tracing::warn;
+
+use std::{
+    path::{Path, PathBuf},
+    process::ExitStatus,
+};
+
+use crate::{
+    command::stdout_sha256,
+    command_line_args::{CommandLineArgs, FsyncPolicy},
+    common::OwnedCommandAndArgs,
+};
+
+/// Output of one job for --results, before --show-output and output filters apply.
+#[derive(Debug, Default)]
+pub struct ResultsOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// Job written to --results.
+pub struct JobResult<'a> {
+    pub job_number: usize,
+    pub command_and_args: &'a OwnedCommandAndArgs,
+    pub input_value: &'a str,
+    /// None if the command could not be spawned or did not exit.
+    pub exit_status: Option<ExitStatus>,
+    pub output: &'a ResultsOutput,
+}
+
+/// Writes each job's command, input, stdout, stderr, and exit code to
+/// files in a directory per job number under the --results directory.
+#[derive(Debug)]
+pub struct ResultsWriter {
+    dir: &'static Path,
+    hash_output: bool,
+    /// Job directories written since the last sync, tracked unless --fsync is never.
+    unsynced_job_dirs: Option<Vec<PathBuf>>,
+}
+
+impl ResultsWriter {
+    pub fn new(command_line_args: &'static CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        let Some(dir) = &command_line_args.results else {
+            return Ok(None);
+        };
+
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("error creating results directory '{}'", dir))?;
+
+        Ok(Some(Self {
+            dir: Path::new(dir),
+            hash_output: command_line_args.hash_output,
+            unsynced_job_dirs: (command_line_args.fsync != FsyncPolicy::Never).then(Vec::new),
+        }))
+    }
+
+    pub async fn write(&mut self, job_result: JobResult<'_>) {
+        let job_dir = self.dir.join(job_result.job_number.to_string());
+
+        if let Err(e) = self.write_job_dir(&job_dir, job_result).await {
+            warn!("error writing results {:?}: {:#}", job_dir, e);
+        }
+
+        if let Some(unsynced_job_dirs) = &mut self.unsynced_job_dirs {
+            unsynced_job_dirs.push(job_dir);
+        }
+    }
+
+    /// Fsync the files of job directories written since the last sync, for --fsync.
+    pub async fn sync(&mut self) {
+        let Some(unsynced_job_dirs) = &mut self.unsynced_job_dirs else {
+            return;
+        };
+
+        for job_dir in unsynced_job_dirs.drain(..) {
+            if let Err(e) = sync_job_dir(&job_dir).await {
+                warn!("error syncing results {:?}: {:#}", job_dir, e);
+            }
+        }
+    }
+
+    async fn write_job_dir(&self, job_dir: &Path, job_result: JobResult<'_>) -> anyhow::Result<()> {
+        tokio::fs::create_dir_all(job_dir).await?;
+
+        let command = job_result.command_and_args.command_line_lossy() + "\n";
+
+        let input = format!("{}\n", job_result.input_value);
+
+        let exit_code = job_result
+            .exit_status
+            .and_then(|exit_status| exit_status.code())
+            .map(|code| format!("{}\n", code))
+            .unwrap_or_default();
+
+        let files: [(&str, &[u8]); 5] = [
+            ("cmd", command.as_bytes()),
+            ("input", input.as_bytes()),
+            ("stdout", &job_result.output.stdout),
+            ("stderr", &job_result.output.stderr),
+            ("exitcode", exit_code.as_bytes()),
+        ];
+
+        for (file_name, contents) in files {
+            tokio::fs::write(job_dir.join(file_name), contents).await?;
+        }
+
+        if self.hash_output {
+            let stdout_hash = stdout_sha256(&job_result.output.stdout) + "\n";
+            tokio::fs::write(job_dir.join("stdout.sha256"), stdout_hash).await?;
+        }
+
+        Ok(())
+    }
+}
+
+async fn sync_job_dir(job_dir: &Path) -> anyhow::Result<()> {
+    let mut entries = tokio::fs::read_dir(job_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        tokio::fs::File::open(entry.path())
+            .await?
+            .sync_data()
+            .await?;
+    }
+
+    // sync the directory so its entries survive a crash
+    tokio::fs::File::open(job_dir).await?.sync_all().await?;
+
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use anyhow::Context;
+
+use tokio::{
+    fs::File,
+    io::{AsyncWriteExt, BufWriter},
+};
+
+use tracing::warn;
+
+use crate::command_line_args::CommandLineArgs;
+
+struct RouteFile {
+    path: &'static str,
+    writer: BufWriter<File>,
+}
+
+impl RouteFile {
+    fn create(path: &'static str) -> anyhow::Result<Self> {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("error creating output file '{}'", path))?;
+
+        Ok(Self {
+            path,
+            writer: BufWriter::new(File::from_std(file)),
+        })
+    }
+
+    async fn write_line(&mut self, line: &str) {
+        let mut buffer = Vec::with_capacity(line.len() + 1);
+        buffer.extend_from_slice(line.as_bytes());
+        buffer.push(b'\n');
+
+        if let Err(e) = self.writer.write_all(&buffer).await {
+            warn!("error writing '{}': {}", self.path, e);
+        }
+    }
+
+    async fn flush(&mut self) {
+        if let Err(e) = self.writer.flush().await {
+            warn!("error flushing '{}': {}", self.path, e);
+        }
+    }
+
+    async fn sync(&mut self) {
+        self.flush().await;
+        if let Err(e) = self.writer.get_ref().sync_data().await {
+            warn!("error syncing '{}': {}", self.path, e);
+        }
+    }
+}
+
+/// Writes the input value of each command to --success-out or --failure-out
+/// depending on its exit status.
+pub struct OutputRouter {
+    success: Option<RouteFile>,
+    failure: Option<RouteFile>,
+}
+
+impl OutputRouter {
+    pub fn new(command_line_args: &'static CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        let create = |path: &'static Option<String>| path.as_deref().map(RouteFile::create);
+
+        let success = create(&command_line_args.success_out).transpose()?;
+        let failure = create(&command_line_args.failure_out).transpose()?;
+
+        if success.is_none() && failure.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self { success, failure }))
+    }
+
+    pub fn routes_successes(&self) -> bool {
+        self.success.is_some()
+    }
+
+    pub fn routes_failures(&self) -> bool {
+        self.failure.is_some()
+    }
+
+    pub async fn route(&mut self, success: bool, input_value: &str) {
+        let route_file = if success {
+            &mut self.success
+        } else {
+            &mut self.failure
+        };
+
+        if let Some(route_file) = route_file {
+            route_file.write_line(input_value).await;
+        }
+    }
+
+    pub async fn flush(&mut self) {
+        for route_file in [&mut self.success, &mut self.failure].into_iter().flatten() {
+            route_file.flush().await;
+        }
+    }
+
+    /// Flush and fsync, for --fsync.
+    pub async fn sync(&mut self) {
+        for route_file in [&mut self.success, &mut self.failure].into_iter().flatten() {
+            route_file.sync().await;
+        }
+    }
+}
@@ -0,0 +1,32 @@
+/// Prefix each line of buffer with tag and a tab, for --tag.
+///
+/// A final line without a newline is prefixed too.
+pub fn tag_lines(buffer: Vec<u8>, tag: &str) -> Vec<u8> {
+    if buffer.is_empty() {
+        return buffer;
+    }
+
+    let line_count = buffer.split_inclusive(|&b| b == b'\n').count();
+
+    let mut tagged = Vec::with_capacity(buffer.len() + line_count * (tag.len() + 1));
+
+    for line in buffer.split_inclusive(|&b| b == b'\n') {
+        tagged.extend_from_slice(tag.as_bytes());
+        tagged.push(b'\t');
+        tagged.extend_from_slice(line);
+    }
+
+    tagged
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tag_lines() {
+        assert_eq!(tag_lines(vec![], "a"), b"");
+        assert_eq!(tag_lines(b"x\ny\n".to_vec(), "a b"), b"a b\tx\na b\ty\n");
+        assert_eq!(tag_lines(b"x\n\ny".to_vec(), "a"), b"a\tx\na\t\na\ty");
+    }
+}
@@ -1,49 +1,325 @@
-use tokio::{io::AsyncWrite, sync::mpsc::Receiver};
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt, Stdout},
+    sync::mpsc::Receiver,
+};
 
 use tracing::{debug, error, instrument, trace};
 
-use super::OutputMessage;
+use super::{
+    fsync::OutputSyncer,
+    join::JoinOutput,
+    map::MapOutput,
+    results::{JobResult, ResultsWriter},
+    route::OutputRouter,
+    OutputTaskMessage,
+};
+
+/// Largest write that is atomic on a pipe, PIPE_BUF is 4096 on Linux and 512 on macOS.
+///
+/// Larger writes may be interleaved with log messages, which are also written to stdout.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const ATOMIC_WRITE_SIZE: usize = nix::libc::PIPE_BUF;
+
+/// POSIX requires PIPE_BUF to be at least 512.
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+const ATOMIC_WRITE_SIZE: usize = 512;
+
+/// Split buffer into chunks that end on line boundaries and are no larger than
+/// max_chunk_size, except when a single line is longer than max_chunk_size.
+fn line_chunks(buffer: &[u8], max_chunk_size: usize) -> Vec<&[u8]> {
+    let mut chunks = vec![];
+    let mut chunk_start = 0;
+    let mut chunk_end = 0;
+
+    for line in buffer.split_inclusive(|&b| b == b'\n') {
+        let line_end = chunk_end + line.len();
+        if line_end - chunk_start > max_chunk_size && chunk_end > chunk_start {
+            chunks.push(&buffer[chunk_start..chunk_end]);
+            chunk_start = chunk_end;
+        }
+        chunk_end = line_end;
+    }
+
+    if chunk_end > chunk_start {
+        chunks.push(&buffer[chunk_start..chunk_end]);
+    }
+
+    chunks
+}
+
+// Write whole lines so command output is never spliced mid-line with
+// log messages or output of other commands.
+async fn copy(buffer: &[u8], max_write_size: usize, output_stream: &mut (impl AsyncWrite + Unpin)) {
+    for chunk in line_chunks(buffer, max_write_size) {
+        if let Err(e) = output_stream.write_all(chunk).await {
+            trace!("write_all error = {}", e);
+            return;
+        }
+    }
+}
+
+async fn flush(output_stream: &mut (impl AsyncWrite + Unpin)) {
+    let result = output_stream.flush().await;
+    trace!("flush result = {:?}", result);
+}
+
+/// Sync stdout, --results files, and --success-out and --failure-out files written so far.
+async fn sync(
+    output_syncer: &OutputSyncer,
+    results_writer: &mut Option<ResultsWriter>,
+    output_router: &mut Option<OutputRouter>,
+) {
+    output_syncer.sync_stdout().await;
+    if let Some(results_writer) = results_writer {
+        results_writer.sync().await;
+    }
+    if let Some(output_router) = output_router {
+        output_router.sync().await;
+    }
+}
+
+/// True if stdout is a pipe, where writes larger than ATOMIC_WRITE_SIZE may be split.
+#[cfg(unix)]
+fn stdout_is_pipe() -> bool {
+    use std::os::{fd::AsFd, unix::fs::FileTypeExt};
+
+    std::io::stdout()
+        .as_fd()
+        .try_clone_to_owned()
+        .and_then(|fd| std::fs::File::from(fd).metadata())
+        .is_ok_and(|metadata| metadata.file_type().is_fifo())
+}
+
+#[cfg(not(unix))]
+fn stdout_is_pipe() -> bool {
+    true
+}
+
+/// Stdout buffered up to --output-buffer-size.
+///
+/// Unlike a `BufWriter`, when stdout is a pipe buffered output is written in whole-line
+/// chunks no larger than ATOMIC_WRITE_SIZE, so log messages written to stdout meanwhile
+/// never split its lines.  Otherwise it is written with a single write.
+struct BufferedStdout {
+    buffer: Vec<u8>,
+    capacity: usize,
+    max_write_size: usize,
+    stdout: Stdout,
+}
+
+impl BufferedStdout {
+    fn new(capacity: usize) -> Self {
+        let max_write_size = if stdout_is_pipe() {
+            ATOMIC_WRITE_SIZE
+        } else {
+            usize::MAX
+        };
+        debug!("stdout max_write_size = {}", max_write_size);
+
+        Self {
+            buffer: Vec::with_capacity(capacity),
+            capacity,
+            max_write_size,
+            stdout: tokio::io::stdout(),
+        }
+    }
+
+    async fn write(&mut self, output: &[u8]) {
+        self.buffer.extend_from_slice(output);
+        if self.buffer.len() >= self.capacity {
+            self.flush().await;
+        }
+    }
+
+    async fn flush(&mut self) {
+        copy(&self.buffer, self.max_write_size, &mut self.stdout).await;
+        self.buffer.clear();
+        flush(&mut self.stdout).await;
+    }
+}
 
 pub struct OutputTask {
-    receiver: Receiver<OutputMessage>,
+    receiver: Receiver<OutputTaskMessage>,
+    output_buffer_size: usize,
+    output_syncer: OutputSyncer,
+    output_router: Option<OutputRouter>,
+    map_output: Option<MapOutput>,
+    join_output: Option<JoinOutput>,
+    results_writer: Option<ResultsWriter>,
 }
 
 impl OutputTask {
-    pub fn new(receiver: Receiver<OutputMessage>) -> Self {
-        Self { receiver }
+    pub fn new(
+        receiver: Receiver<OutputTaskMessage>,
+        output_buffer_size: usize,
+        output_syncer: OutputSyncer,
+        output_router: Option<OutputRouter>,
+        map_output: Option<MapOutput>,
+        join_output: Option<JoinOutput>,
+        results_writer: Option<ResultsWriter>,
+    ) -> Self {
+        Self {
+            receiver,
+            output_buffer_size,
+            output_syncer,
+            output_router,
+            map_output,
+            join_output,
+            results_writer,
+        }
     }
 
     #[instrument(skip_all, name = "OutputTask::run", level = "debug")]
     pub async fn run(self) {
         debug!("begin run");
 
-        async fn copy(mut buffer: &[u8], output_stream: &mut (impl AsyncWrite + Unpin)) {
-            let result = tokio::io::copy(&mut buffer, &mut *output_stream).await;
-            trace!("copy result = {:?}", result);
-        }
-
-        let mut stdout = tokio::io::stdout();
+        let mut stdout = BufferedStdout::new(self.output_buffer_size);
         let mut stderr = tokio::io::stderr();
 
         let mut receiver = self.receiver;
+        let mut output_syncer = self.output_syncer;
+        let mut output_router = self.output_router;
+        let mut map_output = self.map_output;
+        let join_output = self.join_output;
+        let mut results_writer = self.results_writer;
 
-        while let Some(output_message) = receiver.recv().await {
-            if !output_message.stdout.is_empty() {
-                copy(&output_message.stdout, &mut stdout).await;
+        while let Some(output_task_message) = receiver.recv().await {
+            let output_message = match output_task_message {
+                OutputTaskMessage::Output(output_message) => output_message,
+                OutputTaskMessage::Flush(flushed_sender) => {
+                    stdout.flush().await;
+                    let _ = flushed_sender.send(());
+                    continue;
+                }
+            };
+
+            if let (Some(results_writer), Some(results_output)) =
+                (&mut results_writer, &output_message.results_output)
+            {
+                results_writer
+                    .write(JobResult {
+                        job_number: output_message.job_number,
+                        command_and_args: &output_message.command_and_args,
+                        input_value: &output_message.input_value,
+                        exit_status: output_message.exit_status,
+                        output: results_output,
+                    })
+                    .await;
+            }
+
+            let success = output_message.succeeded;
+
+            match (&mut map_output, &join_output) {
+                (Some(map_output), _) => {
+                    let records =
+                        map_output.add(output_message.job_number, success, &output_message.stdout);
+                    stdout.write(&records).await;
+                }
+                (None, Some(join_output)) => {
+                    let record = join_output.record(
+                        &output_message.input_value,
+                        output_message.exit_status,
+                        &output_message.stdout,
+                    );
+                    stdout.write(&record).await;
+                }
+                (None, None) if !output_message.stdout.is_empty() => {
+                    stdout.write(&output_message.stdout).await;
+                }
+                (None, None) => {}
             }
             if !output_message.stderr.is_empty() {
-                copy(&output_message.stderr, &mut stderr).await;
+                stdout.flush().await;
+                copy(&output_message.stderr, ATOMIC_WRITE_SIZE, &mut stderr).await;
+                flush(&mut stderr).await;
             }
-            if !output_message.exit_status.success() {
+            if let (Some(exit_status), false) = (output_message.exit_status, success) {
+                stdout.flush().await;
                 error!(
                     "command failed: {},line={} exit_status={}",
                     output_message.command_and_args,
                     output_message.input_line_number,
-                    output_message.exit_status.code().unwrap_or_default(),
+                    exit_status.code().unwrap_or_default(),
                 );
             }
+
+            if let Some(output_router) = &mut output_router {
+                output_router
+                    .route(success, &output_message.input_value)
+                    .await;
+            }
+
+            let sync_due = output_syncer.sync_due();
+            if sync_due || receiver.is_empty() {
+                stdout.flush().await;
+            }
+            if sync_due {
+                sync(&output_syncer, &mut results_writer, &mut output_router).await;
+            }
+        }
+
+        if let Some(map_output) = &mut map_output {
+            stdout.write(&map_output.finish()).await;
+        }
+        stdout.flush().await;
+        if let Some(output_router) = &mut output_router {
+            output_router.flush().await;
+        }
+        if output_syncer.enabled() {
+            sync(&output_syncer, &mut results_writer, &mut output_router).await;
         }
 
         debug!("end run");
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_line_chunks() {
+        assert_eq!(line_chunks(b"", 8), Vec::<&[u8]>::new());
+        assert_eq!(line_chunks(b"a\nb\nc\n", 8), vec![&b"a\nb\nc\n"[..]]);
+        assert_eq!(
+            line_chunks(b"aaa\nbbb\nccc\n", 8),
+            vec![&b"aaa\nbbb\n"[..], &b"ccc\n"[..]]
+        );
+        assert_eq!(
+            line_chunks(b"a\nbbbbbbbbbbbb\nc", 8),
+            vec![&b"a\n"[..], &b"bbbbbbbbbbbb\n"[..], &b"c"[..]]
+        );
+    }
+
+    #[test]
+    fn test_line_chunks_atomic_write_size() {
+        let line = |len: usize| {
+            let mut line = vec![b'x'; len - 1];
+            line.push(b'\n');
+            line
+        };
+
+        let below = line(ATOMIC_WRITE_SIZE - 1);
+        assert_eq!(line_chunks(&below, ATOMIC_WRITE_SIZE), vec![&below[..]]);
+
+        let at = [line(ATOMIC_WRITE_SIZE / 2), line(ATOMIC_WRITE_SIZE / 2)].concat();
+        assert_eq!(line_chunks(&at, ATOMIC_WRITE_SIZE), vec![&at[..]]);
+
+        let above = [at.clone(), line(1)].concat();
+        assert_eq!(
+            line_chunks(&above, ATOMIC_WRITE_SIZE),
+            vec![&above[..ATOMIC_WRITE_SIZE], &above[ATOMIC_WRITE_SIZE..]]
+        );
+
+        let long_line = line(ATOMIC_WRITE_SIZE + 1);
+        let around_long_line = [line(2), long_line.clone(), line(2)].concat();
+        assert_eq!(
+            line_chunks(&around_long_line, ATOMIC_WRITE_SIZE),
+            vec![
+                &around_long_line[..2],
+                &long_line[..],
+                &around_long_line[2 + long_line.len()..]
+            ]
+        );
+    }
+}
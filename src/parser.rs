@@ -1,43 +1,154 @@
 pub mod buffered;
 pub mod command_line;
+pub mod manifest;
+mod plus;
+pub mod product_filter;
 mod regex;
+pub mod row;
+
+use itertools::Itertools;
 
 use tokio::sync::OnceCell;
 
-use std::sync::Arc;
+use std::{ffi::OsString, path::Path, sync::Arc};
 
-use crate::{command_line_args::CommandLineArgs, common::OwnedCommandAndArgs};
+use crate::{
+    command_line_args::{
+        Combinations, CommandLineArgs, COMMANDS_FROM_ARGS_SEPARATOR, INLINE_COMMANDS_SEPARATOR,
+    },
+    common::OwnedCommandAndArgs,
+    recorded_env::EVAL_RECORDED_ENV,
+};
 
 use self::{
-    buffered::BufferedInputLineParser, command_line::CommandLineArgsParser, regex::RegexProcessor,
+    buffered::BufferedInputLineParser, command_line::CommandLineArgsParser,
+    manifest::ManifestCommandParser, regex::RegexProcessor, row::RowParser,
 };
 
-struct ShellCommandAndArgs(Option<Vec<String>>);
+struct ShellCommandAndArgs {
+    shell_command_and_args: Option<Vec<String>>,
+    /// Prefixed to each shell command, e.g. to evaluate --use-recorded-env definitions.
+    command_prefix: Option<&'static str>,
+    /// For --shell-positional-args and --args-to-shell, the $0 argument if the shell takes one.
+    positional_args: Option<Option<&'static str>>,
+}
 
 impl ShellCommandAndArgs {
     fn new(command_line_args: &CommandLineArgs) -> Self {
-        Self(if command_line_args.shell {
-            Some(vec![
-                command_line_args.shell_path.clone(),
-                command_line_args.shell_argument.clone(),
-            ])
+        if command_line_args.shell {
+            Self {
+                shell_command_and_args: Some(
+                    std::iter::once(command_line_args.shell_path.clone())
+                        .chain(
+                            command_line_args
+                                .shell_argument
+                                .split_whitespace()
+                                .map_into(),
+                        )
+                        .collect(),
+                ),
+                command_prefix: command_line_args
+                    .use_recorded_env
+                    .is_some()
+                    .then_some(EVAL_RECORDED_ENV),
+                positional_args: (command_line_args.shell_positional_args
+                    || command_line_args.args_to_shell)
+                    .then(|| Self::dollar_zero(&command_line_args.shell_path)),
+            }
         } else {
-            None
-        })
+            Self {
+                shell_command_and_args: None,
+                command_prefix: None,
+                positional_args: None,
+            }
+        }
+    }
+
+    fn is_shell(&self) -> bool {
+        self.shell_command_and_args.is_some()
+    }
+
+    fn positional_args(&self) -> bool {
+        self.positional_args.is_some()
+    }
+
+    /// fish puts all positional arguments in $argv, other shells take $0 first.
+    fn dollar_zero(shell_path: &str) -> Option<&'static str> {
+        match Path::new(shell_path).file_stem() {
+            Some(stem) if stem == "fish" => None,
+            _ => Some("rust-parallel"),
+        }
     }
 }
 
 fn build_owned_command_and_args(
     shell_command_and_args: &ShellCommandAndArgs,
-    command_and_args: Vec<String>,
+    command_and_args: Vec<impl Into<OsString>>,
 ) -> Option<OwnedCommandAndArgs> {
-    match &shell_command_and_args.0 {
+    build_owned_command_and_input_args(
+        shell_command_and_args,
+        command_and_args,
+        Vec::<OsString>::new(),
+    )
+}
+
+/// Build a command from a template and the arguments from an input.
+///
+/// With --shell-positional-args or --args-to-shell the template is the shell script and the
+/// input arguments are passed to it as positional parameters, otherwise they are appended to
+/// the template.
+fn build_owned_command_and_input_args(
+    shell_command_and_args: &ShellCommandAndArgs,
+    command_and_args: Vec<impl Into<OsString>>,
+    input_args: Vec<impl Into<OsString>>,
+) -> Option<OwnedCommandAndArgs> {
+    let mut command_and_args: Vec<OsString> = command_and_args.into_iter().map_into().collect();
+    let mut input_args: Vec<OsString> = input_args.into_iter().map_into().collect();
+
+    let ShellCommandAndArgs {
+        shell_command_and_args,
+        command_prefix,
+        positional_args,
+    } = shell_command_and_args;
+
+    let positional_args = match positional_args {
+        Some(_) if command_and_args.is_empty() => None,
+        positional_args => *positional_args,
+    };
+
+    if positional_args.is_none() {
+        command_and_args.append(&mut input_args);
+    }
+
+    match shell_command_and_args {
         None => OwnedCommandAndArgs::try_from(command_and_args).ok(),
         Some(shell_command_and_args) => {
-            let mut result = Vec::with_capacity(shell_command_and_args.len() + 1);
+            let mut result: Vec<OsString> =
+                Vec::with_capacity(shell_command_and_args.len() + 2 + input_args.len());
+
+            let command = command_and_args
+                .into_iter()
+                .reduce(|mut command, arg| {
+                    command.push(" ");
+                    command.push(arg);
+                    command
+                })
+                .unwrap_or_default();
+
+            result.extend(shell_command_and_args.iter().map_into());
+            result.push(match command_prefix {
+                None => command,
+                Some(command_prefix) => {
+                    let mut prefixed_command = OsString::from(command_prefix);
+                    prefixed_command.push(command);
+                    prefixed_command
+                }
+            });
 
-            result.extend(shell_command_and_args.iter().cloned());
-            result.push(command_and_args.join(" "));
+            if let Some(dollar_zero) = positional_args {
+                result.extend(dollar_zero.map(OsString::from));
+                result.append(&mut input_args);
+            }
 
             OwnedCommandAndArgs::try_from(result).ok()
         }
@@ -52,8 +163,61 @@ pub struct Parsers {
 
 impl Parsers {
     pub fn new(command_line_args: &'static CommandLineArgs) -> anyhow::Result<Self> {
+        if command_line_args.commands_from_args_mode() && command_line_args.inline_commands_mode() {
+            anyhow::bail!(
+                "{} and {} separators cannot be combined",
+                COMMANDS_FROM_ARGS_SEPARATOR,
+                INLINE_COMMANDS_SEPARATOR
+            );
+        }
+
+        if (command_line_args.shell_positional_args || command_line_args.args_to_shell)
+            && command_line_args
+                .command_and_initial_arguments
+                .first()
+                .is_none_or(|arg| arg == COMMANDS_FROM_ARGS_SEPARATOR)
+        {
+            anyhow::bail!("--shell-positional-args and --args-to-shell require a command");
+        }
+
+        if command_line_args.combinations != Combinations::Product {
+            let group_count = command_line_args
+                .command_and_initial_arguments
+                .iter()
+                .filter(|arg| *arg == COMMANDS_FROM_ARGS_SEPARATOR)
+                .count();
+
+            if !(1..=2).contains(&group_count) {
+                anyhow::bail!(
+                    "--combinations pairs and unique-pairs require 1 or 2 {} groups",
+                    COMMANDS_FROM_ARGS_SEPARATOR
+                );
+            }
+        }
+
+        if let Some(product_filter) = &command_line_args.product_filter {
+            if !command_line_args.commands_from_args_mode() {
+                anyhow::bail!(
+                    "--product-filter requires {} argument groups",
+                    COMMANDS_FROM_ARGS_SEPARATOR
+                );
+            }
+
+            product_filter
+                .validate_group_count(command_line_args.commands_from_args_combination_size())?;
+        }
+
         let regex_processor = RegexProcessor::new(command_line_args)?;
 
+        if command_line_args.shell_positional_args
+            && (regex_processor.regex_mode() || command_line_args.inline_commands_mode())
+        {
+            anyhow::bail!(
+                "--shell-positional-args can not be combined with --regex or {} inline commands, use --args-to-shell for --regex",
+                INLINE_COMMANDS_SEPARATOR
+            );
+        }
+
         Ok(Self {
             buffered_input_line_parser: OnceCell::new(),
             regex_processor,
@@ -69,6 +233,38 @@ impl Parsers {
             .await
     }
 
+    pub fn regex_mode(&self) -> bool {
+        self.regex_processor.regex_mode()
+    }
+
+    /// Expand placeholders of a per job template in a single pass, see
+    /// `RegexProcessor::expand_job_template`.
+    pub fn expand_job_template(
+        &self,
+        template: &str,
+        input_line: &str,
+        job_number: usize,
+    ) -> String {
+        self.regex_processor
+            .expand_job_template(template, input_line, job_number)
+    }
+
+    /// Expand regex capture group placeholders such as {1} in template using input_line.
+    pub fn expand_template(&self, template: &str, input_line: &str) -> Option<String> {
+        self.regex_processor
+            .apply_regex_to_arguments(&vec![template.to_owned()], input_line)?
+            .arguments
+            .pop()
+    }
+
+    pub fn manifest_command_parser(&self) -> ManifestCommandParser {
+        ManifestCommandParser::new(self.command_line_args)
+    }
+
+    pub fn row_parser(&self) -> RowParser {
+        RowParser::new(self.command_line_args)
+    }
+
     pub fn command_line_args_parser(&self) -> CommandLineArgsParser {
         CommandLineArgsParser::new(self.command_line_args, &self.regex_processor)
     }
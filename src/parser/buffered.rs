@@ -1,6 +1,9 @@
 use itertools::Itertools;
 
-use std::sync::Arc;
+use std::{
+    ffi::{OsStr, OsString},
+    sync::Arc,
+};
 
 use crate::{
     command_line_args::CommandLineArgs,
@@ -33,12 +36,30 @@ impl BufferedInputLineParser {
         }
     }
 
-    pub fn parse_segment(&self, segment: Vec<u8>) -> Option<OwnedCommandAndArgs> {
-        if let Ok(input_line) = std::str::from_utf8(&segment) {
-            self.parse_line(input_line)
+    /// Parser for --then second stage commands.  Input lines are first stage stdout lines.
+    pub fn new_then_stage(
+        command_line_args: &CommandLineArgs,
+        then_command: &str,
+    ) -> anyhow::Result<Self> {
+        let command_and_initial_arguments: Vec<String> =
+            then_command.split_whitespace().map_into().collect();
+
+        let regex = if command_and_initial_arguments
+            .iter()
+            .any(|arg| arg.contains("{}"))
+        {
+            Some(".*")
         } else {
             None
-        }
+        };
+
+        Ok(Self {
+            no_run_if_empty: true,
+            split_whitespace: true,
+            shell_command_and_args: ShellCommandAndArgs::new(command_line_args),
+            command_and_initial_arguments,
+            regex_processor: RegexProcessor::new_with_regex(regex)?,
+        })
     }
 
     pub fn parse_line(&self, input_line: &str) -> Option<OwnedCommandAndArgs> {
@@ -46,26 +67,73 @@ impl BufferedInputLineParser {
             return None;
         }
 
-        let cmd_and_args = if !self.regex_processor.regex_mode() {
-            let mut cmd_and_args = if self.split_whitespace {
+        if !self.regex_processor.regex_mode() {
+            let input_args: Vec<String> = if self.split_whitespace {
                 input_line.split_whitespace().map_into().collect()
             } else {
                 vec![input_line.into()]
             };
 
-            if !self.command_and_initial_arguments.is_empty() {
-                cmd_and_args = [self.command_and_initial_arguments.clone(), cmd_and_args].concat();
-            }
-
-            cmd_and_args
+            super::build_owned_command_and_input_args(
+                &self.shell_command_and_args,
+                self.command_and_initial_arguments.clone(),
+                input_args,
+            )
+        } else if self.shell_command_and_args.positional_args() {
+            super::build_owned_command_and_input_args(
+                &self.shell_command_and_args,
+                self.command_and_initial_arguments.clone(),
+                self.regex_processor.captures(input_line)?,
+            )
         } else {
             let apply_regex_result = self
                 .regex_processor
                 .apply_regex_to_arguments(&self.command_and_initial_arguments, input_line)?;
-            apply_regex_result.arguments
+
+            super::build_owned_command_and_args(
+                &self.shell_command_and_args,
+                apply_regex_result.arguments,
+            )
+        }
+    }
+
+    /// Parse an input line that is not valid UTF-8, such as a file name from `find -print0`.
+    ///
+    /// The bytes are passed through unchanged, so this is only possible without -r.
+    #[cfg(unix)]
+    pub fn parse_non_utf8_line(&self, input_line: &[u8]) -> Option<OwnedCommandAndArgs> {
+        use std::os::unix::ffi::OsStrExt;
+
+        if self.regex_processor.regex_mode()
+            || (self.no_run_if_empty && input_line.iter().all(u8::is_ascii_whitespace))
+        {
+            return None;
+        }
+
+        let words: Vec<&[u8]> = if self.split_whitespace {
+            input_line
+                .split(u8::is_ascii_whitespace)
+                .filter(|word| !word.is_empty())
+                .collect()
+        } else {
+            vec![input_line]
         };
 
-        super::build_owned_command_and_args(&self.shell_command_and_args, cmd_and_args)
+        let input_args: Vec<OsString> = words
+            .into_iter()
+            .map(|word| OsStr::from_bytes(word).to_owned())
+            .collect();
+
+        super::build_owned_command_and_input_args(
+            &self.shell_command_and_args,
+            self.command_and_initial_arguments.clone(),
+            input_args,
+        )
+    }
+
+    #[cfg(not(unix))]
+    pub fn parse_non_utf8_line(&self, _input_line: &[u8]) -> Option<OwnedCommandAndArgs> {
+        None
     }
 }
 
@@ -124,6 +192,40 @@ mod test {
         assert_eq!(result, None);
     }
 
+    #[test]
+    fn test_then_stage() {
+        let command_line_args = CommandLineArgs {
+            shell: false,
+            ..Default::default()
+        };
+
+        let parser =
+            BufferedInputLineParser::new_then_stage(&command_line_args, "gzip -k").unwrap();
+
+        assert_eq!(
+            parser.parse_line("file1 file2"),
+            Some(OwnedCommandAndArgs {
+                command_path: PathBuf::from("gzip"),
+                args: vec!["-k", "file1", "file2"]
+                    .into_iter()
+                    .map_into()
+                    .collect(),
+            })
+        );
+        assert_eq!(parser.parse_line("  "), None);
+
+        let parser =
+            BufferedInputLineParser::new_then_stage(&command_line_args, "cp {} {}.bak").unwrap();
+
+        assert_eq!(
+            parser.parse_line("file1"),
+            Some(OwnedCommandAndArgs {
+                command_path: PathBuf::from("cp"),
+                args: vec!["file1", "file1.bak"].into_iter().map_into().collect(),
+            })
+        );
+    }
+
     #[test]
     fn test_null_separator() {
         let command_line_args = CommandLineArgs {
@@ -209,6 +311,92 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_shell_positional_args() {
+        let command_line_args = CommandLineArgs {
+            shell: true,
+            shell_positional_args: true,
+            command_and_initial_arguments: vec![r#"echo "$1""#.to_owned()],
+            shell_path: "/bin/bash".to_owned(),
+            shell_argument: "-l -c".to_owned(),
+            ..Default::default()
+        };
+
+        let parser = BufferedInputLineParser::new(
+            &command_line_args,
+            &RegexProcessor::new(&command_line_args).unwrap(),
+        );
+
+        assert_eq!(
+            parser.parse_line("a;b c"),
+            Some(OwnedCommandAndArgs {
+                command_path: PathBuf::from("/bin/bash"),
+                args: vec!["-l", "-c", r#"echo "$1""#, "rust-parallel", "a;b", "c"]
+                    .into_iter()
+                    .map_into()
+                    .collect(),
+            })
+        );
+
+        let command_line_args = CommandLineArgs {
+            shell_path: "/usr/bin/fish".to_owned(),
+            shell_argument: "-c".to_owned(),
+            ..command_line_args
+        };
+
+        let parser = BufferedInputLineParser::new(
+            &command_line_args,
+            &RegexProcessor::new(&command_line_args).unwrap(),
+        );
+
+        assert_eq!(
+            parser.parse_line("a;b c"),
+            Some(OwnedCommandAndArgs {
+                command_path: PathBuf::from("/usr/bin/fish"),
+                args: vec!["-c", r#"echo "$1""#, "a;b", "c"]
+                    .into_iter()
+                    .map_into()
+                    .collect(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_args_to_shell() {
+        let command_line_args = CommandLineArgs {
+            shell: true,
+            args_to_shell: true,
+            regex: vec!["(?P<first>.*),(.*),?(x)?".to_owned()],
+            command_and_initial_arguments: vec![r#"echo "$2" "$1" {1}"#.to_owned()],
+            shell_path: "/bin/bash".to_owned(),
+            shell_argument: "-c".to_owned(),
+            ..Default::default()
+        };
+
+        let parser = BufferedInputLineParser::new(
+            &command_line_args,
+            &RegexProcessor::new(&command_line_args).unwrap(),
+        );
+
+        assert_eq!(
+            parser.parse_line("a;b,c d"),
+            Some(OwnedCommandAndArgs {
+                command_path: PathBuf::from("/bin/bash"),
+                args: vec![
+                    "-c",
+                    r#"echo "$2" "$1" {1}"#,
+                    "rust-parallel",
+                    "a;b",
+                    "c d",
+                    ""
+                ]
+                .into_iter()
+                .map_into()
+                .collect(),
+            })
+        );
+    }
+
     #[test]
     fn test_no_run_if_empty() {
         let command_line_args = CommandLineArgs {
@@ -278,7 +466,7 @@ mod test {
                 "echo".to_owned(),
                 "got arg1={arg1} arg2={arg2}".to_owned(),
             ],
-            regex: Some("(?P<arg1>.*),(?P<arg2>.*)".to_owned()),
+            regex: vec!["(?P<arg1>.*),(?P<arg2>.*)".to_owned()],
             ..Default::default()
         };
 
@@ -308,7 +496,7 @@ mod test {
                 "echo".to_owned(),
                 "got arg1={2} arg2={1} arg3={0}".to_owned(),
             ],
-            regex: Some("(.*),(.*)".to_owned()),
+            regex: vec!["(.*),(.*)".to_owned()],
             ..Default::default()
         };
 
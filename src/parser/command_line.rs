@@ -3,7 +3,9 @@ use itertools::Itertools;
 use std::{collections::VecDeque, sync::Arc};
 
 use crate::{
-    command_line_args::{CommandLineArgs, COMMANDS_FROM_ARGS_SEPARATOR},
+    command_line_args::{
+        Combinations, CommandLineArgs, COMMANDS_FROM_ARGS_SEPARATOR, INLINE_COMMANDS_SEPARATOR,
+    },
     common::OwnedCommandAndArgs,
     parser::{regex::RegexProcessor, ShellCommandAndArgs},
 };
@@ -12,6 +14,7 @@ use crate::{
 struct ArgumentGroups {
     first_command_and_args: Vec<String>,
     all_argument_groups: VecDeque<Vec<String>>,
+    inline_commands: bool,
 }
 
 pub struct CommandLineArgsParser {
@@ -34,6 +37,14 @@ impl CommandLineArgsParser {
     }
 
     fn build_argument_groups(command_line_args: &CommandLineArgs) -> ArgumentGroups {
+        let inline_commands = command_line_args.inline_commands_mode();
+
+        let separator_arg = if inline_commands {
+            INLINE_COMMANDS_SEPARATOR
+        } else {
+            COMMANDS_FROM_ARGS_SEPARATOR
+        };
+
         let command_and_initial_arguments = &command_line_args.command_and_initial_arguments;
 
         let mut remaining_argument_groups = Vec::with_capacity(command_and_initial_arguments.len());
@@ -44,7 +55,7 @@ impl CommandLineArgsParser {
 
         for (separator, group) in &command_and_initial_arguments
             .iter()
-            .group_by(|arg| *arg == COMMANDS_FROM_ARGS_SEPARATOR)
+            .group_by(|arg| *arg == separator_arg)
         {
             let group_vec = group.cloned().collect();
 
@@ -58,34 +69,95 @@ impl CommandLineArgsParser {
             }
         }
 
-        let all_argument_groups = remaining_argument_groups
-            .into_iter()
-            .multi_cartesian_product()
-            .collect();
+        let all_argument_groups = if inline_commands {
+            remaining_argument_groups.into()
+        } else {
+            let product_filter = command_line_args.product_filter.as_ref();
+
+            let combinations: Box<dyn Iterator<Item = Vec<&String>>> =
+                match command_line_args.combinations {
+                    Combinations::Product => Box::new(
+                        remaining_argument_groups
+                            .iter()
+                            .map(|group| group.iter())
+                            .multi_cartesian_product(),
+                    ),
+                    Combinations::Pairs => {
+                        Box::new(Self::upper_triangle_pairs(&remaining_argument_groups, 0))
+                    }
+                    Combinations::UniquePairs => {
+                        Box::new(Self::upper_triangle_pairs(&remaining_argument_groups, 1))
+                    }
+                };
+
+            combinations
+                .filter(|combination| {
+                    product_filter.is_none_or(|product_filter| product_filter.matches(combination))
+                })
+                .map(|combination| combination.into_iter().cloned().collect())
+                .collect()
+        };
 
         ArgumentGroups {
             first_command_and_args,
             all_argument_groups,
+            inline_commands,
         }
     }
 
+    /// Pairs (first[i], second[j]) with j >= i + offset, where second is first with a single group.
+    fn upper_triangle_pairs(
+        argument_groups: &[Vec<String>],
+        offset: usize,
+    ) -> impl Iterator<Item = Vec<&String>> {
+        let (first, second) = match argument_groups {
+            // ::: without values
+            [] => (&[][..], &[][..]),
+            [group] => (group.as_slice(), group.as_slice()),
+            [first, second] => (first.as_slice(), second.as_slice()),
+            _ => unreachable!(
+                "{} ::: groups for --combinations pairs, Parsers::new allows at most 2",
+                argument_groups.len()
+            ),
+        };
+
+        first.iter().enumerate().flat_map(move |(i, first_value)| {
+            second
+                .iter()
+                .skip(i + offset)
+                .map(move |second_value| vec![first_value, second_value])
+        })
+    }
+
     fn parse_argument_group(&self, argument_group: Vec<String>) -> Option<OwnedCommandAndArgs> {
         let first_command_and_args = &self.argument_groups.first_command_and_args;
 
-        let cmd_and_args = if !self.regex_processor.regex_mode() {
-            [first_command_and_args.clone(), argument_group].concat()
-        } else {
-            let input_line = argument_group.join(" ");
+        if self.argument_groups.inline_commands || !self.regex_processor.regex_mode() {
+            return super::build_owned_command_and_input_args(
+                &self.shell_command_and_args,
+                first_command_and_args.clone(),
+                argument_group,
+            );
+        }
 
-            let apply_regex_result = self
-                .regex_processor
-                .apply_regex_to_arguments(first_command_and_args, &input_line)?;
+        let input_line = argument_group.join(" ");
 
-            if apply_regex_result.modified_arguments {
-                apply_regex_result.arguments
-            } else {
-                [first_command_and_args.clone(), argument_group].concat()
-            }
+        if self.shell_command_and_args.positional_args() {
+            return super::build_owned_command_and_input_args(
+                &self.shell_command_and_args,
+                first_command_and_args.clone(),
+                self.regex_processor.captures(&input_line)?,
+            );
+        }
+
+        let apply_regex_result = self
+            .regex_processor
+            .apply_regex_to_arguments(first_command_and_args, &input_line)?;
+
+        let cmd_and_args = if apply_regex_result.modified_arguments {
+            apply_regex_result.arguments
+        } else {
+            [first_command_and_args.clone(), argument_group].concat()
         };
 
         super::build_owned_command_and_args(&self.shell_command_and_args, cmd_and_args)
@@ -95,9 +167,11 @@ impl CommandLineArgsParser {
         !self.argument_groups.all_argument_groups.is_empty()
     }
 
-    pub fn parse_next_argument_group(&mut self) -> Option<OwnedCommandAndArgs> {
+    /// Next command and its argument group joined with spaces.
+    pub fn parse_next_argument_group(&mut self) -> Option<(OwnedCommandAndArgs, String)> {
         let argument_group = self.argument_groups.all_argument_groups.pop_front()?;
-        self.parse_argument_group(argument_group)
+        let input_value = argument_group.join(" ");
+        Some((self.parse_argument_group(argument_group)?, input_value))
     }
 }
 
@@ -111,7 +185,7 @@ mod test {
         let mut result = vec![];
 
         while parser.has_remaining_argument_groups() {
-            let Some(cmd_and_args) = parser.parse_next_argument_group() else {
+            let Some((cmd_and_args, _)) = parser.parse_next_argument_group() else {
                 continue;
             };
 
@@ -259,6 +333,45 @@ mod test {
         assert_eq!(result, vec![]);
     }
 
+    #[test]
+    fn test_parse_inline_commands() {
+        let command_line_args = CommandLineArgs {
+            shell: false,
+            command_and_initial_arguments: vec![
+                "nice", ":::::", "echo", "A", ":::::", "sleep", "1", ":::::", "ls",
+            ]
+            .into_iter()
+            .map_into()
+            .collect(),
+            ..Default::default()
+        };
+
+        let parser = CommandLineArgsParser::new(
+            &command_line_args,
+            &RegexProcessor::new(&command_line_args).unwrap(),
+        );
+
+        let result = collect_into_vec(parser);
+
+        assert_eq!(
+            result,
+            vec![
+                OwnedCommandAndArgs {
+                    command_path: PathBuf::from("nice"),
+                    args: vec!["echo", "A"].into_iter().map_into().collect(),
+                },
+                OwnedCommandAndArgs {
+                    command_path: PathBuf::from("nice"),
+                    args: vec!["sleep", "1"].into_iter().map_into().collect(),
+                },
+                OwnedCommandAndArgs {
+                    command_path: PathBuf::from("nice"),
+                    args: vec!["ls"].into_iter().map_into().collect(),
+                },
+            ]
+        );
+    }
+
     #[test]
     fn test_parse_command_line_args_shell_mode_with_initial_command() {
         let command_line_args = CommandLineArgs {
@@ -379,7 +492,7 @@ mod test {
             .into_iter()
             .map_into()
             .collect(),
-            regex: Some("(?P<arg1>.*),(?P<arg2>.*),(?P<arg3>.*)".to_owned()),
+            regex: vec!["(?P<arg1>.*),(?P<arg2>.*),(?P<arg3>.*)".to_owned()],
             ..Default::default()
         };
 
@@ -427,7 +540,7 @@ mod test {
             .into_iter()
             .map_into()
             .collect(),
-            regex: Some("(.*),(.*),(.*)".to_owned()),
+            regex: vec!["(.*),(.*),(.*)".to_owned()],
             ..Default::default()
         };
 
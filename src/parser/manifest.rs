@@ -0,0 +1,93 @@
+use itertools::Itertools;
+
+use crate::{
+    command_line_args::CommandLineArgs, common::OwnedCommandAndArgs,
+    input::manifest::ManifestCommand, parser::ShellCommandAndArgs,
+};
+
+pub struct ManifestCommandParser {
+    shell_command_and_args: ShellCommandAndArgs,
+}
+
+impl ManifestCommandParser {
+    pub fn new(command_line_args: &CommandLineArgs) -> Self {
+        Self {
+            shell_command_and_args: ShellCommandAndArgs::new(command_line_args),
+        }
+    }
+
+    pub fn parse_command(&self, command: &ManifestCommand) -> Option<OwnedCommandAndArgs> {
+        let cmd_and_args = match command {
+            ManifestCommand::Line(line) => {
+                if self.shell_command_and_args.is_shell() {
+                    vec![line.trim().to_owned()]
+                } else {
+                    line.split_whitespace().map_into().collect()
+                }
+            }
+            ManifestCommand::Args(args) => args.clone(),
+        };
+
+        super::build_owned_command_and_args(&self.shell_command_and_args, cmd_and_args)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_command() {
+        let command_line_args = CommandLineArgs {
+            shell: false,
+            ..Default::default()
+        };
+
+        let parser = ManifestCommandParser::new(&command_line_args);
+
+        let expected = Some(OwnedCommandAndArgs {
+            command_path: PathBuf::from("echo"),
+            args: vec!["hello", "world"].into_iter().map_into().collect(),
+        });
+
+        assert_eq!(
+            parser.parse_command(&ManifestCommand::Line(" echo hello  world ".to_owned())),
+            expected
+        );
+        assert_eq!(
+            parser.parse_command(&ManifestCommand::Args(
+                vec!["echo", "hello", "world"]
+                    .into_iter()
+                    .map_into()
+                    .collect()
+            )),
+            expected
+        );
+        assert_eq!(parser.parse_command(&ManifestCommand::Args(vec![])), None);
+    }
+
+    #[test]
+    fn test_parse_command_shell() {
+        let command_line_args = CommandLineArgs {
+            shell: true,
+            shell_path: "/bin/bash".to_owned(),
+            shell_argument: "-c".to_owned(),
+            ..Default::default()
+        };
+
+        let parser = ManifestCommandParser::new(&command_line_args);
+
+        assert_eq!(
+            parser.parse_command(&ManifestCommand::Line("echo a | wc -c".to_owned())),
+            Some(OwnedCommandAndArgs {
+                command_path: PathBuf::from("/bin/bash"),
+                args: vec!["-c", "echo a | wc -c"]
+                    .into_iter()
+                    .map_into()
+                    .collect(),
+            })
+        );
+    }
+}
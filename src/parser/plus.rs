@@ -0,0 +1,180 @@
+use std::{
+    borrow::Cow,
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Placeholder names enabled by --plus that look like regex named groups.
+pub const NAMED_PLUS_PLACEHOLDERS: [&str; 2] = ["uniq", "host"];
+
+const PLUS_PLACEHOLDERS: [&str; 6] = ["{+/}", "{+.}", "{...}", "{..}", "{uniq}", "{host}"];
+
+/// Extended placeholders for --plus, expanded from the whole regex match {}.
+///
+/// * `{+/}` directory of the input, `.` if it has none
+/// * `{+.}` extension of the input without the dot
+/// * `{..}` and `{...}` input with 2 or 3 extensions removed
+/// * `{uniq}` value unique to each command, e.g. for temporary file names
+/// * `{host}` host name of this machine
+pub struct PlusPlaceholders {
+    host: String,
+    next_uniq: AtomicUsize,
+}
+
+impl PlusPlaceholders {
+    pub fn new() -> Self {
+        Self {
+            host: host_name(),
+            next_uniq: AtomicUsize::new(1),
+        }
+    }
+
+    pub fn contains_placeholder(argument: &str) -> bool {
+        PLUS_PLACEHOLDERS
+            .iter()
+            .any(|placeholder| argument.contains(placeholder))
+    }
+
+    fn next_uniq(&self) -> String {
+        format!(
+            "{}-{}",
+            std::process::id(),
+            self.next_uniq.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+
+    /// Expand placeholders in all arguments of one command, so {uniq} is the same in each.
+    pub fn expand_arguments(&self, arguments: &mut [String], input: &str) -> bool {
+        let mut uniq = None;
+        let mut modified_arguments = false;
+
+        for argument in arguments.iter_mut() {
+            if !Self::contains_placeholder(argument) {
+                continue;
+            }
+
+            let uniq = uniq.get_or_insert_with(|| self.next_uniq());
+
+            *argument = argument
+                .replace("{+/}", &directory(input))
+                .replace("{+.}", extension(input))
+                .replace("{...}", remove_extensions(input, 3))
+                .replace("{..}", remove_extensions(input, 2))
+                .replace("{uniq}", uniq)
+                .replace("{host}", &self.host);
+            modified_arguments = true;
+        }
+
+        modified_arguments
+    }
+
+    /// Value of a single placeholder such as {+.} for input, None if it is not a --plus
+    /// placeholder.
+    pub fn expand_placeholder<'a>(
+        &self,
+        placeholder: &str,
+        input: &'a str,
+    ) -> Option<Cow<'a, str>> {
+        match placeholder {
+            "{+/}" => Some(directory(input)),
+            "{+.}" => Some(Cow::from(extension(input))),
+            "{...}" => Some(Cow::from(remove_extensions(input, 3))),
+            "{..}" => Some(Cow::from(remove_extensions(input, 2))),
+            "{uniq}" => Some(Cow::from(self.next_uniq())),
+            "{host}" => Some(Cow::from(self.host.clone())),
+            _ => None,
+        }
+    }
+}
+
+fn directory(input: &str) -> Cow<'_, str> {
+    match Path::new(input).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_string_lossy(),
+        _ => Cow::from("."),
+    }
+}
+
+fn extension(input: &str) -> &str {
+    let file_name = file_name(input);
+
+    match file_name.rfind('.') {
+        Some(i) if i > 0 => &file_name[i + 1..],
+        _ => "",
+    }
+}
+
+fn remove_extensions(input: &str, count: usize) -> &str {
+    let file_name_start = input.len() - file_name(input).len();
+
+    let mut end = input.len();
+    for _ in 0..count {
+        match input[file_name_start..end].rfind('.') {
+            Some(i) if i > 0 => end = file_name_start + i,
+            _ => break,
+        }
+    }
+
+    &input[..end]
+}
+
+fn file_name(input: &str) -> &str {
+    match input.rfind(std::path::is_separator) {
+        Some(i) => &input[i + 1..],
+        None => input,
+    }
+}
+
+#[cfg(unix)]
+fn host_name() -> String {
+    nix::unistd::gethostname()
+        .map(|host_name| host_name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(not(unix))]
+fn host_name() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_expand_arguments() {
+        let plus_placeholders = PlusPlaceholders {
+            host: "host1".to_owned(),
+            next_uniq: AtomicUsize::new(1),
+        };
+
+        let mut arguments = vec![
+            "{+/}".to_owned(),
+            "{+.}".to_owned(),
+            "{..}".to_owned(),
+            "{...}".to_owned(),
+            "{host}:{uniq}".to_owned(),
+            "{uniq}".to_owned(),
+            "{1}".to_owned(),
+        ];
+
+        assert!(plus_placeholders.expand_arguments(&mut arguments, "dir/a.b/file.tar.gz.sig"));
+
+        let uniq = format!("{}-1", std::process::id());
+        assert_eq!(
+            arguments,
+            vec![
+                "dir/a.b".to_owned(),
+                "sig".to_owned(),
+                "dir/a.b/file.tar".to_owned(),
+                "dir/a.b/file".to_owned(),
+                format!("host1:{}", uniq),
+                uniq,
+                "{1}".to_owned(),
+            ]
+        );
+
+        let mut arguments = vec!["{+/} {+.} {..}".to_owned()];
+        plus_placeholders.expand_arguments(&mut arguments, ".bashrc");
+        assert_eq!(arguments, vec![".  .bashrc".to_owned()]);
+    }
+}
@@ -0,0 +1,311 @@
+use std::cmp::Ordering;
+
+/// Expression deciding which ::: combinations are run with --product-filter.
+///
+/// Comparisons `==`, `!=`, `<`, `<=`, `>`, `>=` between placeholders {1}, {2}, ... for
+/// the value from each group and literals, combined with `&&`, `||`, `!`, and parentheses.
+/// Values are compared as numbers when both are numbers, otherwise as strings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProductFilter(Expression);
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expression {
+    Compare(Operand, CompareOperator, Operand),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Operand {
+    /// Index of the ::: group, 0 based.
+    Placeholder(usize),
+    Literal(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CompareOperator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Operand(Operand),
+    CompareOperator(CompareOperator),
+    And,
+    Or,
+    Not,
+    OpenParen,
+    CloseParen,
+}
+
+impl ProductFilter {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let tokens = tokenize(s)?;
+
+        let mut parser = Parser {
+            tokens: &tokens,
+            position: 0,
+        };
+
+        let expression = parser.parse_or()?;
+
+        match parser.tokens.get(parser.position) {
+            None => Ok(Self(expression)),
+            Some(token) => Err(format!("unexpected {:?} in filter `{}`", token, s)),
+        }
+    }
+
+    /// Fail if a placeholder refers to a group beyond the number of ::: groups.
+    pub fn validate_group_count(&self, group_count: usize) -> anyhow::Result<()> {
+        if let Some(max_placeholder) = self.0.max_placeholder() {
+            if max_placeholder >= group_count {
+                anyhow::bail!(
+                    "--product-filter placeholder {{{}}} has no matching ::: group, there are {} groups",
+                    max_placeholder + 1,
+                    group_count
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn matches(&self, values: &[&String]) -> bool {
+        self.0.evaluate(values)
+    }
+}
+
+impl Expression {
+    fn evaluate(&self, values: &[&String]) -> bool {
+        match self {
+            Self::Compare(left, operator, right) => {
+                let ordering = compare(left.value(values), right.value(values));
+                match operator {
+                    CompareOperator::Eq => ordering == Ordering::Equal,
+                    CompareOperator::Ne => ordering != Ordering::Equal,
+                    CompareOperator::Lt => ordering == Ordering::Less,
+                    CompareOperator::Le => ordering != Ordering::Greater,
+                    CompareOperator::Gt => ordering == Ordering::Greater,
+                    CompareOperator::Ge => ordering != Ordering::Less,
+                }
+            }
+            Self::Not(expression) => !expression.evaluate(values),
+            Self::And(left, right) => left.evaluate(values) && right.evaluate(values),
+            Self::Or(left, right) => left.evaluate(values) || right.evaluate(values),
+        }
+    }
+
+    fn max_placeholder(&self) -> Option<usize> {
+        match self {
+            Self::Compare(left, _, right) => left.placeholder().max(right.placeholder()),
+            Self::Not(expression) => expression.max_placeholder(),
+            Self::And(left, right) | Self::Or(left, right) => {
+                left.max_placeholder().max(right.max_placeholder())
+            }
+        }
+    }
+}
+
+impl Operand {
+    fn value<'a>(&'a self, values: &[&'a String]) -> &'a str {
+        match self {
+            Self::Placeholder(i) => values.get(*i).map_or("", |value| value.as_str()),
+            Self::Literal(literal) => literal,
+        }
+    }
+
+    fn placeholder(&self) -> Option<usize> {
+        match self {
+            Self::Placeholder(i) => Some(*i),
+            Self::Literal(_) => None,
+        }
+    }
+}
+
+fn compare(left: &str, right: &str) -> Ordering {
+    match (left.parse::<f64>(), right.parse::<f64>()) {
+        (Ok(left), Ok(right)) => left.total_cmp(&right),
+        _ => left.cmp(right),
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            '&' if chars.next_if_eq(&'&').is_some() => Token::And,
+            '|' if chars.next_if_eq(&'|').is_some() => Token::Or,
+            '=' if chars.next_if_eq(&'=').is_some() => Token::CompareOperator(CompareOperator::Eq),
+            '!' if chars.next_if_eq(&'=').is_some() => Token::CompareOperator(CompareOperator::Ne),
+            '!' => Token::Not,
+            '<' if chars.next_if_eq(&'=').is_some() => Token::CompareOperator(CompareOperator::Le),
+            '<' => Token::CompareOperator(CompareOperator::Lt),
+            '>' if chars.next_if_eq(&'=').is_some() => Token::CompareOperator(CompareOperator::Ge),
+            '>' => Token::CompareOperator(CompareOperator::Gt),
+            '{' => {
+                let mut number = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err(format!("unterminated placeholder {{{}", number)),
+                        Some('}') => break,
+                        Some(next) => number.push(next),
+                    }
+                }
+                match number.parse::<usize>() {
+                    Ok(i) if i > 0 => Token::Operand(Operand::Placeholder(i - 1)),
+                    _ => return Err(format!("invalid placeholder {{{}}}", number)),
+                }
+            }
+            '\'' | '"' => {
+                let mut literal = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err(format!("unterminated string {}{}", c, literal)),
+                        Some(next) if next == c => break,
+                        Some(next) => literal.push(next),
+                    }
+                }
+                Token::Operand(Operand::Literal(literal))
+            }
+            c if is_word_char(c) => {
+                let mut literal = c.to_string();
+                while let Some(next) = chars.next_if(|c| is_word_char(*c)) {
+                    literal.push(next);
+                }
+                Token::Operand(Operand::Literal(literal))
+            }
+            c => return Err(format!("unexpected character '{}'", c)),
+        };
+
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '.' | '-' | '_' | '+' | '/' | ':')
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    fn next_if_eq(&mut self, token: &Token) -> bool {
+        let found = self.tokens.get(self.position) == Some(token);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, String> {
+        let mut expression = self.parse_and()?;
+        while self.next_if_eq(&Token::Or) {
+            expression = Expression::Or(Box::new(expression), Box::new(self.parse_and()?));
+        }
+        Ok(expression)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, String> {
+        let mut expression = self.parse_unary()?;
+        while self.next_if_eq(&Token::And) {
+            expression = Expression::And(Box::new(expression), Box::new(self.parse_unary()?));
+        }
+        Ok(expression)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, String> {
+        if self.next_if_eq(&Token::Not) {
+            return Ok(Expression::Not(Box::new(self.parse_unary()?)));
+        }
+
+        if self.next_if_eq(&Token::OpenParen) {
+            let expression = self.parse_or()?;
+            if !self.next_if_eq(&Token::CloseParen) {
+                return Err("missing )".to_owned());
+            }
+            return Ok(expression);
+        }
+
+        let left = self.parse_operand()?;
+        let operator = match self.next() {
+            Some(Token::CompareOperator(operator)) => *operator,
+            token => return Err(format!("expected comparison operator, found {:?}", token)),
+        };
+        let right = self.parse_operand()?;
+
+        Ok(Expression::Compare(left, operator, right))
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand, String> {
+        match self.next() {
+            Some(Token::Operand(operand)) => Ok(operand.clone()),
+            token => Err(format!("expected placeholder or value, found {:?}", token)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn matches(filter: &str, values: &[&str]) -> bool {
+        let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        let values: Vec<&String> = values.iter().collect();
+
+        ProductFilter::parse(filter).unwrap().matches(&values)
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("{1} != {2}", &["a", "b"]));
+        assert!(!matches("{1} != {2}", &["a", "a"]));
+        assert!(matches("{1} < {2}", &["9", "10"]));
+        assert!(!matches("{1} < {2}", &["b", "a"]));
+        assert!(matches("{1} == 'x y' || {2} >= 3", &["x y", "1"]));
+        assert!(matches("!({1} == a && {2} == b)", &["a", "c"]));
+        assert!(!matches("!({1} == a && {2} == b)", &["a", "b"]));
+        assert!(matches("{1} == a || {1} == b && {2} == c", &["a", "x"]));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(ProductFilter::parse("{1} !=").is_err());
+        assert!(ProductFilter::parse("{0} == a").is_err());
+        assert!(ProductFilter::parse("({1} == a").is_err());
+        assert!(ProductFilter::parse("{1} == 'a").is_err());
+        assert!(ProductFilter::parse("{1} == a b").is_err());
+        assert!(ProductFilter::parse("{1} = a").is_err());
+        assert!(ProductFilter::parse("{1 == a").is_err());
+
+        let product_filter = ProductFilter::parse("{1} != {3}").unwrap();
+        assert!(product_filter.validate_group_count(3).is_ok());
+        assert_eq!(
+            product_filter
+                .validate_group_count(2)
+                .unwrap_err()
+                .to_string(),
+            "--product-filter placeholder {3} has no matching ::: group, there are 2 groups"
+        );
+    }
+}
@@ -4,9 +4,20 @@ use itertools::Itertools;
 
 use tracing::warn;
 
-use std::{borrow::Cow, sync::Arc};
+use std::{
+    borrow::Cow,
+    sync::{Arc, LazyLock},
+};
 
-use crate::command_line_args::{CommandLineArgs, COMMANDS_FROM_ARGS_SEPARATOR};
+use crate::command_line_args::{
+    Combinations, CommandLineArgs, RegexFlag, COMMANDS_FROM_ARGS_SEPARATOR,
+};
+
+use super::plus::{PlusPlaceholders, NAMED_PLUS_PLACEHOLDERS};
+
+/// Placeholders of per job templates: {}, {#}, {N}, {name}, and --plus placeholders.
+static JOB_TEMPLATE_PLACEHOLDER_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"\{[^{}]*\}").unwrap());
 
 #[derive(Debug, Eq, PartialEq)]
 pub struct ApplyRegexToArgumentsResult {
@@ -15,42 +26,111 @@ pub struct ApplyRegexToArgumentsResult {
 }
 
 pub struct RegexProcessor {
-    command_line_regex: Option<CommandLineRegex>,
+    command_line_regexes: Vec<CommandLineRegex>,
+    plus_placeholders: Option<PlusPlaceholders>,
 }
 
 impl RegexProcessor {
     pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Arc<Self>> {
         let auto_regex = AutoCommandLineArgsRegex::new(command_line_args);
 
-        let command_line_regex = match (auto_regex, &command_line_args.regex) {
-            (Some(auto_regex), _) => Some(CommandLineRegex::new(&auto_regex.0)?),
-            (_, Some(cla_regex)) => Some(CommandLineRegex::new(cla_regex)?),
-            _ => None,
+        let mut command_line_regexes = match auto_regex {
+            Some(auto_regex) => vec![CommandLineRegex::new(
+                &auto_regex.0,
+                &command_line_args.regex_flags,
+            )?],
+            None => command_line_args
+                .regex
+                .iter()
+                .map(|regex| CommandLineRegex::new(regex, &command_line_args.regex_flags))
+                .collect::<anyhow::Result<_>>()?,
+        };
+
+        let templates = || {
+            command_line_args
+                .command_and_initial_arguments
+                .iter()
+                .take_while(|arg| *arg != COMMANDS_FROM_ARGS_SEPARATOR)
+                .chain(command_line_args.cost.iter())
+                .chain(command_line_args.tagstring.iter())
+        };
+
+        let plus_placeholders = command_line_args.plus.then(PlusPlaceholders::new);
+
+        let mut allowed_names = vec![];
+        if plus_placeholders.is_some() {
+            allowed_names.extend(NAMED_PLUS_PLACEHOLDERS);
+        }
+        if command_line_args.repeat.is_some() {
+            allowed_names.push("rep");
+        }
+
+        if !command_line_args.inline_commands_mode() {
+            // input lines are matched as a whole so --plus placeholders work without -r
+            if command_line_regexes.is_empty()
+                && plus_placeholders.is_some()
+                && templates().any(|template| PlusPlaceholders::contains_placeholder(template))
+            {
+                command_line_regexes
+                    .push(CommandLineRegex::new(".*", &command_line_args.regex_flags)?);
+            }
+
+            for command_line_regex in &command_line_regexes {
+                command_line_regex.validate_placeholders(templates(), &allowed_names)?;
+            }
+        }
+
+        Ok(Arc::new(Self {
+            command_line_regexes,
+            plus_placeholders,
+        }))
+    }
+
+    pub fn new_with_regex(regex: Option<&str>) -> anyhow::Result<Arc<Self>> {
+        let command_line_regexes = match regex {
+            Some(regex) => vec![CommandLineRegex::new(regex, &[])?],
+            None => vec![],
         };
 
-        Ok(Arc::new(Self { command_line_regex }))
+        Ok(Arc::new(Self {
+            command_line_regexes,
+            plus_placeholders: None,
+        }))
     }
 
     pub fn regex_mode(&self) -> bool {
-        self.command_line_regex.is_some()
+        !self.command_line_regexes.is_empty()
     }
 
+    /// Expand arguments with the first regex matching the input data.
     pub fn apply_regex_to_arguments(
         &self,
         arguments: &Vec<String>,
         input_data: &str,
     ) -> Option<ApplyRegexToArgumentsResult> {
-        let command_line_regex = self.command_line_regex.as_ref()?;
+        if !self.regex_mode() {
+            return None;
+        }
+
+        let Some((command_line_regex, input_match)) =
+            self.command_line_regexes
+                .iter()
+                .find_map(|command_line_regex| {
+                    let input_match = command_line_regex.regex.find(input_data)?;
+                    Some((command_line_regex, input_match.as_str()))
+                })
+        else {
+            warn!("regex did not match input data: {}", input_data);
+            return None;
+        };
 
         let mut results: Vec<String> = Vec::with_capacity(arguments.len());
-        let mut found_input_data_match = false;
         let mut modified_arguments = false;
 
         for argument in arguments {
             match command_line_regex.expand(argument.into(), input_data) {
                 Ok(result) => {
                     results.push(result.argument.to_string());
-                    found_input_data_match = true;
                     modified_arguments = modified_arguments || result.modified_argument;
                 }
                 Err(ExpandError::RegexDoesNotMatchInputData) => {
@@ -59,15 +139,99 @@ impl RegexProcessor {
             };
         }
 
-        if !found_input_data_match {
-            warn!("regex did not match input data: {}", input_data);
-            None
+        if let Some(plus_placeholders) = &self.plus_placeholders {
+            modified_arguments =
+                plus_placeholders.expand_arguments(&mut results, input_match) || modified_arguments;
+        }
+
+        Some(ApplyRegexToArgumentsResult {
+            arguments: results,
+            modified_arguments,
+        })
+    }
+
+    /// Expand placeholders of a per job template such as --tagstring in a single pass, so
+    /// values containing placeholders are not expanded again.
+    ///
+    /// {} is the match of the first regex matching the input data, or the input data without
+    /// one, {#} the job number, {N} and {name} capture groups, and --plus placeholders are
+    /// expanded from {}.  Other placeholders are left as is.
+    pub fn expand_job_template(
+        &self,
+        template: &str,
+        input_data: &str,
+        job_number: usize,
+    ) -> String {
+        let captures = if self.regex_mode() {
+            let captures = self
+                .command_line_regexes
+                .iter()
+                .find_map(|command_line_regex| command_line_regex.regex.captures(input_data));
+            if captures.is_none() {
+                warn!("regex did not match input data: {}", input_data);
+            }
+            captures
         } else {
-            Some(ApplyRegexToArgumentsResult {
-                arguments: results,
-                modified_arguments,
+            None
+        };
+
+        let input_match = captures
+            .as_ref()
+            .and_then(|captures| captures.get(0))
+            .map_or(input_data, |input_match| input_match.as_str());
+
+        let capture = |name: &str| {
+            let captures = captures.as_ref()?;
+            let capture = match name.parse::<usize>() {
+                Ok(i) => captures.get(i),
+                Err(_) => captures.name(name),
+            };
+            capture.map(|capture| Cow::from(capture.as_str()))
+        };
+
+        JOB_TEMPLATE_PLACEHOLDER_REGEX
+            .replace_all(template, |placeholder: &regex::Captures| {
+                let placeholder = &placeholder[0];
+                let value = match &placeholder[1..placeholder.len() - 1] {
+                    "" => Some(Cow::from(input_match)),
+                    "#" => Some(Cow::from(job_number.to_string())),
+                    name => capture(name).or_else(|| {
+                        self.plus_placeholders
+                            .as_ref()
+                            .and_then(|plus_placeholders| {
+                                plus_placeholders.expand_placeholder(placeholder, input_match)
+                            })
+                    }),
+                };
+                value.unwrap_or(Cow::from(placeholder)).into_owned()
             })
-        }
+            .into_owned()
+    }
+
+    /// Capture groups 1.. of the first regex matching the input data, for --args-to-shell.
+    ///
+    /// Groups that did not participate in the match are empty.
+    pub fn captures(&self, input_data: &str) -> Option<Vec<String>> {
+        let Some(captures) = self
+            .command_line_regexes
+            .iter()
+            .find_map(|command_line_regex| command_line_regex.regex.captures(input_data))
+        else {
+            warn!("regex did not match input data: {}", input_data);
+            return None;
+        };
+
+        Some(
+            captures
+                .iter()
+                .skip(1)
+                .map(|match_option| {
+                    match_option
+                        .map(|match_value| match_value.as_str().to_owned())
+                        .unwrap_or_default()
+                })
+                .collect(),
+        )
     }
 }
 
@@ -90,8 +254,19 @@ struct CommandLineRegex {
 }
 
 impl CommandLineRegex {
-    fn new(command_line_args_regex: &str) -> anyhow::Result<Self> {
-        let regex = regex::Regex::new(command_line_args_regex)
+    fn new(command_line_args_regex: &str, regex_flags: &[RegexFlag]) -> anyhow::Result<Self> {
+        let mut regex_builder = regex::RegexBuilder::new(command_line_args_regex);
+        for regex_flag in regex_flags {
+            match regex_flag {
+                RegexFlag::CaseInsensitive => regex_builder.case_insensitive(true),
+                RegexFlag::MultiLine => regex_builder.multi_line(true),
+                RegexFlag::DotMatchesNewLine => regex_builder.dot_matches_new_line(true),
+                RegexFlag::UnicodeOff => regex_builder.unicode(false),
+            };
+        }
+
+        let regex = regex_builder
+            .build()
             .context("CommandLineRegex::new: error creating regex")?;
 
         let capture_names = regex.capture_names();
@@ -117,6 +292,64 @@ impl CommandLineRegex {
         })
     }
 
+    /// Fail if templates use {N} placeholders beyond the regex's capture groups, or
+    /// {name} placeholders that are not named groups when the regex has named groups.
+    /// Placeholders preceded by $ are shell variables and are ignored.
+    fn validate_placeholders<'a>(
+        &self,
+        templates: impl Iterator<Item = &'a String>,
+        allowed_names: &[&str],
+    ) -> anyhow::Result<()> {
+        let placeholder_regex = regex::Regex::new(r"\{([0-9]+|[A-Za-z_][A-Za-z0-9_]*)\}").unwrap();
+
+        let has_named_groups = !self.named_group_to_match_key.is_empty();
+
+        let mut missing = vec![];
+
+        for template in templates {
+            for captures in placeholder_regex.captures_iter(template) {
+                let placeholder = captures.get(0).unwrap();
+                if template[..placeholder.start()].ends_with('$') {
+                    continue;
+                }
+
+                let name = &captures[1];
+                if allowed_names.contains(&name) {
+                    continue;
+                }
+
+                let found = match name.parse::<usize>() {
+                    Ok(i) => i < self.numbered_group_match_keys.len(),
+                    Err(_) => {
+                        !has_named_groups
+                            || self
+                                .named_group_to_match_key
+                                .iter()
+                                .any(|(group_name, _)| group_name == name)
+                    }
+                };
+
+                if !found && !missing.contains(&placeholder.as_str()) {
+                    missing.push(placeholder.as_str());
+                }
+            }
+        }
+
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "placeholders {} have no matching capture group in regex '{}', available placeholders: {}",
+                missing.join(", "),
+                self.regex,
+                self.numbered_group_match_keys
+                    .iter()
+                    .chain(self.named_group_to_match_key.iter().map(|(_, match_key)| match_key))
+                    .join(", ")
+            );
+        }
+
+        Ok(())
+    }
+
     fn expand<'a>(
         &self,
         argument: Cow<'a, str>,
@@ -169,7 +402,10 @@ struct AutoCommandLineArgsRegex(String);
 
 impl AutoCommandLineArgsRegex {
     fn new(command_line_args: &CommandLineArgs) -> Option<Self> {
-        if command_line_args.regex.is_none() && command_line_args.commands_from_args_mode() {
+        if command_line_args.regex.is_empty()
+            && command_line_args.commands_from_args_mode()
+            && !command_line_args.shell_positional_args
+        {
             Self::new_auto_interpolate_commands_from_args(command_line_args)
         } else {
             None
@@ -197,7 +433,11 @@ impl AutoCommandLineArgsRegex {
             }
         }
 
-        let argument_group_count = argument_group_count;
+        // the pairs modes pair a single group with itself
+        let argument_group_count = match command_line_args.combinations {
+            Combinations::Product => argument_group_count,
+            Combinations::Pairs | Combinations::UniquePairs => 2,
+        };
 
         let mut generated_regex = String::with_capacity(argument_group_count * 5);
 
@@ -219,13 +459,13 @@ mod test {
     #[test]
     fn test_regex_disabled() {
         let command_line_args = CommandLineArgs {
-            regex: None,
+            regex: vec![],
             ..Default::default()
         };
 
         let regex_processor = RegexProcessor::new(&command_line_args).unwrap();
 
-        assert_eq!(regex_processor.regex_mode(), false);
+        assert!(!regex_processor.regex_mode());
 
         let arguments = vec!["{0}".to_string()];
         assert_eq!(
@@ -237,13 +477,13 @@ mod test {
     #[test]
     fn test_regex_numbered_groups() {
         let command_line_args = CommandLineArgs {
-            regex: Some("(.*),(.*)".to_string()),
+            regex: vec!["(.*),(.*)".to_string()],
             ..Default::default()
         };
 
         let regex_processor = RegexProcessor::new(&command_line_args).unwrap();
 
-        assert_eq!(regex_processor.regex_mode(), true);
+        assert!(regex_processor.regex_mode());
 
         let arguments = vec!["{1} {2}".to_string()];
         assert_eq!(
@@ -258,13 +498,13 @@ mod test {
     #[test]
     fn test_regex_named_groups() {
         let command_line_args = CommandLineArgs {
-            regex: Some("(?P<arg1>.*),(?P<arg2>.*)".to_string()),
+            regex: vec!["(?P<arg1>.*),(?P<arg2>.*)".to_string()],
             ..Default::default()
         };
 
         let regex_processor = RegexProcessor::new(&command_line_args).unwrap();
 
-        assert_eq!(regex_processor.regex_mode(), true);
+        assert!(regex_processor.regex_mode());
 
         let arguments = vec!["{arg1} {arg2}".to_string()];
         assert_eq!(
@@ -279,13 +519,13 @@ mod test {
     #[test]
     fn test_regex_numbered_groups_json() {
         let command_line_args = CommandLineArgs {
-            regex: Some("(.*),(.*)".to_string()),
+            regex: vec!["(.*),(.*)".to_string()],
             ..Default::default()
         };
 
         let regex_processor = RegexProcessor::new(&command_line_args).unwrap();
 
-        assert_eq!(regex_processor.regex_mode(), true);
+        assert!(regex_processor.regex_mode());
 
         let arguments =
             vec![r#"{"id": 123, "$zero": "{0}", "one": "{1}", "two": "{2}"}"#.to_string()];
@@ -304,13 +544,13 @@ mod test {
     #[test]
     fn test_regex_numbered_groups_json_empty_group() {
         let command_line_args = CommandLineArgs {
-            regex: Some("(.*),(.*)".to_string()),
+            regex: vec!["(.*),(.*)".to_string()],
             ..Default::default()
         };
 
         let regex_processor = RegexProcessor::new(&command_line_args).unwrap();
 
-        assert_eq!(regex_processor.regex_mode(), true);
+        assert!(regex_processor.regex_mode());
 
         let arguments =
             vec![r#"{"id": 123, "$zero": "{}", "one": "{1}", "two": "{2}"}"#.to_string()];
@@ -329,13 +569,13 @@ mod test {
     #[test]
     fn test_regex_named_groups_json() {
         let command_line_args = CommandLineArgs {
-            regex: Some("(?P<arg1>.*),(?P<arg2>.*)".to_string()),
+            regex: vec!["(?P<arg1>.*),(?P<arg2>.*)".to_string()],
             ..Default::default()
         };
 
         let regex_processor = RegexProcessor::new(&command_line_args).unwrap();
 
-        assert_eq!(regex_processor.regex_mode(), true);
+        assert!(regex_processor.regex_mode());
 
         let arguments =
             vec![r#"{"id": 123, "$zero": "{0}", "one": "{arg1}", "two": "{arg2}"}"#.to_string()];
@@ -354,13 +594,13 @@ mod test {
     #[test]
     fn test_regex_named_groups_json_empty_group() {
         let command_line_args = CommandLineArgs {
-            regex: Some("(?P<arg1>.*),(?P<arg2>.*)".to_string()),
+            regex: vec!["(?P<arg1>.*),(?P<arg2>.*)".to_string()],
             ..Default::default()
         };
 
         let regex_processor = RegexProcessor::new(&command_line_args).unwrap();
 
-        assert_eq!(regex_processor.regex_mode(), true);
+        assert!(regex_processor.regex_mode());
 
         let arguments =
             vec![r#"{"id": 123, "$zero": "{}", "one": "{arg1}", "two": "{arg2}"}"#.to_string()];
@@ -379,13 +619,13 @@ mod test {
     #[test]
     fn test_regex_string_containing_dollar_curly_brace_variable() {
         let command_line_args = CommandLineArgs {
-            regex: Some("(?P<arg1>.*),(?P<arg2>.*)".to_string()),
+            regex: vec!["(?P<arg1>.*),(?P<arg2>.*)".to_string()],
             ..Default::default()
         };
 
         let regex_processor = RegexProcessor::new(&command_line_args).unwrap();
 
-        assert_eq!(regex_processor.regex_mode(), true);
+        assert!(regex_processor.regex_mode());
 
         let arguments = vec![r#"{arg2}${FOO}{arg1}$BAR${BAR}{arg2}"#.to_string()];
         assert_eq!(
@@ -400,13 +640,13 @@ mod test {
     #[test]
     fn test_regex_not_matching_input_data() {
         let command_line_args = CommandLineArgs {
-            regex: Some("(?P<arg1>.*),(?P<arg2>.*)".to_string()),
+            regex: vec!["(?P<arg1>.*),(?P<arg2>.*)".to_string()],
             ..Default::default()
         };
 
         let regex_processor = RegexProcessor::new(&command_line_args).unwrap();
 
-        assert_eq!(regex_processor.regex_mode(), true);
+        assert!(regex_processor.regex_mode());
 
         let arguments = vec!["{arg2},{arg1}".to_string()];
         assert_eq!(
@@ -423,10 +663,116 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_expand_job_template() {
+        let command_line_args = CommandLineArgs {
+            regex: vec!["(.*),(?P<name>.*)".to_string()],
+            ..Default::default()
+        };
+
+        let regex_processor = RegexProcessor::new(&command_line_args).unwrap();
+
+        assert_eq!(
+            regex_processor.expand_job_template("{#} {1} {name} {}", "a,b", 3),
+            "3 a b a,b"
+        );
+
+        // values containing placeholders are not expanded again
+        assert_eq!(
+            regex_processor.expand_job_template("{1}-{name}", "{}x{#},{1}", 3),
+            "{}x{#}-{1}"
+        );
+
+        assert_eq!(
+            regex_processor.expand_job_template("{2} {other} {#}", "no match", 3),
+            "{2} {other} 3"
+        );
+
+        let regex_processor = RegexProcessor::new_with_regex(None).unwrap();
+
+        assert_eq!(
+            regex_processor.expand_job_template("{} {#} {1}", "{#}", 3),
+            "{#} 3 {1}"
+        );
+    }
+
+    #[test]
+    fn test_regex_multiple_first_match() {
+        let command_line_args = CommandLineArgs {
+            regex: vec![
+                "(?P<arg1>[0-9]+) (?P<arg2>.*)".to_string(),
+                "(?P<arg2>.*)\\|(?P<arg1>.*)".to_string(),
+            ],
+            ..Default::default()
+        };
+
+        let regex_processor = RegexProcessor::new(&command_line_args).unwrap();
+
+        assert!(regex_processor.regex_mode());
+
+        let arguments = vec!["{arg1}-{arg2}".to_string()];
+        assert_eq!(
+            regex_processor.apply_regex_to_arguments(&arguments, "1 hello|world"),
+            Some(ApplyRegexToArgumentsResult {
+                arguments: vec!["1-hello|world".to_string()],
+                modified_arguments: true,
+            })
+        );
+
+        assert_eq!(
+            regex_processor.apply_regex_to_arguments(&arguments, "hello|world"),
+            Some(ApplyRegexToArgumentsResult {
+                arguments: vec!["world-hello".to_string()],
+                modified_arguments: true,
+            })
+        );
+
+        assert_eq!(
+            regex_processor.apply_regex_to_arguments(&arguments, "hello world"),
+            None,
+        );
+    }
+
+    #[test]
+    fn test_regex_flags() {
+        let command_line_args = CommandLineArgs {
+            regex: vec!["^key=(.*)$".to_string()],
+            regex_flags: vec![RegexFlag::CaseInsensitive, RegexFlag::MultiLine],
+            ..Default::default()
+        };
+
+        let regex_processor = RegexProcessor::new(&command_line_args).unwrap();
+
+        let arguments = vec!["{1}".to_string()];
+        assert_eq!(
+            regex_processor.apply_regex_to_arguments(&arguments, "first\nKEY=value\nlast"),
+            Some(ApplyRegexToArgumentsResult {
+                arguments: vec!["value".to_string()],
+                modified_arguments: true,
+            })
+        );
+
+        let command_line_args = CommandLineArgs {
+            regex: vec![r"(\w+)".to_string()],
+            regex_flags: vec![RegexFlag::UnicodeOff],
+            ..Default::default()
+        };
+
+        let regex_processor = RegexProcessor::new(&command_line_args).unwrap();
+
+        assert_eq!(
+            regex_processor.apply_regex_to_arguments(&arguments, "été"),
+            Some(ApplyRegexToArgumentsResult {
+                arguments: vec!["t".to_string()],
+                modified_arguments: true,
+            })
+        );
+    }
+
     #[test]
     fn test_regex_invalid() {
         let command_line_args = CommandLineArgs {
-            regex: Some("(?Parg1>.*),(?P<arg2>.*)".to_string()),
+            regex: vec!["(?Parg1>.*),(?P<arg2>.*)".to_string()],
             ..Default::default()
         };
 
@@ -435,10 +781,62 @@ mod test {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_regex_missing_placeholders() {
+        let command_line_args = CommandLineArgs {
+            regex: vec!["(.*),(.*)".to_string()],
+            command_and_initial_arguments: ["echo", "{1}", "{3}", "${HOME}", "{name}"]
+                .into_iter()
+                .map_into()
+                .collect(),
+            ..Default::default()
+        };
+
+        let error = RegexProcessor::new(&command_line_args)
+            .err()
+            .unwrap()
+            .to_string();
+
+        assert_eq!(
+            error,
+            "placeholders {3} have no matching capture group in regex '(.*),(.*)', available placeholders: {0}, {1}, {2}"
+        );
+
+        let command_line_args = CommandLineArgs {
+            regex: vec!["(?P<arg1>.*),(?P<arg2>.*)".to_string()],
+            command_and_initial_arguments: ["echo", "{arg1}", "{arg3}{arg3}"]
+                .into_iter()
+                .map_into()
+                .collect(),
+            cost: Some("{2}".to_string()),
+            ..Default::default()
+        };
+
+        let error = RegexProcessor::new(&command_line_args)
+            .err()
+            .unwrap()
+            .to_string();
+
+        assert_eq!(
+            error,
+            "placeholders {arg3} have no matching capture group in regex '(?P<arg1>.*),(?P<arg2>.*)', available placeholders: {0}, {1}, {2}, {arg1}, {arg2}"
+        );
+
+        let command_line_args = CommandLineArgs {
+            command_and_initial_arguments: ["echo", "{1}", "{3}", ":::", "A", ":::", "B"]
+                .into_iter()
+                .map_into()
+                .collect(),
+            ..Default::default()
+        };
+
+        assert!(RegexProcessor::new(&command_line_args).is_err());
+    }
+
     #[test]
     fn test_auto_regex_command_line_regex() {
         let command_line_args = CommandLineArgs {
-            regex: Some("(?Parg1>.*),(?P<arg2>.*)".to_string()),
+            regex: vec!["(?Parg1>.*),(?P<arg2>.*)".to_string()],
             ..Default::default()
         };
 
@@ -450,7 +848,7 @@ mod test {
     #[test]
     fn test_auto_regex_not_command_line_args_mode() {
         let command_line_args = CommandLineArgs {
-            regex: None,
+            regex: vec![],
             command_and_initial_arguments: ["echo"].into_iter().map_into().collect(),
             ..Default::default()
         };
@@ -463,7 +861,7 @@ mod test {
     #[test]
     fn test_auto_regex() {
         let command_line_args = CommandLineArgs {
-            regex: None,
+            regex: vec![],
             command_and_initial_arguments: ["echo", ":::", "A", "B", ":::", "C", "D"]
                 .into_iter()
                 .map_into()
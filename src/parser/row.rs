@@ -0,0 +1,139 @@
+use crate::{
+    command_line_args::CommandLineArgs, common::OwnedCommandAndArgs, parser::ShellCommandAndArgs,
+};
+
+pub struct RowParser {
+    command_and_initial_arguments: Vec<String>,
+    shell_command_and_args: ShellCommandAndArgs,
+}
+
+impl RowParser {
+    pub fn new(command_line_args: &CommandLineArgs) -> Self {
+        Self {
+            command_and_initial_arguments: command_line_args.command_and_initial_arguments.clone(),
+            shell_command_and_args: ShellCommandAndArgs::new(command_line_args),
+        }
+    }
+
+    /// Replace {column} placeholders with row values, e.g. from --sql or --s3-list.
+    ///
+    /// If no argument contains a placeholder the first `appended_columns` row values
+    /// are appended as arguments.
+    pub fn parse_row(
+        &self,
+        row: &[(String, String)],
+        appended_columns: usize,
+    ) -> Option<OwnedCommandAndArgs> {
+        let mut modified_arguments = false;
+
+        let cmd_and_args: Vec<String> = self
+            .command_and_initial_arguments
+            .iter()
+            .map(|argument| {
+                let mut argument = argument.clone();
+                for (column, value) in row {
+                    let placeholder = format!("{{{}}}", column);
+                    if argument.contains(&placeholder) {
+                        argument = argument.replace(&placeholder, value);
+                        modified_arguments = true;
+                    }
+                }
+                argument
+            })
+            .collect();
+
+        let input_args: Vec<String> = if modified_arguments {
+            vec![]
+        } else {
+            row.iter()
+                .take(appended_columns)
+                .map(|(_, value)| value.clone())
+                .collect()
+        };
+
+        super::build_owned_command_and_input_args(
+            &self.shell_command_and_args,
+            cmd_and_args,
+            input_args,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use itertools::Itertools;
+
+    use std::path::PathBuf;
+
+    fn row() -> Vec<(String, String)> {
+        vec![
+            ("id".to_owned(), "1".to_owned()),
+            ("url".to_owned(), "https://example.com".to_owned()),
+        ]
+    }
+
+    #[test]
+    fn test_parse_row_placeholders() {
+        let command_line_args = CommandLineArgs {
+            command_and_initial_arguments: vec!["curl", "-o", "{id}.html", "{url}"]
+                .into_iter()
+                .map_into()
+                .collect(),
+            ..Default::default()
+        };
+
+        let parser = RowParser::new(&command_line_args);
+
+        assert_eq!(
+            parser.parse_row(&row(), 2),
+            Some(OwnedCommandAndArgs {
+                command_path: PathBuf::from("curl"),
+                args: vec!["-o", "1.html", "https://example.com"]
+                    .into_iter()
+                    .map_into()
+                    .collect(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_row_append() {
+        let command_line_args = CommandLineArgs {
+            command_and_initial_arguments: vec!["echo".to_owned()],
+            ..Default::default()
+        };
+
+        let parser = RowParser::new(&command_line_args);
+
+        assert_eq!(
+            parser.parse_row(&row(), 2),
+            Some(OwnedCommandAndArgs {
+                command_path: PathBuf::from("echo"),
+                args: vec!["1", "https://example.com"]
+                    .into_iter()
+                    .map_into()
+                    .collect(),
+            })
+        );
+
+        assert_eq!(
+            parser.parse_row(&row(), 1),
+            Some(OwnedCommandAndArgs {
+                command_path: PathBuf::from("echo"),
+                args: vec!["1".into()],
+            })
+        );
+
+        let parser = RowParser::new(&CommandLineArgs::default());
+
+        assert_eq!(
+            parser.parse_row(&row(), 2),
+            Some(OwnedCommandAndArgs {
+                command_path: PathBuf::from("1"),
+                args: vec!["https://example.com".into()],
+            })
+        );
+    }
+}
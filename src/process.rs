@@ -1,14 +1,56 @@
+mod arg_max;
+mod audit;
+mod cgroup;
+mod cpu_timeout;
+mod jail;
+mod numa;
+mod sandbox;
+mod self_nice;
+mod systemd;
+mod umask;
+#[cfg(windows)]
+mod windows;
+
 use tokio::{
     process::{Child, Command},
+    sync::{watch, Notify},
     time::Duration,
 };
 
 use std::{
+    collections::BTreeMap,
+    convert::Infallible,
     ffi::OsStr,
+    io::IsTerminal,
+    path::Path,
     process::{Output, Stdio},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::{
+    command_line_args::{CommandLineArgs, DiscardOutput},
+    detach::DETACHED_RUN_DIR_VAR,
+    input,
+    recorded_env::{self, RECORDED_ENV_VAR},
 };
 
-use crate::command_line_args::{CommandLineArgs, DiscardOutput};
+use self::{
+    arg_max::ArgMax,
+    audit::AuditLog,
+    cgroup::{CgroupManager, JobCgroup},
+    cpu_timeout::CpuTimeout,
+    jail::JailExec,
+    numa::NumaPlacement,
+    sandbox::SandboxExec,
+    self_nice::SelfNice,
+    systemd::SystemdScope,
+    umask::UmaskWrapper,
+};
+
+pub use self::audit::{AuditEvent, AuditRecord, REDACTED};
 
 #[derive(thiserror::Error, Debug)]
 pub enum ChildProcessExecutionError {
@@ -17,6 +59,15 @@ pub enum ChildProcessExecutionError {
 
     #[error("i/o error: {0}")]
     IOError(#[from] std::io::Error),
+
+    #[error("cpu timeout: {0:?} of cpu time elapsed")]
+    CpuTimeout(Duration),
+
+    #[error("killed")]
+    Killed,
+
+    #[error("killed to be requeued")]
+    Requeued,
 }
 
 #[derive(Debug)]
@@ -24,6 +75,16 @@ pub struct ChildProcess {
     child: Child,
     discard_all_output: bool,
     timeout: Option<Duration>,
+    cpu_timeout: Option<CpuTimeout>,
+    job_number: usize,
+    audit_log: Option<Arc<AuditLog>>,
+    kill_receiver: watch::Receiver<bool>,
+    running_job: RunningJob,
+    job_cgroup: Option<JobCgroup>,
+    /// The child leads its own process group.
+    process_group: bool,
+    /// Time to exit after SIGTERM before being killed with --term-timeout.
+    term_timeout: Option<Duration>,
 }
 
 impl ChildProcess {
@@ -45,30 +106,345 @@ impl ChildProcess {
         Ok(output)
     }
 
-    pub async fn await_completion(self) -> Result<Output, ChildProcessExecutionError> {
-        match self.timeout {
-            None => self.await_output().await,
-            Some(timeout) => {
-                let result = tokio::time::timeout(timeout, self.await_output()).await?;
+    pub async fn await_completion(mut self) -> Result<Output, ChildProcessExecutionError> {
+        let pid = self.id();
+        let job_number = self.job_number;
+        let audit_log = self.audit_log.take();
+        let mut kill_receiver = self.kill_receiver.clone();
+        let requeue = Arc::clone(&self.running_job.requeue);
+        let cpu_timeout = self.cpu_timeout;
+        let job_cgroup = self.job_cgroup.take();
+        let cgroup_cpu_stat = job_cgroup.as_ref().map(JobCgroup::cpu_stat_path);
+        let process_group = self.process_group;
+
+        let cpu_timeout_exceeded = async {
+            match cpu_timeout {
+                None => std::future::pending().await,
+                Some(cpu_timeout) => {
+                    cpu_timeout.exceeded(pid, cgroup_cpu_stat).await;
+                    cpu_timeout.limit()
+                }
+            }
+        };
+
+        let timeout = self.timeout;
+        let term_timeout = self.term_timeout;
+
+        let timed_out = async {
+            match timeout {
+                None => std::future::pending().await,
+                Some(timeout) => {
+                    match tokio::time::timeout(timeout, std::future::pending::<Infallible>()).await
+                    {
+                        Ok(never) => match never {},
+                        Err(elapsed) => elapsed,
+                    }
+                }
+            }
+        };
+
+        let mut output = Box::pin(self.await_output());
+
+        let outcome = tokio::select! {
+            result = &mut output => Ok(result),
+            elapsed = timed_out => Err(ChildProcessExecutionError::Timeout(elapsed)),
+            limit = cpu_timeout_exceeded => Err(ChildProcessExecutionError::CpuTimeout(limit)),
+            _ = requeue.notified() => Err(ChildProcessExecutionError::Requeued),
+            Ok(_) = kill_receiver.wait_for(|killed| *killed) => {
+                Err(ChildProcessExecutionError::Killed)
+            }
+        };
+
+        let result = match outcome {
+            Ok(result) => result,
+            Err(error) => {
+                if let Some(term_timeout) = term_timeout {
+                    terminate(pid, process_group);
+
+                    // a command exiting after SIGTERM still failed with the error
+                    let _ = tokio::time::timeout(term_timeout, &mut output).await;
+                }
+
+                // dropping the output future kills the child, also kill the commands it
+                // started
+                drop(output);
+                if process_group {
+                    kill_process_group(pid);
+                }
 
-                let output = result?;
+                Err(error)
+            }
+        };
+
+        if let Some(job_cgroup) = job_cgroup {
+            job_cgroup.remove().await;
+        }
 
-                Ok(output)
+        if let Some(audit_log) = audit_log {
+            if let Err(e) = audit_log.record_exit(job_number, pid, &result) {
+                tracing::warn!("audit log error: {:#}", e);
             }
         }
+
+        result
+    }
+}
+
+/// Send SIGTERM to a child, and to the commands it started if it leads its process group.
+#[cfg(unix)]
+fn terminate(pid: Option<u32>, process_group: bool) {
+    use nix::{
+        sys::signal::{kill, killpg, Signal},
+        unistd::Pid,
+    };
+
+    let Some(pid) = pid.and_then(|pid| i32::try_from(pid).ok()) else {
+        return;
+    };
+
+    let result = if process_group {
+        killpg(Pid::from_raw(pid), Signal::SIGTERM)
+    } else {
+        kill(Pid::from_raw(pid), Signal::SIGTERM)
+    };
+
+    if let Err(e) = result {
+        tracing::debug!("error terminating pid {}: {}", pid, e);
     }
 }
 
+#[cfg(not(unix))]
+fn terminate(_pid: Option<u32>, _process_group: bool) {}
+
+/// Kill the process group led by a child, e.g. the pipeline of `bash -c 'foo | bar'`.
+#[cfg(unix)]
+fn kill_process_group(pid: Option<u32>) {
+    use nix::{
+        sys::signal::{killpg, Signal},
+        unistd::Pid,
+    };
+
+    let Some(pid) = pid.and_then(|pid| i32::try_from(pid).ok()) else {
+        return;
+    };
+
+    if let Err(e) = killpg(Pid::from_raw(pid), Signal::SIGKILL) {
+        tracing::debug!("error killing process group {}: {}", pid, e);
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(_pid: Option<u32>) {}
+
+/// Running children in the order they were started, e.g. to kill the youngest one.
+#[derive(Debug, Default)]
+pub struct RunningJobs {
+    next_id: AtomicU64,
+    jobs: Mutex<RunningJobsState>,
+}
+
+#[derive(Debug, Default)]
+struct RunningJobsState {
+    /// Requeue notifier, pid, and whether the child leads its process group.
+    requeue_notifiers: BTreeMap<u64, (Arc<Notify>, Option<u32>, bool)>,
+    /// Set while children are stopped, children started meanwhile are stopped too.
+    stopped: bool,
+}
+
+impl RunningJobs {
+    fn register(self: &Arc<Self>, pid: Option<u32>, process_group: bool) -> RunningJob {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let requeue = Arc::new(Notify::new());
+
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.requeue_notifiers
+            .insert(id, (Arc::clone(&requeue), pid, process_group));
+        if jobs.stopped {
+            signal_child(pid, process_group, ChildSignal::Stop);
+        }
+
+        RunningJob {
+            id,
+            requeue,
+            running_jobs: Arc::clone(self),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.lock().unwrap().requeue_notifiers.len()
+    }
+
+    /// Kill the most recently started child, its completion fails with
+    /// [`ChildProcessExecutionError::Requeued`].
+    pub fn requeue_youngest(&self) -> bool {
+        match self.jobs.lock().unwrap().requeue_notifiers.pop_last() {
+            Some((_, (requeue, _, _))) => {
+                requeue.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stop all running children, and children started until they are continued.
+    /// Returns the number of children stopped.
+    pub fn stop_all(&self) -> usize {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.stopped = true;
+        jobs.signal_all(ChildSignal::Stop)
+    }
+
+    /// Continue all stopped children.  Returns the number of children continued.
+    pub fn continue_all(&self) -> usize {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.stopped = false;
+        jobs.signal_all(ChildSignal::Continue)
+    }
+
+    /// Send SIGTERM to all running children.  Returns the number of children terminated.
+    pub fn terminate_all(&self) -> usize {
+        self.jobs.lock().unwrap().signal_all(ChildSignal::Terminate)
+    }
+
+    /// Send SIGKILL to all running children right away, e.g. when exiting without waiting
+    /// for them.  Returns the number of children killed.
+    pub fn force_kill_all(&self) -> usize {
+        self.jobs.lock().unwrap().signal_all(ChildSignal::Kill)
+    }
+}
+
+impl RunningJobsState {
+    fn signal_all(&self, signal: ChildSignal) -> usize {
+        self.requeue_notifiers
+            .values()
+            .filter(|(_, pid, process_group)| signal_child(*pid, *process_group, signal))
+            .count()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ChildSignal {
+    Stop,
+    Continue,
+    Terminate,
+    Kill,
+}
+
+/// Send SIGSTOP, SIGCONT, SIGTERM, or SIGKILL to a child, and to the commands it started if
+/// it leads its process group.  Returns false if the child was not signaled.
+#[cfg(unix)]
+fn signal_child(pid: Option<u32>, process_group: bool, signal: ChildSignal) -> bool {
+    use nix::{
+        sys::signal::{kill, killpg, Signal},
+        unistd::Pid,
+    };
+
+    let Some(pid) = pid.and_then(|pid| i32::try_from(pid).ok()) else {
+        return false;
+    };
+
+    let signal = match signal {
+        ChildSignal::Stop => Signal::SIGSTOP,
+        ChildSignal::Continue => Signal::SIGCONT,
+        ChildSignal::Terminate => Signal::SIGTERM,
+        ChildSignal::Kill => Signal::SIGKILL,
+    };
+
+    let result = if process_group {
+        killpg(Pid::from_raw(pid), signal)
+    } else {
+        kill(Pid::from_raw(pid), signal)
+    };
+
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::debug!("error sending {} to pid {}: {}", signal, pid, e);
+            false
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn signal_child(_pid: Option<u32>, _process_group: bool, _signal: ChildSignal) -> bool {
+    false
+}
+
+/// Registration of a child in [`RunningJobs`] until it completes.
+#[derive(Debug)]
+struct RunningJob {
+    id: u64,
+    requeue: Arc<Notify>,
+    running_jobs: Arc<RunningJobs>,
+}
+
+impl Drop for RunningJob {
+    fn drop(&mut self) {
+        self.running_jobs
+            .jobs
+            .lock()
+            .unwrap()
+            .requeue_notifiers
+            .remove(&self.id);
+    }
+}
+
+/// Per-command settings used when spawning a child process.
+#[derive(Debug, Default)]
+pub struct SpawnOptions<'a> {
+    pub job_number: usize,
+    pub slot: usize,
+    pub current_dir: Option<&'a Path>,
+    pub env: &'a [(String, String)],
+    /// Do not inherit the environment of this process.
+    pub clear_env: bool,
+    /// Overrides the command line timeout when set.
+    pub timeout: Option<Duration>,
+}
+
 #[derive(Debug)]
 pub struct ChildProcessFactory {
     discard_stdout: bool,
     discard_stderr: bool,
     timeout: Option<Duration>,
+    cpu_timeout: Option<CpuTimeout>,
+    term_timeout: Option<Duration>,
+    cgroup_manager: Option<CgroupManager>,
+    systemd_scope: Option<SystemdScope>,
+    numa_placement: Option<NumaPlacement>,
+    sandbox_exec: Option<SandboxExec>,
+    jail_exec: Option<JailExec>,
+    umask_wrapper: Option<UmaskWrapper>,
+    self_nice: Option<SelfNice>,
+    audit_log: Option<Arc<AuditLog>>,
+    arg_max: Option<ArgMax>,
+    /// Definitions from --use-recorded-env for $RUST_PARALLEL_ENV.
+    recorded_env: Option<String>,
+    /// Connect the child's stdin to the terminal for interactive commands.
+    inherit_stdin: bool,
+    /// Start each child in its own process group, so it can be killed with the commands it
+    /// started.  Interactive commands stay in the terminal's foreground process group.
+    process_group: bool,
+    /// Set to true to kill all running children.
+    kill_sender: watch::Sender<bool>,
+    running_jobs: Arc<RunningJobs>,
+    #[cfg(windows)]
+    windows_args: windows::WindowsArgs,
 }
 
 impl ChildProcessFactory {
-    pub fn new(command_line_args: &CommandLineArgs) -> Self {
-        Self {
+    pub fn new(command_line_args: &'static CommandLineArgs) -> anyhow::Result<Self> {
+        if command_line_args.raw_args && !cfg!(windows) {
+            anyhow::bail!("--raw-args is only supported on Windows");
+        }
+
+        let inherit_stdin = inherit_stdin(
+            command_line_args.jobs,
+            std::io::stdin().is_terminal(),
+            input::reads_stdin(command_line_args),
+        );
+
+        Ok(Self {
             discard_stdout: matches!(
                 command_line_args.discard_output,
                 Some(DiscardOutput::All) | Some(DiscardOutput::Stdout)
@@ -80,6 +456,36 @@ impl ChildProcessFactory {
             timeout: command_line_args
                 .timeout_seconds
                 .map(Duration::from_secs_f64),
+            cpu_timeout: CpuTimeout::new(command_line_args)?,
+            term_timeout: command_line_args.term_timeout.map(Duration::from_secs_f64),
+            cgroup_manager: CgroupManager::new(command_line_args)?,
+            systemd_scope: SystemdScope::new(command_line_args),
+            numa_placement: NumaPlacement::new(command_line_args)?,
+            sandbox_exec: SandboxExec::new(command_line_args)?,
+            jail_exec: JailExec::new(command_line_args)?,
+            umask_wrapper: UmaskWrapper::new(command_line_args)?,
+            self_nice: SelfNice::new(command_line_args)?,
+            audit_log: AuditLog::new(command_line_args)?.map(Arc::new),
+            arg_max: ArgMax::new(),
+            recorded_env: command_line_args
+                .use_recorded_env
+                .as_deref()
+                .map(recorded_env::load)
+                .transpose()?,
+            inherit_stdin,
+            process_group: cfg!(unix) && !inherit_stdin,
+            kill_sender: watch::Sender::new(false),
+            running_jobs: Arc::default(),
+            #[cfg(windows)]
+            windows_args: windows::WindowsArgs::new(command_line_args),
+        })
+    }
+
+    fn stdin(&self) -> Stdio {
+        if self.inherit_stdin {
+            Stdio::inherit()
+        } else {
+            Stdio::null()
         }
     }
 
@@ -103,24 +509,213 @@ impl ChildProcessFactory {
         self.discard_stdout && self.discard_stderr
     }
 
-    pub async fn spawn<C, AI, A>(&self, command: C, args: AI) -> std::io::Result<ChildProcess>
+    /// Kill all running children, e.g. for --halt now.
+    pub fn kill_all(&self) {
+        self.kill_sender.send_replace(true);
+    }
+
+    pub fn running_jobs(&self) -> &Arc<RunningJobs> {
+        &self.running_jobs
+    }
+
+    /// Called when all job slots are running commands.
+    pub fn slots_saturated(&self) {
+        if let Some(self_nice) = &self.self_nice {
+            self_nice.slots_saturated();
+        }
+    }
+
+    #[cfg(windows)]
+    fn add_args<AI, A>(&self, command: &mut Command, args: AI)
+    where
+        AI: IntoIterator<Item = A>,
+        A: AsRef<OsStr>,
+    {
+        self.windows_args.add_args(command, args);
+    }
+
+    #[cfg(not(windows))]
+    fn add_args<AI, A>(&self, command: &mut Command, args: AI)
+    where
+        AI: IntoIterator<Item = A>,
+        A: AsRef<OsStr>,
+    {
+        command.args(args);
+    }
+
+    /// Build the command, prefixed by any wrapper commands such as systemd-run, numactl,
+    /// sandbox-exec, jexec, or sh for --umask.
+    fn command<C, AI, A>(&self, command: C, args: AI, slot: usize) -> Command
     where
         C: AsRef<OsStr>,
         AI: IntoIterator<Item = A>,
         A: AsRef<OsStr>,
     {
-        let child = Command::new(command)
-            .args(args)
-            .stdin(Stdio::null())
+        let mut wrapper_args: Vec<String> = vec![];
+
+        if let Some(systemd_scope) = &self.systemd_scope {
+            wrapper_args.extend_from_slice(systemd_scope.wrapper_args());
+        }
+
+        if let Some(numa_placement) = &self.numa_placement {
+            wrapper_args.extend(numa_placement.wrapper_args(slot));
+        }
+
+        if let Some(sandbox_exec) = &self.sandbox_exec {
+            wrapper_args.extend_from_slice(sandbox_exec.wrapper_args());
+        }
+
+        if let Some(jail_exec) = &self.jail_exec {
+            wrapper_args.extend_from_slice(jail_exec.wrapper_args());
+        }
+
+        if let Some(umask_wrapper) = &self.umask_wrapper {
+            wrapper_args.extend_from_slice(umask_wrapper.wrapper_args());
+        }
+
+        match wrapper_args.split_first() {
+            None => {
+                let mut command = Command::new(command);
+                self.add_args(&mut command, args);
+                command
+            }
+            Some((wrapper_command, wrapper_args)) => {
+                let mut wrapper = Command::new(wrapper_command);
+                wrapper.args(wrapper_args).arg(command).args(args);
+                wrapper
+            }
+        }
+    }
+
+    pub async fn spawn<C, AI, A>(
+        &self,
+        command: C,
+        args: AI,
+        spawn_options: SpawnOptions<'_>,
+    ) -> anyhow::Result<ChildProcess>
+    where
+        C: AsRef<OsStr>,
+        AI: IntoIterator<Item = A>,
+        A: AsRef<OsStr>,
+    {
+        let job_cgroup = match &self.cgroup_manager {
+            None => None,
+            Some(cgroup_manager) => Some(cgroup_manager.create_job_cgroup()?),
+        };
+
+        let mut command = self.command(command, args, spawn_options.slot);
+
+        if let Some(current_dir) = spawn_options.current_dir {
+            command.current_dir(current_dir);
+        }
+
+        if spawn_options.clear_env {
+            command.env_clear();
+        }
+
+        command.env_remove(DETACHED_RUN_DIR_VAR);
+
+        command.envs(spawn_options.env.iter().map(|(k, v)| (k, v)));
+
+        if let Some(recorded_env) = &self.recorded_env {
+            command.env(RECORDED_ENV_VAR, recorded_env);
+        }
+
+        if let Some(arg_max) = &self.arg_max {
+            arg_max.check(command.as_std(), spawn_options.clear_env)?;
+        }
+
+        let timeout = spawn_options.timeout.or(self.timeout);
+
+        command
+            .stdin(self.stdin())
             .stdout(self.stdout())
             .stderr(self.stderr())
-            .kill_on_drop(self.timeout.is_some())
-            .spawn()?;
+            .kill_on_drop(true);
+
+        #[cfg(unix)]
+        if self.process_group {
+            command.process_group(0);
+        }
+
+        let command_description = match &self.audit_log {
+            None => None,
+            Some(audit_log) => Some(audit_log.describe(command.as_std())?),
+        };
+
+        let mut child = match &self.self_nice {
+            None => command.spawn()?,
+            Some(self_nice) => self_nice.spawn(command).await?,
+        };
+
+        if let (Some(job_cgroup), Some(pid)) = (&job_cgroup, child.id()) {
+            if let Err(e) = job_cgroup.add_process(pid) {
+                let _ = child.start_kill();
+                return Err(e);
+            }
+        }
+
+        if let (Some(audit_log), Some(command_description)) = (&self.audit_log, command_description)
+        {
+            if let Err(e) =
+                audit_log.record_spawn(command_description, spawn_options.job_number, child.id())
+            {
+                let _ = child.start_kill();
+                return Err(e);
+            }
+        }
+
+        let running_job = self.running_jobs.register(child.id(), self.process_group);
 
         Ok(ChildProcess {
             child,
             discard_all_output: self.discard_all_output(),
-            timeout: self.timeout,
+            timeout,
+            cpu_timeout: self.cpu_timeout,
+            job_number: spawn_options.job_number,
+            audit_log: self.audit_log.clone(),
+            kill_receiver: self.kill_sender.subscribe(),
+            running_job,
+            job_cgroup,
+            process_group: self.process_group,
+            term_timeout: self.term_timeout,
         })
     }
 }
+
+/// With a single job slot, commands of inputs not read from a terminal stdin can use it,
+/// so interactive commands such as editors work one item at a time.
+fn inherit_stdin(jobs: usize, stdin_is_terminal: bool, reads_stdin: bool) -> bool {
+    jobs == 1 && stdin_is_terminal && !reads_stdin
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_running_jobs_requeue_youngest() {
+        let running_jobs = Arc::new(RunningJobs::default());
+
+        let first = running_jobs.register(None, false);
+        let second = running_jobs.register(None, false);
+        assert_eq!(running_jobs.len(), 2);
+
+        assert!(running_jobs.requeue_youngest());
+        second.requeue.notified().await;
+        assert_eq!(running_jobs.len(), 1);
+
+        drop(second);
+        drop(first);
+        assert_eq!(running_jobs.len(), 0);
+        assert!(!running_jobs.requeue_youngest());
+    }
+
+    #[test]
+    fn test_inherit_stdin() {
+        assert!(inherit_stdin(1, true, false));
+        assert!(!inherit_stdin(2, true, false));
+        assert!(!inherit_stdin(1, false, false));
+        assert!(!inherit_stdin(1, true, true));
+    }
+}
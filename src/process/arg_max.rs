@@ -0,0 +1,113 @@
+use std::{ffi::OsStr, process::Command};
+
+/// Fails commands whose argv and environment exceed ARG_MAX before exec does with E2BIG.
+#[derive(Debug)]
+pub struct ArgMax {
+    arg_max: usize,
+}
+
+impl ArgMax {
+    #[cfg(unix)]
+    pub fn new() -> Option<Self> {
+        use nix::unistd::{sysconf, SysconfVar};
+
+        let arg_max = sysconf(SysconfVar::ARG_MAX).ok()??;
+
+        Some(Self {
+            arg_max: usize::try_from(arg_max).ok()?,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn new() -> Option<Self> {
+        None
+    }
+
+    pub fn check(&self, command: &Command, clear_env: bool) -> anyhow::Result<()> {
+        let size = command_line_size(command, clear_env);
+
+        if size > self.arg_max {
+            anyhow::bail!(
+                "command line too long ({} bytes > ARG_MAX {})",
+                size,
+                self.arg_max
+            );
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Some(arg) = command
+            .get_args()
+            .find(|arg| arg.len() + 1 > MAX_ARG_STRLEN)
+        {
+            anyhow::bail!(
+                "command argument too long ({} bytes > MAX_ARG_STRLEN {})",
+                arg.len() + 1,
+                MAX_ARG_STRLEN
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Linux limit for a single argument or environment string, 32 pages.
+#[cfg(target_os = "linux")]
+const MAX_ARG_STRLEN: usize = 32 * 4096;
+
+/// Bytes exec copies for the command: each string with its terminating nul and a pointer to it.
+fn command_line_size(command: &Command, clear_env: bool) -> usize {
+    let string_size = |s: &OsStr| s.len() + 1 + std::mem::size_of::<usize>();
+
+    let argv_size =
+        string_size(command.get_program()) + command.get_args().map(string_size).sum::<usize>();
+
+    let mut env: Vec<(&OsStr, Option<&OsStr>)> = command.get_envs().collect();
+
+    let inherited_env = if clear_env {
+        vec![]
+    } else {
+        std::env::vars_os().collect()
+    };
+
+    for (key, value) in &inherited_env {
+        if !env.iter().any(|(k, _)| k == key) {
+            env.push((key, Some(value)));
+        }
+    }
+
+    let env_size = env
+        .into_iter()
+        .filter_map(|(key, value)| Some(string_size(key) + 1 + value?.len()))
+        .sum::<usize>();
+
+    argv_size + env_size
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let arg_max = ArgMax { arg_max: 1024 };
+
+        let mut command = Command::new("echo");
+        command.arg("a".repeat(100)).env_clear().env("K", "V");
+
+        let pointer_size = std::mem::size_of::<usize>();
+        let size = (4 + 1 + pointer_size) + (100 + 1 + pointer_size) + (3 + 1 + pointer_size);
+
+        assert_eq!(command_line_size(&command, true), size);
+        assert!(arg_max.check(&command, true).is_ok());
+
+        command.arg("b".repeat(1000));
+
+        assert_eq!(
+            arg_max.check(&command, true).unwrap_err().to_string(),
+            format!(
+                "command line too long ({} bytes > ARG_MAX 1024)",
+                size + 1000 + 1 + pointer_size
+            )
+        );
+    }
+}
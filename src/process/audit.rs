@@ -0,0 +1,275 @@
+use anyhow::Context;
+
+use serde::{Deserialize, Serialize};
+
+use sha2::{Digest, Sha256};
+
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    process::{Command, Output},
+    sync::Mutex,
+};
+
+use crate::{command_line_args::CommandLineArgs, output::SyncSchedule};
+
+/// Environment variables with names containing these words have their values redacted.
+const SENSITIVE_ENV_WORDS: [&str; 6] =
+    ["SECRET", "TOKEN", "PASSWORD", "PASSWD", "CREDENTIAL", "KEY"];
+
+pub const REDACTED: &str = "<redacted>";
+
+/// An event written to the audit log as one JSON line.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    Spawn {
+        job: usize,
+        pid: Option<u32>,
+        program: String,
+        argv: Vec<String>,
+        cwd: String,
+        env: BTreeMap<String, String>,
+    },
+    Exit {
+        job: usize,
+        pid: Option<u32>,
+        success: bool,
+        /// None if the process timed out or was killed by a signal.
+        exit_code: Option<i32>,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AuditRecord {
+    pub timestamp: String,
+    #[serde(flatten)]
+    pub event: AuditEvent,
+    /// sha256 of the previous line, chaining records so edits are detectable.
+    pub prev_hash: String,
+}
+
+#[derive(Debug)]
+pub struct CommandDescription {
+    program: String,
+    argv: Vec<String>,
+    cwd: String,
+    env: BTreeMap<String, String>,
+}
+
+#[derive(Debug)]
+struct AuditLogState {
+    file: File,
+    prev_hash: String,
+    sync_schedule: SyncSchedule,
+}
+
+/// Append-only JSONL log of every spawned process configured with --audit-log.
+#[derive(Debug)]
+pub struct AuditLog {
+    state: Mutex<AuditLogState>,
+}
+
+impl AuditLog {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        let Some(file_name) = &command_line_args.audit_log else {
+            return Ok(None);
+        };
+
+        let prev_hash = last_line_hash(file_name)
+            .with_context(|| format!("error reading audit log '{}'", file_name))?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(file_name)
+            .with_context(|| format!("error opening audit log '{}'", file_name))?;
+
+        Ok(Some(Self {
+            state: Mutex::new(AuditLogState {
+                file,
+                prev_hash,
+                sync_schedule: SyncSchedule::new(command_line_args.fsync),
+            }),
+        }))
+    }
+
+    /// Capture what will run before the command is spawned.
+    pub fn describe(&self, command: &Command) -> anyhow::Result<CommandDescription> {
+        let argv = std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+
+        let program = which::which(command.get_program())
+            .unwrap_or_else(|_| PathBuf::from(command.get_program()));
+
+        let cwd = match command.get_current_dir() {
+            Some(current_dir) => current_dir.to_path_buf(),
+            None => std::env::current_dir().context("error getting current dir")?,
+        };
+
+        Ok(CommandDescription {
+            program: program.to_string_lossy().into_owned(),
+            argv,
+            cwd: cwd.to_string_lossy().into_owned(),
+            env: command_env(command),
+        })
+    }
+
+    pub fn record_spawn(
+        &self,
+        command_description: CommandDescription,
+        job_number: usize,
+        pid: Option<u32>,
+    ) -> anyhow::Result<()> {
+        self.record(AuditEvent::Spawn {
+            job: job_number,
+            pid,
+            program: command_description.program,
+            argv: command_description.argv,
+            cwd: command_description.cwd,
+            env: command_description.env,
+        })
+    }
+
+    pub fn record_exit<E>(
+        &self,
+        job_number: usize,
+        pid: Option<u32>,
+        result: &Result<Output, E>,
+    ) -> anyhow::Result<()> {
+        let (success, exit_code) = match result {
+            Ok(output) => (output.status.success(), output.status.code()),
+            Err(_) => (false, None),
+        };
+
+        self.record(AuditEvent::Exit {
+            job: job_number,
+            pid,
+            success,
+            exit_code,
+        })
+    }
+
+    fn record(&self, event: AuditEvent) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        let record = AuditRecord {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            event,
+            prev_hash: state.prev_hash.clone(),
+        };
+
+        let mut line = serde_json::to_string(&record)?;
+        let hash = sha256_hex(&line);
+        line.push('\n');
+
+        state
+            .file
+            .write_all(line.as_bytes())
+            .context("error writing audit log")?;
+        state.prev_hash = hash;
+
+        if state.sync_schedule.sync_due() {
+            state.file.sync_data().context("error syncing audit log")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for AuditLog {
+    fn drop(&mut self) {
+        if let Ok(state) = self.state.lock() {
+            if state.sync_schedule.enabled() {
+                let _ = state.file.sync_data();
+            }
+        }
+    }
+}
+
+fn sha256_hex(line: &str) -> String {
+    hex::encode(Sha256::digest(line.as_bytes()))
+}
+
+/// Hash of the last line of an existing audit log, or all zeros for a new log.
+fn last_line_hash(file_name: &str) -> anyhow::Result<String> {
+    let file = match File::open(file_name) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok("0".repeat(64)),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut last_line = None;
+    for line in BufReader::new(file).lines() {
+        last_line = Some(line?);
+    }
+
+    Ok(match last_line {
+        Some(line) => sha256_hex(&line),
+        None => "0".repeat(64),
+    })
+}
+
+/// Environment the command runs with, with sensitive values redacted.
+fn command_env(command: &Command) -> BTreeMap<String, String> {
+    let mut env: BTreeMap<String, String> = std::env::vars_os()
+        .map(|(k, v)| {
+            (
+                k.to_string_lossy().into_owned(),
+                v.to_string_lossy().into_owned(),
+            )
+        })
+        .collect();
+
+    for (k, v) in command.get_envs() {
+        let k = k.to_string_lossy().into_owned();
+        match v {
+            None => {
+                env.remove(&k);
+            }
+            Some(v) => {
+                env.insert(k, v.to_string_lossy().into_owned());
+            }
+        }
+    }
+
+    for (k, v) in env.iter_mut() {
+        let upper_k = k.to_ascii_uppercase();
+        if SENSITIVE_ENV_WORDS
+            .iter()
+            .any(|word| upper_k.contains(word))
+        {
+            *v = REDACTED.to_owned();
+        }
+    }
+
+    env
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_command_env() {
+        let mut command = Command::new("echo");
+        command
+            .env("RUST_PARALLEL_TEST_VALUE", "visible")
+            .env("RUST_PARALLEL_TEST_API_TOKEN", "hidden");
+
+        let env = command_env(&command);
+
+        assert_eq!(
+            env.get("RUST_PARALLEL_TEST_VALUE").map(String::as_str),
+            Some("visible")
+        );
+        assert_eq!(
+            env.get("RUST_PARALLEL_TEST_API_TOKEN").map(String::as_str),
+            Some(REDACTED)
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use anyhow::Context;
+
+use tracing::{debug, warn};
+
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::command_line_args::CommandLineArgs;
+
+const CPU_MAX_PERIOD_MICROS: u64 = 100_000;
+
+/// How long to wait for killed processes to leave a job cgroup before removing it.
+const POPULATED_POLL_INTERVAL: Duration = Duration::from_millis(10);
+const POPULATED_POLL_ATTEMPTS: u32 = 100;
+
+#[derive(Debug)]
+struct CgroupLimits {
+    memory_max: Option<u64>,
+    cpu_max: Option<f64>,
+}
+
+impl CgroupLimits {
+    fn new(command_line_args: &CommandLineArgs) -> Option<Self> {
+        if command_line_args.cgroup_memory_max.is_none()
+            && command_line_args.cgroup_cpu_max.is_none()
+        {
+            return None;
+        }
+
+        Some(Self {
+            memory_max: command_line_args.cgroup_memory_max,
+            cpu_max: command_line_args.cgroup_cpu_max,
+        })
+    }
+
+    fn controllers(&self) -> Vec<&'static str> {
+        let mut controllers = vec![];
+        if self.memory_max.is_some() {
+            controllers.push("memory");
+        }
+        if self.cpu_max.is_some() {
+            controllers.push("cpu");
+        }
+        controllers
+    }
+}
+
+/// Transient cgroup v2 directory created for this run.
+///
+/// Each job gets its own child cgroup below the run cgroup.
+#[derive(Debug)]
+pub struct CgroupManager {
+    limits: CgroupLimits,
+    run_cgroup_path: PathBuf,
+    next_job_id: AtomicU64,
+}
+
+impl CgroupManager {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        let Some(limits) = CgroupLimits::new(command_line_args) else {
+            return Ok(None);
+        };
+
+        if !cfg!(target_os = "linux") {
+            anyhow::bail!("cgroup options are only supported on linux");
+        }
+
+        let parent_cgroup_path = match &command_line_args.cgroup_parent {
+            Some(cgroup_parent) => PathBuf::from(cgroup_parent),
+            None => current_cgroup_path()?,
+        };
+
+        let controllers = limits.controllers();
+
+        // The parent may already delegate these controllers to us.  Enabling them fails in
+        // the current cgroup if it contains processes, which is checked below.
+        let parent_result = enable_controllers(&parent_cgroup_path, &controllers);
+        if let Err(e) = &parent_result {
+            debug!(
+                "unable to enable controllers in parent cgroup {:?}: {:#}",
+                parent_cgroup_path, e
+            );
+        }
+
+        let run_cgroup_path =
+            parent_cgroup_path.join(format!("rust-parallel.{}", std::process::id()));
+
+        std::fs::create_dir(&run_cgroup_path)
+            .with_context(|| format!("error creating run cgroup {:?}", run_cgroup_path))?;
+
+        let missing_controllers = missing_controllers(&run_cgroup_path, &controllers);
+        if !missing_controllers.is_empty() {
+            let _ = std::fs::remove_dir(&run_cgroup_path);
+            let mut message = format!(
+                "controllers {:?} are not available below cgroup {:?}, \
+                 --cgroup-parent must name a cgroup delegating them",
+                missing_controllers, parent_cgroup_path
+            );
+            if let Err(e) = parent_result {
+                message.push_str(&format!(": {:#}", e));
+            }
+            anyhow::bail!(message);
+        }
+
+        if let Err(e) = enable_controllers(&run_cgroup_path, &controllers) {
+            let _ = std::fs::remove_dir(&run_cgroup_path);
+            return Err(e).with_context(|| {
+                format!(
+                    "error enabling controllers {:?} in run cgroup {:?}",
+                    controllers, run_cgroup_path
+                )
+            });
+        }
+
+        debug!("created run cgroup {:?}", run_cgroup_path);
+
+        Ok(Some(Self {
+            limits,
+            run_cgroup_path,
+            next_job_id: AtomicU64::new(1),
+        }))
+    }
+
+    pub fn create_job_cgroup(&self) -> anyhow::Result<JobCgroup> {
+        let job_id = self.next_job_id.fetch_add(1, Ordering::SeqCst);
+
+        let path = self.run_cgroup_path.join(format!("job-{}", job_id));
+
+        std::fs::create_dir(&path)
+            .with_context(|| format!("error creating job cgroup {:?}", path))?;
+
+        if let Some(memory_max) = self.limits.memory_max {
+            write_cgroup_file(&path, "memory.max", &memory_max.to_string())?;
+        }
+
+        if let Some(cpu_max) = self.limits.cpu_max {
+            let quota_micros = (cpu_max * CPU_MAX_PERIOD_MICROS as f64).round() as u64;
+            write_cgroup_file(
+                &path,
+                "cpu.max",
+                &format!("{} {}", quota_micros.max(1000), CPU_MAX_PERIOD_MICROS),
+            )?;
+        }
+
+        Ok(JobCgroup {
+            path,
+            removed: false,
+        })
+    }
+}
+
+impl Drop for CgroupManager {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir(&self.run_cgroup_path) {
+            warn!(
+                "error removing run cgroup {:?}: {}",
+                self.run_cgroup_path, e
+            );
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct JobCgroup {
+    path: PathBuf,
+    removed: bool,
+}
+
+impl JobCgroup {
+    /// Move a spawned child into the cgroup.
+    ///
+    /// The child is moved after it started, so processes it forks before then stay in the
+    /// cgroup of rust-parallel and escape the limits.  Commands usually exec before forking,
+    /// which leaves only a short window.
+    pub fn add_process(&self, pid: u32) -> anyhow::Result<()> {
+        write_cgroup_file(&self.path, "cgroup.procs", &pid.to_string())
+    }
+
+    pub fn cpu_stat_path(&self) -> PathBuf {
+        self.path.join("cpu.stat")
+    }
+
+    /// Whether processes are still in the cgroup, from the populated field of cgroup.events.
+    fn populated(&self) -> bool {
+        std::fs::read_to_string(self.path.join("cgroup.events"))
+            .is_ok_and(|events| events.lines().any(|line| line.trim() == "populated 1"))
+    }
+
+    fn log_accounting(&self) {
+        let memory_peak = std::fs::read_to_string(self.path.join("memory.peak"));
+
+        let cpu_usage_usec = std::fs::read_to_string(self.cpu_stat_path()).map(|cpu_stat| {
+            cpu_stat
+                .lines()
+                .find_map(|line| line.strip_prefix("usage_usec "))
+                .map(|value| value.trim().to_owned())
+        });
+
+        debug!(
+            "job cgroup {:?} memory_peak = {:?} cpu_usage_usec = {:?}",
+            self.path,
+            memory_peak.map(|s| s.trim().to_owned()).ok(),
+            cpu_usage_usec.ok().flatten(),
+        );
+    }
+}
+
+impl JobCgroup {
+    /// Kill anything left behind in the cgroup and remove it once empty, rmdir fails until
+    /// the killed processes have exited.
+    pub async fn remove(mut self) {
+        self.removed = true;
+
+        self.log_accounting();
+
+        let _ = write_cgroup_file(&self.path, "cgroup.kill", "1");
+
+        for _ in 0..POPULATED_POLL_ATTEMPTS {
+            if !self.populated() {
+                break;
+            }
+            tokio::time::sleep(POPULATED_POLL_INTERVAL).await;
+        }
+
+        if let Err(e) = std::fs::remove_dir(&self.path) {
+            warn!("error removing job cgroup {:?}: {}", self.path, e);
+        }
+    }
+}
+
+impl Drop for JobCgroup {
+    fn drop(&mut self) {
+        if self.removed {
+            return;
+        }
+
+        // Fallback for cgroups of commands that were not awaited, without waiting for killed
+        // processes to exit.
+
+        let _ = write_cgroup_file(&self.path, "cgroup.kill", "1");
+
+        if let Err(e) = std::fs::remove_dir(&self.path) {
+            debug!("error removing job cgroup {:?}: {}", self.path, e);
+        }
+    }
+}
+
+fn write_cgroup_file(cgroup_path: &Path, file_name: &str, value: &str) -> anyhow::Result<()> {
+    let path = cgroup_path.join(file_name);
+
+    std::fs::write(&path, value).with_context(|| format!("error writing '{}' to {:?}", value, path))
+}
+
+/// Controllers missing from cgroup.controllers of a cgroup, which can then not be enabled
+/// for its children.
+fn missing_controllers<'a>(cgroup_path: &Path, controllers: &[&'a str]) -> Vec<&'a str> {
+    let available =
+        std::fs::read_to_string(cgroup_path.join("cgroup.controllers")).unwrap_or_default();
+
+    controllers
+        .iter()
+        .copied()
+        .filter(|controller| !available.split_whitespace().any(|c| c == *controller))
+        .collect()
+}
+
+fn enable_controllers(cgroup_path: &Path, controllers: &[&str]) -> anyhow::Result<()> {
+    let value = controllers
+        .iter()
+        .map(|c| format!("+{}", c))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    write_cgroup_file(cgroup_path, "cgroup.subtree_control", &value)
+}
+
+fn cgroup2_mount_point() -> anyhow::Result<PathBuf> {
+    let mountinfo =
+        std::fs::read_to_string("/proc/self/mountinfo").context("error reading mountinfo")?;
+
+    // mountinfo fields: id parent major:minor root mount_point options ... - fstype source
+    mountinfo
+        .lines()
+        .find_map(|line| {
+            let (fields, fs_fields) = line.split_once(" - ")?;
+            if fs_fields.split_whitespace().next()? != "cgroup2" {
+                return None;
+            }
+            fields.split_whitespace().nth(4).map(PathBuf::from)
+        })
+        .context("cgroup v2 filesystem is not mounted")
+}
+
+fn current_cgroup_path() -> anyhow::Result<PathBuf> {
+    let proc_self_cgroup =
+        std::fs::read_to_string("/proc/self/cgroup").context("error reading /proc/self/cgroup")?;
+
+    let relative_path = proc_self_cgroup
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .context("cgroup v2 hierarchy not found in /proc/self/cgroup")?;
+
+    Ok(cgroup2_mount_point()?.join(relative_path.trim_start_matches('/')))
+}
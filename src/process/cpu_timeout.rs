@@ -0,0 +1,131 @@
+use tokio::time::Duration;
+
+use std::path::{Path, PathBuf};
+
+use crate::command_line_args::CommandLineArgs;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Kills jobs that use more than --cpu-timeout seconds of CPU time.
+///
+/// CPU time is read from the job cgroup with cgroup options, which includes all processes of
+/// the job.  Otherwise it is read from /proc and includes the job process and its waited for
+/// children.
+#[derive(Clone, Copy, Debug)]
+pub struct CpuTimeout {
+    limit: Duration,
+    clock_ticks_per_second: u64,
+}
+
+impl CpuTimeout {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        let Some(cpu_timeout_seconds) = command_line_args.cpu_timeout else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            limit: Duration::from_secs_f64(cpu_timeout_seconds),
+            clock_ticks_per_second: clock_ticks_per_second()?,
+        }))
+    }
+
+    pub fn limit(&self) -> Duration {
+        self.limit
+    }
+
+    /// Completes when the job has used more CPU time than the limit.
+    pub async fn exceeded(&self, pid: Option<u32>, cgroup_cpu_stat: Option<PathBuf>) {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+
+            let cpu_time = match (&cgroup_cpu_stat, pid) {
+                (Some(cpu_stat), _) => cgroup_cpu_time(cpu_stat),
+                (None, Some(pid)) => process_cpu_time(pid, self.clock_ticks_per_second),
+                (None, None) => None,
+            };
+
+            if cpu_time.is_some_and(|cpu_time| cpu_time > self.limit) {
+                tracing::debug!(
+                    "pid {:?} cpu time {:?} exceeded cpu timeout {:?}",
+                    pid,
+                    cpu_time,
+                    self.limit
+                );
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn clock_ticks_per_second() -> anyhow::Result<u64> {
+    use nix::unistd::{sysconf, SysconfVar};
+
+    match sysconf(SysconfVar::CLK_TCK)? {
+        Some(ticks) if ticks > 0 => Ok(ticks as u64),
+        _ => anyhow::bail!("unable to read clock ticks per second for --cpu-timeout"),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn clock_ticks_per_second() -> anyhow::Result<u64> {
+    anyhow::bail!("--cpu-timeout is only supported on linux")
+}
+
+/// CPU time of all processes in a cgroup from usage_usec in cpu.stat.
+fn cgroup_cpu_time(cpu_stat: &Path) -> Option<Duration> {
+    std::fs::read_to_string(cpu_stat)
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("usage_usec "))?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_micros)
+}
+
+fn process_cpu_time(pid: u32, clock_ticks_per_second: u64) -> Option<Duration> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    parse_proc_stat_cpu_time(&stat, clock_ticks_per_second)
+}
+
+/// Sum of utime, stime, cutime, and cstime, fields 14 to 17 of /proc/PID/stat.
+///
+/// The command name in field 2 may contain spaces, so fields are counted after its ')'.
+fn parse_proc_stat_cpu_time(stat: &str, clock_ticks_per_second: u64) -> Option<Duration> {
+    let (_, fields) = stat.rsplit_once(')')?;
+
+    let ticks = fields
+        .split_whitespace()
+        .skip(11)
+        .take(4)
+        .map(|field| field.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    if ticks.len() != 4 {
+        return None;
+    }
+
+    let ticks: u64 = ticks.iter().sum();
+
+    Some(Duration::from_secs_f64(
+        ticks as f64 / clock_ticks_per_second as f64,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_stat_cpu_time() {
+        let stat = "1234 (my (cmd)) R 1 1234 1234 0 -1 4194560 100 0 0 0 250 50 10 40 20 0 1 0 100 1000 10";
+        assert_eq!(
+            parse_proc_stat_cpu_time(stat, 100),
+            Some(Duration::from_secs_f64(3.5))
+        );
+
+        assert_eq!(parse_proc_stat_cpu_time("1234 (cmd) R 1", 100), None);
+        assert_eq!(parse_proc_stat_cpu_time("garbage", 100), None);
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::Context;
+
+use crate::command_line_args::CommandLineArgs;
+
+const JEXEC: &str = "jexec";
+
+/// Runs each command inside an existing FreeBSD jail using `jexec`.
+#[derive(Debug)]
+pub struct JailExec {
+    jexec_args: Vec<String>,
+}
+
+impl JailExec {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        let Some(jail) = &command_line_args.jail else {
+            return Ok(None);
+        };
+
+        if !cfg!(target_os = "freebsd") {
+            anyhow::bail!("--jail is only supported on freebsd");
+        }
+
+        which::which(JEXEC).context("--jail requires jexec in PATH")?;
+
+        Ok(Some(Self {
+            jexec_args: Self::build_args(jail, command_line_args.jail_user.as_deref()),
+        }))
+    }
+
+    fn build_args(jail: &str, jail_user: Option<&str>) -> Vec<String> {
+        let mut jexec_args = vec![JEXEC.to_owned()];
+
+        if let Some(jail_user) = jail_user {
+            jexec_args.push("-U".to_owned());
+            jexec_args.push(jail_user.to_owned());
+        }
+
+        jexec_args.push(jail.to_owned());
+
+        jexec_args
+    }
+
+    pub fn wrapper_args(&self) -> &[String] {
+        &self.jexec_args
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_args() {
+        assert_eq!(
+            JailExec::build_args("worker", None),
+            vec!["jexec", "worker"]
+        );
+        assert_eq!(
+            JailExec::build_args("worker", Some("nobody")),
+            vec!["jexec", "-U", "nobody", "worker"]
+        );
+    }
+}
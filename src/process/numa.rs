@@ -0,0 +1,114 @@
+use anyhow::Context;
+
+use tracing::debug;
+
+use crate::command_line_args::{CommandLineArgs, NumaPolicy};
+
+const NUMACTL: &str = "numactl";
+
+const NODES_WITH_MEMORY_PATH: &str = "/sys/devices/system/node/has_memory";
+
+const NODES_ONLINE_PATH: &str = "/sys/devices/system/node/online";
+
+/// Places commands on NUMA nodes by running them under `numactl`.
+#[derive(Debug)]
+pub struct NumaPlacement {
+    policy: NumaPolicy,
+    nodes: Vec<u32>,
+}
+
+impl NumaPlacement {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        let Some(policy) = command_line_args.numa_policy else {
+            return Ok(None);
+        };
+
+        if !cfg!(target_os = "linux") {
+            anyhow::bail!("--numa-policy is only supported on linux");
+        }
+
+        which::which(NUMACTL).context("--numa-policy requires numactl in PATH")?;
+
+        let node_list = std::fs::read_to_string(NODES_WITH_MEMORY_PATH)
+            .or_else(|_| std::fs::read_to_string(NODES_ONLINE_PATH))
+            .context("error reading numa node list")?;
+
+        let nodes = parse_node_list(&node_list)?;
+
+        debug!("numa policy = {:?} nodes = {:?}", policy, nodes);
+
+        Ok(Some(Self { policy, nodes }))
+    }
+
+    pub fn wrapper_args(&self, slot: usize) -> Vec<String> {
+        match self.policy {
+            NumaPolicy::Interleave => vec![NUMACTL.to_owned(), "--interleave=all".to_owned()],
+            NumaPolicy::BindPerSlot => {
+                let node = self.nodes[(slot - 1) % self.nodes.len()];
+                vec![
+                    NUMACTL.to_owned(),
+                    format!("--cpunodebind={}", node),
+                    format!("--membind={}", node),
+                ]
+            }
+        }
+    }
+}
+
+/// Parse a kernel node list such as "0-1,3".
+fn parse_node_list(node_list: &str) -> anyhow::Result<Vec<u32>> {
+    let mut nodes = vec![];
+
+    for range in node_list.trim().split(',').filter(|s| !s.is_empty()) {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+
+        let start: u32 = start
+            .parse()
+            .with_context(|| format!("invalid numa node list '{}'", node_list.trim()))?;
+        let end: u32 = end
+            .parse()
+            .with_context(|| format!("invalid numa node list '{}'", node_list.trim()))?;
+
+        nodes.extend(start..=end);
+    }
+
+    if nodes.is_empty() {
+        anyhow::bail!("no numa nodes found");
+    }
+
+    Ok(nodes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_node_list() {
+        assert_eq!(parse_node_list("0\n").unwrap(), vec![0]);
+        assert_eq!(parse_node_list("0-1,3").unwrap(), vec![0, 1, 3]);
+        assert!(parse_node_list("").is_err());
+        assert!(parse_node_list("a-b").is_err());
+    }
+
+    #[test]
+    fn test_bind_per_slot() {
+        let numa_placement = NumaPlacement {
+            policy: NumaPolicy::BindPerSlot,
+            nodes: vec![0, 1],
+        };
+
+        assert_eq!(
+            numa_placement.wrapper_args(1),
+            vec!["numactl", "--cpunodebind=0", "--membind=0"]
+        );
+        assert_eq!(
+            numa_placement.wrapper_args(2),
+            vec!["numactl", "--cpunodebind=1", "--membind=1"]
+        );
+        assert_eq!(
+            numa_placement.wrapper_args(3),
+            vec!["numactl", "--cpunodebind=0", "--membind=0"]
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use anyhow::Context;
+
+use std::path::Path;
+
+use crate::command_line_args::CommandLineArgs;
+
+const SANDBOX_EXEC: &str = "sandbox-exec";
+
+/// Runs each command under macOS `sandbox-exec` with a sandbox profile.
+#[derive(Debug)]
+pub struct SandboxExec {
+    sandbox_exec_args: Vec<String>,
+}
+
+impl SandboxExec {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        let Some(profile) = &command_line_args.sandbox_profile else {
+            return Ok(None);
+        };
+
+        if !cfg!(target_os = "macos") {
+            anyhow::bail!("--sandbox-profile is only supported on macos");
+        }
+
+        if !Path::new(profile).is_file() {
+            anyhow::bail!("sandbox profile '{}' not found", profile);
+        }
+
+        which::which(SANDBOX_EXEC).context("--sandbox-profile requires sandbox-exec in PATH")?;
+
+        Ok(Some(Self {
+            sandbox_exec_args: Self::build_args(profile, &command_line_args.sandbox_param),
+        }))
+    }
+
+    fn build_args(profile: &str, params: &[String]) -> Vec<String> {
+        let mut sandbox_exec_args =
+            vec![SANDBOX_EXEC.to_owned(), "-f".to_owned(), profile.to_owned()];
+
+        for param in params {
+            sandbox_exec_args.push("-D".to_owned());
+            sandbox_exec_args.push(param.clone());
+        }
+
+        sandbox_exec_args
+    }
+
+    pub fn wrapper_args(&self) -> &[String] {
+        &self.sandbox_exec_args
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_args() {
+        assert_eq!(
+            SandboxExec::build_args("untrusted.sb", &["INPUT=/data".to_owned()]),
+            vec!["sandbox-exec", "-f", "untrusted.sb", "-D", "INPUT=/data"]
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use tokio::process::{Child, Command};
+
+use crate::command_line_args::CommandLineArgs;
+
+/// Lowers the priority of rust-parallel's own threads with --self-nice.
+///
+/// On Linux niceness is per thread and inherited by child processes, so commands
+/// are spawned from a dedicated thread that keeps its original priority.
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub struct SelfNice {
+    nice: i32,
+    spawner_tid: i32,
+    spawn_request_sender: std::sync::mpsc::Sender<SpawnRequest>,
+    applied: std::sync::atomic::AtomicBool,
+}
+
+#[cfg(target_os = "linux")]
+type SpawnRequest = (
+    Command,
+    tokio::sync::oneshot::Sender<std::io::Result<Child>>,
+);
+
+#[cfg(target_os = "linux")]
+impl SelfNice {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        use anyhow::Context;
+
+        let Some(nice) = command_line_args.self_nice else {
+            return Ok(None);
+        };
+
+        let runtime_handle = tokio::runtime::Handle::current();
+
+        let (spawn_request_sender, spawn_request_receiver) =
+            std::sync::mpsc::channel::<SpawnRequest>();
+        let (tid_sender, tid_receiver) = std::sync::mpsc::channel();
+
+        std::thread::Builder::new()
+            .name("spawner".to_owned())
+            .spawn(move || {
+                let _runtime_guard = runtime_handle.enter();
+
+                let _ = tid_sender.send(rustix::thread::gettid().as_raw_pid());
+
+                for (mut command, reply_sender) in spawn_request_receiver {
+                    let _ = reply_sender.send(command.spawn());
+                }
+            })
+            .context("error spawning spawner thread")?;
+
+        let spawner_tid = tid_receiver
+            .recv()
+            .context("error receiving spawner thread id")?;
+
+        let self_nice = Self {
+            nice,
+            spawner_tid,
+            spawn_request_sender,
+            applied: Default::default(),
+        };
+
+        if !command_line_args.self_nice_when_saturated {
+            self_nice.apply();
+        }
+
+        Ok(Some(self_nice))
+    }
+
+    /// Called when all job slots are running commands.
+    pub fn slots_saturated(&self) {
+        self.apply();
+    }
+
+    fn apply(&self) {
+        use std::sync::atomic::Ordering;
+
+        if self.applied.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let thread_ids = match std::fs::read_dir("/proc/self/task") {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<i32>().ok())
+                .filter(|&tid| tid != self.spawner_tid)
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                tracing::warn!("error reading /proc/self/task: {}", e);
+                return;
+            }
+        };
+
+        for tid in thread_ids {
+            if let Err(e) =
+                rustix::process::setpriority_process(rustix::process::Pid::from_raw(tid), self.nice)
+            {
+                tracing::warn!("error setting nice {} for thread {}: {}", self.nice, tid, e);
+            }
+        }
+
+        tracing::info!("lowered rust-parallel priority to nice {}", self.nice);
+    }
+
+    pub async fn spawn(&self, command: Command) -> std::io::Result<Child> {
+        let (reply_sender, reply_receiver) = tokio::sync::oneshot::channel();
+
+        let closed_error = || std::io::Error::other("spawner thread exited");
+
+        self.spawn_request_sender
+            .send((command, reply_sender))
+            .map_err(|_| closed_error())?;
+
+        reply_receiver.await.map_err(|_| closed_error())?
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+#[derive(Debug)]
+pub struct SelfNice;
+
+#[cfg(not(target_os = "linux"))]
+impl SelfNice {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        if command_line_args.self_nice.is_some() {
+            anyhow::bail!("--self-nice is only supported on Linux");
+        }
+        Ok(None)
+    }
+
+    pub fn slots_saturated(&self) {}
+
+    pub async fn spawn(&self, mut command: Command) -> std::io::Result<Child> {
+        command.spawn()
+    }
+}
@@ -0,0 +1,86 @@
+use crate::command_line_args::CommandLineArgs;
+
+const SYSTEMD_RUN: &str = "systemd-run";
+
+/// Wraps each command in `systemd-run --scope` so systemd owns the
+/// process tree and applies resource limits.
+#[derive(Debug)]
+pub struct SystemdScope {
+    systemd_run_args: Vec<String>,
+}
+
+impl SystemdScope {
+    pub fn new(command_line_args: &CommandLineArgs) -> Option<Self> {
+        if !command_line_args.systemd_scope {
+            return None;
+        }
+
+        let mut systemd_run_args = vec![
+            SYSTEMD_RUN.to_owned(),
+            "--scope".to_owned(),
+            "--quiet".to_owned(),
+            "--collect".to_owned(),
+        ];
+
+        if command_line_args.systemd_user {
+            systemd_run_args.push("--user".to_owned());
+        }
+
+        if let Some(slice) = &command_line_args.systemd_slice {
+            systemd_run_args.push(format!("--slice={}", slice));
+        }
+
+        for property in &command_line_args.systemd_property {
+            systemd_run_args.push(format!("--property={}", property));
+        }
+
+        systemd_run_args.push("--".to_owned());
+
+        Some(Self { systemd_run_args })
+    }
+
+    pub fn wrapper_args(&self) -> &[String] {
+        &self.systemd_run_args
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_systemd_scope_disabled() {
+        let command_line_args = CommandLineArgs {
+            systemd_scope: false,
+            ..Default::default()
+        };
+
+        assert!(SystemdScope::new(&command_line_args).is_none());
+    }
+
+    #[test]
+    fn test_systemd_scope_args() {
+        let command_line_args = CommandLineArgs {
+            systemd_scope: true,
+            systemd_slice: Some("batch.slice".to_owned()),
+            systemd_property: vec!["MemoryMax=1G".to_owned(), "CPUQuota=50%".to_owned()],
+            ..Default::default()
+        };
+
+        let systemd_scope = SystemdScope::new(&command_line_args).unwrap();
+
+        assert_eq!(
+            systemd_scope.wrapper_args(),
+            vec![
+                "systemd-run",
+                "--scope",
+                "--quiet",
+                "--collect",
+                "--slice=batch.slice",
+                "--property=MemoryMax=1G",
+                "--property=CPUQuota=50%",
+                "--",
+            ]
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use crate::command_line_args::CommandLineArgs;
+
+const SH: &str = "/bin/sh";
+
+/// Sets --umask for each command with a `sh -c 'umask MASK && exec "$0" "$@"'` wrapper, so
+/// the umask of rust-parallel and the files it creates stay unchanged.
+#[derive(Debug)]
+pub struct UmaskWrapper {
+    wrapper_args: Vec<String>,
+}
+
+impl UmaskWrapper {
+    pub fn new(command_line_args: &CommandLineArgs) -> anyhow::Result<Option<Self>> {
+        let Some(umask) = command_line_args.umask else {
+            return Ok(None);
+        };
+
+        if !cfg!(unix) {
+            anyhow::bail!("--umask is only supported on unix");
+        }
+
+        Ok(Some(Self {
+            wrapper_args: Self::build_args(umask),
+        }))
+    }
+
+    fn build_args(umask: u32) -> Vec<String> {
+        vec![
+            SH.to_owned(),
+            "-c".to_owned(),
+            format!("umask {:03o} && exec \"$0\" \"$@\"", umask),
+        ]
+    }
+
+    pub fn wrapper_args(&self) -> &[String] {
+        &self.wrapper_args
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_args() {
+        assert_eq!(
+            UmaskWrapper::build_args(0o22),
+            vec!["/bin/sh", "-c", "umask 022 && exec \"$0\" \"$@\""]
+        );
+    }
+}
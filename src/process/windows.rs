@@ -0,0 +1,53 @@
+use tokio::process::Command;
+
+use std::ffi::OsStr;
+
+use crate::command_line_args::CommandLineArgs;
+
+/// Controls how arguments are added to the Windows command line.
+///
+/// Arguments are normally quoted following the CommandLineToArgvW rules, which
+/// keeps arguments containing spaces, quotes, and backslashes intact.  cmd.exe
+/// does not follow those rules, so in shell mode the command line is passed to
+/// it unquoted, as are all arguments with --raw-args.
+#[derive(Debug)]
+pub struct WindowsArgs {
+    raw_args: bool,
+    shell: bool,
+}
+
+impl WindowsArgs {
+    pub fn new(command_line_args: &CommandLineArgs) -> Self {
+        Self {
+            raw_args: command_line_args.raw_args,
+            shell: command_line_args.shell,
+        }
+    }
+
+    pub fn add_args<AI, A>(&self, command: &mut Command, args: AI)
+    where
+        AI: IntoIterator<Item = A>,
+        A: AsRef<OsStr>,
+    {
+        if self.raw_args {
+            for arg in args {
+                command.raw_arg(arg);
+            }
+            return;
+        }
+
+        if !self.shell {
+            command.args(args);
+            return;
+        }
+
+        let mut args = args.into_iter().peekable();
+        while let Some(arg) = args.next() {
+            if args.peek().is_none() {
+                command.raw_arg(arg);
+            } else {
+                command.arg(arg);
+            }
+        }
+    }
+}
@@ -4,6 +4,8 @@ use indicatif::ProgressBar;
 
 use tokio::time::Duration;
 
+use tracing::debug;
+
 use std::sync::Arc;
 
 use crate::command_line_args::CommandLineArgs;
@@ -18,6 +20,7 @@ impl Progress {
             None
         } else {
             let style_info = style::choose_progress_style()?;
+            debug!("using progress style {}", style_info.style_name);
 
             let progress_bar = ProgressBar::new(0);
             if style_info.enable_steady_tick {
@@ -84,41 +84,41 @@ mod test {
 
         env::remove_var(PROGRESS_STYLE);
         let result = choose_progress_style();
-        assert_eq!(result.is_err(), false);
+        assert!(result.is_ok());
         let result = result.unwrap();
         assert_eq!(result.style_name, LIGHT_BG_PROGRESS_STYLE);
-        assert_eq!(result.enable_steady_tick, true);
+        assert!(result.enable_steady_tick);
 
         env::set_var(PROGRESS_STYLE, DEFAULT_PROGRESS_STYLE);
         let result = choose_progress_style();
-        assert_eq!(result.is_err(), false);
+        assert!(result.is_ok());
         let result = result.unwrap();
         assert_eq!(result.style_name, LIGHT_BG_PROGRESS_STYLE);
-        assert_eq!(result.enable_steady_tick, true);
+        assert!(result.enable_steady_tick);
 
         env::set_var(PROGRESS_STYLE, LIGHT_BG_PROGRESS_STYLE);
         let result = choose_progress_style();
-        assert_eq!(result.is_err(), false);
+        assert!(result.is_ok());
         let result = result.unwrap();
         assert_eq!(result.style_name, LIGHT_BG_PROGRESS_STYLE);
-        assert_eq!(result.enable_steady_tick, true);
+        assert!(result.enable_steady_tick);
 
         env::set_var(PROGRESS_STYLE, DARK_BG_PROGRESS_STYLE);
         let result = choose_progress_style();
-        assert_eq!(result.is_err(), false);
+        assert!(result.is_ok());
         let result = result.unwrap();
         assert_eq!(result.style_name, DARK_BG_PROGRESS_STYLE);
-        assert_eq!(result.enable_steady_tick, true);
+        assert!(result.enable_steady_tick);
 
         env::set_var(PROGRESS_STYLE, SIMPLE_PROGRESS_STYLE);
         let result = choose_progress_style();
-        assert_eq!(result.is_err(), false);
+        assert!(result.is_ok());
         let result = result.unwrap();
         assert_eq!(result.style_name, SIMPLE_PROGRESS_STYLE);
-        assert_eq!(result.enable_steady_tick, false);
+        assert!(!result.enable_steady_tick);
 
         env::set_var(PROGRESS_STYLE, "unknown");
         let result = choose_progress_style();
-        assert_eq!(result.is_err(), true);
+        assert!(result.is_err());
     }
 }
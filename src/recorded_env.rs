@@ -0,0 +1,147 @@
+use anyhow::Context;
+
+use std::{collections::HashSet, io::BufRead, path::PathBuf};
+
+use crate::common::UserDir;
+
+/// Environment variable holding the recorded definitions for -s commands.
+pub const RECORDED_ENV_VAR: &str = "RUST_PARALLEL_ENV";
+
+/// Shell command prefixed to -s commands to define the recorded functions, aliases,
+/// and variables.
+pub const EVAL_RECORDED_ENV: &str = "eval \"$RUST_PARALLEL_ENV\"\n";
+
+/// Names defined in a clean shell, recorded with --record-env and not exported.
+fn ignored_names_file() -> anyhow::Result<PathBuf> {
+    Ok(UserDir::Config.path()?.join("ignored_env_names"))
+}
+
+/// Save the names read from names_reader, one per line, as the names to ignore.
+pub fn record(names_reader: impl BufRead) -> anyhow::Result<(PathBuf, usize)> {
+    let mut names = names_reader
+        .lines()
+        .collect::<Result<Vec<_>, _>>()
+        .context("error reading names")?;
+
+    names.retain(|name| !name.trim().is_empty());
+    names.sort();
+    names.dedup();
+
+    let path = ignored_names_file()?;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("error creating config directory '{}'", dir.display()))?;
+    }
+
+    let mut contents = names.join("\n");
+    contents.push('\n');
+
+    std::fs::write(&path, contents)
+        .with_context(|| format!("error writing '{}'", path.display()))?;
+
+    Ok((path, names.len()))
+}
+
+fn load_ignored_names() -> anyhow::Result<HashSet<String>> {
+    let path = ignored_names_file()?;
+
+    match std::fs::read_to_string(&path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashSet::new()),
+        result => Ok(result
+            .with_context(|| format!("error reading '{}'", path.display()))?
+            .lines()
+            .map(str::to_owned)
+            .collect()),
+    }
+}
+
+/// Load the definitions written by env_rust_parallel as a shell script, without
+/// names recorded with --record-env.
+pub fn load(file_name: &str) -> anyhow::Result<String> {
+    let contents = std::fs::read(file_name)
+        .with_context(|| format!("error reading recorded env '{}'", file_name))?;
+
+    let contents = String::from_utf8(contents)
+        .with_context(|| format!("recorded env '{}' is not valid utf-8", file_name))?;
+
+    definitions(&contents, &load_ignored_names()?)
+        .with_context(|| format!("invalid recorded env '{}'", file_name))
+}
+
+/// Records are kind, name, and definition, each terminated by a nul byte.
+fn definitions(contents: &str, ignored_names: &HashSet<String>) -> anyhow::Result<String> {
+    let fields: Vec<&str> = contents.split_terminator('\0').collect();
+
+    if !fields.len().is_multiple_of(3) {
+        anyhow::bail!("expected kind, name, and definition for each record");
+    }
+
+    let mut script = String::new();
+    let mut has_aliases = false;
+
+    for record in fields.chunks(3) {
+        let [kind, name, definition] = record else {
+            unreachable!()
+        };
+
+        if ignored_names.contains(*name) {
+            continue;
+        }
+
+        match *kind {
+            "function" => {}
+            "alias" => has_aliases = true,
+            // readonly variables such as BASHOPTS can not be assigned again
+            "variable" if is_readonly_declaration(definition) => continue,
+            "variable" => {}
+            _ => anyhow::bail!("unknown kind '{}' for '{}'", kind, name),
+        }
+
+        script.push_str(definition);
+        script.push('\n');
+    }
+
+    if has_aliases {
+        script.insert_str(0, "shopt -s expand_aliases\n");
+    }
+
+    Ok(script)
+}
+
+/// True for `declare -r name=...` or other flags including r.
+fn is_readonly_declaration(definition: &str) -> bool {
+    definition
+        .strip_prefix("declare -")
+        .and_then(|rest| rest.split_whitespace().next())
+        .is_some_and(|flags| flags.contains('r'))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_definitions() {
+        let contents = concat!(
+            "function\0greet\0greet () \n{ \n    echo hi $1\n}\0",
+            "alias\0ll\0alias ll='ls -l'\0",
+            "variable\0NAME\0declare -x NAME=\"value\"\0",
+            "variable\0BASHOPTS\0declare -r BASHOPTS=\"checkwinsize\"\0",
+            "variable\0HOME\0declare -x HOME=\"/root\"\0",
+        );
+
+        let ignored_names = HashSet::from(["HOME".to_owned()]);
+
+        assert_eq!(
+            definitions(contents, &ignored_names).unwrap(),
+            "shopt -s expand_aliases\n\
+             greet () \n{ \n    echo hi $1\n}\n\
+             alias ll='ls -l'\n\
+             declare -x NAME=\"value\"\n"
+        );
+
+        assert!(definitions("function\0greet\0", &ignored_names).is_err());
+        assert!(definitions("other\0x\0y\0", &ignored_names).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use anyhow::Context;
+
+use serde::Serialize;
+
+use tracing::warn;
+
+use std::{fs::File, io::Write, path::PathBuf, sync::OnceLock, time::Instant};
+
+use crate::{
+    command::metrics::{CommandMetrics, CommandMetricsSummary},
+    command_line_args::CommandLineArgs,
+    detach,
+};
+
+const CONFIG_FILE: &str = "config.txt";
+const ARGS_FILE: &str = "args.json";
+const JOBLOG_FILE: &str = "joblog";
+pub const RESULTS_DIR: &str = "results";
+const LOG_FILE: &str = "log";
+const SUMMARY_FILE: &str = "summary.json";
+
+/// Internal log of the run, written by [`LogWriter`] once the run directory exists.
+static LOG: OnceLock<File> = OnceLock::new();
+
+/// For --run-dir create DIR/<timestamp>/ and default --joblog and --results to files in it.
+///
+/// The configuration is written after the defaults are applied.
+pub fn create(command_line_args: &mut CommandLineArgs) -> anyhow::Result<()> {
+    let Some(dir) = &command_line_args.run_dir else {
+        return Ok(());
+    };
+
+    // the background process of --detach creates the run directory
+    if detach::is_foreground(command_line_args) {
+        return Ok(());
+    }
+
+    let path = PathBuf::from(dir).join(format!(
+        "{}-{}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+        std::process::id()
+    ));
+
+    std::fs::create_dir_all(&path)
+        .with_context(|| format!("error creating run directory '{}'", path.display()))?;
+
+    let path_string = |name: &str| path.join(name).to_string_lossy().into_owned();
+
+    command_line_args
+        .joblog
+        .get_or_insert_with(|| path_string(JOBLOG_FILE));
+    command_line_args
+        .results
+        .get_or_insert_with(|| path_string(RESULTS_DIR));
+
+    let write = |name: &str, contents: String| {
+        let file_path = path.join(name);
+        std::fs::write(&file_path, contents)
+            .with_context(|| format!("error writing '{}'", file_path.display()))
+    };
+
+    write(
+        ARGS_FILE,
+        serde_json::to_string(&command_line_args.invocation_args)?,
+    )?;
+    write(CONFIG_FILE, format!("{:#?}\n", command_line_args))?;
+
+    let log_path = path.join(LOG_FILE);
+    let log = File::create(&log_path)
+        .with_context(|| format!("error creating '{}'", log_path.display()))?;
+    let _ = LOG.set(log);
+
+    command_line_args.run_dir_path = Some(path);
+
+    Ok(())
+}
+
+/// Writes log events to the --run-dir log, or nowhere without --run-dir.
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match LOG.get() {
+            Some(mut log) => log.write(buf),
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match LOG.get() {
+            Some(mut log) => log.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RunSummary<'a> {
+    started: &'a str,
+    elapsed_seconds: f64,
+    exit_status: i32,
+    metrics: CommandMetricsSummary,
+}
+
+/// Writes the summary of the run to the --run-dir when it finishes.
+#[derive(Debug)]
+pub struct RunDir {
+    path: &'static PathBuf,
+    started: String,
+    start_time: Instant,
+}
+
+impl RunDir {
+    pub fn new(command_line_args: &'static CommandLineArgs) -> Option<Self> {
+        command_line_args.run_dir_path.as_ref().map(|path| Self {
+            path,
+            started: chrono::Local::now().to_rfc3339(),
+            start_time: Instant::now(),
+        })
+    }
+
+    pub fn run_finished(&self, command_metrics: &CommandMetrics, exit_status: i32) {
+        let summary = RunSummary {
+            started: &self.started,
+            elapsed_seconds: self.start_time.elapsed().as_secs_f64(),
+            exit_status,
+            metrics: command_metrics.summary(),
+        };
+
+        let path = self.path.join(SUMMARY_FILE);
+
+        let result = serde_json::to_string_pretty(&summary)
+            .context("error serializing summary")
+            .and_then(|mut json| {
+                json.push('\n');
+                std::fs::write(&path, json)
+                    .with_context(|| format!("error writing '{}'", path.display()))
+            });
+
+        if let Err(e) = result {
+            warn!("run directory error: {:#}", e);
+        }
+    }
+}
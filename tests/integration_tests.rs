@@ -7,6 +7,11 @@ use predicates::prelude::*;
 fn rust_parallel_raw_command() -> Command {
     let mut cmd = Command::cargo_bin("rust-parallel").unwrap();
     cmd.current_dir("tests/");
+    // Keep run history out of the user's data directory.
+    cmd.env(
+        "XDG_DATA_HOME",
+        std::env::temp_dir().join("rust-parallel-test-data"),
+    );
     cmd
 }
 
@@ -328,6 +333,45 @@ fn runs_regex_from_command_line_args_j1() {
         .stderr(predicate::str::is_empty());
 }
 
+#[test]
+fn runs_multiple_regexes_first_match_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("-r")
+        .arg("(?P<key>[a-z]+),(?P<value>.*)")
+        .arg("-r")
+        .arg("(?P<key>[a-z]+)=(?P<value>.*)")
+        .arg("echo")
+        .arg("key={key}")
+        .arg("value={value}")
+        .arg(":::")
+        .arg("a,1")
+        .arg("b=2")
+        .arg("c,3=4")
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "key=a value=1\nkey=b value=2\nkey=c value=3=4\n",
+        ))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_regex_flags_with_auto_regex_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--regex-flags")
+        .arg("s")
+        .arg("echo")
+        .arg("{1}")
+        .arg(":::")
+        .arg("a\nb")
+        .assert()
+        .success()
+        .stdout(predicate::eq("a\nb\n"))
+        .stderr(predicate::str::is_empty());
+}
+
 #[test]
 fn runs_regex_from_command_line_args_nomatch_1() {
     rust_parallel()
@@ -369,6 +413,24 @@ fn fails_invalid_regex() {
         .stderr(predicate::str::is_empty());
 }
 
+#[test]
+fn fails_regex_missing_placeholder() {
+    rust_parallel()
+        .arg("-r")
+        .arg("(.*),(.*)")
+        .arg("echo")
+        .arg("{1}")
+        .arg("{4}")
+        .arg(":::")
+        .arg("a,b")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "placeholders {4} have no matching capture group in regex '(.*),(.*)'",
+        ))
+        .stderr(predicate::str::is_empty());
+}
+
 #[test]
 fn runs_auto_regex_from_command_line_args_j1() {
     rust_parallel()
@@ -512,7 +574,7 @@ fn test_exit_status_on_failing_commands() {
         .arg("C")
         .assert()
         .failure()
-        .code(1)
+        .code(3)
         .stdout(
             (predicate::str::contains("command failed").count(3))
                 .and(predicate::str::contains("command failures:"))
@@ -525,6 +587,35 @@ fn test_exit_status_on_failing_commands() {
         );
 }
 
+#[test]
+fn test_exit_status_on_failing_commands_binary_exit_status() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--binary-exit-status")
+        .arg("cat")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .arg("C")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(predicate::str::contains("exit_status_errors=3"));
+}
+
+#[test]
+fn test_exit_status_on_more_than_100_failing_commands() {
+    rust_parallel()
+        .arg("false")
+        .arg(":::")
+        .args((1..=105).map(|i| i.to_string()))
+        .assert()
+        .failure()
+        .code(101)
+        .stdout(predicate::str::contains("exit_status_errors=105"))
+        .stderr(predicate::str::is_empty());
+}
+
 #[test]
 fn test_exit_status_on_failing_commands_exit_on_error() {
     rust_parallel()
@@ -545,3 +636,3483 @@ fn test_exit_status_on_failing_commands_exit_on_error() {
         )
         .stderr(predicate::str::contains("cat: A: No such file or directory").count(1));
 }
+
+#[test]
+#[cfg(unix)]
+fn runs_with_umask() {
+    rust_parallel()
+        .arg("--umask")
+        .arg("027")
+        .arg("-s")
+        .arg(":::")
+        .arg("umask")
+        .assert()
+        .success()
+        .stdout(predicate::eq("0027\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_with_umask_keeping_own_umask() {
+    let joblog = std::env::temp_dir().join(format!(
+        "rust-parallel-umask-joblog-{}.txt",
+        std::process::id()
+    ));
+
+    rust_parallel()
+        .arg("--umask")
+        .arg("777")
+        .arg("--joblog")
+        .arg(&joblog)
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A\n"))
+        .stderr(predicate::str::is_empty());
+
+    // the joblog created by rust-parallel keeps its permissions despite --umask 777
+    use std::os::unix::fs::PermissionsExt;
+    let mode = std::fs::metadata(&joblog).unwrap().permissions().mode();
+    assert_ne!(mode & 0o777, 0);
+
+    std::fs::remove_file(&joblog).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_collect_artifacts() {
+    let collect_dir =
+        std::env::temp_dir().join(format!("rust-parallel-collect-{}", std::process::id()));
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--collect")
+        .arg(collect_dir.join("{#}"))
+        .arg("-s")
+        .arg(":::")
+        .arg("echo A > a.txt")
+        .arg("echo B > b.txt; false")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(predicate::str::contains("exit_status_errors=1"))
+        .stderr(predicate::str::is_empty());
+
+    assert_eq!(
+        std::fs::read_to_string(collect_dir.join("1").join("a.txt")).unwrap(),
+        "A\n"
+    );
+    assert!(!collect_dir.join("2").exists());
+
+    std::fs::remove_dir_all(collect_dir).unwrap();
+}
+
+#[test]
+fn fails_collect_artifacts_to_file_j1() {
+    let dir =
+        std::env::temp_dir().join(format!("rust-parallel-collect-fail-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let temp_dir = dir.join("tmp");
+    std::fs::create_dir_all(&temp_dir).unwrap();
+    let file = dir.join("file");
+    std::fs::write(&file, "").unwrap();
+
+    rust_parallel()
+        .env("TMPDIR", &temp_dir)
+        .arg("-j1")
+        .arg("--collect")
+        .arg(file.join("{#}"))
+        .arg("-s")
+        .arg(":::")
+        .arg("echo A > a.txt")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(predicate::str::contains("collect error"))
+        .stdout(predicate::str::contains("exit_status_errors=1"))
+        .stderr(predicate::str::is_empty());
+
+    // the temp directory of the run is removed
+    assert_eq!(std::fs::read_dir(&temp_dir).unwrap().count(), 0);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn runs_inline_commands_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg(":::::")
+        .arg("echo")
+        .arg("A")
+        .arg(":::::")
+        .arg("echo")
+        .arg("B")
+        .arg("C")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A\nB C\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn fails_mixed_inline_commands_and_args() {
+    rust_parallel()
+        .arg(":::::")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "::: and ::::: separators cannot be combined",
+        ))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_yaml_manifest_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--manifest")
+        .arg("manifest.yaml")
+        .assert()
+        .success()
+        .stdout(predicate::eq("hello\nfrom_env\n/\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_toml_manifest_with_timeout_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--manifest")
+        .arg("manifest.toml")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(
+            (predicate::str::contains("hello\n").count(1))
+                .and(predicate::str::contains("timeouts=1").count(1)),
+        )
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn fails_invalid_manifest() {
+    rust_parallel()
+        .arg("--manifest")
+        .arg("file.txt")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(predicate::str::contains(
+            "error parsing manifest file_name = 'file.txt'",
+        ))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_manifest_with_dependencies_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--manifest")
+        .arg("manifest_dag.yaml")
+        .assert()
+        .failure()
+        .code(2)
+        .stdout(
+            (predicate::str::contains("fetch\nbuild\n"))
+                .and(predicate::str::contains("package\n").count(1))
+                .and(predicate::str::contains("test\n").not())
+                .and(predicate::str::contains(
+                    "skipping manifest job test: dependency broken failed",
+                ))
+                .and(predicate::str::contains("dependency_failures=1")),
+        )
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_then_stage_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--then")
+        .arg("echo second {}")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .assert()
+        .success()
+        .stdout(predicate::eq("second A\nsecond B\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_then_stage_only_for_successful_commands() {
+    rust_parallel()
+        .arg("-s")
+        .arg("--then")
+        .arg("echo second")
+        .arg("echo first && false")
+        .arg(":::")
+        .arg("A")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("first\n"))
+        .stdout(predicate::str::contains("second").not());
+}
+
+#[test]
+fn runs_with_fsync_per_job_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--fsync")
+        .arg("per-job")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A\nB\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_with_fsync_periodic_output_files_j1() {
+    let dir = std::env::temp_dir().join(format!("rust-parallel-fsync-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let joblog = dir.join("joblog");
+    let results = dir.join("results");
+    let success_out = dir.join("success");
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--fsync")
+        .arg("periodic")
+        .arg("--joblog")
+        .arg(&joblog)
+        .arg("--results")
+        .arg(&results)
+        .arg("--success-out")
+        .arg(&success_out)
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A\nB\n"))
+        .stderr(predicate::str::is_empty());
+
+    assert_eq!(std::fs::read_to_string(&joblog).unwrap().lines().count(), 3);
+    assert_eq!(
+        std::fs::read_to_string(results.join("2").join("stdout")).unwrap(),
+        "B\n"
+    );
+    assert_eq!(std::fs::read_to_string(&success_out).unwrap(), "A\nB\n");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn runs_with_output_buffer_size_j1() {
+    for output_buffer_size in ["0", "1", "1M"] {
+        rust_parallel()
+            .arg("-j1")
+            .arg("--output-buffer-size")
+            .arg(output_buffer_size)
+            .arg("echo")
+            .arg(":::")
+            .arg("A")
+            .arg("B")
+            .assert()
+            .success()
+            .stdout(predicate::eq("A\nB\n"))
+            .stderr(predicate::str::is_empty());
+    }
+}
+
+#[test]
+fn runs_input_with_job_annotations_j1() {
+    let stdin = r#"echo A #parallel: tag=prod
+sleep 5 #parallel: timeout=0.5 tag=slow
+echo C
+"#;
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--annotations")
+        .write_stdin(stdin)
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(
+            predicate::str::contains("A\n")
+                .and(predicate::str::contains("tag=slow"))
+                .and(predicate::str::contains("timeouts=1"))
+                .and(predicate::str::contains("#parallel").not())
+                .and(predicate::str::contains("C\n")),
+        )
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn fails_input_with_invalid_job_annotations_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--annotations")
+        .write_stdin("echo B #parallel: color=red\necho C\n")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(
+            predicate::str::contains(
+                "invalid job annotations line stdin:1: unknown annotation 'color'",
+            )
+            .and(predicate::str::contains("B\n").not())
+            .and(predicate::str::contains("C\n").not()),
+        )
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_input_without_job_annotations_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .write_stdin("echo A #parallel: color=red\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A #parallel: color=red\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn fails_invalid_window() {
+    rust_parallel()
+        .arg("--window")
+        .arg("22:00")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(predicate::str::contains("invalid window '22:00'"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_with_budget_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--budget")
+        .arg("2")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .arg("C")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("A\n")
+                .and(predicate::str::contains("B\n"))
+                .and(predicate::str::contains("C\n").not())
+                .and(predicate::str::contains("budget exhausted"))
+                .and(predicate::str::contains(
+                    "budget used=2 limit=2 skipped_commands=1",
+                )),
+        )
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_with_budget_cost_from_input_j1() {
+    let stdin = "A,1\nB,3\nC,1\n";
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("-r")
+        .arg("(.*),(.*)")
+        .arg("--budget")
+        .arg("3")
+        .arg("--cost")
+        .arg("{2}")
+        .arg("echo")
+        .arg("{1}")
+        .write_stdin(stdin)
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("A\n")
+                .and(predicate::str::contains("B\n").not())
+                .and(predicate::str::contains("C\n").not())
+                .and(predicate::str::contains(
+                    "budget used=1 limit=3 skipped_commands=2",
+                )),
+        )
+        .stderr(predicate::str::is_empty());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn runs_with_self_nice_commands_keep_priority() {
+    rust_parallel()
+        .arg("--self-nice")
+        .arg("10")
+        .write_stdin("nice\n")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("lowered rust-parallel priority to nice 10")
+                .and(predicate::str::ends_with("0\n")),
+        )
+        .stderr(predicate::str::is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn fails_raw_args_not_windows() {
+    rust_parallel()
+        .arg("--raw-args")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(predicate::str::contains(
+            "--raw-args is only supported on Windows",
+        ))
+        .stderr(predicate::str::is_empty());
+}
+
+#[cfg(not(target_os = "macos"))]
+#[test]
+fn fails_sandbox_profile_not_macos() {
+    rust_parallel()
+        .arg("--sandbox-profile")
+        .arg("file.txt")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(predicate::str::contains(
+            "--sandbox-profile is only supported on macos",
+        ))
+        .stderr(predicate::str::is_empty());
+}
+
+#[cfg(not(target_os = "freebsd"))]
+#[test]
+fn fails_jail_not_freebsd() {
+    rust_parallel()
+        .arg("--jail")
+        .arg("worker")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(predicate::str::contains(
+            "--jail is only supported on freebsd",
+        ))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_with_audit_log_j1() {
+    use sha2::{Digest, Sha256};
+
+    let audit_log =
+        std::env::temp_dir().join(format!("rust-parallel-audit-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&audit_log);
+
+    for _ in 0..2 {
+        rust_parallel()
+            .arg("-j1")
+            .arg("--audit-log")
+            .arg(&audit_log)
+            .arg("echo")
+            .arg(":::")
+            .arg("A")
+            .assert()
+            .success()
+            .stdout(predicate::eq("A\n"))
+            .stderr(predicate::str::is_empty());
+    }
+
+    let contents = std::fs::read_to_string(&audit_log).unwrap();
+    let _ = std::fs::remove_file(&audit_log);
+
+    let lines: Vec<&str> = contents.lines().collect();
+    assert_eq!(lines.len(), 4);
+
+    let mut prev_hash = "0".repeat(64);
+    for (i, line) in lines.into_iter().enumerate() {
+        let record: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(record["job"], 1);
+        if i % 2 == 0 {
+            assert_eq!(record["event"], "spawn");
+            assert_eq!(record["argv"][1], "A");
+            assert!(record["program"].as_str().unwrap().ends_with("echo"));
+        } else {
+            assert_eq!(record["event"], "exit");
+            assert_eq!(record["success"], true);
+            assert_eq!(record["exit_code"], 0);
+        }
+        assert_eq!(record["prev_hash"], prev_hash.as_str());
+        prev_hash = hex::encode(Sha256::digest(line.as_bytes()));
+    }
+}
+
+#[test]
+fn runs_replay_failed_j1() {
+    let audit_log =
+        std::env::temp_dir().join(format!("rust-parallel-replay-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&audit_log);
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--audit-log")
+        .arg(&audit_log)
+        .arg("-s")
+        .arg(":::")
+        .arg("echo A")
+        .arg("echo B; exit 1")
+        .assert()
+        .failure();
+
+    let assert = rust_parallel()
+        .arg("-j1")
+        .arg("replay")
+        .arg("--failed")
+        .arg(&audit_log)
+        .assert();
+
+    let _ = std::fs::remove_file(&audit_log);
+
+    assert
+        .failure()
+        .stdout(predicate::str::starts_with("B\n"))
+        .stdout(predicate::str::contains("A\n").not())
+        .stdout(predicate::str::contains("commands_run=1 total_failures=1"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_with_verify_input_j1() {
+    use sha2::{Digest, Sha256};
+
+    let temp_dir = std::env::temp_dir();
+    let data_file = temp_dir.join(format!("rust-parallel-verify-{}.txt", std::process::id()));
+    let checksum_file = temp_dir.join(format!(
+        "rust-parallel-verify-{}.sha256",
+        std::process::id()
+    ));
+
+    std::fs::write(&data_file, "data\n").unwrap();
+    std::fs::write(
+        &checksum_file,
+        format!(
+            "{}  {}\n",
+            hex::encode(Sha256::digest(b"data\n")),
+            data_file.display()
+        ),
+    )
+    .unwrap();
+
+    let valid_assert = rust_parallel()
+        .arg("-j1")
+        .arg("--verify-input")
+        .arg(&checksum_file)
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .assert();
+
+    std::fs::write(&data_file, "changed\n").unwrap();
+
+    let changed_assert = rust_parallel()
+        .arg("-j1")
+        .arg("--verify-input")
+        .arg(&checksum_file)
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .assert();
+
+    let _ = std::fs::remove_file(&data_file);
+    let _ = std::fs::remove_file(&checksum_file);
+
+    valid_assert
+        .success()
+        .stdout(predicate::eq("A\n"))
+        .stderr(predicate::str::is_empty());
+
+    changed_assert
+        .failure()
+        .stdout(predicate::str::contains("A\n").not())
+        .stdout(predicate::str::contains("checksum mismatch for input"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_sql_input_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--sql")
+        .arg("select 1 as id, 'a' as name union all select 2, 'b'")
+        .arg("--sql-url")
+        .arg("sqlite::memory:")
+        .arg("echo")
+        .arg("id={id}")
+        .arg("name={name}")
+        .assert()
+        .success()
+        .stdout(predicate::eq("id=1 name=a\nid=2 name=b\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn fails_sql_input_invalid_query() {
+    rust_parallel()
+        .arg("--sql")
+        .arg("select from")
+        .arg("--sql-url")
+        .arg("sqlite::memory:")
+        .arg("echo")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("error running sql query"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_sql_input_on_success_on_failure_j1() {
+    use sqlx::Row;
+
+    let db_file = std::env::temp_dir().join(format!("rust-parallel-sql-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&db_file);
+    let url = format!("sqlite:{}?mode=rwc", db_file.display());
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    runtime.block_on(async {
+        sqlx::any::install_default_drivers();
+        let pool = sqlx::AnyPool::connect(&url).await.unwrap();
+        sqlx::raw_sql(
+            "create table jobs (id integer, exit_code integer, status text);
+             insert into jobs values (1, 0, 'pending'), (2, 1, 'pending'), (3, 0, 'done');",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool.close().await;
+    });
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--sql")
+        .arg("select id, exit_code from jobs where status = 'pending' order by id")
+        .arg("--sql-url")
+        .arg(&url)
+        .arg("--sql-on-success")
+        .arg("update jobs set status = 'done' where id = {id}")
+        .arg("--sql-on-failure")
+        .arg("update jobs set status = 'failed' where id = {id}")
+        .arg("-s")
+        .arg("exit {exit_code}")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("commands_run=2 total_failures=1"))
+        .stderr(predicate::str::is_empty());
+
+    let statuses: Vec<String> = runtime.block_on(async {
+        let pool = sqlx::AnyPool::connect(&url).await.unwrap();
+        let rows = sqlx::query("select status from jobs order by id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+        rows.iter().map(|row| row.get(0)).collect()
+    });
+
+    let _ = std::fs::remove_file(&db_file);
+
+    assert_eq!(statuses, vec!["done", "failed", "done"]);
+}
+
+/// Sqlite database file with a jobs table of ids and statuses, and its url.
+fn create_sqlite_jobs_db(name: &str, ids: &[i64]) -> (std::path::PathBuf, String) {
+    let db_file =
+        std::env::temp_dir().join(format!("rust-parallel-{}-{}.db", name, std::process::id()));
+    let _ = std::fs::remove_file(&db_file);
+    let url = format!("sqlite:{}?mode=rwc", db_file.display());
+
+    let values = ids
+        .iter()
+        .map(|id| format!("({}, 'pending')", id))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    tokio::runtime::Runtime::new().unwrap().block_on(async {
+        sqlx::any::install_default_drivers();
+        let pool = sqlx::AnyPool::connect(&url).await.unwrap();
+        sqlx::raw_sql(&format!(
+            "create table jobs (id integer, status text); insert into jobs values {};",
+            values
+        ))
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool.close().await;
+    });
+
+    (db_file, url)
+}
+
+/// Statuses of the jobs table ordered by id.
+fn sqlite_job_statuses(url: &str) -> Vec<String> {
+    use sqlx::Row;
+
+    tokio::runtime::Runtime::new().unwrap().block_on(async {
+        let pool = sqlx::AnyPool::connect(url).await.unwrap();
+        let rows = sqlx::query("select status from jobs order by id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+        rows.iter().map(|row| row.get(0)).collect()
+    })
+}
+
+#[test]
+fn runs_sql_input_dry_run_leaves_rows_unchanged() {
+    let (db_file, url) = create_sqlite_jobs_db("sql-dry-run", &[1, 2]);
+
+    rust_parallel()
+        .arg("--dry-run")
+        .arg("--sql")
+        .arg("select id from jobs order by id")
+        .arg("--sql-url")
+        .arg(&url)
+        .arg("--sql-on-success")
+        .arg("update jobs set status = 'done' where id = {id}")
+        .arg("--sql-on-failure")
+        .arg("update jobs set status = 'failed' where id = {id}")
+        .arg("echo")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("args=[\"1\"]"))
+        .stderr(predicate::str::is_empty());
+
+    let statuses = sqlite_job_statuses(&url);
+    let _ = std::fs::remove_file(&db_file);
+
+    assert_eq!(statuses, vec!["pending", "pending"]);
+}
+
+#[test]
+fn runs_sql_input_shard_leaves_other_shard_rows_unchanged() {
+    let (db_file, url) = create_sqlite_jobs_db("sql-shard", &[1, 2, 3, 4]);
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--shard-count")
+        .arg("2")
+        .arg("--shard-index")
+        .arg("0")
+        .arg("--sql")
+        .arg("select id from jobs order by id")
+        .arg("--sql-url")
+        .arg(&url)
+        .arg("--sql-on-success")
+        .arg("update jobs set status = 'done' where id = {id}")
+        .arg("echo")
+        .assert()
+        .success()
+        .stdout(predicate::eq("1\n3\n"))
+        .stderr(predicate::str::is_empty());
+
+    let statuses = sqlite_job_statuses(&url);
+    let _ = std::fs::remove_file(&db_file);
+
+    assert_eq!(statuses, vec!["done", "pending", "done", "pending"]);
+}
+
+#[test]
+fn runs_s3_list_file_url_j1() {
+    let dir = std::env::temp_dir().join(format!("rust-parallel-list-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("prefix")).unwrap();
+    std::fs::write(dir.join("prefix").join("a.txt"), "abc").unwrap();
+    std::fs::write(dir.join("prefix").join("b.txt"), "hello").unwrap();
+
+    let assert = rust_parallel()
+        .arg("-j1")
+        .arg("--s3-list")
+        .arg(format!("file://{}", dir.join("prefix").display()))
+        .arg("echo")
+        .arg("size={size}")
+        .arg("{key}")
+        .assert();
+
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert
+        .success()
+        .stdout(predicate::str::is_match("^size=(3|5) .*/prefix/(a|b).txt\n").unwrap())
+        .stdout(predicate::str::contains("size=3 ").and(predicate::str::contains("prefix/a.txt\n")))
+        .stdout(predicate::str::contains("size=5 ").and(predicate::str::contains("prefix/b.txt\n")))
+        .stderr(predicate::str::is_empty());
+}
+
+/// Method, path, and body of each request received by `serve_http_requests`.
+type ReceivedHttpRequests = std::thread::JoinHandle<Vec<(String, String, String)>>;
+
+/// Serve `count` HTTP requests on a local port, responding with `respond(method, path, body)`.
+fn serve_http_requests(
+    count: usize,
+    respond: fn(&str, &str, &str) -> (&'static str, String),
+) -> (std::net::SocketAddr, ReceivedHttpRequests) {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let server = std::thread::spawn(move || {
+        let mut requests = vec![];
+
+        for _ in 0..count {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut parts = request_line.split_whitespace();
+            let method = parts.next().unwrap().to_owned();
+            let path = parts.next().unwrap().to_owned();
+
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            let body = String::from_utf8(body).unwrap();
+
+            let (status, response_body) = respond(&method, &path, &body);
+
+            let mut stream = reader.into_inner();
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                response_body.len(),
+                response_body
+            )
+            .unwrap();
+
+            requests.push((method, path, body));
+        }
+
+        requests
+    });
+
+    (address, server)
+}
+
+#[test]
+fn runs_http_requests_j1() {
+    let (address, server) = serve_http_requests(3, |method, path, body| {
+        if path == "/missing" {
+            ("404 Not Found", String::new())
+        } else {
+            ("200 OK", format!("{} {} {}\n", method, path, body))
+        }
+    });
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--http")
+        .arg(format!("http://{}/{{}}", address))
+        .arg("-d")
+        .arg("id={}")
+        .arg(":::")
+        .arg("a")
+        .arg("b")
+        .arg("missing")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("POST /a id=a\nPOST /b id=b\n"))
+        .stdout(predicate::str::contains("commands_run=3 total_failures=1"))
+        .stderr(predicate::str::contains("HTTP 404 Not Found"));
+
+    server.join().unwrap();
+}
+
+#[test]
+fn runs_with_notify_url_j1() {
+    let (address, server) = serve_http_requests(2, |_, _, _| ("200 OK", String::new()));
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--notify-url")
+        .arg(format!("http://{}/hook", address))
+        .arg("--notify-failure-threshold")
+        .arg("0")
+        .arg("-s")
+        .arg(":::")
+        .arg("true")
+        .arg("exit 1")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("commands_run=2 total_failures=1"))
+        .stderr(predicate::str::is_empty());
+
+    let requests = server.join().unwrap();
+
+    let events: Vec<serde_json::Value> = requests
+        .iter()
+        .map(|(method, path, body)| {
+            assert_eq!(method, "POST");
+            assert_eq!(path, "/hook");
+            serde_json::from_str(body).unwrap()
+        })
+        .collect();
+
+    assert_eq!(events[0]["event"], "failure_threshold");
+    assert_eq!(events[0]["total_failures"], 1);
+    assert_eq!(events[1]["event"], "finished");
+    assert_eq!(events[1]["success"], false);
+    assert_eq!(events[1]["commands_run"], 2);
+    assert_eq!(events[1]["exit_status_errors"], 1);
+}
+
+#[test]
+fn runs_with_notify_bell_title_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--notify")
+        .arg("bell,title")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_with_mail_to_smtp_error_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--mail-to")
+        .arg("ops@example.com")
+        .arg("--smtp-url")
+        .arg("smtp://127.0.0.1:1")
+        .arg("false")
+        .arg(":::")
+        .arg("A")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(predicate::str::contains(
+            "mail report error: error sending mail with smtp",
+        ))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_with_mail_on_failure_success_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--mail-to")
+        .arg("ops@example.com")
+        .arg("--mail-on")
+        .arg("failure")
+        .arg("--smtp-url")
+        .arg("smtp://127.0.0.1:1")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_saved_template_j1() {
+    let config_dir =
+        std::env::temp_dir().join(format!("rust-parallel-template-{}", std::process::id()));
+
+    rust_parallel()
+        .env("XDG_CONFIG_HOME", &config_dir)
+        .arg("template")
+        .arg("save")
+        .arg("greet")
+        .arg("echo hello {}")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("saved template 'greet' to "))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .env("XDG_CONFIG_HOME", &config_dir)
+        .arg("template")
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::eq("greet\techo hello {}\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .env("XDG_CONFIG_HOME", &config_dir)
+        .arg("-j1")
+        .arg("template")
+        .arg("run")
+        .arg("greet")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .assert()
+        .success()
+        .stdout(predicate::eq("hello A\nhello B\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .env("XDG_CONFIG_HOME", &config_dir)
+        .arg("template")
+        .arg("run")
+        .arg("missing")
+        .assert()
+        .failure()
+        .code(2)
+        .stderr(predicate::str::contains("error reading template"));
+
+    std::fs::remove_dir_all(config_dir).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_history_rerun_j1() {
+    let data_dir =
+        std::env::temp_dir().join(format!("rust-parallel-history-{}", std::process::id()));
+
+    rust_parallel()
+        .env("XDG_DATA_HOME", &data_dir)
+        .arg("-j1")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A\nB\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .env("XDG_DATA_HOME", &data_dir)
+        .arg("--no-history")
+        .arg("echo")
+        .arg(":::")
+        .arg("C")
+        .assert()
+        .success();
+
+    rust_parallel()
+        .env("XDG_DATA_HOME", &data_dir)
+        .arg("history")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::starts_with("1\t")
+                .and(predicate::str::contains(
+                    " exit_status=0 commands_run=2 total_failures=0 ",
+                ))
+                .and(predicate::str::ends_with(" -j1 echo ::: A B\n")),
+        )
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .env("XDG_DATA_HOME", &data_dir)
+        .arg("history")
+        .arg("rerun")
+        .arg("1")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A\nB\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .env("XDG_DATA_HOME", &data_dir)
+        .arg("history")
+        .arg("show")
+        .arg("2")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"commands_run\": 2"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .env("XDG_DATA_HOME", &data_dir)
+        .arg("history")
+        .arg("rerun")
+        .arg("3")
+        .assert()
+        .failure()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "history entry 3 not found, there are 2 entries",
+        ));
+
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_calibrate() {
+    rust_parallel()
+        .arg("calibrate")
+        .arg("--samples")
+        .arg("4")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::starts_with("spawn_overhead=")
+                .and(predicate::str::contains("\nscheduler_latency="))
+                .and(predicate::str::contains("\noutput_throughput="))
+                .and(predicate::str::contains("\njobs=1 commands_per_second="))
+                .and(
+                    predicate::str::is_match("\nrecommended: -j \\d+ --channel-capacity \\d+\n$")
+                        .unwrap(),
+                ),
+        )
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_with_report_tap_j1() {
+    let report_file =
+        std::env::temp_dir().join(format!("rust-parallel-report-{}.tap", std::process::id()));
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--report")
+        .arg(format!("tap={}", report_file.display()))
+        .arg("-s")
+        .arg(":::")
+        .arg("echo A")
+        .arg("false")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(predicate::str::starts_with("A\n"))
+        .stderr(predicate::str::is_empty());
+
+    let report = std::fs::read_to_string(&report_file).unwrap();
+
+    assert!(report.starts_with("TAP version 13\n1..2\nok 1 - /bin/bash -c echo A\n"));
+    assert!(report.contains("  stdout: |\n    A\n"));
+    assert!(report.contains("not ok 2 - /bin/bash -c false\n"));
+
+    std::fs::remove_file(report_file).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_with_child_log_level_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--child-log-level")
+        .arg("warn")
+        .arg("-s")
+        .arg(":::")
+        .arg("printf 'DEBUG a\\nWARN b\\nc\\n'")
+        .arg("printf 'INFO d\\n'")
+        .assert()
+        .success()
+        .stdout(predicate::eq("WARN b\nc\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_with_output_filter_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--output-filter")
+        .arg("s/secret-[0-9]+/REDACTED/g")
+        .arg("--output-filter")
+        .arg("s/^token/TOKEN/")
+        .arg("echo")
+        .arg("token")
+        .arg(":::")
+        .arg("secret-1")
+        .arg("secret-22")
+        .assert()
+        .success()
+        .stdout(predicate::eq("TOKEN REDACTED\nTOKEN REDACTED\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_with_show_output_failed_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--show-output")
+        .arg("failed")
+        .arg("-s")
+        .arg(":::")
+        .arg("echo A")
+        .arg("echo B; false")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(
+            predicate::str::starts_with("B\n")
+                .and(predicate::str::contains("A\n").not())
+                .and(predicate::str::contains("command failed")),
+        )
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_with_success_out_failure_out_j1() {
+    let temp_dir = std::env::temp_dir();
+    let success_out = temp_dir.join(format!("rust-parallel-ok-{}.txt", std::process::id()));
+    let failure_out = temp_dir.join(format!("rust-parallel-bad-{}.txt", std::process::id()));
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--success-out")
+        .arg(&success_out)
+        .arg("--failure-out")
+        .arg(&failure_out)
+        .arg("test")
+        .arg("-e")
+        .arg(":::")
+        .arg("file.txt")
+        .arg("missing.txt")
+        .arg("csv_file.txt")
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(predicate::str::is_empty());
+
+    assert_eq!(
+        std::fs::read_to_string(&success_out).unwrap(),
+        "file.txt\ncsv_file.txt\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(&failure_out).unwrap(),
+        "missing.txt\n"
+    );
+
+    std::fs::remove_file(success_out).unwrap();
+    std::fs::remove_file(failure_out).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_with_map_in_input_order() {
+    rust_parallel()
+        .arg("-j2")
+        .arg("--map")
+        .arg("-s")
+        .arg(":::")
+        .arg("sleep 0.2; echo A1; echo A2")
+        .arg("echo B1")
+        .arg("true")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A1\nB1\n\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("-j2")
+        .arg("--map=joined")
+        .arg("-s")
+        .arg(":::")
+        .arg("sleep 0.2; echo A1; echo A2")
+        .arg("echo B1")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A1 A2\nB1\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_with_join_output_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--join-output")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A\t0\tA\nB\t0\tB\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--join-output")
+        .arg("--join-delimiter")
+        .arg(",")
+        .arg("echo")
+        .arg(":::")
+        .arg("A,B")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A\\,B,0,A\\,B\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_with_count() {
+    rust_parallel()
+        .arg("--count")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .arg(":::")
+        .arg("1")
+        .arg("2")
+        .arg("3")
+        .assert()
+        .success()
+        .stdout(predicate::eq("6\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("--count")
+        .arg("-i")
+        .arg("file.txt")
+        .arg("false")
+        .assert()
+        .success()
+        .stdout(predicate::eq("4\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_crlf_input_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("-r")
+        .arg("(.*)")
+        .arg("echo")
+        .arg("[{1}]")
+        .write_stdin("a\r\nb\r\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("[a]\n[b]\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--keep-cr")
+        .arg("-r")
+        .arg("(.*)")
+        .arg("echo")
+        .arg("[{1}]")
+        .write_stdin("a\r\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("[a\r]\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_input_encoding_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("echo")
+        .write_stdin(b"\xEF\xBB\xBFa\nb\n".to_vec())
+        .assert()
+        .success()
+        .stdout(predicate::eq("a\nb\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--input-encoding")
+        .arg("latin1")
+        .arg("echo")
+        .write_stdin(b"caf\xE9\n".to_vec())
+        .assert()
+        .success()
+        .stdout(predicate::eq("café\n"))
+        .stderr(predicate::str::is_empty());
+
+    let utf16le_input: Vec<u8> = "\u{FEFF}été\r\nb\r\n"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--input-encoding")
+        .arg("utf16le")
+        .arg("-r")
+        .arg("(.*)")
+        .arg("echo")
+        .arg("[{1}]")
+        .write_stdin(utf16le_input)
+        .assert()
+        .success()
+        .stdout(predicate::eq("[été]\n[b]\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_non_utf8_input_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("echo")
+        .write_stdin(b"a\xff b\n".to_vec())
+        .assert()
+        .success()
+        .stdout(predicate::eq(b"a\xff b\n".as_slice()))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("-r")
+        .arg("(.*)")
+        .arg("echo")
+        .arg("{1}")
+        .write_stdin(b"a\xff\n".to_vec())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "skipping input line stdin:1 that is not valid utf-8",
+        ))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_max_line_length_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--max-line-length")
+        .arg("5")
+        .arg("echo")
+        .write_stdin("a\n0123456789\nb\n")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("a\nb\n").and(predicate::str::contains(
+                "skipping input line stdin:2 of 10 bytes, longer than --max-line-length 5",
+            )),
+        )
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--max-line-length")
+        .arg("5")
+        .arg("--max-line-length-policy")
+        .arg("truncate")
+        .arg("echo")
+        .write_stdin("0123456789\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with("01234\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--max-line-length")
+        .arg("5")
+        .arg("--max-line-length-policy")
+        .arg("fail")
+        .arg("echo")
+        .write_stdin("0123456789\n")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "input line stdin:1 of 10 bytes is longer than --max-line-length 5",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn fails_command_line_too_long_j1() {
+    let long_line = vec!["a".repeat(1000); 4000].join(" ");
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("echo")
+        .write_stdin(long_line)
+        .assert()
+        .failure()
+        .stdout(
+            predicate::str::contains("command line too long (")
+                .and(predicate::str::contains("bytes > ARG_MAX"))
+                .and(predicate::str::contains("spawn_errors=1")),
+        )
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_plus_placeholders_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--plus")
+        .arg("echo")
+        .arg("{+/}")
+        .arg("{+.}")
+        .arg("{..}")
+        .write_stdin("dir/file.tar.gz\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("dir gz dir/file\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--plus")
+        .arg("echo")
+        .arg("{...}")
+        .arg(":::")
+        .arg("a.b.c.d")
+        .assert()
+        .success()
+        .stdout(predicate::eq("a\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("echo")
+        .arg("{..}")
+        .arg(":::")
+        .arg("a.b.c")
+        .assert()
+        .success()
+        .stdout(predicate::eq("{..} a.b.c\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_product_filter_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--product-filter")
+        .arg("{1} < {2}")
+        .arg("echo")
+        .arg(":::")
+        .arg("1")
+        .arg("2")
+        .arg("10")
+        .arg(":::")
+        .arg("1")
+        .arg("2")
+        .arg("10")
+        .assert()
+        .success()
+        .stdout(predicate::eq("1 2\n1 10\n2 10\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn fails_product_filter_missing_group() {
+    rust_parallel()
+        .arg("--product-filter")
+        .arg("{1} != {3}")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg(":::")
+        .arg("B")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "--product-filter placeholder {3} has no matching ::: group, there are 2 groups",
+        ));
+}
+
+#[test]
+fn runs_combinations_pairs_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--combinations")
+        .arg("unique-pairs")
+        .arg("echo")
+        .arg("{2}-{1}")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .arg("C")
+        .assert()
+        .success()
+        .stdout(predicate::eq("B-A\nC-A\nC-B\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--combinations")
+        .arg("pairs")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .arg(":::")
+        .arg("1")
+        .arg("2")
+        .arg("3")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A 1\nA 2\nA 3\nB 2\nB 3\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("--combinations")
+        .arg("pairs")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg(":::")
+        .arg("B")
+        .arg(":::")
+        .arg("C")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "--combinations pairs and unique-pairs require 1 or 2 ::: groups",
+        ));
+}
+
+#[test]
+fn runs_repeat_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--repeat")
+        .arg("3")
+        .arg("echo")
+        .arg("{rep}")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("1 A\n2 A\n3 A\n1 B\n2 B\n3 B\n")
+                .and(predicate::str::contains(
+                    "repeat line=command_line_args:1 input=\"A\" runs=3 failures=0",
+                ))
+                .and(predicate::str::contains(
+                    "repeat line=command_line_args:2 input=\"B\" runs=3 failures=0",
+                )),
+        )
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_loop_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--loop")
+        .arg("2")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("A\nB\n")
+                .count(2)
+                .and(predicate::str::contains(
+                    "loop cycle=1 commands=2 failures=0",
+                ))
+                .and(predicate::str::contains(
+                    "loop cycle=2 commands=2 failures=0",
+                ))
+                .and(predicate::str::contains("loop cycle=3").not()),
+        )
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("--loop")
+        .arg("forever")
+        .arg("echo")
+        .write_stdin("A\n")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "--loop can not re-read stdin, use input files instead",
+        ));
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_warmup_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--warmup")
+        .arg("1")
+        .arg("-s")
+        .arg(":::")
+        .arg("exit 1")
+        .arg("echo A")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("A\n").and(predicate::str::contains(
+                "warmup excluded from stats: commands_run=1 total_failures=1",
+            )),
+        )
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--warmup")
+        .arg("1")
+        .arg("-s")
+        .arg(":::")
+        .arg("echo A")
+        .arg("exit 1")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "command failures: commands_run=1 total_failures=1",
+        ));
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_warmup_with_joblog_j1() {
+    let joblog = std::env::temp_dir().join(format!(
+        "rust-parallel-warmup-joblog-{}.txt",
+        std::process::id()
+    ));
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--warmup")
+        .arg("1")
+        .arg("--joblog")
+        .arg(&joblog)
+        .arg("-s")
+        .arg(":::")
+        .arg("echo A")
+        .arg("echo B")
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty());
+
+    let contents = std::fs::read_to_string(&joblog).unwrap();
+    let lines: Vec<Vec<&str>> = contents
+        .lines()
+        .map(|line| line.split('\t').collect())
+        .collect();
+
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0][8..], ["Command", "Warmup"]);
+    assert_eq!(lines[1][0], "1");
+    assert_eq!(lines[1][9], "1");
+    assert_eq!(lines[2][0], "2");
+    assert_eq!(lines[2][9], "0");
+
+    std::fs::remove_file(&joblog).unwrap();
+}
+
+#[test]
+fn runs_tag_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--tag")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .arg(":::")
+        .arg("1")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A 1\tA 1\nB 1\tB 1\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_wave_size() {
+    rust_parallel()
+        .arg("-j4")
+        .arg("--wave-size")
+        .arg("2")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .arg("C")
+        .arg("D")
+        .arg("E")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("wave 1 finished")
+                .and(predicate::str::contains("wave 2 finished"))
+                .and(predicate::str::contains("wave 3 finished").not())
+                .and(predicate::str::contains("E\n")),
+        )
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("-j4")
+        .arg("--wave-size")
+        .arg("2")
+        .arg("--wave-hook")
+        .arg("exit $RUST_PARALLEL_WAVE")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .arg("C")
+        .assert()
+        .failure()
+        .stdout(
+            predicate::str::contains(
+                "wave hook 'exit $RUST_PARALLEL_WAVE' failed after wave 1: exit status: 1",
+            )
+            .and(predicate::str::contains("C\n").not()),
+        );
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_canary() {
+    rust_parallel()
+        .arg("-j4")
+        .arg("--canary")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("canary job succeeded")
+                .and(predicate::str::contains("A\n"))
+                .and(predicate::str::contains("B\n")),
+        )
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("-j4")
+        .arg("--canary")
+        .arg("-s")
+        .arg(":::")
+        .arg("exit 1")
+        .arg("echo B")
+        .assert()
+        .failure()
+        .stdout(
+            predicate::str::contains("canary job failed, not running remaining jobs")
+                .and(predicate::str::contains("B\n").not()),
+        );
+}
+
+#[test]
+fn runs_tagstring_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--tagstring")
+        .arg("{2}:{#}")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .arg(":::")
+        .arg("1")
+        .assert()
+        .success()
+        .stdout(predicate::eq("1:1\tA 1\n1:2\tB 1\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--tagstring")
+        .arg("[{}]")
+        .arg("echo")
+        .write_stdin("A\nB\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("[A]\tA\n[B]\tB\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_results_j1() {
+    let results_dir =
+        std::env::temp_dir().join(format!("rust-parallel-results-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&results_dir);
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--results")
+        .arg(&results_dir)
+        .arg("--show-output")
+        .arg("none")
+        .arg("-s")
+        .arg(":::")
+        .arg("echo A")
+        .arg("echo B >&2; exit 3")
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_empty());
+
+    let read = |job: &str, file_name: &str| {
+        std::fs::read_to_string(results_dir.join(job).join(file_name)).unwrap()
+    };
+
+    assert_eq!(read("1", "input"), "echo A\n");
+    assert!(read("1", "cmd").ends_with(" echo A\n"));
+    assert_eq!(read("1", "stdout"), "A\n");
+    assert_eq!(read("1", "stderr"), "");
+    assert_eq!(read("1", "exitcode"), "0\n");
+    assert_eq!(read("2", "stdout"), "");
+    assert_eq!(read("2", "stderr"), "B\n");
+    assert_eq!(read("2", "exitcode"), "3\n");
+
+    std::fs::remove_dir_all(&results_dir).unwrap();
+}
+
+#[test]
+fn runs_confirm_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--confirm=1")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .write_stdin("y\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("echo A\n... and 1 more\nA\nB\n"))
+        .stderr(predicate::eq("Run 2 commands? [y/N] "));
+
+    rust_parallel()
+        .arg("--confirm")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .write_stdin("n\n")
+        .assert()
+        .failure()
+        .stdout(
+            predicate::str::starts_with("echo A\n").and(predicate::str::contains(
+                "commands not confirmed, no commands run",
+            )),
+        );
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_joblog_j1() {
+    let joblog =
+        std::env::temp_dir().join(format!("rust-parallel-joblog-{}.txt", std::process::id()));
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--joblog")
+        .arg(&joblog)
+        .arg("-s")
+        .arg(":::")
+        .arg("echo A")
+        .arg("exit 3")
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_empty());
+
+    let contents = std::fs::read_to_string(&joblog).unwrap();
+    let lines: Vec<Vec<&str>> = contents
+        .lines()
+        .map(|line| line.split('\t').collect())
+        .collect();
+
+    assert_eq!(lines.len(), 3);
+    assert_eq!(
+        lines[0],
+        vec![
+            "Seq",
+            "Host",
+            "Starttime",
+            "JobRuntime",
+            "Send",
+            "Receive",
+            "Exitval",
+            "Signal",
+            "Command"
+        ]
+    );
+    assert_eq!(lines[1][0], "1");
+    assert_eq!(lines[1][1], ":");
+    assert_eq!(&lines[1][6..8], &["0", "0"]);
+    assert!(lines[1][8].ends_with(" echo A"));
+    assert_eq!(lines[2][0], "2");
+    assert_eq!(&lines[2][6..8], &["3", "0"]);
+    assert!(lines[2][8].ends_with(" exit 3"));
+
+    std::fs::remove_file(&joblog).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_hash_output_j1() {
+    let joblog = std::env::temp_dir().join(format!(
+        "rust-parallel-hash-joblog-{}.txt",
+        std::process::id()
+    ));
+    let results_dir =
+        std::env::temp_dir().join(format!("rust-parallel-hash-results-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&results_dir);
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--hash-output")
+        .arg("--joblog")
+        .arg(&joblog)
+        .arg("--results")
+        .arg(&results_dir)
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A\nB\n"))
+        .stderr(predicate::str::is_empty());
+
+    let a_hash = "06f961b802bc46ee168555f066d28f4f0e9afdf3f88174c1ee6f9de004fc30a0";
+
+    let contents = std::fs::read_to_string(&joblog).unwrap();
+    let lines: Vec<Vec<&str>> = contents
+        .lines()
+        .map(|line| line.split('\t').collect())
+        .collect();
+
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0][8..], ["Command", "Stdout_sha256"]);
+    assert_eq!(lines[1][9], a_hash);
+    assert_eq!(lines[2][9].len(), 64);
+    assert_ne!(lines[2][9], a_hash);
+
+    assert_eq!(
+        std::fs::read_to_string(results_dir.join("1/stdout.sha256")).unwrap(),
+        format!("{}\n", a_hash)
+    );
+
+    std::fs::remove_file(&joblog).unwrap();
+    std::fs::remove_dir_all(&results_dir).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_use_recorded_env_j1() {
+    let config_dir =
+        std::env::temp_dir().join(format!("rust-parallel-env-config-{}", std::process::id()));
+    let env_file = std::env::temp_dir().join(format!("rust-parallel-env-{}", std::process::id()));
+
+    rust_parallel()
+        .env("XDG_CONFIG_HOME", &config_dir)
+        .arg("--record-env")
+        .write_stdin("IGNORED\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "recorded 1 names to ignore to ",
+        ))
+        .stderr(predicate::str::is_empty());
+
+    std::fs::write(
+        &env_file,
+        concat!(
+            "function\0greet\0greet () \n{ \n    echo hello $1 $GREETING$IGNORED\n}\0",
+            "variable\0GREETING\0declare -- GREETING=\"there\"\0",
+            "variable\0IGNORED\0declare -- IGNORED=\"!\"\0",
+        ),
+    )
+    .unwrap();
+
+    rust_parallel()
+        .env("XDG_CONFIG_HOME", &config_dir)
+        .arg("-j1")
+        .arg("--use-recorded-env")
+        .arg(&env_file)
+        .arg("-s")
+        .arg("greet")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .assert()
+        .success()
+        .stdout(predicate::eq("hello A there\nhello B there\n"))
+        .stderr(predicate::str::is_empty());
+
+    std::fs::remove_file(&env_file).unwrap();
+    std::fs::remove_dir_all(&config_dir).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_resume_j1() {
+    let joblog =
+        std::env::temp_dir().join(format!("rust-parallel-resume-{}.txt", std::process::id()));
+    let marker = std::env::temp_dir().join(format!(
+        "rust-parallel-resume-{}.marker",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&joblog);
+    let _ = std::fs::remove_file(&marker);
+
+    let run = || {
+        let mut command = rust_parallel();
+        command
+            .arg("-j1")
+            .arg("--joblog")
+            .arg(&joblog)
+            .arg("--resume")
+            .arg("-s")
+            .arg(":::")
+            .arg("echo A")
+            .arg(format!("cat {}", marker.display()))
+            .arg("echo C");
+        command
+    };
+
+    run()
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("A\n").and(predicate::str::contains("C\n")));
+
+    std::fs::write(&marker, "B\n").unwrap();
+
+    run()
+        .assert()
+        .success()
+        .stdout(predicate::eq("B\n"))
+        .stderr(predicate::str::is_empty());
+
+    let contents = std::fs::read_to_string(&joblog).unwrap();
+    assert_eq!(contents.lines().count(), 5);
+    assert_eq!(contents.matches("Seq\t").count(), 1);
+
+    std::fs::remove_file(&joblog).unwrap();
+    std::fs::remove_file(&marker).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_shell_positional_args() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("-s")
+        .arg("--shell-positional-args")
+        .arg(r#"echo "[$1]" "$#""#)
+        .arg(":::")
+        .arg("a;b")
+        .arg("$(echo c)")
+        .assert()
+        .success()
+        .stdout(predicate::eq("[a;b] 1\n[$(echo c)] 1\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("-s")
+        .arg("--shell-positional-args")
+        .arg(r#"echo "$2-$1""#)
+        .write_stdin("a b\nc d\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("b-a\nd-c\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn fails_shell_positional_args_with_regex() {
+    rust_parallel()
+        .arg("-s")
+        .arg("--shell-positional-args")
+        .arg("-r")
+        .arg("(.*)")
+        .arg("echo {1}")
+        .write_stdin("A\n")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "--shell-positional-args can not be combined",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_retry_failed() {
+    let joblog = std::env::temp_dir().join(format!(
+        "rust-parallel-retry-failed-joblog-{}",
+        std::process::id()
+    ));
+    let marker = std::env::temp_dir().join(format!(
+        "rust-parallel-retry-failed-marker-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&joblog);
+    let _ = std::fs::remove_file(&marker);
+
+    let run = |retry_failed: bool| {
+        let mut command = rust_parallel();
+        command.arg("-j1").arg("--joblog").arg(&joblog);
+        if retry_failed {
+            command.arg("--retry-failed");
+        }
+        command
+            .arg("-s")
+            .arg(":::")
+            .arg("echo A")
+            .arg(format!("cat {}", marker.display()))
+            .arg("echo C");
+        command
+    };
+
+    run(false).assert().failure();
+
+    std::fs::write(&marker, "B\n").unwrap();
+
+    run(true)
+        .assert()
+        .success()
+        .stdout(predicate::eq("B\n"))
+        .stderr(predicate::str::is_empty());
+
+    let contents = std::fs::read_to_string(&joblog).unwrap();
+    let seqs_and_exit_values: Vec<(&str, &str)> = contents
+        .lines()
+        .skip(1)
+        .map(|line| {
+            let columns: Vec<&str> = line.split('\t').collect();
+            (columns[0], columns[6])
+        })
+        .collect();
+    assert_eq!(
+        seqs_and_exit_values,
+        vec![("1", "0"), ("2", "0"), ("3", "0")]
+    );
+
+    std::fs::remove_file(&joblog).unwrap();
+    std::fs::remove_file(&marker).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_args_to_shell() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("-s")
+        .arg("-r")
+        .arg("(.*),(.*)")
+        .arg("--args-to-shell")
+        .arg(r#"echo "$2" "$1""#)
+        .write_stdin("a;b,$(echo c)\n'd',e\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("$(echo c) a;b\ne 'd'\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_retries_with_backoff() {
+    let marker = std::env::temp_dir().join(format!(
+        "rust-parallel-retries-marker-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&marker);
+
+    // fails until the third attempt
+    rust_parallel()
+        .arg("--retries")
+        .arg("3")
+        .arg("--retry-delay")
+        .arg("10ms")
+        .arg("--retry-backoff")
+        .arg("3")
+        .arg("-s")
+        .arg(format!(
+            "echo x >> {0}; test $(wc -l < {0}) -ge 3 && echo done",
+            marker.display()
+        ))
+        .write_stdin("A\n")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("attempt 1/3 after 10ms")
+                .and(predicate::str::contains("attempt 2/3 after 30ms"))
+                .and(predicate::str::contains("attempt 3/3").not())
+                .and(predicate::str::contains("done A\n")),
+        )
+        .stderr(predicate::str::is_empty());
+
+    std::fs::remove_file(&marker).unwrap();
+
+    rust_parallel()
+        .arg("--retries")
+        .arg("1")
+        .arg("false")
+        .write_stdin("A\n")
+        .assert()
+        .failure()
+        .stdout(
+            predicate::str::contains("attempt 1/1")
+                .and(predicate::str::contains("commands_run=1 total_failures=1")),
+        );
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_detach_and_attach() {
+    let output = rust_parallel()
+        .arg("--detach")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let run_id = stdout
+        .strip_prefix("detached run ")
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap();
+
+    rust_parallel()
+        .arg("attach")
+        .arg(run_id)
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("A\n")
+                .and(predicate::str::contains("B\n"))
+                .and(predicate::str::contains(format!(
+                    "run {} finished\n",
+                    run_id
+                ))),
+        )
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("--detach")
+        .arg("echo")
+        .write_stdin("A\n")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("--detach can not read stdin"));
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_retry_on_exit_codes() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--retries")
+        .arg("2")
+        .arg("--retry-on-exit-codes")
+        .arg("75,SIGTERM")
+        .arg("-s")
+        .arg(":::")
+        .arg("exit 75")
+        .arg("exit 3")
+        .arg("kill $$")
+        .assert()
+        .failure()
+        .stdout(
+            predicate::str::contains("attempt 2/2")
+                .count(2)
+                .and(predicate::str::contains("retrying command").count(4))
+                .and(predicate::str::contains("commands_run=3 total_failures=3")),
+        );
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_halt_policies() {
+    let start = std::time::Instant::now();
+
+    rust_parallel()
+        .arg("-j2")
+        .arg("--halt")
+        .arg("now,fail=1")
+        .arg("-s")
+        .arg(":::")
+        .arg("sleep 5; echo slow")
+        .arg("sleep 0.2; false")
+        .assert()
+        .failure()
+        .stdout(
+            predicate::str::contains("halting now")
+                .and(predicate::str::contains("total_failures=1"))
+                .and(predicate::str::contains("killed=1"))
+                .and(predicate::str::contains("slow\n").not()),
+        );
+
+    assert!(start.elapsed() < std::time::Duration::from_secs(4));
+
+    rust_parallel()
+        .arg("-j2")
+        .arg("--halt")
+        .arg("now,success=1")
+        .arg("-s")
+        .arg(":::")
+        .arg("sleep 5; echo slow")
+        .arg("echo fast")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("fast\n").and(predicate::str::contains("slow\n").not()));
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--halt")
+        .arg("soon,fail=1")
+        .arg("-s")
+        .arg(":::")
+        .arg("false")
+        .arg("echo B")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("B\n").not());
+}
+
+#[test]
+fn runs_run_dir() {
+    let dir = std::env::temp_dir().join(format!("rust-parallel-run-dir-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--run-dir")
+        .arg(&dir)
+        .arg("-s")
+        .arg(":::")
+        .arg("echo A")
+        .arg("exit 1")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("A\n"))
+        .stderr(predicate::str::is_empty());
+
+    let run_dirs: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(run_dirs.len(), 1);
+    let run_dir = &run_dirs[0];
+
+    let config = std::fs::read_to_string(run_dir.join("config.txt")).unwrap();
+    assert!(config.contains("jobs: 1,"));
+
+    let args = std::fs::read_to_string(run_dir.join("args.json")).unwrap();
+    assert!(args.contains("\"--run-dir\""));
+
+    let joblog = std::fs::read_to_string(run_dir.join("joblog")).unwrap();
+    assert_eq!(joblog.lines().count(), 3);
+
+    let stdout = std::fs::read_to_string(run_dir.join("results/1/stdout")).unwrap();
+    assert_eq!(stdout, "A\n");
+
+    let log = std::fs::read_to_string(run_dir.join("log")).unwrap();
+    assert!(log.contains("command failed"));
+
+    let summary: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(run_dir.join("summary.json")).unwrap())
+            .unwrap();
+    assert_eq!(summary["exit_status"], 1);
+    assert_eq!(summary["metrics"]["commands_run"], 2);
+    assert_eq!(summary["metrics"]["exit_status_errors"], 1);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn runs_cpu_timeout() {
+    rust_parallel()
+        .arg("-j2")
+        .arg("--cpu-timeout")
+        .arg("0.5")
+        .arg("-s")
+        .arg(":::")
+        .arg("while :; do :; done")
+        .arg("sleep 1; echo slept")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(
+            (predicate::str::contains("slept\n"))
+                .and(predicate::str::contains("cpu timeout: 500ms of cpu time elapsed").count(1))
+                .and(predicate::str::contains("timeouts=1")),
+        )
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_delay_between_starts() {
+    let start = std::time::Instant::now();
+
+    rust_parallel()
+        .arg("-j4")
+        .arg("--delay")
+        .arg("300ms")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .arg("C")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A\nB\nC\n"))
+        .stderr(predicate::str::is_empty());
+
+    assert!(start.elapsed() >= std::time::Duration::from_millis(600));
+}
+
+#[test]
+fn runs_shards() {
+    let run_shard = |index: usize, shard_by: &str| {
+        let output = rust_parallel()
+            .arg("-j1")
+            .arg("--shard-count")
+            .arg("3")
+            .arg("--shard-index")
+            .arg(index.to_string())
+            .arg("--shard-by")
+            .arg(shard_by)
+            .arg("echo")
+            .arg(":::")
+            .args((1..=7).map(|i| i.to_string()))
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(run_shard(0, "sequence"), "1\n4\n7\n");
+    assert_eq!(run_shard(1, "sequence"), "2\n5\n");
+    assert_eq!(run_shard(2, "sequence"), "3\n6\n");
+
+    let mut hash_jobs: Vec<String> = (0..3)
+        .flat_map(|index| {
+            run_shard(index, "hash")
+                .lines()
+                .map(str::to_owned)
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    hash_jobs.sort();
+    assert_eq!(hash_jobs, vec!["1", "2", "3", "4", "5", "6", "7"]);
+
+    rust_parallel()
+        .arg("--shard-count")
+        .arg("3")
+        .arg("--shard-index")
+        .arg("3")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(predicate::str::contains(
+            "--shard-index 3 must be less than --shard-count 3",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_slot_key() {
+    let dir = std::env::temp_dir().join(format!("rust-parallel-slot-key-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let start = std::time::Instant::now();
+
+    // jobs of the same key fail if they overlap
+    rust_parallel()
+        .arg("-j3")
+        .arg("--slot-key")
+        .arg("{1}")
+        .arg("-r")
+        .arg("(\\w+) (\\d+)")
+        .arg("-s")
+        .arg(format!(
+            "mkdir {}/{{1}} && sleep 0.5 && rmdir {}/{{1}} && echo {{1}} {{2}}",
+            dir.display(),
+            dir.display()
+        ))
+        .arg(":::")
+        .arg("a 1")
+        .arg("a 2")
+        .arg("b 1")
+        .arg("b 2")
+        .arg("c 1")
+        .arg("c 2")
+        .assert()
+        .success()
+        .stdout(
+            (predicate::str::contains("a 1\n"))
+                .and(predicate::str::contains("a 2\n"))
+                .and(predicate::str::contains("b 2\n"))
+                .and(predicate::str::contains("c 2\n")),
+        )
+        .stderr(predicate::str::is_empty());
+
+    assert!(start.elapsed() < std::time::Duration::from_millis(2500));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_slot_key_without_blocking_other_keys() {
+    // the second job of key a waits for its slot without taking the permit of key b's job
+    rust_parallel()
+        .arg("-j2")
+        .arg("--slot-key")
+        .arg("{1}")
+        .arg("-r")
+        .arg("(\\w+) (\\d+)")
+        .arg("-s")
+        .arg("if [ {1} = a ]; then sleep 0.5; fi; echo {1} {2}")
+        .arg(":::")
+        .arg("a 1")
+        .arg("a 2")
+        .arg("b 1")
+        .assert()
+        .success()
+        .stdout(predicate::eq("b 1\na 1\na 2\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn runs_max_rate_with_jitter() {
+    let start = std::time::Instant::now();
+
+    rust_parallel()
+        .arg("-j4")
+        .arg("--max-rate")
+        .arg("10/s")
+        .arg("--jitter")
+        .arg("50ms")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .arg("C")
+        .arg("D")
+        .arg("E")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A\nB\nC\nD\nE\n"))
+        .stderr(predicate::str::is_empty());
+
+    assert!(start.elapsed() >= std::time::Duration::from_millis(400));
+
+    rust_parallel()
+        .arg("--max-rate")
+        .arg("10/d")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .assert()
+        .failure()
+        .code(2)
+        .stderr(predicate::str::contains("unknown unit `d`"));
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_mutex() {
+    let dir = std::env::temp_dir().join(format!("rust-parallel-mutex-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    // jobs of the same key fail if they overlap
+    rust_parallel()
+        .arg("-j4")
+        .arg("--mutex")
+        .arg("{1}")
+        .arg("-r")
+        .arg("(\\w+) (\\d+)")
+        .arg("-s")
+        .arg(format!(
+            "mkdir {}/{{1}} && sleep 0.3 && rmdir {}/{{1}} && echo {{1}} {{2}}",
+            dir.display(),
+            dir.display()
+        ))
+        .arg(":::")
+        .arg("a 1")
+        .arg("a 2")
+        .arg("a 3")
+        .arg("b 1")
+        .assert()
+        .success()
+        .stdout(
+            (predicate::str::contains("a 1\n"))
+                .and(predicate::str::contains("a 2\n"))
+                .and(predicate::str::contains("a 3\n"))
+                .and(predicate::str::contains("b 1\n")),
+        )
+        .stderr(predicate::str::is_empty());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_mutex_without_blocking_other_jobs() {
+    // the second job of mutex a waits without taking the permit of the unrelated job
+    rust_parallel()
+        .arg("-j2")
+        .arg("--mutex")
+        .arg("{1}")
+        .arg("-r")
+        .arg("(\\w+) (\\d+)")
+        .arg("-s")
+        .arg("if [ {1} = a ]; then sleep 0.5; fi; echo {1} {2}")
+        .arg(":::")
+        .arg("a 1")
+        .arg("a 2")
+        .arg("b 1")
+        .assert()
+        .success()
+        .stdout(predicate::eq("b 1\na 1\na 2\n"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn runs_with_load() {
+    rust_parallel()
+        .arg("--load")
+        .arg("10000%")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("--load")
+        .arg("0")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .assert()
+        .failure()
+        .code(2)
+        .stderr(predicate::str::contains("value is not greater than 0"));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn runs_with_memfree() {
+    rust_parallel()
+        .arg("--memfree")
+        .arg("1K")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .assert()
+        .success()
+        .stdout(predicate::eq("A\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("--memfree")
+        .arg("100T")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .timeout(std::time::Duration::from_secs(2))
+        .assert()
+        .interrupted()
+        .stdout(
+            predicate::str::contains("below --memfree").and(predicate::str::contains("A\n").not()),
+        );
+}
+
+#[test]
+fn runs_fair_inputs_with_weights_j1() {
+    let dir =
+        std::env::temp_dir().join(format!("rust-parallel-fair-inputs-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let a = dir.join("a.txt");
+    let b = dir.join("b.txt");
+    std::fs::write(&a, "echo a1\necho a2\necho a3\necho a4\n").unwrap();
+    std::fs::write(&b, "echo b1\necho b2\n").unwrap();
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("-i")
+        .arg(&a)
+        .arg("-i")
+        .arg(&b)
+        .arg("--input-weight")
+        .arg("2,1")
+        .assert()
+        .success()
+        .stdout(predicate::eq("a1\na2\nb1\na3\na4\nb2\n"))
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("-i")
+        .arg(&a)
+        .arg("-i")
+        .arg(&b)
+        .arg("--fair-inputs")
+        .assert()
+        .success()
+        .stdout(predicate::eq("a1\nb1\na2\nb2\na3\na4\n"))
+        .stderr(predicate::str::is_empty());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn runs_with_psi_thresholds() {
+    if !std::path::Path::new("/proc/pressure/cpu").exists() {
+        return;
+    }
+
+    rust_parallel()
+        .arg("-j2")
+        .arg("--psi-cpu")
+        .arg("100")
+        .arg("--psi-io")
+        .arg("100%")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("A\n").and(predicate::str::contains("B\n")))
+        .stderr(predicate::str::is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_raising_jobs_with_sigusr1_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("--jobs-signals")
+        .arg("-s")
+        .arg(":::")
+        .arg("kill -USR1 $PPID; sleep 1; echo A")
+        .arg("echo B")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("received SIGUSR1, raising jobs to 2")
+                .and(predicate::str::is_match("(?s)B\n.*A\n").unwrap()),
+        )
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_compare_with_previous_results_j1() {
+    let results_dir = std::env::temp_dir().join(format!(
+        "rust-parallel-compare-results-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&results_dir);
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--results")
+        .arg(&results_dir)
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .assert()
+        .success();
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--compare-with")
+        .arg(&results_dir)
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("compared 2 jobs with '")
+                .and(predicate::str::contains("0 differ, 0 missing")),
+        )
+        .stderr(predicate::str::is_empty());
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--compare-with")
+        .arg(&results_dir)
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("C")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(
+            predicate::str::contains(
+                "job 2 output differs: 1 lines, previously 1, first difference at line 1: \"C\" was \"B\", previous command was: ",
+            )
+            .and(predicate::str::contains("echo B\n"))
+            .and(predicate::str::contains(
+                "1 of 2 jobs output differs from --compare-with",
+            )),
+        )
+        .stderr(predicate::str::is_empty());
+
+    std::fs::remove_dir_all(&results_dir).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_expect_file_j1() {
+    let expected_dir =
+        std::env::temp_dir().join(format!("rust-parallel-expected-{}", std::process::id()));
+    std::fs::create_dir_all(&expected_dir).unwrap();
+    std::fs::write(expected_dir.join("A.out"), "A\n").unwrap();
+    std::fs::write(expected_dir.join("B.out"), "b\n").unwrap();
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--expect-file")
+        .arg(expected_dir.join("{1}.out"))
+        .arg("-r")
+        .arg("(.*)")
+        .arg("echo")
+        .arg(":::")
+        .arg("A")
+        .arg("B")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(
+            predicate::str::contains("args=[\"B\"]")
+                .and(predicate::str::contains(
+                    "stdout differs from expected file",
+                ))
+                .and(predicate::str::contains("output_failures=1")),
+        )
+        .stderr(predicate::str::ends_with(
+            "B.out\n+++ stdout\n@@ -1,1 +1,1 @@\n-b\n+B\n",
+        ));
+
+    std::fs::remove_dir_all(&expected_dir).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_control_socket_drain_j1() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let socket_path =
+        std::env::temp_dir().join(format!("rust-parallel-control-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&socket_path);
+
+    let child = rust_parallel_raw_command()
+        .arg("-j1")
+        .arg("--control-socket")
+        .arg(&socket_path)
+        .arg("-s")
+        .arg(":::")
+        .arg("sleep 1; echo A")
+        .arg("echo B")
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let stream = (0..50)
+        .find_map(|_| {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            UnixStream::connect(&socket_path).ok()
+        })
+        .unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request = |request: &str| {
+        writeln!(&stream, "{}", request).unwrap();
+        let mut response = String::new();
+        reader.read_line(&mut response).unwrap();
+        response
+    };
+
+    let status = request("status");
+    assert!(status.contains("\"jobs\":1,\"running\":1,\"paused\":false"));
+
+    assert_eq!(
+        request("set-jobs 0"),
+        "error: invalid jobs '0', expected a positive number\n"
+    );
+    assert_eq!(request("resume"), "error: not paused\n");
+    assert_eq!(request("drain"), "ok\n");
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert!(stdout.contains("A\n"));
+    assert!(!stdout.contains("B\n"));
+    assert!(output.stderr.is_empty());
+    assert!(!socket_path.exists());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn runs_control_socket_pause_resume_j1() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let socket_path =
+        std::env::temp_dir().join(format!("rust-parallel-pause-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&socket_path);
+
+    let start = std::time::Instant::now();
+
+    let child = rust_parallel_raw_command()
+        .arg("-j1")
+        .arg("--control-socket")
+        .arg(&socket_path)
+        .arg("-s")
+        .arg(":::")
+        .arg("for i in 1 2 3 4 5 6 7 8; do sleep 0.1; done; echo A")
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let stream = (0..50)
+        .find_map(|_| {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            UnixStream::connect(&socket_path).ok()
+        })
+        .unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request = |request: &str| {
+        writeln!(&stream, "{}", request).unwrap();
+        let mut response = String::new();
+        reader.read_line(&mut response).unwrap();
+        response
+    };
+
+    assert_eq!(request("pause"), "ok\n");
+    assert!(request("status").contains("\"paused\":true"));
+
+    // stopped commands do not progress while paused
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    assert_eq!(request("resume"), "ok\n");
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert!(start.elapsed() >= std::time::Duration::from_millis(1500));
+    assert!(stdout.contains("paused, stopped 1 running commands"));
+    assert!(stdout.contains("A\n"));
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_expect_exit_from_regex_group_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("-s")
+        .arg("-r")
+        .arg("(.*),(.*)")
+        .arg("--expect-exit")
+        .arg("{2}")
+        .arg("exit {1}")
+        .arg(":::")
+        .arg("0,0")
+        .arg("3,3")
+        .arg("0,2")
+        .arg("4,1")
+        .assert()
+        .failure()
+        .code(2)
+        .stdout(
+            predicate::str::contains("but expected exit code '2'")
+                .and(predicate::str::contains("but expected exit code '1'"))
+                .and(predicate::str::contains("exit_status_errors=2")),
+        )
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_expect_exit_and_failure_regex_keep_exit_code_j1() {
+    let results_dir = std::env::temp_dir().join(format!(
+        "rust-parallel-expect-exit-results-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&results_dir);
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("-s")
+        .arg("--expect-exit")
+        .arg("3")
+        .arg("--failure-regex")
+        .arg("^ERROR")
+        .arg("--results")
+        .arg(&results_dir)
+        .arg(":::")
+        .arg("exit 3")
+        .arg("echo ERROR; exit 3")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(
+            predicate::str::contains("output_failures=1")
+                .and(predicate::str::contains("exit_status_errors=0")),
+        )
+        .stderr(predicate::str::is_empty());
+
+    for job in ["1", "2"] {
+        assert_eq!(
+            std::fs::read_to_string(results_dir.join(job).join("exitcode")).unwrap(),
+            "3\n"
+        );
+    }
+
+    std::fs::remove_dir_all(&results_dir).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_failure_regex_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("-s")
+        .arg("--failure-regex")
+        .arg("^ERROR")
+        .arg(":::")
+        .arg("echo ERROR: disk full")
+        .arg("echo ok")
+        .arg("echo ERROR: again >&2")
+        .assert()
+        .failure()
+        .code(2)
+        .stdout(
+            predicate::str::contains("output matched --failure-regex")
+                .and(predicate::str::contains("output_failures=2"))
+                .and(predicate::str::contains("exit_status_errors=0")),
+        )
+        .stderr(predicate::str::contains("ERROR: again"));
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_success_regex_j1() {
+    rust_parallel()
+        .arg("-j1")
+        .arg("-s")
+        .arg("--success-regex")
+        .arg("PASSED")
+        .arg(":::")
+        .arg("echo 3 tests PASSED; exit 1")
+        .arg("echo 1 test FAILED")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(
+            predicate::str::contains("output did not match --success-regex")
+                .and(predicate::str::contains("output_failures=1"))
+                .and(predicate::str::contains("total_failures=1")),
+        )
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_interrupted_by_sigint_j1() {
+    let start = std::time::Instant::now();
+
+    let child = rust_parallel_raw_command()
+        .arg("-j1")
+        .arg("-s")
+        .arg(":::")
+        .arg("sleep 5; echo A")
+        .arg("echo B")
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    let kill_status = std::process::Command::new("kill")
+        .arg("-INT")
+        .arg(child.id().to_string())
+        .status()
+        .unwrap();
+    assert!(kill_status.success());
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(130));
+    assert!(start.elapsed() < std::time::Duration::from_secs(2));
+    assert!(stdout
+        .contains("received SIGINT, skipping remaining jobs and terminating 1 running commands"));
+    assert!(stdout.contains("interrupted, received SIGINT: commands_run=1"));
+    assert!(!stdout.contains("A\n"));
+    assert!(!stdout.contains("B\n"));
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_stop_file_j1() {
+    let stop_file = std::env::temp_dir().join(format!("rust-parallel-stop-{}", std::process::id()));
+    let _ = std::fs::remove_file(&stop_file);
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--stop-file")
+        .arg(&stop_file)
+        .arg("-s")
+        .arg(":::")
+        .arg(format!("echo A; touch {}; sleep 1.5", stop_file.display()))
+        .arg("echo B")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("A\n")
+                .and(predicate::str::contains("exists, skipping remaining jobs"))
+                .and(predicate::str::contains("B\n").not()),
+        )
+        .stderr(predicate::str::is_empty());
+
+    std::fs::remove_file(&stop_file).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_killed_by_second_sigint_j1() {
+    let marker = std::env::temp_dir().join(format!(
+        "rust-parallel-second-sigint-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&marker);
+
+    let start = std::time::Instant::now();
+
+    let child = rust_parallel_raw_command()
+        .arg("-j1")
+        .arg("-s")
+        .arg(":::")
+        .arg(format!(
+            "trap '' TERM; sleep 1.5; touch {}",
+            marker.display()
+        ))
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let sigint = || {
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        let kill_status = std::process::Command::new("kill")
+            .arg("-INT")
+            .arg(child.id().to_string())
+            .status()
+            .unwrap();
+        assert!(kill_status.success());
+    };
+    sigint();
+    sigint();
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(137));
+    assert!(start.elapsed() < std::time::Duration::from_millis(1500));
+    assert!(stdout.contains("received second SIGINT, killed 1 running commands, exiting"));
+    assert!(output.stderr.is_empty());
+
+    // the killed command does not finish
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    assert!(!marker.exists());
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_timeout_kills_process_group_j1() {
+    let marker = std::env::temp_dir().join(format!(
+        "rust-parallel-timeout-group-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&marker);
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("-t")
+        .arg("0.5")
+        .arg("-s")
+        .arg(":::")
+        .arg(format!("(sleep 1.5; touch {}) & wait", marker.display()))
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(predicate::str::contains("timeouts=1"))
+        .stderr(predicate::str::is_empty());
+
+    // the background subshell was killed with the command
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    assert!(!marker.exists());
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_max_runtime_j1() {
+    let start = std::time::Instant::now();
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("--max-runtime")
+        .arg("500ms")
+        .arg("-s")
+        .arg(":::")
+        .arg("echo A")
+        .arg("sleep 5; echo B")
+        .arg("echo C")
+        .assert()
+        .failure()
+        .code(124)
+        .stdout(
+            predicate::str::contains(
+                "--max-runtime 500ms exceeded, skipping remaining jobs and terminating 1 running commands",
+            )
+            .and(predicate::str::contains("interrupted, --max-runtime 500ms exceeded: commands_run=2"))
+            .and(predicate::str::contains("A\n"))
+            .and(predicate::str::contains("B\n").not())
+            .and(predicate::str::contains("C\n").not()),
+        )
+        .stderr(predicate::str::is_empty());
+
+    assert!(start.elapsed() < std::time::Duration::from_secs(2));
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_term_timeout_j1() {
+    let marker =
+        std::env::temp_dir().join(format!("rust-parallel-term-timeout-{}", std::process::id()));
+    let _ = std::fs::remove_file(&marker);
+
+    let start = std::time::Instant::now();
+
+    rust_parallel()
+        .arg("-j1")
+        .arg("-t")
+        .arg("0.5")
+        .arg("--term-timeout")
+        .arg("3s")
+        .arg("-s")
+        .arg(":::")
+        .arg(format!(
+            "trap 'touch {}; exit 1' TERM; sleep 5 & wait",
+            marker.display()
+        ))
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(predicate::str::contains("timeouts=1"))
+        .stderr(predicate::str::is_empty());
+
+    // the command handled SIGTERM and exited before the grace period ended
+    assert!(start.elapsed() < std::time::Duration::from_secs(2));
+    assert!(marker.exists());
+
+    std::fs::remove_file(&marker).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn runs_input_idle_timeout_j1() {
+    use std::io::Write;
+
+    let mut child = rust_parallel_raw_command()
+        .arg("-j1")
+        .arg("--input-idle-timeout")
+        .arg("500ms")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // the producer never closes stdin
+    let mut stdin = child.stdin.take().unwrap();
+    writeln!(stdin, "echo A").unwrap();
+
+    let start = std::time::Instant::now();
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert!(start.elapsed() < std::time::Duration::from_secs(2));
+    assert!(stdout.contains("A\n"));
+    assert!(stdout.contains("no input for --input-idle-timeout 500ms, finishing run"));
+    assert!(output.stderr.is_empty());
+
+    drop(stdin);
+}